@@ -15,6 +15,12 @@ pushd build >/dev/null
 	-d .\
         $(find ../android/src -name "*.java")
     dx --dex --output classes.dex $(find . -name "*.class")
+    mkdir -p pak assets
+    cp -r ../native/shaders pak/shaders
+    cargo run -q --release --manifest-path ../native/Cargo.toml -p pack --\
+        --compression lz4\
+        pak\
+        assets/data.pak
     mkdir -p lib/arm64-v8a
     pushd lib/arm64-v8a >/dev/null
         cp ../../../native/target/aarch64-linux-android/debug/libnative.so .
@@ -25,6 +31,8 @@ pushd build >/dev/null
         -F rustquest.apk\
         -I $ANDROID_HOME/platforms/android-26/android.jar\
         -M ../android/AndroidManifest.xml\
+        -A assets\
+        -0 pak\
         -f
     aapt add rustquest.apk classes.dex > /dev/null
     aapt add rustquest.apk lib/arm64-v8a/libnative.so
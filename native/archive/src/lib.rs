@@ -0,0 +1,425 @@
+mod lz4;
+
+use ruzstd::decoding::StreamingDecoder;
+use ruzstd::encoding::CompressionLevel;
+use std::borrow::Cow;
+use std::io::Read;
+use std::ops::Deref;
+use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const MAGIC: &[u8; 4] = b"RQAR";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 32;
+const ENTRY_SIZE: usize = 48;
+const ALIGNMENT: usize = 16;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Compression {
+    None,
+    Lz4,
+    Zstd,
+}
+
+impl Compression {
+    fn from_u32(value: u32) -> Option<Compression> {
+        match value {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Lz4),
+            2 => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    fn to_u32(self) -> u32 {
+        match self {
+            Compression::None => 0,
+            Compression::Lz4 => 1,
+            Compression::Zstd => 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub compression: Compression,
+    pub offset: usize,
+    pub stored_size: usize,
+    pub size: usize,
+    checksum: u32,
+}
+
+pub struct Archive<B> {
+    bytes: B,
+    entries: Vec<Entry>,
+    verified: Vec<AtomicBool>,
+}
+
+impl<B: Deref<Target = [u8]>> Archive<B> {
+    pub fn new(bytes: B) -> Result<Archive<B>, String> {
+        let entries = parse_entries(&bytes)?;
+        let verified = entries.iter().map(|_| AtomicBool::new(false)).collect();
+        Ok(Archive {
+            bytes,
+            entries,
+            verified,
+        })
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&Entry> {
+        self.index(name).map(|index| &self.entries[index])
+    }
+
+    pub fn read(&self, name: &str) -> Result<Cow<'_, [u8]>, String> {
+        let index = self
+            .index(name)
+            .ok_or_else(|| format!("archive has no entry {}", name))?;
+        let entry = &self.entries[index];
+        let stored = &self.bytes[entry.offset..entry.offset + entry.stored_size];
+        if !self.verified[index].load(Ordering::Relaxed) {
+            if checksum(stored) != entry.checksum {
+                return Err(format!("entry {} is corrupt", name));
+            }
+            self.verified[index].store(true, Ordering::Relaxed);
+        }
+        match entry.compression {
+            Compression::None => Ok(Cow::Borrowed(stored)),
+            Compression::Lz4 => lz4::decompress(stored, entry.size)
+                .map(Cow::Owned)
+                .map_err(|error| format!("can't decompress entry {}: {}", name, error)),
+            Compression::Zstd => {
+                let mut data = Vec::with_capacity(entry.size);
+                StreamingDecoder::new(stored)
+                    .map_err(|error| format!("can't decompress entry {}: {}", name, error))?
+                    .take(entry.size as u64 + 1)
+                    .read_to_end(&mut data)
+                    .map_err(|error| format!("can't decompress entry {}: {}", name, error))?;
+                if data.len() != entry.size {
+                    return Err(format!("entry {} has the wrong size", name));
+                }
+                Ok(Cow::Owned(data))
+            }
+        }
+    }
+
+    pub fn read_to_string(&self, name: &str) -> Result<String, String> {
+        String::from_utf8(self.read(name)?.into_owned())
+            .map_err(|_| format!("entry {} is not valid UTF-8", name))
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.entries
+            .binary_search_by(|entry| entry.name.as_str().cmp(name))
+            .ok()
+    }
+}
+
+pub struct ArchiveWriter {
+    entries: Vec<(String, Compression, Vec<u8>, usize)>,
+}
+
+impl ArchiveWriter {
+    pub fn new() -> ArchiveWriter {
+        ArchiveWriter {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, name: &str, data: &[u8], compression: Compression) {
+        let compressed = match compression {
+            Compression::None => None,
+            Compression::Lz4 => Some(lz4::compress(data)),
+            Compression::Zstd => Some(ruzstd::encoding::compress_to_vec(
+                data,
+                CompressionLevel::Fastest,
+            )),
+        };
+        let (compression, stored) = match compressed {
+            Some(compressed) if compressed.len() < data.len() => (compression, compressed),
+            _ => (Compression::None, data.to_vec()),
+        };
+        self.entries.retain(|entry| entry.0 != name);
+        self.entries
+            .push((String::from(name), compression, stored, data.len()));
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.entries.sort_by(|a, b| a.0.cmp(&b.0));
+        let names_size: usize = self.entries.iter().map(|entry| entry.0.len()).sum();
+        let toc_size = self.entries.len() * ENTRY_SIZE + names_size;
+
+        let mut toc = Vec::with_capacity(toc_size);
+        let mut names = Vec::with_capacity(names_size);
+        let mut offset = align(HEADER_SIZE + toc_size, ALIGNMENT);
+        for (name, compression, stored, size) in self.entries.iter() {
+            toc.extend_from_slice(&(names.len() as u32).to_le_bytes());
+            toc.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            toc.extend_from_slice(&compression.to_u32().to_le_bytes());
+            toc.extend_from_slice(&checksum(stored).to_le_bytes());
+            toc.extend_from_slice(&(offset as u64).to_le_bytes());
+            toc.extend_from_slice(&(stored.len() as u64).to_le_bytes());
+            toc.extend_from_slice(&(*size as u64).to_le_bytes());
+            toc.extend_from_slice(&0u64.to_le_bytes());
+            names.extend_from_slice(name.as_bytes());
+            offset = align(offset + stored.len(), ALIGNMENT);
+        }
+        toc.extend_from_slice(&names);
+
+        let mut bytes = Vec::with_capacity(offset);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(names_size as u32).to_le_bytes());
+        bytes.extend_from_slice(&checksum(&toc).to_le_bytes());
+        bytes.resize(HEADER_SIZE, 0);
+        bytes.extend_from_slice(&toc);
+        for (_, _, stored, _) in self.entries.iter() {
+            bytes.resize(align(bytes.len(), ALIGNMENT), 0);
+            bytes.extend_from_slice(stored);
+        }
+        bytes.resize(align(bytes.len(), ALIGNMENT), 0);
+        bytes
+    }
+}
+
+impl Default for ArchiveWriter {
+    fn default() -> ArchiveWriter {
+        ArchiveWriter::new()
+    }
+}
+
+fn parse_entries(bytes: &[u8]) -> Result<Vec<Entry>, String> {
+    if bytes.len() < HEADER_SIZE || &bytes[0..4] != MAGIC {
+        return Err(String::from("not an archive"));
+    }
+    let version = read_u32(bytes, 4);
+    if version != VERSION {
+        return Err(format!("unsupported archive version {}", version));
+    }
+    let entry_count = read_u32(bytes, 8) as usize;
+    let names_size = read_u32(bytes, 12) as usize;
+    let toc_checksum = read_u32(bytes, 16);
+    let toc = entry_count
+        .checked_mul(ENTRY_SIZE)
+        .and_then(|size| size.checked_add(names_size))
+        .and_then(|size| bytes.get(HEADER_SIZE..HEADER_SIZE + size))
+        .ok_or("truncated table of contents")?;
+    if checksum(toc) != toc_checksum {
+        return Err(String::from("table of contents is corrupt"));
+    }
+    let names = &toc[entry_count * ENTRY_SIZE..];
+    let data_start = HEADER_SIZE + toc.len();
+
+    let mut entries: Vec<Entry> = Vec::with_capacity(entry_count);
+    for index in 0..entry_count {
+        let entry = &toc[index * ENTRY_SIZE..(index + 1) * ENTRY_SIZE];
+        let name_offset = read_u32(entry, 0) as usize;
+        let name_length = read_u32(entry, 4) as usize;
+        let name = names
+            .get(name_offset..name_offset + name_length)
+            .and_then(|name| str::from_utf8(name).ok())
+            .ok_or_else(|| format!("entry {} has an invalid name", index))?;
+        let compression = Compression::from_u32(read_u32(entry, 8))
+            .ok_or_else(|| format!("entry {} has an invalid compression", name))?;
+        let offset = read_u64(entry, 16) as usize;
+        let stored_size = read_u64(entry, 24) as usize;
+        let size = read_u64(entry, 32) as usize;
+        if !offset.is_multiple_of(ALIGNMENT)
+            || offset < data_start
+            || offset
+                .checked_add(stored_size)
+                .is_none_or(|end| end > bytes.len())
+        {
+            return Err(format!("entry {} is out of bounds", name));
+        }
+        if compression == Compression::None && stored_size != size {
+            return Err(format!("entry {} has the wrong size", name));
+        }
+        if let Some(previous) = entries.last() {
+            if previous.name.as_str() >= name {
+                return Err(format!("entry {} is out of order", name));
+            }
+        }
+        entries.push(Entry {
+            name: String::from(name),
+            compression,
+            offset,
+            stored_size,
+            size,
+            checksum: read_u32(entry, 12),
+        });
+    }
+    Ok(entries)
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in bytes.iter() {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
+}
+
+fn align(offset: usize, alignment: usize) -> usize {
+    offset.div_ceil(alignment) * alignment
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    read_u32(bytes, offset) as u64 | (read_u32(bytes, offset + 4) as u64) << 32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shader_source() -> Vec<u8> {
+        b"#version 300 es\nuniform mat4 uModelMatrix;\nin vec3 aPosition;\n".repeat(32)
+    }
+
+    fn archive() -> Vec<u8> {
+        let mut writer = ArchiveWriter::new();
+        writer.add("shaders/lit.vert", &shader_source(), Compression::Zstd);
+        writer.add("shaders/lit.frag", &shader_source(), Compression::Lz4);
+        writer.add("models/box.bin", &[1, 2, 3], Compression::None);
+        writer.add("models/tiny.bin", &[4, 5], Compression::Lz4);
+        writer.finish()
+    }
+
+    #[test]
+    fn round_trips_entries() {
+        let archive = Archive::new(archive()).unwrap();
+        let entries: Vec<(&str, Compression)> = archive
+            .entries()
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.compression))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("models/box.bin", Compression::None),
+                ("models/tiny.bin", Compression::None),
+                ("shaders/lit.frag", Compression::Lz4),
+                ("shaders/lit.vert", Compression::Zstd),
+            ]
+        );
+        assert!(archive
+            .entries()
+            .iter()
+            .all(|entry| entry.offset % ALIGNMENT == 0));
+        assert_eq!(&*archive.read("models/box.bin").unwrap(), [1, 2, 3]);
+        assert_eq!(&*archive.read("models/tiny.bin").unwrap(), [4, 5]);
+        for name in ["shaders/lit.frag", "shaders/lit.vert"] {
+            let entry = archive.entry(name).unwrap();
+            assert!(entry.stored_size < entry.size);
+            assert_eq!(archive.read(name).unwrap(), shader_source());
+        }
+        assert_eq!(
+            archive
+                .read_to_string("shaders/lit.vert")
+                .unwrap()
+                .as_bytes(),
+            &shader_source()[..]
+        );
+        assert!(archive
+            .verified
+            .iter()
+            .all(|verified| verified.load(Ordering::Relaxed)));
+        assert_eq!(
+            archive.read("missing").err(),
+            Some(String::from("archive has no entry missing"))
+        );
+        assert!(Archive::new(ArchiveWriter::new().finish())
+            .unwrap()
+            .entries()
+            .is_empty());
+    }
+
+    #[test]
+    fn replaces_entries_with_the_same_name() {
+        let mut writer = ArchiveWriter::new();
+        writer.add("a", b"old", Compression::None);
+        writer.add("a", b"new", Compression::None);
+        let archive = Archive::new(writer.finish()).unwrap();
+        assert_eq!(archive.entries().len(), 1);
+        assert_eq!(archive.read_to_string("a").unwrap(), "new");
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let bytes = archive();
+        for name in ["models/box.bin", "shaders/lit.frag", "shaders/lit.vert"] {
+            let mut corrupt = bytes.clone();
+            let offset = Archive::new(&bytes[..])
+                .unwrap()
+                .entry(name)
+                .unwrap()
+                .offset;
+            corrupt[offset] ^= 0x01;
+            let archive = Archive::new(corrupt).unwrap();
+            for _ in 0..2 {
+                assert_eq!(
+                    archive.read(name).err(),
+                    Some(format!("entry {} is corrupt", name))
+                );
+            }
+        }
+
+        let mut corrupt = bytes;
+        corrupt[HEADER_SIZE + 1] ^= 0x01;
+        assert_eq!(
+            Archive::new(corrupt).err(),
+            Some(String::from("table of contents is corrupt"))
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_archives() {
+        let bytes = archive();
+        assert_eq!(
+            Archive::new(&bytes[..HEADER_SIZE - 1]).err(),
+            Some(String::from("not an archive"))
+        );
+        assert_eq!(
+            Archive::new(&bytes[..HEADER_SIZE + ENTRY_SIZE]).err(),
+            Some(String::from("truncated table of contents"))
+        );
+        let last = Archive::new(&bytes[..])
+            .unwrap()
+            .entries()
+            .iter()
+            .map(|entry| entry.offset)
+            .max()
+            .unwrap();
+        assert_eq!(
+            Archive::new(&bytes[..last + 1]).err(),
+            Some(String::from("entry shaders/lit.vert is out of bounds"))
+        );
+
+        let mut bytes = bytes;
+        bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            Archive::new(bytes.clone()).err(),
+            Some(String::from("truncated table of contents"))
+        );
+        bytes[4] = 2;
+        assert_eq!(
+            Archive::new(bytes).err(),
+            Some(String::from("unsupported archive version 2"))
+        );
+    }
+}
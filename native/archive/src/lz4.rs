@@ -0,0 +1,223 @@
+const MIN_MATCH: usize = 4;
+const LAST_LITERALS: usize = 5;
+const MATCH_FIND_LIMIT: usize = 12;
+const MAX_OFFSET: usize = 65535;
+const HASH_LOG: u32 = 12;
+
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() / 2 + 16);
+    let mut table = vec![0usize; 1 << HASH_LOG];
+    let mut anchor = 0;
+    let mut position = 0;
+    let limit = input.len().saturating_sub(MATCH_FIND_LIMIT);
+    while position < limit {
+        let sequence = read_u32(input, position);
+        let hash = hash(sequence);
+        let candidate = table[hash];
+        table[hash] = position + 1;
+        if candidate > 0 {
+            let candidate = candidate - 1;
+            if position - candidate <= MAX_OFFSET && read_u32(input, candidate) == sequence {
+                let max_length = input.len() - LAST_LITERALS - position;
+                let mut length = MIN_MATCH;
+                while length < max_length && input[candidate + length] == input[position + length] {
+                    length += 1;
+                }
+                write_sequence(
+                    &mut output,
+                    &input[anchor..position],
+                    Some(((position - candidate) as u16, length)),
+                );
+                position += length;
+                anchor = position;
+                continue;
+            }
+        }
+        position += 1;
+    }
+    write_sequence(&mut output, &input[anchor..], None);
+    output
+}
+
+pub fn decompress(input: &[u8], size: usize) -> Result<Vec<u8>, String> {
+    let mut output = Vec::with_capacity(size);
+    let mut position = 0;
+    loop {
+        let token = *input.get(position).ok_or("truncated LZ4 block")?;
+        position += 1;
+
+        let literal_length = read_length(input, &mut position, (token >> 4) as usize)?;
+        let literals = input
+            .get(position..position + literal_length)
+            .ok_or("truncated LZ4 literals")?;
+        if output.len() + literal_length > size {
+            return Err(String::from("LZ4 block is larger than expected"));
+        }
+        output.extend_from_slice(literals);
+        position += literal_length;
+        if position == input.len() {
+            break;
+        }
+
+        let offset = input
+            .get(position..position + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+            .ok_or("truncated LZ4 offset")?;
+        position += 2;
+        if offset == 0 || offset > output.len() {
+            return Err(format!("invalid LZ4 offset {}", offset));
+        }
+        let match_length = read_length(input, &mut position, (token & 0xf) as usize)? + MIN_MATCH;
+        if output.len() + match_length > size {
+            return Err(String::from("LZ4 block is larger than expected"));
+        }
+        let start = output.len() - offset;
+        for index in 0..match_length {
+            let byte = output[start + index];
+            output.push(byte);
+        }
+    }
+    if output.len() != size {
+        return Err(format!(
+            "LZ4 block has {} bytes, expected {}",
+            output.len(),
+            size
+        ));
+    }
+    Ok(output)
+}
+
+fn write_sequence(output: &mut Vec<u8>, literals: &[u8], match_: Option<(u16, usize)>) {
+    let match_length = match_.map_or(0, |(_, length)| length - MIN_MATCH);
+    output.push((literals.len().min(15) << 4 | match_length.min(15)) as u8);
+    write_length(output, literals.len());
+    output.extend_from_slice(literals);
+    if let Some((offset, _)) = match_ {
+        output.extend_from_slice(&offset.to_le_bytes());
+        write_length(output, match_length);
+    }
+}
+
+fn write_length(output: &mut Vec<u8>, length: usize) {
+    if length < 15 {
+        return;
+    }
+    let mut remaining = length - 15;
+    while remaining >= 255 {
+        output.push(255);
+        remaining -= 255;
+    }
+    output.push(remaining as u8);
+}
+
+fn read_length(input: &[u8], position: &mut usize, mut length: usize) -> Result<usize, String> {
+    if length == 15 {
+        loop {
+            let byte = *input.get(*position).ok_or("truncated LZ4 length")?;
+            *position += 1;
+            length += byte as usize;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+    Ok(length)
+}
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2_654_435_761) >> (32 - HASH_LOG)) as usize
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(input: &[u8]) -> Vec<u8> {
+        let compressed = compress(input);
+        assert_eq!(decompress(&compressed, input.len()).unwrap(), input);
+        compressed
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip(b"");
+        round_trip(b"abc");
+        round_trip(b"abcdefghijklmnopqrstuvwxyz");
+        let repeated = b"uniform mat4 uModelMatrix;\n".repeat(64);
+        assert!(round_trip(&repeated).len() < repeated.len() / 4);
+        let run = vec![7; 5000];
+        assert!(round_trip(&run).len() < 64);
+        let mut state = 0x1234_5678u32;
+        let noise: Vec<u8> = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        round_trip(&noise);
+    }
+
+    #[test]
+    fn rejects_invalid_offsets() {
+        assert_eq!(
+            decompress(&[0x10, b'a', 0, 0, 0x00], 5),
+            Err(String::from("invalid LZ4 offset 0"))
+        );
+        assert_eq!(
+            decompress(&[0x10, b'a', 2, 0, 0x00], 5),
+            Err(String::from("invalid LZ4 offset 2"))
+        );
+        assert_eq!(
+            decompress(&[0x10, b'a', 0xff, 0xff, 0x00], 5),
+            Err(String::from("invalid LZ4 offset 65535"))
+        );
+    }
+
+    #[test]
+    fn rejects_overlong_matches() {
+        assert_eq!(
+            decompress(&[0x1f, b'a', 1, 0, 255, 10, 0x00], 16),
+            Err(String::from("LZ4 block is larger than expected"))
+        );
+        assert_eq!(
+            decompress(&[0x30, b'a', b'b', b'c'], 2),
+            Err(String::from("LZ4 block is larger than expected"))
+        );
+        assert_eq!(
+            decompress(&[0x10, b'a', 1, 0], 8),
+            Err(String::from("truncated LZ4 block"))
+        );
+        assert_eq!(
+            decompress(&[0x10, b'a', 1, 0, 0x00], 8),
+            Err(String::from("LZ4 block has 5 bytes, expected 8"))
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        assert_eq!(decompress(&[], 0), Err(String::from("truncated LZ4 block")));
+        assert_eq!(
+            decompress(&[0x20, b'a'], 2),
+            Err(String::from("truncated LZ4 literals"))
+        );
+        assert_eq!(
+            decompress(&[0xf0], 20),
+            Err(String::from("truncated LZ4 length"))
+        );
+        assert_eq!(
+            decompress(&[0x10, b'a', 1], 5),
+            Err(String::from("truncated LZ4 offset"))
+        );
+    }
+}
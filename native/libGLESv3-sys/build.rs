@@ -1,28 +1,69 @@
+use std::collections::HashSet;
 use std::env;
+use std::fmt::Write;
+use std::fs;
 use std::path::PathBuf;
 
 fn main() {
-    let android_ndk_home = PathBuf::from(env::var("ANDROID_NDK_HOME").unwrap());
-    println!(
-        "cargo:rustc-link-search={}",
-        android_ndk_home
-            .join("platforms/android-26/arch-arm64/usr/lib")
-            .to_str()
-            .unwrap()
-    );
-    println!("cargo:rustc-link-lib=GLESv3");
-    let bindings = bindgen::Builder::default()
-        .clang_arg(format!(
+    let mut builder = bindgen::Builder::default().header("wrapper.h");
+    if env::var("CARGO_CFG_TARGET_OS").unwrap() == "android" {
+        let android_ndk_home = PathBuf::from(env::var("ANDROID_NDK_HOME").unwrap());
+        println!(
+            "cargo:rustc-link-search={}",
+            android_ndk_home
+                .join("platforms/android-26/arch-arm64/usr/lib")
+                .to_str()
+                .unwrap()
+        );
+        println!("cargo:rustc-link-lib=GLESv3");
+        builder = builder.clang_arg(format!(
             "-I{}",
             android_ndk_home
                 .join("toolchains/llvm/prebuilt/linux-x86_64/sysroot/usr/include")
                 .to_str()
                 .unwrap()
-        ))
-        .header("wrapper.h")
-        .generate()
-        .expect("can't generate bindings");
+        ));
+    } else {
+        builder = builder.ignore_functions();
+    }
+    let bindings = builder.generate().expect("can't generate bindings");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     bindings
-        .write_to_file(PathBuf::from(env::var("OUT_DIR").unwrap()).join("bindings.rs"))
+        .write_to_file(out_dir.join("bindings.rs"))
         .expect("can't write bindings");
+    fs::write(
+        out_dir.join("enums.rs"),
+        generate_enums(&bindings.to_string()),
+    )
+    .expect("can't write enums");
+}
+
+fn generate_enums(bindings: &str) -> String {
+    let mut values = HashSet::new();
+    let mut arms = String::new();
+    for statement in bindings.split(';') {
+        let statement: String = statement.split_whitespace().collect();
+        if !statement.starts_with("pubconstGL_") {
+            continue;
+        }
+        let mut parts = statement["pubconst".len()..].splitn(2, ":u32=");
+        let (name, value) = match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => (name, value),
+            _ => continue,
+        };
+        if name.contains("_BIT")
+            || name.starts_with("GL_ES_VERSION_")
+            || name == "GL_GLES_PROTOTYPES"
+            || name.chars().any(|char| char.is_ascii_lowercase())
+        {
+            continue;
+        }
+        if values.insert(value.to_string()) {
+            writeln!(arms, "        {} => Some(\"{}\"),", value, name).unwrap();
+        }
+    }
+    format!(
+        "pub fn gl_enum_name(value: GLenum) -> Option<&'static str> {{\n    match value {{\n{}        _ => None,\n    }}\n}}\n",
+        arms
+    )
 }
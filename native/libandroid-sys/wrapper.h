@@ -1 +1,3 @@
 #include <android/native_window_jni.h>
+#include <android/asset_manager_jni.h>
+#include <android/trace.h>
@@ -0,0 +1,115 @@
+use archive::{ArchiveWriter, Compression};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+fn main() {
+    let mut compression = Compression::None;
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--compression" => {
+                compression = match args.next().as_deref() {
+                    Some("none") => Compression::None,
+                    Some("lz4") => Compression::Lz4,
+                    Some("zstd") => Compression::Zstd,
+                    _ => usage(),
+                }
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        usage();
+    }
+    let input = Path::new(&paths[0]);
+    let output = Path::new(&paths[1]);
+
+    let (file_count, bytes) = pack(input, compression);
+    fs::write(output, bytes).unwrap_or_else(|error| {
+        eprintln!("can't write {}: {}", output.display(), error);
+        process::exit(1);
+    });
+    println!("packed {} files into {}", file_count, output.display());
+}
+
+fn pack(input: &Path, compression: Compression) -> (usize, Vec<u8>) {
+    let mut files = Vec::new();
+    collect_files(input, "", &mut files);
+    files.sort();
+    let mut writer = ArchiveWriter::new();
+    for name in files.iter() {
+        let data = fs::read(input.join(name)).unwrap_or_else(|error| {
+            eprintln!("can't read {}: {}", name, error);
+            process::exit(1);
+        });
+        writer.add(name, &data, compression);
+    }
+    (files.len(), writer.finish())
+}
+
+fn collect_files(directory: &Path, prefix: &str, files: &mut Vec<String>) {
+    let entries = fs::read_dir(directory).unwrap_or_else(|error| {
+        eprintln!("can't read {}: {}", directory.display(), error);
+        process::exit(1);
+    });
+    for entry in entries {
+        let entry = entry.unwrap();
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, &format!("{}/", name), files);
+        } else {
+            files.push(name);
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: pack [--compression none|lz4|zstd] <directory> <archive>");
+    process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use archive::Archive;
+
+    #[test]
+    fn packs_directory_trees() {
+        let input = env::temp_dir().join(format!("rustquest-pack-{}", process::id()));
+        fs::create_dir_all(input.join("shaders/include")).unwrap();
+        let source = b"vec3 light(vec3 normal) { return normal; }\n".repeat(16);
+        fs::write(input.join("shaders/include/light.glsl"), &source).unwrap();
+        fs::write(input.join("shaders/lit.frag"), &source).unwrap();
+        fs::write(input.join("scene.json"), b"{}").unwrap();
+
+        for compression in [Compression::None, Compression::Lz4, Compression::Zstd] {
+            let (file_count, bytes) = pack(&input, compression);
+            assert_eq!(file_count, 3);
+            let archive = Archive::new(bytes).unwrap();
+            let names: Vec<&str> = archive
+                .entries()
+                .iter()
+                .map(|entry| entry.name.as_str())
+                .collect();
+            assert_eq!(
+                names,
+                [
+                    "scene.json",
+                    "shaders/include/light.glsl",
+                    "shaders/lit.frag"
+                ]
+            );
+            assert_eq!(
+                archive.entry("shaders/lit.frag").unwrap().compression,
+                compression
+            );
+            assert_eq!(archive.read("shaders/include/light.glsl").unwrap(), source);
+            assert_eq!(archive.read_to_string("scene.json").unwrap(), "{}");
+        }
+        fs::remove_dir_all(&input).unwrap();
+    }
+}
@@ -0,0 +1,17 @@
+#version 300 es
+
+in highp vec3 vColor;
+#ifdef HAS_BASE_COLOR_TEXTURE
+in highp vec2 vTexCoord0;
+
+uniform lowp sampler2D uBaseColorTexture;
+#endif
+
+out highp vec4 fragColor;
+
+void main() {
+    fragColor = vec4(vColor, 1.0);
+#ifdef HAS_BASE_COLOR_TEXTURE
+    fragColor *= texture(uBaseColorTexture, vTexCoord0);
+#endif
+}
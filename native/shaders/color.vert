@@ -0,0 +1,42 @@
+#version 300 es
+
+layout(std140) uniform View {
+    mat4 uViewMatrix;
+    mat4 uProjectionMatrix;
+    vec4 uCameraPosition;
+};
+
+#ifndef INSTANCED
+layout(std140) uniform Object {
+    mat4 uModelMatrix;
+};
+#endif
+
+in vec3 aPosition;
+in vec3 aColor;
+#ifdef INSTANCED
+in mat4 aInstanceModelMatrix;
+in vec4 aInstanceColor;
+#endif
+#ifdef HAS_BASE_COLOR_TEXTURE
+in vec2 aTexCoord0;
+#endif
+
+out vec3 vColor;
+#ifdef HAS_BASE_COLOR_TEXTURE
+out vec2 vTexCoord0;
+#endif
+
+void main() {
+#ifdef INSTANCED
+    mat4 modelMatrix = aInstanceModelMatrix;
+    vColor = aColor * aInstanceColor.rgb;
+#else
+    mat4 modelMatrix = uModelMatrix;
+    vColor = aColor;
+#endif
+    gl_Position = uProjectionMatrix * (uViewMatrix * (modelMatrix * vec4(aPosition, 1.0)));
+#ifdef HAS_BASE_COLOR_TEXTURE
+    vTexCoord0 = aTexCoord0;
+#endif
+}
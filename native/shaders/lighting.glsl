@@ -0,0 +1,161 @@
+#define MAX_LIGHTS 8
+#define LIGHT_DIRECTIONAL 0
+#define LIGHT_POINT 1
+#define LIGHT_SPOT 2
+
+const float PI = 3.14159265359;
+
+struct Light {
+    vec4 positionRange;
+    vec4 directionType;
+    vec4 colorIntensity;
+    vec4 parameters;
+};
+
+layout(std140) uniform Lights {
+    vec4 uEnvironment;
+    ivec4 uLightCount;
+    Light uLights[MAX_LIGHTS];
+};
+
+uniform mediump samplerCube uEnvironmentMap;
+
+#ifdef HAS_SHADOWS
+layout(std140) uniform Shadow {
+    mat4 uShadowMatrix;
+    vec4 uShadowParameters;
+};
+
+uniform highp sampler2DShadow uShadowMap;
+#endif
+
+struct Surface {
+    vec3 position;
+    vec3 normal;
+    vec3 view;
+    vec3 diffuseColor;
+    vec3 f0;
+    float alpha;
+    float roughness;
+};
+
+float distributionGgx(float nDotH, float alpha) {
+    float alpha2 = alpha * alpha;
+    float d = nDotH * nDotH * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * d * d);
+}
+
+float visibilitySmithGgx(float nDotL, float nDotV, float alpha) {
+    float alpha2 = alpha * alpha;
+    float ggxV = nDotL * sqrt(nDotV * nDotV * (1.0 - alpha2) + alpha2);
+    float ggxL = nDotV * sqrt(nDotL * nDotL * (1.0 - alpha2) + alpha2);
+    return 0.5 / max(ggxV + ggxL, 1e-5);
+}
+
+vec3 fresnelSchlick(vec3 f0, float vDotH) {
+    return f0 + (1.0 - f0) * pow(1.0 - vDotH, 5.0);
+}
+
+float rangeAttenuation(float range, float lightDistance) {
+    float attenuation = 1.0 / max(lightDistance * lightDistance, 1e-4);
+    if (range <= 0.0) {
+        return attenuation;
+    }
+    float ratio = lightDistance / range;
+    return clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0) * attenuation;
+}
+
+float spotAttenuation(vec3 lightDirection, vec3 spotDirection, vec2 scaleOffset) {
+    float cosAngle = dot(spotDirection, -lightDirection);
+    float attenuation = clamp(cosAngle * scaleOffset.x + scaleOffset.y, 0.0, 1.0);
+    return attenuation * attenuation;
+}
+
+vec3 environmentBrdf(vec3 f0, float roughness, float nDotV) {
+    const vec4 c0 = vec4(-1.0, -0.0275, -0.572, 0.022);
+    const vec4 c1 = vec4(1.0, 0.0425, 1.04, -0.04);
+    vec4 r = roughness * c0 + c1;
+    float a004 = min(r.x * r.x, exp2(-9.28 * nDotV)) * r.x + r.y;
+    vec2 scaleBias = vec2(-1.04, 1.04) * a004 + r.zw;
+    return f0 * scaleBias.x + scaleBias.y;
+}
+
+#ifdef HAS_SHADOWS
+float sampleShadow(vec3 position, vec3 normal) {
+    vec4 shadowPosition = uShadowMatrix * vec4(position + normal * uShadowParameters.y, 1.0);
+    vec3 coord = shadowPosition.xyz / shadowPosition.w;
+    if (any(lessThan(coord, vec3(0.0))) || any(greaterThan(coord, vec3(1.0)))) {
+        return 1.0;
+    }
+    coord.z -= uShadowParameters.x;
+    float shadow = 0.0;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            vec2 offset = vec2(float(x), float(y)) * uShadowParameters.z;
+            shadow += texture(uShadowMap, vec3(coord.xy + offset, coord.z));
+        }
+    }
+    return shadow / 9.0;
+}
+#endif
+
+vec3 shadeLight(Surface surface, Light light) {
+    int lightType = int(light.directionType.w);
+    vec3 lightDirection;
+    float attenuation = 1.0;
+    if (lightType == LIGHT_DIRECTIONAL) {
+        lightDirection = -normalize(light.directionType.xyz);
+    } else {
+        vec3 toLight = light.positionRange.xyz - surface.position;
+        float lightDistance = length(toLight);
+        lightDirection = toLight / lightDistance;
+        attenuation = rangeAttenuation(light.positionRange.w, lightDistance);
+        if (lightType == LIGHT_SPOT) {
+            attenuation *= spotAttenuation(
+                lightDirection,
+                normalize(light.directionType.xyz),
+                light.parameters.xy
+            );
+        }
+    }
+#ifdef HAS_SHADOWS
+    if (light.parameters.z > 0.0) {
+        attenuation *= sampleShadow(surface.position, surface.normal);
+    }
+#endif
+
+    float nDotL = clamp(dot(surface.normal, lightDirection), 0.0, 1.0);
+    if (nDotL <= 0.0 || attenuation <= 0.0) {
+        return vec3(0.0);
+    }
+    vec3 halfVector = normalize(lightDirection + surface.view);
+    float nDotV = clamp(abs(dot(surface.normal, surface.view)), 1e-4, 1.0);
+    float nDotH = clamp(dot(surface.normal, halfVector), 0.0, 1.0);
+    float vDotH = clamp(dot(surface.view, halfVector), 0.0, 1.0);
+
+    vec3 fresnel = fresnelSchlick(surface.f0, vDotH);
+    vec3 diffuse = (1.0 - fresnel) * surface.diffuseColor / PI;
+    vec3 specular = fresnel
+        * distributionGgx(nDotH, surface.alpha)
+        * visibilitySmithGgx(nDotL, nDotV, surface.alpha);
+    vec3 radiance = light.colorIntensity.rgb * light.colorIntensity.a * attenuation;
+    return (diffuse + specular) * radiance * nDotL;
+}
+
+vec3 shadeEnvironment(Surface surface) {
+    float nDotV = clamp(abs(dot(surface.normal, surface.view)), 1e-4, 1.0);
+    vec3 reflection = reflect(-surface.view, surface.normal);
+    float maxLevel = uEnvironment.y;
+    vec3 irradiance = textureLod(uEnvironmentMap, surface.normal, maxLevel).rgb;
+    vec3 prefiltered = textureLod(uEnvironmentMap, reflection, surface.roughness * maxLevel).rgb;
+    vec3 specular = prefiltered * environmentBrdf(surface.f0, surface.roughness, nDotV);
+    return (irradiance * surface.diffuseColor + specular) * uEnvironment.x;
+}
+
+vec3 shadeSurface(Surface surface) {
+    vec3 color = shadeEnvironment(surface);
+    for (int index = 0; index < uLightCount.x; index++) {
+        color += shadeLight(surface, uLights[index]);
+    }
+    return color;
+}
@@ -0,0 +1,119 @@
+#version 300 es
+
+precision highp float;
+
+#include "lighting.glsl"
+
+layout(std140) uniform View {
+    mat4 uViewMatrix;
+    mat4 uProjectionMatrix;
+    vec4 uCameraPosition;
+};
+
+layout(std140) uniform Material {
+    vec4 uBaseColorFactor;
+    vec4 uEmissiveFactorAlphaCutoff;
+    vec4 uMaterialParameters;
+};
+
+in vec3 vPosition;
+in vec3 vNormal;
+#ifdef HAS_TANGENTS
+in vec4 vTangent;
+#endif
+#ifdef HAS_TEX_COORD_0
+in vec2 vTexCoord0;
+#endif
+in vec4 vColor;
+
+#ifdef HAS_BASE_COLOR_TEXTURE
+uniform lowp sampler2D uBaseColorTexture;
+#endif
+#ifdef HAS_METALLIC_ROUGHNESS_TEXTURE
+uniform lowp sampler2D uMetallicRoughnessTexture;
+#endif
+#ifdef HAS_NORMAL_TEXTURE
+uniform lowp sampler2D uNormalTexture;
+#endif
+#ifdef HAS_OCCLUSION_TEXTURE
+uniform lowp sampler2D uOcclusionTexture;
+#endif
+#ifdef HAS_EMISSIVE_TEXTURE
+uniform lowp sampler2D uEmissiveTexture;
+#endif
+
+out vec4 fragColor;
+
+vec3 getNormal() {
+#ifdef HAS_NORMALS
+    vec3 normal = normalize(vNormal);
+#else
+    vec3 normal = normalize(cross(dFdx(vPosition), dFdy(vPosition)));
+#endif
+    if (!gl_FrontFacing) {
+        normal = -normal;
+    }
+#ifdef HAS_NORMAL_TEXTURE
+    vec3 tangentNormal = texture(uNormalTexture, vTexCoord0).xyz * 2.0 - 1.0;
+    tangentNormal.xy *= uMaterialParameters.z;
+#ifdef HAS_TANGENTS
+    vec3 tangent = normalize(vTangent.xyz - normal * dot(normal, vTangent.xyz));
+    vec3 bitangent = cross(normal, tangent) * vTangent.w;
+#else
+    vec3 positionDx = dFdx(vPosition);
+    vec3 positionDy = dFdy(vPosition);
+    vec2 texCoordDx = dFdx(vTexCoord0);
+    vec2 texCoordDy = dFdy(vTexCoord0);
+    vec3 tangent = positionDx * texCoordDy.t - positionDy * texCoordDx.t;
+    tangent = normalize(tangent - normal * dot(normal, tangent));
+    vec3 bitangent = cross(normal, tangent);
+#endif
+    normal = normalize(mat3(tangent, bitangent, normal) * tangentNormal);
+#endif
+    return normal;
+}
+
+void main() {
+    vec4 baseColor = uBaseColorFactor * vColor;
+#ifdef HAS_BASE_COLOR_TEXTURE
+    baseColor *= texture(uBaseColorTexture, vTexCoord0);
+#endif
+#ifdef ALPHA_MASK
+    if (baseColor.a < uEmissiveFactorAlphaCutoff.w) {
+        discard;
+    }
+#endif
+
+    float metallic = uMaterialParameters.x;
+    float roughness = uMaterialParameters.y;
+#ifdef HAS_METALLIC_ROUGHNESS_TEXTURE
+    vec4 metallicRoughness = texture(uMetallicRoughnessTexture, vTexCoord0);
+    metallic *= metallicRoughness.b;
+    roughness *= metallicRoughness.g;
+#endif
+    roughness = clamp(roughness, 0.04, 1.0);
+    metallic = clamp(metallic, 0.0, 1.0);
+
+    Surface surface;
+    surface.position = vPosition;
+    surface.normal = getNormal();
+    surface.view = normalize(uCameraPosition.xyz - vPosition);
+    surface.diffuseColor = baseColor.rgb * (1.0 - metallic);
+    surface.f0 = mix(vec3(0.04), baseColor.rgb, metallic);
+    surface.roughness = roughness;
+    surface.alpha = roughness * roughness;
+
+    vec3 color = shadeSurface(surface);
+#ifdef HAS_OCCLUSION_TEXTURE
+    float occlusion = texture(uOcclusionTexture, vTexCoord0).r;
+    color = mix(color, color * occlusion, uMaterialParameters.w);
+#endif
+    vec3 emissive = uEmissiveFactorAlphaCutoff.rgb;
+#ifdef HAS_EMISSIVE_TEXTURE
+    emissive *= texture(uEmissiveTexture, vTexCoord0).rgb;
+#endif
+    color += emissive;
+
+    color = color / (1.0 + color);
+    fragColor = vec4(pow(color, vec3(1.0 / 2.2)), baseColor.a);
+}
@@ -0,0 +1,69 @@
+#version 300 es
+
+layout(std140) uniform View {
+    mat4 uViewMatrix;
+    mat4 uProjectionMatrix;
+    vec4 uCameraPosition;
+};
+
+#ifndef INSTANCED
+layout(std140) uniform Object {
+    mat4 uModelMatrix;
+};
+#endif
+
+in vec3 aPosition;
+#ifdef HAS_NORMALS
+in vec3 aNormal;
+#endif
+#ifdef HAS_TANGENTS
+in vec4 aTangent;
+#endif
+#ifdef HAS_TEX_COORD_0
+in vec2 aTexCoord0;
+#endif
+#ifdef HAS_VERTEX_COLORS
+in vec4 aColor;
+#endif
+#ifdef INSTANCED
+in mat4 aInstanceModelMatrix;
+in vec4 aInstanceColor;
+#endif
+
+out vec3 vPosition;
+out vec3 vNormal;
+#ifdef HAS_TANGENTS
+out vec4 vTangent;
+#endif
+#ifdef HAS_TEX_COORD_0
+out vec2 vTexCoord0;
+#endif
+out vec4 vColor;
+
+void main() {
+#ifdef INSTANCED
+    mat4 modelMatrix = aInstanceModelMatrix;
+    vColor = aInstanceColor;
+#else
+    mat4 modelMatrix = uModelMatrix;
+    vColor = vec4(1.0);
+#endif
+#ifdef HAS_VERTEX_COLORS
+    vColor *= aColor;
+#endif
+    vec4 position = modelMatrix * vec4(aPosition, 1.0);
+    mat3 normalMatrix = transpose(inverse(mat3(modelMatrix)));
+#ifdef HAS_NORMALS
+    vNormal = normalMatrix * aNormal;
+#else
+    vNormal = vec3(0.0);
+#endif
+#ifdef HAS_TANGENTS
+    vTangent = vec4(mat3(modelMatrix) * aTangent.xyz, aTangent.w);
+#endif
+#ifdef HAS_TEX_COORD_0
+    vTexCoord0 = aTexCoord0;
+#endif
+    vPosition = position.xyz;
+    gl_Position = uProjectionMatrix * (uViewMatrix * position);
+}
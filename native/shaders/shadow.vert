@@ -0,0 +1,27 @@
+#version 300 es
+
+layout(std140) uniform View {
+    mat4 uViewMatrix;
+    mat4 uProjectionMatrix;
+    vec4 uCameraPosition;
+};
+
+#ifndef INSTANCED
+layout(std140) uniform Object {
+    mat4 uModelMatrix;
+};
+#endif
+
+in vec3 aPosition;
+#ifdef INSTANCED
+in mat4 aInstanceModelMatrix;
+#endif
+
+void main() {
+#ifdef INSTANCED
+    mat4 modelMatrix = aInstanceModelMatrix;
+#else
+    mat4 modelMatrix = uModelMatrix;
+#endif
+    gl_Position = uProjectionMatrix * (uViewMatrix * (modelMatrix * vec4(aPosition, 1.0)));
+}
@@ -1,39 +1,85 @@
-use crate::{Geometry, Program, SwapChain, EGL};
+use crate::assets::{AssetBuffer, Assets};
+use crate::egl::EGL;
+use crate::environment::Environment;
+use crate::frame_stats::{FrameSample, FrameStats};
+use crate::geometry::Geometry;
+use crate::gl::Context;
+use crate::gltf::Material;
+use crate::hud::Hud;
+use crate::image::Image;
+use crate::java;
+use crate::light::Light;
+use crate::math::{Mat4, Quat, Transform, Vec3};
+use crate::profiler;
+use crate::program;
+use crate::program_cache::ProgramCache;
+use crate::renderer::{Eye, Renderer};
+use crate::scene::{Drawable, NodeId, Scene};
+use crate::screenshot::CaptureEyes;
+use crate::shader_library::ShaderLibrary;
+use crate::shadow::ShadowSettings;
+use archive::Archive;
 use jni::sys::{jobject, JavaVM};
-use libGLESv3_sys::{
-    GLboolean, GLsizei, GL_COLOR_BUFFER_BIT, GL_CULL_FACE, GL_DEPTH_ATTACHMENT,
-    GL_DEPTH_BUFFER_BIT, GL_DEPTH_TEST, GL_DRAW_FRAMEBUFFER, GL_FALSE, GL_SCISSOR_TEST,
-    GL_TRIANGLES, GL_UNSIGNED_SHORT,
-};
 use libandroid_sys::ANativeWindow;
 use libvrapi_sys::{
+    ovrButton__ovrButton_A, ovrButton__ovrButton_B, ovrButton__ovrButton_Trigger,
     ovrControllerType__ovrControllerType_TrackedRemote,
+    ovrFrameLayerBlend__VRAPI_FRAME_LAYER_BLEND_ONE_MINUS_SRC_ALPHA,
+    ovrFrameLayerBlend__VRAPI_FRAME_LAYER_BLEND_SRC_ALPHA,
     ovrFrameLayerFlags__VRAPI_FRAME_LAYER_FLAG_CHROMATIC_ABERRATION_CORRECTION,
+    ovrFrameLayerFlags__VRAPI_FRAME_LAYER_FLAG_FIXED_TO_VIEW, ovrLayerProjection2,
     ovrInitializeStatus__VRAPI_INITIALIZE_SUCCESS, ovrInputCapabilityHeader, ovrJava, ovrMobile,
     ovrModeFlags__VRAPI_MODE_FLAG_NATIVE_WINDOW,
     ovrModeFlags__VRAPI_MODE_FLAG_RESET_WINDOW_FULLSCREEN, ovrSubmitFrameDescription2,
+    ovrSystemProperty__VRAPI_SYS_PROP_DISPLAY_REFRESH_RATE,
     ovrSystemProperty__VRAPI_SYS_PROP_SUGGESTED_EYE_TEXTURE_HEIGHT,
-    ovrSystemProperty__VRAPI_SYS_PROP_SUGGESTED_EYE_TEXTURE_WIDTH, ovrTracking, ovrVector3f,
-    ovrQuatf,
-    ovrInputTrackedRemoteCapabilities,
+    ovrSystemProperty__VRAPI_SYS_PROP_SUGGESTED_EYE_TEXTURE_WIDTH, ovrSystemStatus,
+    ovrSystemStatus__VRAPI_SYS_STATUS_RENDER_LATENCY_MILLISECONDS,
+    ovrSystemStatus__VRAPI_SYS_STATUS_TIMEWARP_LATENCY_MILLISECONDS, ovrTracking, ovrTracking2,
+    ovrInputTrackedRemoteCapabilities, ovrInputStateTrackedRemote,
     ovrControllerCapabilities__ovrControllerCaps_RightHand,
 };
+use std::fs;
 use std::mem;
+use std::path::{Path, PathBuf};
 use std::ptr;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ARCHIVE_NAME: &'static str = "data.pak";
+const SHADER_DIRECTORY: &'static str = "/sdcard/rustquest/shaders";
+const FIELD_SIZE: usize = 32;
+const FIELD_SPACING: f32 = 0.5;
+const CUBE_SCALE: f32 = 0.1;
+const FLOOR_OFFSET: f32 = -0.5;
+const FLOOR_THICKNESS: f32 = 0.02;
+const ENVIRONMENT_NAME: &'static str = "environment.ktx2";
+const ENVIRONMENT_INTENSITY: f32 = 1.0;
+const SCREENSHOT_BUTTONS: u32 = ovrButton__ovrButton_A | ovrButton__ovrButton_Trigger;
+const PROFILE_BUTTONS: u32 = ovrButton__ovrButton_B | ovrButton__ovrButton_Trigger;
+const HUD_BUTTONS: u32 = ovrButton__ovrButton_A | ovrButton__ovrButton_B;
+const FRAME_STATS_LOG_INTERVAL: u64 = 720;
+const HUD_CENTER: (f32, f32) = (0.0, -0.2);
+const HUD_HALF_EXTENT: (f32, f32) = (0.25, 0.125);
 
 pub struct App {
     vm: *mut JavaVM,
     java: ovrJava,
-    egl: EGL,
-    swap_chains: [SwapChain; 2],
-    program: Program,
-    geometry: Geometry,
+    archive: Archive<AssetBuffer>,
+    assets: Assets,
+    renderer: Renderer,
+    scene: Scene,
+    controller_node: NodeId,
+    external_files_dir: PathBuf,
+    screenshot_buttons_down: bool,
+    profile_buttons_down: bool,
+    hud_buttons_down: bool,
     resumed: bool,
     window: *mut ANativeWindow,
     vr: *mut ovrMobile,
     frame_index: u64,
-    position: ovrVector3f,
-    orientation: ovrQuatf,
+    frame_stats: FrameStats,
+    hud: Hud,
 }
 
 impl App {
@@ -71,29 +117,148 @@ impl App {
                 ovrSystemProperty__VRAPI_SYS_PROP_SUGGESTED_EYE_TEXTURE_HEIGHT,
             )
         };
-        let egl = EGL::new();
+        let refresh_rate = unsafe {
+            libvrapi_sys::vrapi_GetSystemPropertyFloat(
+                &java,
+                ovrSystemProperty__VRAPI_SYS_PROP_DISPLAY_REFRESH_RATE,
+            )
+        };
+        let files_dir = unsafe { java::get_files_dir(java.Env as _, activity) };
+        let external_files_dir = unsafe { java::get_external_files_dir(java.Env as _, activity) };
+        let assets = unsafe { Assets::from_activity(java.Env as _, activity) };
+        let archive = assets
+            .map(ARCHIVE_NAME)
+            .and_then(Archive::new)
+            .unwrap_or_else(|error| panic!("can't open archive {}: {}", ARCHIVE_NAME, error));
+        let gl = Context::new(EGL::new());
+        let mut shader_library = ShaderLibrary::new(&gl);
+        shader_library.add_sources_from_archive(&archive, "shaders");
+        shader_library.set_program_cache(ProgramCache::open(
+            &files_dir.join("program_cache.bin"),
+            &program::get_driver_string(),
+        ));
+        if cfg!(debug_assertions) && Path::new(SHADER_DIRECTORY).is_dir() {
+            shader_library.set_watch_directory(Path::new(SHADER_DIRECTORY));
+        }
+        let environment = match archive.read(ENVIRONMENT_NAME) {
+            Ok(bytes) => Environment::from_ktx2(&gl, &bytes, ENVIRONMENT_INTENSITY)
+                .unwrap_or_else(|error| panic!("can't load {}: {}", ENVIRONMENT_NAME, error)),
+            Err(_) => Environment::procedural(&gl, ENVIRONMENT_INTENSITY),
+        };
+        let hud = Hud::new(&gl);
+        let mut renderer = Renderer::new(&gl, shader_library, environment, width, height);
+        let cube_mesh = renderer.add_mesh(Geometry::cube(&gl));
+        renderer.set_materials(vec![Material {
+            metallic_factor: 0.0,
+            roughness_factor: 0.4,
+            ..Material::default()
+        }]);
+        renderer.set_lights(vec![
+            Light {
+                shadow: Some(ShadowSettings::default()),
+                ..Light::directional(Vec3::new(-0.3, -1.0, -0.4), Vec3::new(1.0, 0.95, 0.85), 2.0)
+            },
+            Light::point(
+                Vec3::new(-2.0, -1.0, -2.0),
+                Vec3::new(1.0, 0.3, 0.1),
+                4.0,
+                Some(4.0),
+            ),
+            Light::point(
+                Vec3::new(2.0, -1.0, -2.0),
+                Vec3::new(0.1, 0.4, 1.0),
+                4.0,
+                Some(4.0),
+            ),
+        ]);
+        let mut scene = Scene::new();
+        let controller_node = scene.create_node("controller");
+        let cube_node = scene.create_node("cube");
+        scene.set_parent(cube_node, Some(controller_node)).unwrap();
+        scene.set_transform(
+            cube_node,
+            Transform {
+                scale: Vec3::new(CUBE_SCALE, CUBE_SCALE, CUBE_SCALE),
+                ..Transform::IDENTITY
+            },
+        );
+        scene.set_drawable(
+            cube_node,
+            Some(Drawable {
+                mesh: cube_mesh,
+                material: Some(0),
+            }),
+        );
+        let field_node = scene.create_node("field");
+        scene.set_transform(
+            field_node,
+            Transform {
+                translation: Vec3::new(0.0, -1.5, 0.0),
+                ..Transform::IDENTITY
+            },
+        );
+        let floor_node = scene.create_node("floor");
+        scene.set_parent(floor_node, Some(field_node)).unwrap();
+        let floor_extent = FIELD_SIZE as f32 * FIELD_SPACING * 0.5;
+        scene.set_transform(
+            floor_node,
+            Transform {
+                translation: Vec3::new(0.0, FLOOR_OFFSET, 0.0),
+                scale: Vec3::new(floor_extent, FLOOR_THICKNESS, floor_extent),
+                ..Transform::IDENTITY
+            },
+        );
+        scene.set_drawable(
+            floor_node,
+            Some(Drawable {
+                mesh: cube_mesh,
+                material: Some(0),
+            }),
+        );
+        for x in 0..FIELD_SIZE {
+            for z in 0..FIELD_SIZE {
+                let node = scene.create_node(&format!("field {} {}", x, z));
+                scene.set_parent(node, Some(field_node)).unwrap();
+                let offset = (FIELD_SIZE - 1) as f32 * 0.5;
+                scene.set_transform(
+                    node,
+                    Transform {
+                        translation: Vec3::new(
+                            (x as f32 - offset) * FIELD_SPACING,
+                            0.0,
+                            (z as f32 - offset) * FIELD_SPACING,
+                        ),
+                        scale: Vec3::new(CUBE_SCALE, CUBE_SCALE, CUBE_SCALE),
+                        ..Transform::IDENTITY
+                    },
+                );
+                scene.set_drawable(
+                    node,
+                    Some(Drawable {
+                        mesh: cube_mesh,
+                        material: Some(0),
+                    }),
+                );
+            }
+        }
         App {
             vm,
             java,
-            egl,
-            swap_chains: [SwapChain::new(width, height), SwapChain::new(width, height)],
-            program: Program::new(),
-            geometry: Geometry::new(),
+            archive,
+            assets,
+            renderer,
+            scene,
+            controller_node,
+            external_files_dir,
+            screenshot_buttons_down: false,
+            profile_buttons_down: false,
+            hud_buttons_down: false,
             resumed: false,
             window: ptr::null_mut(),
             vr: ptr::null_mut(),
             frame_index: 0,
-            position: ovrVector3f {
-                x: 0.0,
-                y: 0.0,
-                z: 0.0,
-            },
-            orientation: ovrQuatf {
-                x: 0.0,
-                y: 0.0,
-                z: 0.0,
-                w: 1.0,
-            },
+            frame_stats: FrameStats::new(refresh_rate as f64),
+            hud,
         }
     }
 
@@ -108,6 +273,7 @@ impl App {
     }
 
     pub fn handle_input(&mut self) {
+        profile_scope!("input");
         unsafe {
             for index in 0.. {
                 logv!("enumerate input device {}", index);
@@ -139,14 +305,93 @@ impl App {
                             panic!("can't get input tracking state");
                         }
 
-                        self.position = tracking.HeadPose.Pose.__bindgen_anon_1.Position;
-                        self.orientation = tracking.HeadPose.Pose.Orientation;
+                        logv!("get input state");
+                        let mut input = mem::zeroed::<ovrInputStateTrackedRemote>();
+                        input.Header.ControllerType = header.Type;
+                        if libvrapi_sys::vrapi_GetCurrentInputState(
+                            self.vr,
+                            header.DeviceID,
+                            &mut input.Header,
+                        ) >= 0
+                        {
+                            let buttons_down =
+                                input.Buttons & SCREENSHOT_BUTTONS == SCREENSHOT_BUTTONS;
+                            if buttons_down && !self.screenshot_buttons_down {
+                                self.capture_screenshot(CaptureEyes::Both);
+                            }
+                            self.screenshot_buttons_down = buttons_down;
+                            let buttons_down = input.Buttons & PROFILE_BUTTONS == PROFILE_BUTTONS;
+                            if buttons_down && !self.profile_buttons_down {
+                                if profiler::is_capturing() {
+                                    self.stop_profiling();
+                                } else {
+                                    self.start_profiling();
+                                }
+                            }
+                            self.profile_buttons_down = buttons_down;
+                            let buttons_down = input.Buttons & HUD_BUTTONS == HUD_BUTTONS;
+                            if buttons_down && !self.hud_buttons_down {
+                                self.toggle_hud();
+                            }
+                            self.hud_buttons_down = buttons_down;
+                        }
+
+                        let position = tracking.HeadPose.Pose.__bindgen_anon_1.Position;
+                        let orientation = tracking.HeadPose.Pose.Orientation;
+                        self.scene.set_transform(
+                            self.controller_node,
+                            Transform {
+                                translation: Vec3::new(position.x, position.y, position.z),
+                                rotation: Quat::new(
+                                    orientation.x,
+                                    orientation.y,
+                                    orientation.z,
+                                    orientation.w,
+                                ),
+                                scale: Vec3::ONE,
+                            },
+                        );
                     }
                 }
             }
         }
     }
 
+    pub fn reload_shaders(&mut self) {
+        self.renderer.reload_shaders();
+    }
+
+    pub fn capture_screenshot(&mut self, eyes: CaptureEyes) {
+        self.renderer.request_screenshot(eyes);
+    }
+
+    pub fn set_hud_visible(&mut self, visible: bool) {
+        self.hud.set_visible(visible);
+    }
+
+    pub fn toggle_hud(&mut self) {
+        self.hud.toggle();
+    }
+
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.frame_stats
+    }
+
+    pub fn start_profiling(&mut self) {
+        logi!("start profile capture");
+        profiler::start_capture();
+    }
+
+    pub fn stop_profiling(&mut self) {
+        let capture = profiler::stop_capture();
+        logi!("stop profile capture with {} events", capture.event_count());
+        let path = self.output_path("trace", "json");
+        thread::spawn(move || match capture.write_chrome_trace(&path) {
+            Ok(()) => logi!("wrote trace {}", path.display()),
+            Err(error) => loge!("can't write trace {}: {}", path.display(), error),
+        });
+    }
+
     pub fn render_frame(&mut self) {
         unsafe {
             if self.vr.is_null() {
@@ -154,16 +399,12 @@ impl App {
             }
 
             self.frame_index += 1;
+            let frame_start_time = libvrapi_sys::vrapi_GetTimeInSeconds();
 
-            let translation = libvrapi_sys::ovrMatrix4f_CreateTranslation(
-                self.position.x,
-                self.position.y,
-                self.position.z,
-            );
-            let rotation = libvrapi_sys::ovrMatrix4f_CreateFromQuaternion(&self.orientation);
-            let mut model_matrix = rotation;
-            model_matrix = libvrapi_sys::ovrMatrix4f_Multiply(&translation, &model_matrix);
-            model_matrix = libvrapi_sys::ovrMatrix4f_Transpose(&model_matrix);
+            {
+                profile_scope!("update");
+                self.scene.update();
+            }
 
             logv!("get predicted display time");
             let display_time =
@@ -176,93 +417,147 @@ impl App {
             layer.Header.Flags =
                 ovrFrameLayerFlags__VRAPI_FRAME_LAYER_FLAG_CHROMATIC_ABERRATION_CORRECTION;
             layer.HeadPose = tracking.HeadPose;
-            for (index, swap_chain) in self.swap_chains.iter_mut().enumerate() {
+            for (index, swap_chain) in self.renderer.swap_chains().iter().enumerate() {
                 layer.Textures[index].ColorSwapChain = swap_chain.color_swap_chain();
                 layer.Textures[index].SwapChainIndex = swap_chain.index();
                 layer.Textures[index].TexCoordsFromTanAngles =
                     libvrapi_sys::ovrMatrix4f_TanAngleMatrixFromProjection(
                         &tracking.Eye[index].ProjectionMatrix,
                     );
+            }
 
-                libGLESv3_sys::glBindFramebuffer(
-                    GL_DRAW_FRAMEBUFFER,
-                    swap_chain.frame_buffer(swap_chain.index() as usize),
-                );
-
-                libGLESv3_sys::glEnable(GL_CULL_FACE);
-                libGLESv3_sys::glEnable(GL_DEPTH_TEST);
-                libGLESv3_sys::glEnable(GL_SCISSOR_TEST);
-                libGLESv3_sys::glClearColor(0.1, 0.1, 0.1, 0.0);
-                libGLESv3_sys::glScissor(0, 0, swap_chain.width(), swap_chain.height());
-                libGLESv3_sys::glViewport(0, 0, swap_chain.width(), swap_chain.height());
-                libGLESv3_sys::glClear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT);
-                libGLESv3_sys::glUseProgram(self.program.program());
-                libGLESv3_sys::glUniformMatrix4fv(
-                    self.program.uniform_location("uModelMatrix"),
-                    1,
-                    GL_FALSE as GLboolean,
-                    model_matrix.M.as_ptr() as *const _,
-                );
-                let view_matrix =
-                    libvrapi_sys::ovrMatrix4f_Transpose(&tracking.Eye[index].ViewMatrix);
-                libGLESv3_sys::glUniformMatrix4fv(
-                    self.program.uniform_location("uViewMatrix"),
-                    1,
-                    GL_FALSE as GLboolean,
-                    view_matrix.M.as_ptr() as *const _,
-                );
-                let projection_matrix =
-                    libvrapi_sys::ovrMatrix4f_Transpose(&tracking.Eye[index].ProjectionMatrix);
-                libGLESv3_sys::glUniformMatrix4fv(
-                    self.program.uniform_location("uProjectionMatrix"),
-                    1,
-                    GL_FALSE as GLboolean,
-                    projection_matrix.M.as_ptr() as *const _,
-                );
-                libGLESv3_sys::glBindVertexArray(self.geometry.vertex_array());
-                libGLESv3_sys::glDrawElements(
-                    GL_TRIANGLES,
-                    self.geometry.count(),
-                    GL_UNSIGNED_SHORT,
-                    ptr::null_mut(),
-                );
-                libGLESv3_sys::glBindVertexArray(0);
-                libGLESv3_sys::glUseProgram(0);
-
-                libGLESv3_sys::glClearColor(0.0, 0.0, 0.0, 1.0);
-                libGLESv3_sys::glScissor(0, 0, 1, swap_chain.height());
-                libGLESv3_sys::glClear(GL_COLOR_BUFFER_BIT);
-                libGLESv3_sys::glScissor(swap_chain.width() - 1, 0, 1, swap_chain.height());
-                libGLESv3_sys::glClear(GL_COLOR_BUFFER_BIT);
-                libGLESv3_sys::glScissor(0, 0, swap_chain.width(), 1);
-                libGLESv3_sys::glClear(GL_COLOR_BUFFER_BIT);
-                libGLESv3_sys::glScissor(0, swap_chain.height() - 1, swap_chain.width(), 1);
-                libGLESv3_sys::glClear(GL_COLOR_BUFFER_BIT);
-
-                let attachments = [GL_DEPTH_ATTACHMENT];
-                libGLESv3_sys::glInvalidateFramebuffer(
-                    GL_DRAW_FRAMEBUFFER,
-                    attachments.len() as GLsizei,
-                    attachments.as_ptr(),
-                );
-                libGLESv3_sys::glFlush();
-                libGLESv3_sys::glBindFramebuffer(GL_DRAW_FRAMEBUFFER, 0);
-                swap_chain.advance();
+            let eye = |index: usize| Eye {
+                view_matrix: Mat4::from_columns(
+                    libvrapi_sys::ovrMatrix4f_Transpose(&tracking.Eye[index].ViewMatrix).M,
+                ),
+                projection_matrix: Mat4::from_columns(
+                    libvrapi_sys::ovrMatrix4f_Transpose(&tracking.Eye[index].ProjectionMatrix).M,
+                ),
+            };
+            self.renderer.render_frame(&self.scene, &[eye(0), eye(1)]);
+            for screenshot in self.renderer.poll_screenshots() {
+                self.save_screenshot(screenshot);
             }
 
             logv!("submit frame");
-            let layers = [&layer.Header as *const _];
+            let hud_layer = if self.hud.is_visible() {
+                profile_scope!("hud");
+                let hud_layer = self.hud_layer(&tracking);
+                let swap_chain = &self.renderer.swap_chains()[0];
+                self.hud
+                    .update(&self.frame_stats, swap_chain.width(), swap_chain.height());
+                Some(hud_layer)
+            } else {
+                None
+            };
+
+            let mut layers = vec![&layer.Header as *const _];
+            if let Some(hud_layer) = hud_layer.as_ref() {
+                layers.push(&hud_layer.Header as *const _);
+            }
             let mut frame = mem::zeroed::<ovrSubmitFrameDescription2>();
             frame.Flags = 0;
             frame.SwapInterval = 1;
             frame.FrameIndex = self.frame_index;
             frame.DisplayTime = display_time;
-            frame.LayerCount = 1;
+            frame.LayerCount = layers.len() as u32;
             frame.Layers = layers.as_ptr();
-            libvrapi_sys::vrapi_SubmitFrame2(self.vr, &frame);
+            let submit_time = libvrapi_sys::vrapi_GetTimeInSeconds();
+            {
+                profile_scope!("submit frame");
+                libvrapi_sys::vrapi_SubmitFrame2(self.vr, &frame);
+            }
+
+            self.frame_stats.record(FrameSample {
+                frame_index: self.frame_index,
+                frame_start_time,
+                predicted_display_time: display_time,
+                submit_time,
+                app_latency: self
+                    .latency_status(ovrSystemStatus__VRAPI_SYS_STATUS_RENDER_LATENCY_MILLISECONDS),
+                compositor_latency: self.latency_status(
+                    ovrSystemStatus__VRAPI_SYS_STATUS_TIMEWARP_LATENCY_MILLISECONDS,
+                ),
+            });
+            if self.frame_index % FRAME_STATS_LOG_INTERVAL == 0 {
+                self.log_frame_stats();
+            }
         }
     }
 
+    fn hud_layer(&self, tracking: &ovrTracking2) -> ovrLayerProjection2 {
+        unsafe {
+            let mut layer = libvrapi_sys::vrapi_DefaultLayerProjection2();
+            layer.Header.Flags =
+                ovrFrameLayerFlags__VRAPI_FRAME_LAYER_FLAG_CHROMATIC_ABERRATION_CORRECTION
+                    | ovrFrameLayerFlags__VRAPI_FRAME_LAYER_FLAG_FIXED_TO_VIEW;
+            layer.Header.SrcBlend = ovrFrameLayerBlend__VRAPI_FRAME_LAYER_BLEND_SRC_ALPHA;
+            layer.Header.DstBlend = ovrFrameLayerBlend__VRAPI_FRAME_LAYER_BLEND_ONE_MINUS_SRC_ALPHA;
+            layer.HeadPose = tracking.HeadPose;
+            let projection = libvrapi_sys::ovrMatrix4f_CreateProjection(
+                HUD_CENTER.0 - HUD_HALF_EXTENT.0,
+                HUD_CENTER.0 + HUD_HALF_EXTENT.0,
+                HUD_CENTER.1 - HUD_HALF_EXTENT.1,
+                HUD_CENTER.1 + HUD_HALF_EXTENT.1,
+                1.0,
+                0.0,
+            );
+            let swap_chain = self.hud.swap_chain();
+            for texture in layer.Textures.iter_mut() {
+                texture.ColorSwapChain = swap_chain.color_swap_chain();
+                texture.SwapChainIndex = swap_chain.index();
+                texture.TexCoordsFromTanAngles =
+                    libvrapi_sys::ovrMatrix4f_TanAngleMatrixFromProjection(&projection);
+            }
+            layer
+        }
+    }
+
+    fn latency_status(&self, status: ovrSystemStatus) -> Option<f64> {
+        let milliseconds = unsafe { libvrapi_sys::vrapi_GetSystemStatusFloat(&self.java, status) };
+        if milliseconds > 0.0 {
+            Some(milliseconds as f64 / 1000.0)
+        } else {
+            None
+        }
+    }
+
+    fn log_frame_stats(&self) {
+        let stats = self.frame_stats();
+        let milliseconds = |value: Option<f64>| value.unwrap_or(0.0) * 1000.0;
+        logi!(
+            "{:.1} fps, cpu frame time p50 {:.2} ms p99 {:.2} ms, app latency p50 {:.2} ms, compositor latency p50 {:.2} ms, {} of {} frames dropped",
+            stats.frame_rate().unwrap_or(0.0),
+            milliseconds(stats.cpu_frame_times().percentile(50.0)),
+            milliseconds(stats.cpu_frame_times().percentile(99.0)),
+            milliseconds(stats.app_latencies().percentile(50.0)),
+            milliseconds(stats.compositor_latencies().percentile(50.0)),
+            stats.dropped_frame_count(),
+            stats.frame_count()
+        );
+    }
+
+    fn output_path(&self, prefix: &str, extension: &str) -> PathBuf {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis());
+        self.external_files_dir
+            .join(format!("{}-{}.{}", prefix, timestamp, extension))
+    }
+
+    fn save_screenshot(&self, screenshot: Image) {
+        let path = self.output_path("screenshot", "png");
+        thread::spawn(move || {
+            match screenshot
+                .encode_png()
+                .and_then(|bytes| fs::write(&path, bytes).map_err(|error| error.to_string()))
+            {
+                Ok(()) => logi!("wrote screenshot {}", path.display()),
+                Err(error) => loge!("can't write screenshot {}: {}", path.display(), error),
+            }
+        });
+    }
+
     fn update_vr_mode(&mut self) {
         if self.resumed && !self.window.is_null() {
             if self.vr.is_null() {
@@ -271,13 +566,14 @@ impl App {
                     let mut parms = libvrapi_sys::vrapi_DefaultModeParms(&self.java);
                     parms.Flags &= !ovrModeFlags__VRAPI_MODE_FLAG_RESET_WINDOW_FULLSCREEN;
                     parms.Flags |= ovrModeFlags__VRAPI_MODE_FLAG_NATIVE_WINDOW;
-                    parms.Display = self.egl.display() as u64;
+                    parms.Display = self.renderer.context().egl().display() as u64;
                     parms.WindowSurface = self.window as u64;
-                    parms.ShareContext = self.egl.context() as u64;
+                    parms.ShareContext = self.renderer.context().egl().context() as u64;
                     self.vr = libvrapi_sys::vrapi_EnterVrMode(&parms);
                     if self.vr.is_null() {
                         panic!("can't enter vr mode");
                     }
+                    self.renderer.context().state().invalidate();
                 }
             }
         } else {
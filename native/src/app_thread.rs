@@ -1,4 +1,4 @@
-use crate::App;
+use crate::app::App;
 use jni::sys::{jobject, JNIEnv, JavaVM};
 use libandroid_sys::ANativeWindow;
 use std::ptr;
@@ -62,6 +62,7 @@ impl AppThread {
 
                     let app = app.as_mut().unwrap();
                     app.handle_input();
+                    app.reload_shaders();
                     app.render_frame();
                 }
                 logi!("leaving event loop");
@@ -0,0 +1,384 @@
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Deref;
+
+pub use self::platform::Assets;
+
+pub struct Asset {
+    inner: platform::Asset,
+}
+
+impl Asset {
+    pub fn length(&self) -> u64 {
+        self.inner.length()
+    }
+}
+
+impl Read for Asset {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buffer)
+    }
+}
+
+impl Seek for Asset {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+pub struct AssetBuffer {
+    inner: platform::AssetBuffer,
+}
+
+impl Deref for AssetBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.inner.as_bytes()
+    }
+}
+
+impl Assets {
+    pub fn open(&self, name: &str) -> Result<Asset, String> {
+        Ok(Asset {
+            inner: self.open_platform(name)?,
+        })
+    }
+
+    pub fn map(&self, name: &str) -> Result<AssetBuffer, String> {
+        Ok(AssetBuffer {
+            inner: self.map_platform(name)?,
+        })
+    }
+
+    pub fn read_to_string(&self, name: &str) -> Result<String, String> {
+        String::from_utf8(self.map(name)?.to_vec())
+            .map_err(|_| format!("asset {} is not valid UTF-8", name))
+    }
+}
+
+#[cfg(target_os = "android")]
+mod platform {
+    use crate::java;
+    use jni::sys::{jobject, JNIEnv, JavaVM, JNI_OK, JNI_VERSION_1_6};
+    use libandroid_sys::{AAsset, AAssetManager, AASSET_MODE_BUFFER, AASSET_MODE_STREAMING};
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::io::SeekFrom;
+    use std::os::raw::c_void;
+    use std::ptr;
+    use std::slice;
+
+    const SEEK_SET: i32 = 0;
+    const SEEK_CUR: i32 = 1;
+    const SEEK_END: i32 = 2;
+
+    pub struct Assets {
+        vm: *mut JavaVM,
+        java_asset_manager: jobject,
+        asset_manager: *mut AAssetManager,
+    }
+
+    impl Assets {
+        pub unsafe fn from_activity(env: *mut JNIEnv, activity: jobject) -> Assets {
+            logi!("get asset manager");
+            let mut vm: *mut JavaVM = ptr::null_mut();
+            ((**env).GetJavaVM.unwrap())(env, &mut vm);
+            let local_asset_manager = java::call_object_method(
+                env,
+                activity,
+                "getAssets",
+                "()Landroid/content/res/AssetManager;",
+            );
+            let java_asset_manager = ((**env).NewGlobalRef.unwrap())(env, local_asset_manager);
+            java::delete_local_ref(env, local_asset_manager);
+            let asset_manager =
+                libandroid_sys::AAssetManager_fromJava(env as _, java_asset_manager as _);
+            if asset_manager.is_null() {
+                panic!("can't get asset manager");
+            }
+            Assets {
+                vm,
+                java_asset_manager,
+                asset_manager,
+            }
+        }
+
+        pub fn list(&self, directory: &str) -> Vec<String> {
+            let mut names = Vec::new();
+            unsafe {
+                let directory = CString::new(directory).unwrap();
+                let asset_dir =
+                    libandroid_sys::AAssetManager_openDir(self.asset_manager, directory.as_ptr());
+                if asset_dir.is_null() {
+                    return names;
+                }
+                loop {
+                    let name = libandroid_sys::AAssetDir_getNextFileName(asset_dir);
+                    if name.is_null() {
+                        break;
+                    }
+                    names.push(String::from(CStr::from_ptr(name).to_str().unwrap()));
+                }
+                libandroid_sys::AAssetDir_close(asset_dir);
+            }
+            names.sort();
+            names
+        }
+
+        pub(super) fn open_platform(&self, name: &str) -> Result<Asset, String> {
+            Ok(Asset {
+                asset: self.open_asset(name, AASSET_MODE_STREAMING as i32)?,
+            })
+        }
+
+        pub(super) fn map_platform(&self, name: &str) -> Result<AssetBuffer, String> {
+            let asset = self.open_asset(name, AASSET_MODE_BUFFER as i32)?;
+            unsafe {
+                let buffer = libandroid_sys::AAsset_getBuffer(asset);
+                if buffer.is_null() {
+                    libandroid_sys::AAsset_close(asset);
+                    return Err(format!("can't map asset {}", name));
+                }
+                Ok(AssetBuffer {
+                    asset,
+                    buffer: buffer as *const u8,
+                    length: libandroid_sys::AAsset_getLength64(asset) as usize,
+                })
+            }
+        }
+
+        fn open_asset(&self, name: &str, mode: i32) -> Result<*mut AAsset, String> {
+            let asset = unsafe {
+                libandroid_sys::AAssetManager_open(
+                    self.asset_manager,
+                    CString::new(name).unwrap().as_ptr(),
+                    mode,
+                )
+            };
+            if asset.is_null() {
+                return Err(format!("can't open asset {}", name));
+            }
+            Ok(asset)
+        }
+    }
+
+    impl Drop for Assets {
+        fn drop(&mut self) {
+            unsafe {
+                let mut env: *mut JNIEnv = ptr::null_mut();
+                if ((**self.vm).GetEnv.unwrap())(
+                    self.vm,
+                    &mut env as *mut _ as *mut *mut c_void,
+                    JNI_VERSION_1_6,
+                ) == JNI_OK
+                {
+                    logi!("release asset manager");
+                    ((**env).DeleteGlobalRef.unwrap())(env, self.java_asset_manager);
+                }
+            }
+        }
+    }
+
+    pub struct Asset {
+        asset: *mut AAsset,
+    }
+
+    impl Asset {
+        pub fn length(&self) -> u64 {
+            unsafe { libandroid_sys::AAsset_getLength64(self.asset) as u64 }
+        }
+
+        pub fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let count = unsafe {
+                libandroid_sys::AAsset_read(
+                    self.asset,
+                    buffer.as_mut_ptr() as *mut c_void,
+                    buffer.len() as _,
+                )
+            };
+            if count < 0 {
+                return Err(io::Error::new(io::ErrorKind::Other, "can't read asset"));
+            }
+            Ok(count as usize)
+        }
+
+        pub fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+            let (offset, whence) = match position {
+                SeekFrom::Start(offset) => (offset as i64, SEEK_SET),
+                SeekFrom::Current(offset) => (offset, SEEK_CUR),
+                SeekFrom::End(offset) => (offset, SEEK_END),
+            };
+            let position =
+                unsafe { libandroid_sys::AAsset_seek64(self.asset, offset as _, whence) };
+            if position < 0 {
+                return Err(io::Error::new(io::ErrorKind::Other, "can't seek asset"));
+            }
+            Ok(position as u64)
+        }
+    }
+
+    impl Drop for Asset {
+        fn drop(&mut self) {
+            unsafe {
+                libandroid_sys::AAsset_close(self.asset);
+            }
+        }
+    }
+
+    pub struct AssetBuffer {
+        asset: *mut AAsset,
+        buffer: *const u8,
+        length: usize,
+    }
+
+    impl AssetBuffer {
+        pub fn as_bytes(&self) -> &[u8] {
+            unsafe { slice::from_raw_parts(self.buffer, self.length) }
+        }
+    }
+
+    impl Drop for AssetBuffer {
+        fn drop(&mut self) {
+            unsafe {
+                libandroid_sys::AAsset_close(self.asset);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+mod platform {
+    use std::fs;
+    use std::fs::File;
+    use std::io;
+    use std::io::{Read, Seek, SeekFrom};
+    use std::path::{Path, PathBuf};
+
+    pub struct Assets {
+        directory: PathBuf,
+    }
+
+    impl Assets {
+        pub fn from_directory(directory: &Path) -> Assets {
+            Assets {
+                directory: directory.to_path_buf(),
+            }
+        }
+
+        pub fn list(&self, directory: &str) -> Vec<String> {
+            let mut names: Vec<String> = match fs::read_dir(self.directory.join(directory)) {
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_file())
+                    .filter_map(|entry| entry.file_name().into_string().ok())
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+            names.sort();
+            names
+        }
+
+        pub(super) fn open_platform(&self, name: &str) -> Result<Asset, String> {
+            let file = File::open(self.directory.join(name))
+                .map_err(|error| format!("can't open asset {}: {}", name, error))?;
+            let length = file
+                .metadata()
+                .map_err(|error| format!("can't open asset {}: {}", name, error))?
+                .len();
+            Ok(Asset { file, length })
+        }
+
+        pub(super) fn map_platform(&self, name: &str) -> Result<AssetBuffer, String> {
+            Ok(AssetBuffer {
+                bytes: fs::read(self.directory.join(name))
+                    .map_err(|error| format!("can't map asset {}: {}", name, error))?,
+            })
+        }
+    }
+
+    pub struct Asset {
+        file: File,
+        length: u64,
+    }
+
+    impl Asset {
+        pub fn length(&self) -> u64 {
+            self.length
+        }
+
+        pub fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            self.file.read(buffer)
+        }
+
+        pub fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+            self.file.seek(position)
+        }
+    }
+
+    pub struct AssetBuffer {
+        bytes: Vec<u8>,
+    }
+
+    impl AssetBuffer {
+        pub fn as_bytes(&self) -> &[u8] {
+            &self.bytes
+        }
+    }
+}
+
+#[cfg(all(test, not(target_os = "android")))]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn reads_assets_from_directory() {
+        let directory = env::temp_dir().join(format!("rustquest-assets-{}", process::id()));
+        fs::create_dir_all(directory.join("shaders/include")).unwrap();
+        fs::write(directory.join("shaders/b.frag"), "void main() {}\n").unwrap();
+        fs::write(directory.join("shaders/a.vert"), "void main() {}\n").unwrap();
+        fs::write(directory.join("model.bin"), [0u8, 1, 2, 3, 4, 5, 6, 7]).unwrap();
+        fs::write(directory.join("invalid.txt"), [0xffu8, 0xfe]).unwrap();
+        let assets = Assets::from_directory(&directory);
+
+        assert_eq!(assets.list("shaders"), ["a.vert", "b.frag"]);
+        assert!(assets.list("missing").is_empty());
+        assert_eq!(
+            assets.read_to_string("shaders/b.frag").unwrap(),
+            "void main() {}\n"
+        );
+        assert_eq!(&*assets.map("model.bin").unwrap(), [0, 1, 2, 3, 4, 5, 6, 7]);
+
+        let mut asset = assets.open("model.bin").unwrap();
+        assert_eq!(asset.length(), 8);
+        assert_eq!(asset.seek(SeekFrom::Start(5)).unwrap(), 5);
+        let mut bytes = Vec::new();
+        asset.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, [5, 6, 7]);
+
+        assert_eq!(
+            assets.read_to_string("invalid.txt").err(),
+            Some(String::from("asset invalid.txt is not valid UTF-8"))
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn reports_missing_assets() {
+        let assets = Assets::from_directory(&env::temp_dir().join("rustquest-missing-assets"));
+        assert!(assets
+            .open("missing.bin")
+            .err()
+            .unwrap()
+            .starts_with("can't open asset missing.bin: "));
+        assert!(assets
+            .map("missing.bin")
+            .err()
+            .unwrap()
+            .starts_with("can't map asset missing.bin: "));
+        assert!(assets.read_to_string("missing.bin").is_err());
+    }
+}
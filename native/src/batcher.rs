@@ -0,0 +1,168 @@
+use crate::geometry::{Geometry, Instance};
+use crate::math::Mat4;
+use libGLESv3_sys::{GLsizei, GL_TRIANGLES};
+use std::ptr;
+
+pub struct Batcher {
+    batches: Vec<Vec<(Option<usize>, Instance)>>,
+    ranges: Vec<Vec<(Option<usize>, usize, usize)>>,
+}
+
+impl Batcher {
+    pub fn new() -> Batcher {
+        Batcher {
+            batches: Vec::new(),
+            ranges: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for batch in self.batches.iter_mut() {
+            batch.clear();
+        }
+        for ranges in self.ranges.iter_mut() {
+            ranges.clear();
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        mesh: usize,
+        material: Option<usize>,
+        model_matrix: &Mat4,
+        color: [f32; 4],
+    ) {
+        self.batch_mut(mesh).push((
+            material,
+            Instance {
+                model_matrix: model_matrix.columns,
+                color,
+            },
+        ));
+    }
+
+    pub fn add_all(&mut self, mesh: usize, material: Option<usize>, model_matrices: &[Mat4]) {
+        self.batch_mut(mesh)
+            .extend(model_matrices.iter().map(|model_matrix| {
+                (
+                    material,
+                    Instance {
+                        model_matrix: model_matrix.columns,
+                        color: [1.0, 1.0, 1.0, 1.0],
+                    },
+                )
+            }));
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.batches.iter().map(|batch| batch.len()).sum()
+    }
+
+    pub fn upload(&mut self, meshes: &mut [Geometry]) {
+        self.ranges.resize_with(self.batches.len(), Vec::new);
+        let mut instances = Vec::new();
+        for (mesh, geometry) in meshes.iter_mut().enumerate() {
+            let batch = match self.batches.get_mut(mesh) {
+                Some(batch) if !batch.is_empty() => batch,
+                _ => {
+                    if geometry.instance_count() != 0 {
+                        geometry.set_instances(&[]);
+                    }
+                    continue;
+                }
+            };
+            batch.sort_by_key(|(material, _)| *material);
+            let ranges = &mut self.ranges[mesh];
+            for (index, (material, _)) in batch.iter().enumerate() {
+                match ranges.last_mut() {
+                    Some((last_material, _, count)) if last_material == material => *count += 1,
+                    _ => ranges.push((*material, index, 1)),
+                }
+            }
+            instances.clear();
+            instances.extend(batch.iter().map(|(_, instance)| *instance));
+            geometry.set_instances(&instances);
+        }
+    }
+
+    pub fn draw<F: FnMut(&Geometry, Option<usize>)>(&self, meshes: &[Geometry], mut bind: F) {
+        for (mesh, ranges) in self.ranges.iter().enumerate() {
+            let geometry = &meshes[mesh];
+            for (material, first, count) in ranges.iter().cloned() {
+                bind(geometry, material);
+                geometry.bind_instances(first);
+                unsafe {
+                    gl_check!(glDrawElementsInstanced(
+                        GL_TRIANGLES,
+                        geometry.count(),
+                        geometry.index_type(),
+                        ptr::null(),
+                        count as GLsizei,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn batch_mut(&mut self, mesh: usize) -> &mut Vec<(Option<usize>, Instance)> {
+        if mesh >= self.batches.len() {
+            self.batches.resize_with(mesh + 1, Vec::new);
+        }
+        &mut self.batches[mesh]
+    }
+}
+
+impl Default for Batcher {
+    fn default() -> Batcher {
+        Batcher::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gl::{Context, ProgramHandle};
+    use crate::math::Vec3;
+    use crate::mock_gl::MockGl;
+    use std::rc::Rc;
+
+    #[test]
+    fn batches_instances_by_mesh_and_material() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut meshes = vec![Geometry::cube(&context), Geometry::cube(&context)];
+        let mut batcher = Batcher::new();
+        let model_matrices: Vec<Mat4> = (0..3)
+            .map(|index| Mat4::translation(Vec3::new(index as f32, 0.0, 0.0)))
+            .collect();
+        batcher.add_all(0, Some(1), &model_matrices);
+        batcher.add(0, Some(0), &Mat4::IDENTITY, [1.0, 0.0, 0.0, 1.0]);
+        batcher.add_all(0, None, &model_matrices[..1]);
+        assert_eq!(batcher.instance_count(), 5);
+
+        batcher.upload(&mut meshes);
+        assert_eq!(meshes[0].instance_count(), 5);
+        assert_eq!(meshes[1].instance_count(), 0);
+        let program = ProgramHandle::new(&context);
+        gl.clear_calls();
+        let mut materials = Vec::new();
+        batcher.draw(&meshes, |_, material| {
+            context.state().use_program(program.name());
+            materials.push(material);
+        });
+        assert_eq!(materials, [None, Some(0), Some(1)]);
+        let counts: Vec<i64> = gl
+            .calls()
+            .iter()
+            .filter(|call| call.function == "glDrawElementsInstanced")
+            .map(|call| call.argument(4).int())
+            .collect();
+        assert_eq!(counts, [1, 1, 3]);
+
+        batcher.clear();
+        batcher.upload(&mut meshes);
+        assert_eq!(batcher.instance_count(), 0);
+        assert_eq!(meshes[0].instance_count(), 0);
+        assert!(gl.errors().is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::gl::Context;
+use crate::image;
+use crate::math::Vec3;
+use crate::sampler::{Sampler, SamplerState};
+use crate::texture::{Texture, TextureKind};
+use libGLESv3_sys::{GLsizei, GL_LINEAR_MIPMAP_LINEAR, GL_SRGB8_ALPHA8};
+
+const PROCEDURAL_SIZE: usize = 32;
+const ZENITH_COLOR: Vec3 = Vec3::new(0.15, 0.3, 0.65);
+const HORIZON_COLOR: Vec3 = Vec3::new(0.6, 0.7, 0.8);
+const GROUND_COLOR: Vec3 = Vec3::new(0.2, 0.17, 0.15);
+
+pub struct Environment {
+    texture: Texture,
+    sampler: Sampler,
+    intensity: f32,
+}
+
+impl Environment {
+    pub fn from_ktx2(
+        context: &Context,
+        bytes: &[u8],
+        intensity: f32,
+    ) -> Result<Environment, String> {
+        let texture = Texture::from_ktx2(context, bytes)?;
+        if texture.kind() != TextureKind::Cube {
+            return Err(String::from("environment map is not a cube map"));
+        }
+        Ok(Environment::new(context, texture, intensity))
+    }
+
+    pub fn procedural(context: &Context, intensity: f32) -> Environment {
+        let size = PROCEDURAL_SIZE;
+        let levels = image::mip_level_count(size, size);
+        let texture = Texture::new(
+            context,
+            TextureKind::Cube,
+            GL_SRGB8_ALPHA8,
+            size as GLsizei,
+            size as GLsizei,
+            6,
+            levels as GLsizei,
+        );
+        for level in 0..levels {
+            let level_size = (size >> level).max(1);
+            let blur = level as f32 / (levels - 1).max(1) as f32;
+            for face in 0..6 {
+                let mut data = Vec::with_capacity(level_size * level_size * 4);
+                for y in 0..level_size {
+                    for x in 0..level_size {
+                        let u = 2.0 * (x as f32 + 0.5) / level_size as f32 - 1.0;
+                        let v = 2.0 * (y as f32 + 0.5) / level_size as f32 - 1.0;
+                        let color = sky_color(face_direction(face, u, v).normalize(), blur);
+                        for channel in color.to_array().iter() {
+                            data.push(linear_to_srgb(*channel));
+                        }
+                        data.push(255);
+                    }
+                }
+                texture.upload(
+                    level as GLsizei,
+                    face as GLsizei,
+                    level_size as GLsizei,
+                    level_size as GLsizei,
+                    &data,
+                );
+            }
+        }
+        Environment::new(context, texture, intensity)
+    }
+
+    fn new(context: &Context, texture: Texture, intensity: f32) -> Environment {
+        Environment {
+            texture,
+            sampler: Sampler::new(
+                context,
+                SamplerState {
+                    min_filter: GL_LINEAR_MIPMAP_LINEAR,
+                    ..SamplerState::CLAMP
+                },
+            ),
+            intensity,
+        }
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    pub fn max_level(&self) -> f32 {
+        (self.texture.levels() - 1) as f32
+    }
+
+    pub fn bind(&self, unit: u32) {
+        self.texture.bind(unit);
+        self.sampler.bind(unit);
+    }
+}
+
+fn face_direction(face: usize, u: f32, v: f32) -> Vec3 {
+    match face {
+        0 => Vec3::new(1.0, -v, -u),
+        1 => Vec3::new(-1.0, -v, u),
+        2 => Vec3::new(u, 1.0, v),
+        3 => Vec3::new(u, -1.0, -v),
+        4 => Vec3::new(u, -v, 1.0),
+        _ => Vec3::new(-u, -v, -1.0),
+    }
+}
+
+fn sky_color(direction: Vec3, blur: f32) -> Vec3 {
+    let lerp = |a: Vec3, b: Vec3, t: f32| a + (b - a) * t.clamp(0.0, 1.0);
+    let width = 0.02 + blur;
+    let t = (direction.y / width * 0.5 + 0.5).clamp(0.0, 1.0);
+    let sky = lerp(HORIZON_COLOR, ZENITH_COLOR, direction.y.max(0.0).sqrt());
+    let color = lerp(GROUND_COLOR, sky, t * t * (3.0 - 2.0 * t));
+    let average = (ZENITH_COLOR + HORIZON_COLOR + GROUND_COLOR * 2.0) * 0.25;
+    lerp(color, average, blur * blur)
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let srgb = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (srgb * 255.0 + 0.5) as u8
+}
@@ -0,0 +1,273 @@
+use std::collections::VecDeque;
+
+const HISTORY_LENGTH: usize = 256;
+
+#[derive(Clone, Debug)]
+pub struct RollingHistogram {
+    samples: VecDeque<f64>,
+    capacity: usize,
+}
+
+impl RollingHistogram {
+    pub fn new(capacity: usize) -> RollingHistogram {
+        RollingHistogram {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn samples(&self) -> impl Iterator<Item = f64> + '_ {
+        self.samples.iter().copied()
+    }
+
+    pub fn push(&mut self, sample: f64) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        Some(self.samples.iter().sum::<f64>() / self.samples.len() as f64)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        self.samples.iter().copied().reduce(f64::max)
+    }
+
+    pub fn percentile(&self, percentile: f64) -> Option<f64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<f64> = self.samples.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+        Some(sorted[rank.max(1) - 1])
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameSample {
+    pub frame_index: u64,
+    pub frame_start_time: f64,
+    pub predicted_display_time: f64,
+    pub submit_time: f64,
+    pub app_latency: Option<f64>,
+    pub compositor_latency: Option<f64>,
+}
+
+impl FrameSample {
+    pub fn cpu_frame_time(&self) -> f64 {
+        self.submit_time - self.frame_start_time
+    }
+
+    pub fn app_latency(&self) -> f64 {
+        self.app_latency
+            .unwrap_or(self.predicted_display_time - self.frame_start_time)
+    }
+
+    pub fn compositor_latency(&self) -> f64 {
+        self.compositor_latency
+            .unwrap_or(self.predicted_display_time - self.submit_time)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FrameStats {
+    display_period: f64,
+    latest: Option<FrameSample>,
+    frame_count: u64,
+    dropped_frame_count: u64,
+    display_intervals: RollingHistogram,
+    cpu_frame_times: RollingHistogram,
+    app_latencies: RollingHistogram,
+    compositor_latencies: RollingHistogram,
+}
+
+impl FrameStats {
+    pub fn new(refresh_rate: f64) -> FrameStats {
+        FrameStats {
+            display_period: 1.0 / refresh_rate,
+            latest: None,
+            frame_count: 0,
+            dropped_frame_count: 0,
+            display_intervals: RollingHistogram::new(HISTORY_LENGTH),
+            cpu_frame_times: RollingHistogram::new(HISTORY_LENGTH),
+            app_latencies: RollingHistogram::new(HISTORY_LENGTH),
+            compositor_latencies: RollingHistogram::new(HISTORY_LENGTH),
+        }
+    }
+
+    pub fn display_period(&self) -> f64 {
+        self.display_period
+    }
+
+    pub fn latest(&self) -> Option<&FrameSample> {
+        self.latest.as_ref()
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn dropped_frame_count(&self) -> u64 {
+        self.dropped_frame_count
+    }
+
+    pub fn display_intervals(&self) -> &RollingHistogram {
+        &self.display_intervals
+    }
+
+    pub fn cpu_frame_times(&self) -> &RollingHistogram {
+        &self.cpu_frame_times
+    }
+
+    pub fn app_latencies(&self) -> &RollingHistogram {
+        &self.app_latencies
+    }
+
+    pub fn compositor_latencies(&self) -> &RollingHistogram {
+        &self.compositor_latencies
+    }
+
+    pub fn recent_dropped_frame_count(&self) -> u64 {
+        self.display_intervals
+            .samples()
+            .map(|interval| dropped_frames(interval, self.display_period))
+            .sum()
+    }
+
+    pub fn frame_rate(&self) -> Option<f64> {
+        self.display_intervals
+            .mean()
+            .filter(|interval| *interval > 0.0)
+            .map(|interval| 1.0 / interval)
+    }
+
+    pub fn record(&mut self, sample: FrameSample) {
+        if let Some(latest) = self.latest {
+            let interval = sample.predicted_display_time - latest.predicted_display_time;
+            self.dropped_frame_count += dropped_frames(interval, self.display_period);
+            self.display_intervals.push(interval);
+        }
+        self.cpu_frame_times.push(sample.cpu_frame_time());
+        self.app_latencies.push(sample.app_latency());
+        self.compositor_latencies.push(sample.compositor_latency());
+        self.frame_count += 1;
+        self.latest = Some(sample);
+    }
+}
+
+pub fn dropped_frames(interval: f64, display_period: f64) -> u64 {
+    let periods = (interval / display_period).round();
+    if periods > 1.0 {
+        periods as u64 - 1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: f64 = 1.0 / 72.0;
+
+    fn sample(frame_index: u64, display_index: u64) -> FrameSample {
+        let predicted_display_time = display_index as f64 * PERIOD;
+        FrameSample {
+            frame_index,
+            frame_start_time: predicted_display_time - 0.03,
+            predicted_display_time,
+            submit_time: predicted_display_time - 0.02,
+            app_latency: None,
+            compositor_latency: None,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn queries_percentiles() {
+        let mut histogram = RollingHistogram::new(100);
+        assert_eq!(histogram.percentile(50.0), None);
+        for value in (1..=100).rev() {
+            histogram.push(value as f64);
+        }
+        assert_eq!(histogram.percentile(0.0), Some(1.0));
+        assert_eq!(histogram.percentile(50.0), Some(50.0));
+        assert_eq!(histogram.percentile(99.0), Some(99.0));
+        assert_eq!(histogram.percentile(100.0), Some(100.0));
+        assert_eq!(histogram.max(), Some(100.0));
+        assert_eq!(histogram.mean(), Some(50.5));
+    }
+
+    #[test]
+    fn keeps_most_recent_samples() {
+        let mut histogram = RollingHistogram::new(3);
+        assert!(histogram.is_empty());
+        for value in 0..5 {
+            histogram.push(value as f64);
+        }
+        assert_eq!(histogram.len(), 3);
+        assert_eq!(histogram.samples().collect::<Vec<_>>(), [2.0, 3.0, 4.0]);
+        assert_eq!(histogram.percentile(0.0), Some(2.0));
+    }
+
+    #[test]
+    fn counts_dropped_frames_from_display_time_gaps() {
+        let mut stats = FrameStats::new(72.0);
+        for (frame_index, display_index) in [(1, 1), (2, 2), (3, 4), (4, 5), (5, 9)] {
+            stats.record(sample(frame_index, display_index));
+        }
+        assert_eq!(stats.frame_count(), 5);
+        assert_eq!(stats.dropped_frame_count(), 4);
+        assert_eq!(stats.recent_dropped_frame_count(), 4);
+        assert_eq!(stats.display_intervals().len(), 4);
+        assert_close(stats.frame_rate().unwrap(), 72.0 / 2.0);
+        assert_eq!(stats.latest().unwrap().frame_index, 5);
+    }
+
+    #[test]
+    fn falls_back_to_own_clocks_for_latency() {
+        let mut stats = FrameStats::new(72.0);
+        stats.record(sample(1, 1));
+        stats.record(FrameSample {
+            app_latency: Some(0.05),
+            compositor_latency: Some(0.01),
+            ..sample(2, 2)
+        });
+        let app_latencies: Vec<f64> = stats.app_latencies().samples().collect();
+        let compositor_latencies: Vec<f64> = stats.compositor_latencies().samples().collect();
+        assert_close(app_latencies[0], 0.03);
+        assert_close(app_latencies[1], 0.05);
+        assert_close(compositor_latencies[0], 0.02);
+        assert_close(compositor_latencies[1], 0.01);
+        assert_close(stats.cpu_frame_times().mean().unwrap(), 0.01);
+    }
+
+    #[test]
+    fn ignores_jitter_below_half_a_period() {
+        assert_eq!(dropped_frames(PERIOD * 1.4, PERIOD), 0);
+        assert_eq!(dropped_frames(PERIOD * 1.6, PERIOD), 1);
+        assert_eq!(dropped_frames(0.0, PERIOD), 0);
+    }
+}
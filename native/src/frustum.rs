@@ -0,0 +1,259 @@
+use crate::math::{Aabb, Mat4, Vec3};
+
+const CONTAINMENT_EPSILON: f32 = 1e-4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+impl Plane {
+    pub const EVERYWHERE: Plane = Plane {
+        normal: Vec3::ZERO,
+        distance: f32::MAX,
+    };
+
+    fn from_coefficients(coefficients: [f32; 4]) -> Plane {
+        let normal = Vec3::new(coefficients[0], coefficients[1], coefficients[2]);
+        let length = normal.length();
+        if length < 1e-6 {
+            return if coefficients[3] >= 0.0 {
+                Plane::EVERYWHERE
+            } else {
+                Plane {
+                    normal: Vec3::ZERO,
+                    distance: f32::MIN,
+                }
+            };
+        }
+        Plane {
+            normal: normal * (1.0 / length),
+            distance: coefficients[3] / length,
+        }
+    }
+
+    pub fn signed_distance(&self, point: Vec3) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+
+    fn contains_homogeneous(&self, point: [f32; 4]) -> bool {
+        if *self == Plane::EVERYWHERE {
+            return true;
+        }
+        self.normal.x * point[0]
+            + self.normal.y * point[1]
+            + self.normal.z * point[2]
+            + self.distance * point[3]
+            >= -CONTAINMENT_EPSILON
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frustum {
+    planes: [Plane; 6],
+    corners: Option<[[f32; 4]; 8]>,
+}
+
+impl Frustum {
+    pub fn from_view_projection(view_projection: &Mat4) -> Frustum {
+        let x = view_projection.row(0);
+        let y = view_projection.row(1);
+        let z = view_projection.row(2);
+        let w = view_projection.row(3);
+        let plane = |row: [f32; 4], sign: f32| {
+            Plane::from_coefficients([
+                w[0] + sign * row[0],
+                w[1] + sign * row[1],
+                w[2] + sign * row[2],
+                w[3] + sign * row[3],
+            ])
+        };
+        Frustum {
+            planes: [
+                plane(x, 1.0),
+                plane(x, -1.0),
+                plane(y, 1.0),
+                plane(y, -1.0),
+                plane(z, 1.0),
+                plane(z, -1.0),
+            ],
+            corners: view_projection.inverse().map(|inverse| corners(&inverse)),
+        }
+    }
+
+    pub fn stereo(left: &Frustum, right: &Frustum) -> Frustum {
+        let mut planes = [Plane::EVERYWHERE; 6];
+        for (index, plane) in planes.iter_mut().enumerate() {
+            *plane = if left.plane_contains(index, right) {
+                left.planes[index]
+            } else if right.plane_contains(index, left) {
+                right.planes[index]
+            } else {
+                Plane::EVERYWHERE
+            };
+        }
+        Frustum {
+            planes,
+            corners: None,
+        }
+    }
+
+    pub fn planes(&self) -> &[Plane; 6] {
+        &self.planes
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(center) >= -radius)
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let farthest = Vec3::new(
+                if plane.normal.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.normal.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.normal.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.signed_distance(farthest) >= 0.0
+        })
+    }
+
+    fn plane_contains(&self, index: usize, other: &Frustum) -> bool {
+        match other.corners {
+            Some(ref corners) => corners
+                .iter()
+                .all(|corner| self.planes[index].contains_homogeneous(*corner)),
+            None => false,
+        }
+    }
+}
+
+fn corners(inverse_view_projection: &Mat4) -> [[f32; 4]; 8] {
+    let mut corners = [[0.0; 4]; 8];
+    for (index, corner) in corners.iter_mut().enumerate() {
+        let ndc = [
+            if index & 1 == 0 { -1.0 } else { 1.0 },
+            if index & 2 == 0 { -1.0 } else { 1.0 },
+            if index & 4 == 0 { -1.0 } else { 1.0 },
+            1.0,
+        ];
+        let mut point = inverse_view_projection.transform(ndc);
+        let sign = if point[3] < 0.0 { -1.0 } else { 1.0 };
+        let length = point.iter().map(|value| value * value).sum::<f32>().sqrt();
+        for value in point.iter_mut() {
+            *value *= sign / length;
+        }
+        *corner = point;
+    }
+    corners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn eye_frustum(x: f32, far: f32) -> Frustum {
+        let projection = Mat4::perspective(FRAC_PI_2, 1.0, 0.1, far);
+        let view = Mat4::translation(Vec3::new(-x, 0.0, 0.0));
+        Frustum::from_view_projection(&(projection * view))
+    }
+
+    #[test]
+    fn extracts_normalized_planes() {
+        let frustum = eye_frustum(0.0, 100.0);
+        for plane in frustum.planes().iter() {
+            assert!((plane.normal.length() - 1.0).abs() < 1e-5);
+        }
+        let near = frustum.planes()[4];
+        assert!((near.signed_distance(Vec3::new(0.0, 0.0, -0.1))).abs() < 1e-5);
+        let far = frustum.planes()[5];
+        assert!((far.signed_distance(Vec3::new(0.0, 0.0, -100.0))).abs() < 1e-3);
+    }
+
+    #[test]
+    fn classifies_points() {
+        let frustum = eye_frustum(0.0, 100.0);
+        assert!(frustum.contains_point(Vec3::new(0.0, 0.0, -1.0)));
+        assert!(frustum.contains_point(Vec3::new(0.9, -0.9, -1.0)));
+        assert!(!frustum.contains_point(Vec3::new(1.1, 0.0, -1.0)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 1.0)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -0.05)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -101.0)));
+    }
+
+    #[test]
+    fn intersects_spheres_and_aabbs() {
+        let frustum = eye_frustum(0.0, 100.0);
+        assert!(frustum.intersects_sphere(Vec3::new(1.5, 0.0, -1.0), 0.5));
+        assert!(!frustum.intersects_sphere(Vec3::new(1.5, 0.0, -1.0), 0.3));
+        assert!(frustum.intersects_sphere(Vec3::new(0.0, 0.0, 0.5), 1.0));
+
+        let aabb = |center: Vec3, extent: f32| {
+            Aabb::new(
+                center - Vec3::new(extent, extent, extent),
+                center + Vec3::new(extent, extent, extent),
+            )
+        };
+        assert!(frustum.intersects_aabb(&aabb(Vec3::new(0.0, 0.0, -5.0), 1.0)));
+        assert!(frustum.intersects_aabb(&aabb(Vec3::new(5.5, 0.0, -5.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&aabb(Vec3::new(7.5, 0.0, -5.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&aabb(Vec3::new(0.0, 0.0, 5.0), 1.0)));
+        assert!(frustum.intersects_aabb(&Aabb::new(
+            Vec3::new(-100.0, -100.0, -50.0),
+            Vec3::new(100.0, 100.0, -40.0)
+        )));
+    }
+
+    #[test]
+    fn handles_infinite_far_planes() {
+        let frustum = eye_frustum(0.0, f32::INFINITY);
+        assert_eq!(frustum.planes()[5], Plane::EVERYWHERE);
+        assert!(frustum.contains_point(Vec3::new(0.0, 0.0, -1.0e6)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn combines_stereo_frustums() {
+        for far in [100.0, f32::INFINITY].iter().cloned() {
+            let left = eye_frustum(-0.032, far);
+            let right = eye_frustum(0.032, far);
+            let stereo = Frustum::stereo(&left, &right);
+
+            let only_left = Vec3::new(-1.03, 0.0, -1.0);
+            assert!(left.contains_point(only_left) && !right.contains_point(only_left));
+            assert!(stereo.contains_point(only_left));
+            let only_right = Vec3::new(1.03, 0.0, -1.0);
+            assert!(!left.contains_point(only_right) && right.contains_point(only_right));
+            assert!(stereo.contains_point(only_right));
+
+            assert!(!stereo.contains_point(Vec3::new(-1.1, 0.0, -1.0)));
+            assert!(!stereo.contains_point(Vec3::new(1.1, 0.0, -1.0)));
+            assert!(!stereo.contains_point(Vec3::new(0.0, 1.1, -1.0)));
+            assert!(!stereo.contains_point(Vec3::new(0.0, 0.0, 1.0)));
+            assert!(!stereo.contains_point(Vec3::new(0.0, 0.0, -0.05)));
+            assert_eq!(stereo.planes()[0], left.planes()[0]);
+            assert_eq!(stereo.planes()[1], right.planes()[1]);
+        }
+    }
+}
@@ -1,161 +1,521 @@
+use crate::gl;
+use crate::gl::Context;
+use crate::math::Aabb;
+use crate::vertex_layout::{
+    ComponentType, Vertex, VertexLayout, INSTANCE_COLOR_LOCATION, INSTANCE_MODEL_MATRIX_LOCATION,
+};
 use libGLESv3_sys::{
-    GLboolean, GLenum, GLint, GLsizei, GLsizeiptr, GLuint, GLushort, GLvoid, GL_ARRAY_BUFFER,
-    GL_ELEMENT_ARRAY_BUFFER, GL_FALSE, GL_FLOAT, GL_STATIC_DRAW,
+    GLboolean, GLenum, GLsizei, GLsizeiptr, GLuint, GLvoid, GL_ARRAY_BUFFER, GL_BYTE,
+    GL_ELEMENT_ARRAY_BUFFER, GL_FALSE, GL_FLOAT, GL_HALF_FLOAT, GL_INT, GL_SHORT, GL_STATIC_DRAW,
+    GL_STREAM_DRAW, GL_TRUE, GL_UNSIGNED_BYTE, GL_UNSIGNED_INT, GL_UNSIGNED_SHORT,
 };
 use std::mem;
+use std::ptr;
+use std::slice;
 
-const VERTICES: &'static [Vertex] = &[
-    Vertex {
-        position: [-1.0, 1.0, -1.0],
-        color: [1.0, 0.0, 1.0],
-    },
-    Vertex {
-        position: [1.0, 1.0, -1.0],
-        color: [0.0, 1.0, 0.0],
-    },
-    Vertex {
-        position: [1.0, 1.0, 1.0],
-        color: [0.0, 0.0, 1.0],
-    },
-    Vertex {
-        position: [-1.0, 1.0, 1.0],
-        color: [1.0, 0.0, 0.0],
-    },
-    Vertex {
-        position: [-1.0, -1.0, -1.0],
-        color: [0.0, 0.0, 1.0],
-    },
-    Vertex {
-        position: [-1.0, -1.0, 1.0],
-        color: [0.0, 1.0, 0.0],
-    },
-    Vertex {
-        position: [1.0, -1.0, 1.0],
-        color: [1.0, 0.0, 1.0],
-    },
-    Vertex {
-        position: [1.0, -1.0, -1.0],
-        color: [1.0, 0.0, 0.0],
-    },
-];
+vertex_struct! {
+    struct CubeVertex {
+        position: [f32; 3] => Position,
+        normal: [f32; 3] => Normal,
+        color: [f32; 3] => Color,
+    }
+}
+
+type CubeFace = ([f32; 3], [f32; 3], [f32; 3], [f32; 3]);
 
-const INDICES: &'static [GLushort] = &[
-    0, 2, 1, 2, 0, 3, 4, 6, 5, 6, 4, 7, 2, 6, 7, 7, 1, 2, 0, 4, 5, 5, 3, 0, 3, 5, 6, 6, 2, 3, 0, 1,
-    7, 7, 4, 0,
+const CUBE_FACES: &[CubeFace] = &[
+    (
+        [1.0, 0.0, 0.0],
+        [0.0, 0.0, -1.0],
+        [0.0, 1.0, 0.0],
+        [1.0, 0.0, 0.0],
+    ),
+    (
+        [-1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0],
+        [0.0, 1.0, 0.0],
+        [0.0, 1.0, 1.0],
+    ),
+    (
+        [0.0, 1.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [0.0, 0.0, -1.0],
+        [0.0, 1.0, 0.0],
+    ),
+    (
+        [0.0, -1.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0],
+        [1.0, 0.0, 1.0],
+    ),
+    (
+        [0.0, 0.0, 1.0],
+        [1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, 0.0, 1.0],
+    ),
+    (
+        [0.0, 0.0, -1.0],
+        [-1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [1.0, 1.0, 0.0],
+    ),
 ];
 
+const CUBE_CORNERS: &[(f32, f32)] = &[(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct Instance {
+    pub model_matrix: [[f32; 4]; 4],
+    pub color: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Indices<'a> {
+    U16(&'a [u16]),
+    U32(&'a [u32]),
+}
+
+impl<'a> Indices<'a> {
+    fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    fn type_(&self) -> GLenum {
+        match self {
+            Indices::U16(_) => GL_UNSIGNED_SHORT,
+            Indices::U32(_) => GL_UNSIGNED_INT,
+        }
+    }
+
+    fn as_bytes(&self) -> &'a [u8] {
+        unsafe {
+            match *self {
+                Indices::U16(indices) => {
+                    slice::from_raw_parts(indices.as_ptr() as *const u8, mem::size_of_val(indices))
+                }
+                Indices::U32(indices) => {
+                    slice::from_raw_parts(indices.as_ptr() as *const u8, mem::size_of_val(indices))
+                }
+            }
+        }
+    }
+}
+
 pub struct Geometry {
-    vertex_array: GLuint,
-    vertex_buffer: GLuint,
-    index_buffer: GLuint,
+    layout: VertexLayout,
+    bounds: Option<Aabb>,
+    count: GLsizei,
+    index_type: GLenum,
+    context: Context,
+    vertex_array: gl::VertexArray,
+    _vertex_buffer: gl::Buffer,
+    _index_buffer: gl::Buffer,
+    instance_buffer: Option<gl::Buffer>,
+    instance_capacity: usize,
+    instance_count: GLsizei,
 }
 
 impl Geometry {
-    pub fn new() -> Geometry {
+    pub fn cube(context: &Context) -> Geometry {
+        let mut vertices = Vec::with_capacity(CUBE_FACES.len() * CUBE_CORNERS.len());
+        let mut indices = Vec::with_capacity(CUBE_FACES.len() * 6);
+        for (normal, u, v, color) in CUBE_FACES.iter().cloned() {
+            let base = vertices.len() as u16;
+            for (s, t) in CUBE_CORNERS.iter().cloned() {
+                vertices.push(CubeVertex {
+                    position: [
+                        normal[0] + s * u[0] + t * v[0],
+                        normal[1] + s * u[1] + t * v[1],
+                        normal[2] + s * u[2] + t * v[2],
+                    ],
+                    normal,
+                    color,
+                });
+            }
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+        Geometry::new(
+            context,
+            &vertices,
+            &CubeVertex::layout(),
+            Indices::U16(&indices),
+        )
+    }
+
+    pub fn new<V>(
+        context: &Context,
+        vertices: &[V],
+        layout: &VertexLayout,
+        indices: Indices,
+    ) -> Geometry {
+        if mem::size_of::<V>() != layout.stride() {
+            panic!(
+                "vertex size {} doesn't match layout stride {}",
+                mem::size_of::<V>(),
+                layout.stride()
+            );
+        }
+        let bytes = unsafe {
+            slice::from_raw_parts(vertices.as_ptr() as *const u8, mem::size_of_val(vertices))
+        };
+        Geometry::from_bytes(context, bytes, layout, indices)
+    }
+
+    pub fn from_bytes(
+        context: &Context,
+        vertex_bytes: &[u8],
+        layout: &VertexLayout,
+        indices: Indices,
+    ) -> Geometry {
         unsafe {
+            context.state().bind_vertex_array(0);
+
             logi!("generate vertex buffer");
-            let mut vertex_buffer = 0;
-            libGLESv3_sys::glGenBuffers(1, &mut vertex_buffer);
-            libGLESv3_sys::glBindBuffer(GL_ARRAY_BUFFER, vertex_buffer);
-            libGLESv3_sys::glBufferData(
+            let vertex_buffer = gl::Buffer::new(context);
+            gl_check!(glBindBuffer(GL_ARRAY_BUFFER, vertex_buffer.name()));
+            gl_check!(glBufferData(
                 GL_ARRAY_BUFFER,
-                (VERTICES.len() * mem::size_of::<Vertex>()) as GLsizeiptr,
-                VERTICES.as_ptr() as *const _,
+                vertex_bytes.len() as GLsizeiptr,
+                vertex_bytes.as_ptr() as *const _,
                 GL_STATIC_DRAW,
-            );
-            libGLESv3_sys::glBindBuffer(GL_ARRAY_BUFFER, 0);
+            ));
+            gl_check!(glBindBuffer(GL_ARRAY_BUFFER, 0));
 
             logi!("generate index buffer");
-            let mut index_buffer = 0;
-            libGLESv3_sys::glGenBuffers(1, &mut index_buffer);
-            libGLESv3_sys::glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, index_buffer);
-            libGLESv3_sys::glBufferData(
+            let index_bytes = indices.as_bytes();
+            let index_buffer = gl::Buffer::new(context);
+            gl_check!(glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, index_buffer.name()));
+            gl_check!(glBufferData(
                 GL_ELEMENT_ARRAY_BUFFER,
-                (INDICES.len() * mem::size_of::<GLushort>()) as GLsizeiptr,
-                INDICES.as_ptr() as *const _,
+                index_bytes.len() as GLsizeiptr,
+                index_bytes.as_ptr() as *const _,
                 GL_STATIC_DRAW,
-            );
-            libGLESv3_sys::glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, 0);
+            ));
+            gl_check!(glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, 0));
 
             logi!("generate vertex array");
-            let mut vertex_array = 0;
-            libGLESv3_sys::glGenVertexArrays(1, &mut vertex_array);
+            let vertex_array = gl::VertexArray::new(context);
 
             logi!("record vertex array");
-            libGLESv3_sys::glBindVertexArray(vertex_array);
-            libGLESv3_sys::glBindBuffer(GL_ARRAY_BUFFER, vertex_buffer);
-            let attrib_pointers = [
-                AttribPointer {
-                    size: 3,
-                    type_: GL_FLOAT,
-                    normalized: GL_FALSE as GLboolean,
-                    stride: 24,
-                    pointer: 0 as *mut GLvoid,
-                },
-                AttribPointer {
-                    size: 3,
-                    type_: GL_FLOAT,
-                    normalized: GL_FALSE as GLboolean,
-                    stride: 24,
-                    pointer: 12 as *mut GLvoid,
-                },
-            ];
-            for (index, attrib_pointer) in attrib_pointers.iter().enumerate() {
-                libGLESv3_sys::glVertexAttribPointer(
-                    index as GLuint,
-                    attrib_pointer.size,
-                    attrib_pointer.type_,
-                    attrib_pointer.normalized,
-                    attrib_pointer.stride,
-                    attrib_pointer.pointer,
-                );
-                libGLESv3_sys::glEnableVertexAttribArray(index as GLuint);
+            context.state().bind_vertex_array(vertex_array.name());
+            gl_check!(glBindBuffer(GL_ARRAY_BUFFER, vertex_buffer.name()));
+            for attribute in layout.attributes().iter() {
+                let index = attribute.semantic.location();
+                if attribute.format.component_type.is_integer() && !attribute.normalized {
+                    gl_check!(glVertexAttribIPointer(
+                        index,
+                        attribute.format.component_count as i32,
+                        get_component_type(attribute.format.component_type),
+                        layout.stride() as GLsizei,
+                        attribute.offset as *const GLvoid,
+                    ));
+                } else {
+                    gl_check!(glVertexAttribPointer(
+                        index,
+                        attribute.format.component_count as i32,
+                        get_component_type(attribute.format.component_type),
+                        if attribute.normalized {
+                            GL_TRUE as GLboolean
+                        } else {
+                            GL_FALSE as GLboolean
+                        },
+                        layout.stride() as GLsizei,
+                        attribute.offset as *const GLvoid,
+                    ));
+                }
+                gl_check!(glEnableVertexAttribArray(index));
             }
-            libGLESv3_sys::glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, index_buffer);
-            libGLESv3_sys::glBindVertexArray(0);
+            gl_check!(glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, index_buffer.name()));
+            context.state().bind_vertex_array(0);
 
             Geometry {
-                vertex_buffer,
-                index_buffer,
+                layout: layout.clone(),
+                bounds: layout.bounds(vertex_bytes),
+                count: indices.len() as GLsizei,
+                index_type: indices.type_(),
+                context: context.clone(),
+                _vertex_buffer: vertex_buffer,
+                _index_buffer: index_buffer,
                 vertex_array,
+                instance_buffer: None,
+                instance_capacity: 0,
+                instance_count: 0,
             }
         }
     }
 
+    pub fn layout(&self) -> &VertexLayout {
+        &self.layout
+    }
+
+    pub fn bounds(&self) -> Option<&Aabb> {
+        self.bounds.as_ref()
+    }
+
     pub fn count(&self) -> GLsizei {
-        INDICES.len() as GLsizei
+        self.count
+    }
+
+    pub fn index_type(&self) -> GLenum {
+        self.index_type
     }
 
     pub fn vertex_array(&self) -> GLuint {
-        self.vertex_array
+        self.vertex_array.name()
+    }
+
+    pub fn instance_count(&self) -> GLsizei {
+        self.instance_count
+    }
+
+    pub fn set_instances(&mut self, instances: &[Instance]) {
+        unsafe {
+            if self.instance_buffer.is_none() {
+                self.create_instance_buffer();
+            }
+            if instances.len() > self.instance_capacity {
+                self.instance_capacity = instances.len().next_power_of_two();
+            }
+            let size = mem::size_of::<Instance>();
+            gl_check!(glBindBuffer(GL_ARRAY_BUFFER, self.instance_buffer_name()));
+            gl_check!(glBufferData(
+                GL_ARRAY_BUFFER,
+                (self.instance_capacity * size) as GLsizeiptr,
+                ptr::null(),
+                GL_STREAM_DRAW,
+            ));
+            gl_check!(glBufferSubData(
+                GL_ARRAY_BUFFER,
+                0,
+                mem::size_of_val(instances) as GLsizeiptr,
+                instances.as_ptr() as *const _,
+            ));
+            gl_check!(glBindBuffer(GL_ARRAY_BUFFER, 0));
+            self.instance_count = instances.len() as GLsizei;
+        }
     }
-}
 
-impl Drop for Geometry {
-    fn drop(&mut self) {
+    pub fn bind_instances(&self, first: usize) {
         unsafe {
-            logi!("delete vertex array");
-            libGLESv3_sys::glDeleteVertexArrays(1, &self.vertex_array);
+            self.context
+                .state()
+                .bind_vertex_array(self.vertex_array.name());
+            self.point_instance_attributes(first);
+        }
+    }
 
-            logi!("delete index buffer");
-            libGLESv3_sys::glDeleteBuffers(1, &self.index_buffer);
+    unsafe fn create_instance_buffer(&mut self) {
+        logi!("generate instance buffer");
+        self.instance_buffer = Some(gl::Buffer::new(&self.context));
 
-            logi!("delete vertex_buffer");
-            libGLESv3_sys::glDeleteBuffers(1, &self.vertex_buffer);
+        logi!("record instance attributes");
+        self.context
+            .state()
+            .bind_vertex_array(self.vertex_array.name());
+        self.point_instance_attributes(0);
+        for column in 0..4 {
+            let index = INSTANCE_MODEL_MATRIX_LOCATION + column;
+            gl_check!(glVertexAttribDivisor(index, 1));
+            gl_check!(glEnableVertexAttribArray(index));
         }
+        gl_check!(glVertexAttribDivisor(INSTANCE_COLOR_LOCATION, 1));
+        gl_check!(glEnableVertexAttribArray(INSTANCE_COLOR_LOCATION));
+        self.context.state().bind_vertex_array(0);
+    }
+
+    fn instance_buffer_name(&self) -> GLuint {
+        self.instance_buffer.as_ref().map_or(0, gl::Buffer::name)
+    }
+
+    unsafe fn point_instance_attributes(&self, first: usize) {
+        let stride = mem::size_of::<Instance>();
+        let offset = first * stride;
+        gl_check!(glBindBuffer(GL_ARRAY_BUFFER, self.instance_buffer_name()));
+        for column in 0..4 {
+            gl_check!(glVertexAttribPointer(
+                INSTANCE_MODEL_MATRIX_LOCATION + column,
+                4,
+                GL_FLOAT,
+                GL_FALSE as GLboolean,
+                stride as GLsizei,
+                (offset + column as usize * mem::size_of::<[f32; 4]>()) as *const GLvoid,
+            ));
+        }
+        gl_check!(glVertexAttribPointer(
+            INSTANCE_COLOR_LOCATION,
+            4,
+            GL_FLOAT,
+            GL_FALSE as GLboolean,
+            stride as GLsizei,
+            (offset + mem::size_of::<[[f32; 4]; 4]>()) as *const GLvoid,
+        ));
+        gl_check!(glBindBuffer(GL_ARRAY_BUFFER, 0));
     }
 }
 
-struct AttribPointer {
-    size: GLint,
-    type_: GLenum,
-    normalized: GLboolean,
-    stride: GLsizei,
-    pointer: *const GLvoid,
+fn get_component_type(component_type: ComponentType) -> GLenum {
+    match component_type {
+        ComponentType::Byte => GL_BYTE,
+        ComponentType::UnsignedByte => GL_UNSIGNED_BYTE,
+        ComponentType::Short => GL_SHORT,
+        ComponentType::UnsignedShort => GL_UNSIGNED_SHORT,
+        ComponentType::Int => GL_INT,
+        ComponentType::UnsignedInt => GL_UNSIGNED_INT,
+        ComponentType::HalfFloat => GL_HALF_FLOAT,
+        ComponentType::Float => GL_FLOAT,
+    }
 }
 
-#[repr(C)]
-struct Vertex {
-    position: [f32; 3],
-    color: [f32; 3],
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_gl::MockGl;
+    use crate::vertex_layout::{Format, Semantic};
+    use std::rc::Rc;
+
+    #[test]
+    fn records_vertex_array() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let cube = Geometry::cube(&context);
+        let calls = gl.calls();
+        let begin = calls
+            .iter()
+            .position(|call| {
+                call.function == "glBindVertexArray"
+                    && call.argument(0).int() == cube.vertex_array() as i64
+            })
+            .unwrap();
+        let end = begin
+            + calls[begin..]
+                .iter()
+                .position(|call| {
+                    call.function == "glBindVertexArray" && call.argument(0).int() == 0
+                })
+                .unwrap();
+        let recorded = &calls[begin..end];
+        assert_eq!(
+            recorded
+                .iter()
+                .filter(|call| call.function == "glEnableVertexAttribArray")
+                .count(),
+            CubeVertex::layout().attributes().len()
+        );
+        assert!(recorded.iter().any(|call| {
+            call.function == "glBindBuffer"
+                && call.argument(0).int() == GL_ELEMENT_ARRAY_BUFFER as i64
+                && call.argument(1).int() == cube._index_buffer.name() as i64
+        }));
+        assert_eq!(cube.count(), 36);
+        assert_eq!(cube.index_type(), GL_UNSIGNED_SHORT);
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn uses_integer_pointers_for_unnormalized_integer_attributes() {
+        vertex_struct! {
+            struct SkinnedVertex {
+                position: [f32; 3] => Position,
+                color: [u8; 4] => Color,
+                joints: [u16; 4] => Joints,
+                weights: [f32; 4] => Weights,
+            }
+        }
+
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let vertex = SkinnedVertex {
+            position: [0.0; 3],
+            color: [255; 4],
+            joints: [0, 1, 2, 3],
+            weights: [0.25; 4],
+        };
+        let geometry = Geometry::new(
+            &context,
+            &[vertex],
+            &SkinnedVertex::layout(),
+            Indices::U32(&[0, 0, 0]),
+        );
+        let pointers: Vec<(&str, i64, i64)> = gl
+            .calls()
+            .iter()
+            .filter(|call| call.function.starts_with("glVertexAttrib"))
+            .map(|call| {
+                (
+                    call.function,
+                    call.argument(0).int(),
+                    call.argument(2).int(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            pointers,
+            [
+                (
+                    "glVertexAttribPointer",
+                    Semantic::Position.location() as i64,
+                    GL_FLOAT as i64
+                ),
+                (
+                    "glVertexAttribPointer",
+                    Semantic::Color.location() as i64,
+                    GL_UNSIGNED_BYTE as i64
+                ),
+                (
+                    "glVertexAttribIPointer",
+                    Semantic::Joints.location() as i64,
+                    GL_UNSIGNED_SHORT as i64
+                ),
+                (
+                    "glVertexAttribPointer",
+                    Semantic::Weights.location() as i64,
+                    GL_FLOAT as i64
+                ),
+            ]
+        );
+        assert_eq!(geometry.index_type(), GL_UNSIGNED_INT);
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn points_half_float_attributes_at_float_pointers() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let layout = VertexLayout::packed(&[
+            (Semantic::Position, Format::FLOAT3, false),
+            (
+                Semantic::TexCoord0,
+                Format::new(ComponentType::HalfFloat, 2),
+                false,
+            ),
+        ]);
+        assert_eq!(layout.stride(), 16);
+        let _geometry = Geometry::from_bytes(&context, &[0; 48], &layout, Indices::U16(&[0, 1, 2]));
+        let tex_coords = gl
+            .calls()
+            .into_iter()
+            .find(|call| {
+                call.function.starts_with("glVertexAttrib")
+                    && call.argument(0).int() == Semantic::TexCoord0.location() as i64
+            })
+            .unwrap();
+        assert_eq!(tex_coords.function, "glVertexAttribPointer");
+        assert_eq!(tex_coords.argument(2).int(), GL_HALF_FLOAT as i64);
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn deletes_objects_on_drop() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let cube = Geometry::cube(&context);
+        assert_eq!(gl.object_count(), 3);
+        drop(cube);
+        assert_eq!(gl.object_count(), 0);
+    }
 }
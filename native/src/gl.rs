@@ -0,0 +1,230 @@
+#[cfg(target_os = "android")]
+use crate::egl::EGL;
+use crate::gl_backend;
+use crate::gl_backend::Gl;
+#[cfg(target_os = "android")]
+use crate::gl_backend::NativeGl;
+#[cfg(target_os = "android")]
+use crate::gl_debug;
+use crate::gl_state::Object;
+use crate::state_cache::StateCache;
+use libGLESv3_sys::{GLenum, GLuint};
+use std::cell::{RefCell, RefMut};
+use std::fmt;
+use std::rc::Rc;
+
+struct Shared {
+    state: RefCell<StateCache>,
+    #[cfg(target_os = "android")]
+    egl: Option<EGL>,
+}
+
+#[derive(Clone)]
+pub struct Context {
+    shared: Rc<Shared>,
+}
+
+impl Context {
+    #[cfg(target_os = "android")]
+    pub fn new(egl: EGL) -> Context {
+        let context = Context::from_backend(Rc::new(NativeGl), Some(egl));
+        gl_debug::enable_debug_output();
+        context
+    }
+
+    pub fn with_backend(backend: Rc<dyn Gl>) -> Context {
+        Context::from_backend(
+            backend,
+            #[cfg(target_os = "android")]
+            None,
+        )
+    }
+
+    fn from_backend(
+        backend: Rc<dyn Gl>,
+        #[cfg(target_os = "android")] egl: Option<EGL>,
+    ) -> Context {
+        gl_backend::make_current(backend);
+        Context {
+            shared: Rc::new(Shared {
+                state: RefCell::new(StateCache::new(cfg!(debug_assertions))),
+                #[cfg(target_os = "android")]
+                egl,
+            }),
+        }
+    }
+
+    #[cfg(target_os = "android")]
+    pub fn egl(&self) -> &EGL {
+        self.shared
+            .egl
+            .as_ref()
+            .unwrap_or_else(|| panic!("context isn't backed by EGL"))
+    }
+
+    pub fn state(&self) -> RefMut<'_, StateCache> {
+        self.shared.state.borrow_mut()
+    }
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "Context({:p})", Rc::as_ptr(&self.shared))
+    }
+}
+
+macro_rules! gl_handle {
+    ($name:ident, $kind:expr, $gen:ident, $delete:ident, $object:expr) => {
+        #[derive(Debug)]
+        pub struct $name {
+            context: Context,
+            name: GLuint,
+        }
+
+        impl $name {
+            pub fn new(context: &Context) -> $name {
+                let mut name = 0;
+                unsafe {
+                    gl_check!($gen(1, &mut name));
+                }
+                if name == 0 {
+                    panic!("can't generate {}", $kind);
+                }
+                logv!("generate {} {}", $kind, name);
+                $name {
+                    context: context.clone(),
+                    name,
+                }
+            }
+
+            pub fn context(&self) -> &Context {
+                &self.context
+            }
+
+            pub fn name(&self) -> GLuint {
+                self.name
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                logv!("delete {} {}", $kind, self.name);
+                unsafe {
+                    gl_check!($delete(1, &self.name));
+                }
+                if let Some(object) = $object {
+                    self.context.state().forget(object, self.name);
+                }
+            }
+        }
+    };
+}
+
+gl_handle!(Buffer, "buffer", glGenBuffers, glDeleteBuffers, None);
+gl_handle!(
+    VertexArray,
+    "vertex array",
+    glGenVertexArrays,
+    glDeleteVertexArrays,
+    Some(Object::VertexArray)
+);
+gl_handle!(
+    Texture,
+    "texture",
+    glGenTextures,
+    glDeleteTextures,
+    Some(Object::Texture)
+);
+gl_handle!(
+    Sampler,
+    "sampler",
+    glGenSamplers,
+    glDeleteSamplers,
+    Some(Object::Sampler)
+);
+gl_handle!(
+    Renderbuffer,
+    "renderbuffer",
+    glGenRenderbuffers,
+    glDeleteRenderbuffers,
+    None
+);
+gl_handle!(
+    Framebuffer,
+    "frame buffer",
+    glGenFramebuffers,
+    glDeleteFramebuffers,
+    Some(Object::Framebuffer)
+);
+gl_handle!(Query, "query", glGenQueries, glDeleteQueries, None);
+
+#[derive(Debug)]
+pub struct Shader {
+    _context: Context,
+    name: GLuint,
+}
+
+impl Shader {
+    pub fn new(context: &Context, type_: GLenum) -> Shader {
+        let name = unsafe { gl_check!(glCreateShader(type_)) };
+        if name == 0 {
+            panic!("can't create shader");
+        }
+        logv!("create shader {}", name);
+        Shader {
+            _context: context.clone(),
+            name,
+        }
+    }
+
+    pub fn name(&self) -> GLuint {
+        self.name
+    }
+}
+
+impl Drop for Shader {
+    fn drop(&mut self) {
+        logv!("delete shader {}", self.name);
+        unsafe {
+            gl_check!(glDeleteShader(self.name));
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProgramHandle {
+    context: Context,
+    name: GLuint,
+}
+
+impl ProgramHandle {
+    pub fn new(context: &Context) -> ProgramHandle {
+        let name = unsafe { gl_check!(glCreateProgram()) };
+        if name == 0 {
+            panic!("can't create program");
+        }
+        logv!("create program {}", name);
+        ProgramHandle {
+            context: context.clone(),
+            name,
+        }
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    pub fn name(&self) -> GLuint {
+        self.name
+    }
+}
+
+impl Drop for ProgramHandle {
+    fn drop(&mut self) {
+        logv!("delete program {}", self.name);
+        unsafe {
+            gl_check!(glDeleteProgram(self.name));
+        }
+        self.context.state().forget(Object::Program, self.name);
+    }
+}
@@ -0,0 +1,515 @@
+macro_rules! gl_functions {
+    (
+        $(fn $function:ident($($argument:ident: $type:ty),* $(,)?) $(-> $result:ty)?;)*
+        $(
+            extension fn $extension:ident(
+                $($extension_argument:ident: $extension_type:ty),* $(,)?
+            ) $(-> $extension_result:ty)?;
+        )*
+    ) => {
+        /// The OpenGL ES entry points, implemented by the driver on device and by
+        /// emulators in tests.
+        ///
+        /// # Safety
+        ///
+        /// Every function has the preconditions of the GL function it is named after.
+        #[allow(non_snake_case, clippy::too_many_arguments, clippy::missing_safety_doc)]
+        pub trait Gl {
+            $(unsafe fn $function(&self, $($argument: $type),*) $(-> $result)?;)*
+            $(
+                unsafe fn $extension(
+                    &self,
+                    $($extension_argument: $extension_type),*
+                ) $(-> $extension_result)?;
+            )*
+        }
+
+        #[cfg(target_os = "android")]
+        #[allow(clippy::too_many_arguments)]
+        impl Gl for NativeGl {
+            $(unsafe fn $function(&self, $($argument: $type),*) $(-> $result)? {
+                libGLESv3_sys::$function($($argument),*)
+            })*
+            $(
+                unsafe fn $extension(
+                    &self,
+                    $($extension_argument: $extension_type),*
+                ) $(-> $extension_result)? {
+                    static ADDRESS: std::sync::atomic::AtomicUsize =
+                        std::sync::atomic::AtomicUsize::new(0);
+                    let mut address = ADDRESS.load(std::sync::atomic::Ordering::Relaxed);
+                    if address == 0 {
+                        address = std::mem::transmute::<_, usize>(libEGL_sys::eglGetProcAddress(
+                            concat!(stringify!($extension), "\0").as_ptr() as *const _,
+                        ));
+                        if address == 0 {
+                            panic!("can't get {}", stringify!($extension));
+                        }
+                        ADDRESS.store(address, std::sync::atomic::Ordering::Relaxed);
+                    }
+                    let function: unsafe extern "C" fn(
+                        $($extension_type),*
+                    ) $(-> $extension_result)? = std::mem::transmute(address);
+                    function($($extension_argument),*)
+                }
+            )*
+        }
+
+        #[cfg(test)]
+        #[allow(clippy::too_many_arguments)]
+        impl<T: Emulator> Gl for T {
+            $(unsafe fn $function(&self, $($argument: $type),*) $(-> $result)? {
+                FromValue::from_value(
+                    self.call(stringify!($function), &[$(Argument::from($argument)),*]),
+                )
+            })*
+            $(
+                unsafe fn $extension(
+                    &self,
+                    $($extension_argument: $extension_type),*
+                ) $(-> $extension_result)? {
+                    FromValue::from_value(self.call(
+                        stringify!($extension),
+                        &[$(Argument::from($extension_argument)),*],
+                    ))
+                }
+            )*
+        }
+    };
+}
+
+use libGLESv3_sys::{
+    GLbitfield, GLboolean, GLchar, GLenum, GLfloat, GLint, GLintptr, GLsizei, GLsizeiptr, GLsync,
+    GLubyte, GLuint, GLuint64,
+};
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::rc::Rc;
+
+gl_functions! {
+    fn glActiveTexture(texture: GLenum);
+    fn glAttachShader(program: GLuint, shader: GLuint);
+    fn glBeginQuery(target: GLenum, id: GLuint);
+    fn glBindAttribLocation(program: GLuint, index: GLuint, name: *const GLchar);
+    fn glBindBuffer(target: GLenum, buffer: GLuint);
+    fn glBindBufferRange(
+        target: GLenum,
+        index: GLuint,
+        buffer: GLuint,
+        offset: GLintptr,
+        size: GLsizeiptr,
+    );
+    fn glBindFramebuffer(target: GLenum, framebuffer: GLuint);
+    fn glBindRenderbuffer(target: GLenum, renderbuffer: GLuint);
+    fn glBindSampler(unit: GLuint, sampler: GLuint);
+    fn glBindTexture(target: GLenum, texture: GLuint);
+    fn glBindVertexArray(array: GLuint);
+    fn glBlendFuncSeparate(
+        sfactor_rgb: GLenum,
+        dfactor_rgb: GLenum,
+        sfactor_alpha: GLenum,
+        dfactor_alpha: GLenum,
+    );
+    fn glBlitFramebuffer(
+        src_x0: GLint,
+        src_y0: GLint,
+        src_x1: GLint,
+        src_y1: GLint,
+        dst_x0: GLint,
+        dst_y0: GLint,
+        dst_x1: GLint,
+        dst_y1: GLint,
+        mask: GLbitfield,
+        filter: GLenum,
+    );
+    fn glBufferData(target: GLenum, size: GLsizeiptr, data: *const c_void, usage: GLenum);
+    fn glBufferSubData(target: GLenum, offset: GLintptr, size: GLsizeiptr, data: *const c_void);
+    fn glCheckFramebufferStatus(target: GLenum) -> GLenum;
+    fn glClear(mask: GLbitfield);
+    fn glClearColor(red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat);
+    fn glClearDepthf(d: GLfloat);
+    fn glClientWaitSync(sync: GLsync, flags: GLbitfield, timeout: GLuint64) -> GLenum;
+    fn glCompileShader(shader: GLuint);
+    fn glCompressedTexSubImage2D(
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: GLenum,
+        image_size: GLsizei,
+        data: *const c_void,
+    );
+    fn glCompressedTexSubImage3D(
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        zoffset: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        depth: GLsizei,
+        format: GLenum,
+        image_size: GLsizei,
+        data: *const c_void,
+    );
+    fn glCreateProgram() -> GLuint;
+    fn glCreateShader(type_: GLenum) -> GLuint;
+    fn glDeleteBuffers(n: GLsizei, buffers: *const GLuint);
+    fn glDeleteFramebuffers(n: GLsizei, framebuffers: *const GLuint);
+    fn glDeleteProgram(program: GLuint);
+    fn glDeleteQueries(n: GLsizei, ids: *const GLuint);
+    fn glDeleteRenderbuffers(n: GLsizei, renderbuffers: *const GLuint);
+    fn glDeleteSamplers(count: GLsizei, samplers: *const GLuint);
+    fn glDeleteShader(shader: GLuint);
+    fn glDeleteSync(sync: GLsync);
+    fn glDeleteTextures(n: GLsizei, textures: *const GLuint);
+    fn glDeleteVertexArrays(n: GLsizei, arrays: *const GLuint);
+    fn glDepthFunc(func: GLenum);
+    fn glDepthMask(flag: GLboolean);
+    fn glDisable(cap: GLenum);
+    fn glDrawBuffers(n: GLsizei, bufs: *const GLenum);
+    fn glDrawElementsInstanced(
+        mode: GLenum,
+        count: GLsizei,
+        type_: GLenum,
+        indices: *const c_void,
+        instancecount: GLsizei,
+    );
+    fn glEnable(cap: GLenum);
+    fn glEnableVertexAttribArray(index: GLuint);
+    fn glEndQuery(target: GLenum);
+    fn glFenceSync(condition: GLenum, flags: GLbitfield) -> GLsync;
+    fn glFlush();
+    fn glFramebufferRenderbuffer(
+        target: GLenum,
+        attachment: GLenum,
+        renderbuffertarget: GLenum,
+        renderbuffer: GLuint,
+    );
+    fn glFramebufferTexture2D(
+        target: GLenum,
+        attachment: GLenum,
+        textarget: GLenum,
+        texture: GLuint,
+        level: GLint,
+    );
+    fn glGenBuffers(n: GLsizei, buffers: *mut GLuint);
+    fn glGenFramebuffers(n: GLsizei, framebuffers: *mut GLuint);
+    fn glGenQueries(n: GLsizei, ids: *mut GLuint);
+    fn glGenRenderbuffers(n: GLsizei, renderbuffers: *mut GLuint);
+    fn glGenSamplers(count: GLsizei, samplers: *mut GLuint);
+    fn glGenTextures(n: GLsizei, textures: *mut GLuint);
+    fn glGenVertexArrays(n: GLsizei, arrays: *mut GLuint);
+    fn glGenerateMipmap(target: GLenum);
+    fn glGetActiveAttrib(
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    );
+    fn glGetActiveUniform(
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    );
+    fn glGetBooleanv(pname: GLenum, data: *mut GLboolean);
+    fn glGetError() -> GLenum;
+    fn glGetFloatv(pname: GLenum, data: *mut GLfloat);
+    fn glGetIntegerv(pname: GLenum, data: *mut GLint);
+    fn glGetProgramBinary(
+        program: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        binary_format: *mut GLenum,
+        binary: *mut c_void,
+    );
+    fn glGetProgramInfoLog(
+        program: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    );
+    fn glGetProgramiv(program: GLuint, pname: GLenum, params: *mut GLint);
+    fn glGetQueryObjectuiv(id: GLuint, pname: GLenum, params: *mut GLuint);
+    fn glGetShaderInfoLog(
+        shader: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    );
+    fn glGetShaderiv(shader: GLuint, pname: GLenum, params: *mut GLint);
+    fn glGetString(name: GLenum) -> *const GLubyte;
+    fn glGetUniformBlockIndex(program: GLuint, uniform_block_name: *const GLchar) -> GLuint;
+    fn glGetUniformLocation(program: GLuint, name: *const GLchar) -> GLint;
+    fn glInvalidateFramebuffer(
+        target: GLenum,
+        num_attachments: GLsizei,
+        attachments: *const GLenum,
+    );
+    fn glIsEnabled(cap: GLenum) -> GLboolean;
+    fn glLinkProgram(program: GLuint);
+    fn glMapBufferRange(
+        target: GLenum,
+        offset: GLintptr,
+        length: GLsizeiptr,
+        access: GLbitfield,
+    ) -> *mut c_void;
+    fn glPolygonOffset(factor: GLfloat, units: GLfloat);
+    fn glProgramBinary(
+        program: GLuint,
+        binary_format: GLenum,
+        binary: *const c_void,
+        length: GLsizei,
+    );
+    fn glProgramParameteri(program: GLuint, pname: GLenum, value: GLint);
+    fn glReadBuffer(src: GLenum);
+    fn glReadPixels(
+        x: GLint,
+        y: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: GLenum,
+        type_: GLenum,
+        pixels: *mut c_void,
+    );
+    fn glRenderbufferStorage(
+        target: GLenum,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    );
+    fn glRenderbufferStorageMultisample(
+        target: GLenum,
+        samples: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    );
+    fn glSamplerParameteri(sampler: GLuint, pname: GLenum, param: GLint);
+    fn glScissor(x: GLint, y: GLint, width: GLsizei, height: GLsizei);
+    fn glShaderSource(
+        shader: GLuint,
+        count: GLsizei,
+        string: *const *const GLchar,
+        length: *const GLint,
+    );
+    fn glTexParameteri(target: GLenum, pname: GLenum, param: GLint);
+    fn glTexStorage2D(
+        target: GLenum,
+        levels: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    );
+    fn glTexStorage3D(
+        target: GLenum,
+        levels: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        depth: GLsizei,
+    );
+    fn glTexSubImage2D(
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: GLenum,
+        type_: GLenum,
+        pixels: *const c_void,
+    );
+    fn glTexSubImage3D(
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        zoffset: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        depth: GLsizei,
+        format: GLenum,
+        type_: GLenum,
+        pixels: *const c_void,
+    );
+    fn glUniform1i(location: GLint, v0: GLint);
+    fn glUniformBlockBinding(
+        program: GLuint,
+        uniform_block_index: GLuint,
+        uniform_block_binding: GLuint,
+    );
+    fn glUnmapBuffer(target: GLenum) -> GLboolean;
+    fn glUseProgram(program: GLuint);
+    fn glVertexAttribDivisor(index: GLuint, divisor: GLuint);
+    fn glVertexAttribIPointer(
+        index: GLuint,
+        size: GLint,
+        type_: GLenum,
+        stride: GLsizei,
+        pointer: *const c_void,
+    );
+    fn glVertexAttribPointer(
+        index: GLuint,
+        size: GLint,
+        type_: GLenum,
+        normalized: GLboolean,
+        stride: GLsizei,
+        pointer: *const c_void,
+    );
+    fn glViewport(x: GLint, y: GLint, width: GLsizei, height: GLsizei);
+
+    extension fn glGetQueryObjectui64vEXT(id: GLuint, pname: GLenum, params: *mut GLuint64);
+}
+
+#[cfg(target_os = "android")]
+pub struct NativeGl;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<dyn Gl>>> = RefCell::new(None);
+}
+
+pub fn make_current(gl: Rc<dyn Gl>) {
+    CURRENT.with(|current| *current.borrow_mut() = Some(gl));
+}
+
+pub fn current() -> Rc<dyn Gl> {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .clone()
+            .unwrap_or_else(|| panic!("no GL backend is current on this thread"))
+    })
+}
+
+#[cfg(test)]
+pub use self::emulation::{Argument, Emulator, Value};
+
+#[cfg(test)]
+use self::emulation::FromValue;
+
+#[cfg(test)]
+mod emulation {
+    use std::ffi::c_void;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Argument {
+        Int(i64),
+        Float(f32),
+        Pointer(usize),
+    }
+
+    impl Argument {
+        pub fn int(self) -> i64 {
+            match self {
+                Argument::Int(value) => value,
+                argument => panic!("expected an integer argument, got {:?}", argument),
+            }
+        }
+
+        pub fn float(self) -> f32 {
+            match self {
+                Argument::Float(value) => value,
+                argument => panic!("expected a float argument, got {:?}", argument),
+            }
+        }
+
+        pub fn pointer<T>(self) -> *mut T {
+            match self {
+                Argument::Pointer(value) => value as *mut T,
+                argument => panic!("expected a pointer argument, got {:?}", argument),
+            }
+        }
+    }
+
+    macro_rules! int_argument {
+        ($($type:ty),*) => {
+            $(impl From<$type> for Argument {
+                fn from(value: $type) -> Argument {
+                    Argument::Int(value as i64)
+                }
+            })*
+        };
+    }
+
+    int_argument!(u8, i32, u32, i64, u64);
+
+    impl From<f32> for Argument {
+        fn from(value: f32) -> Argument {
+            Argument::Float(value)
+        }
+    }
+
+    impl<T> From<*const T> for Argument {
+        fn from(value: *const T) -> Argument {
+            Argument::Pointer(value as usize)
+        }
+    }
+
+    impl<T> From<*mut T> for Argument {
+        fn from(value: *mut T) -> Argument {
+            Argument::Pointer(value as usize)
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Value {
+        None,
+        Int(i64),
+        Pointer(*const c_void),
+    }
+
+    pub trait FromValue {
+        fn from_value(value: Value) -> Self;
+    }
+
+    impl FromValue for () {
+        fn from_value(_: Value) {}
+    }
+
+    macro_rules! int_value {
+        ($($type:ty),*) => {
+            $(impl FromValue for $type {
+                fn from_value(value: Value) -> $type {
+                    match value {
+                        Value::Int(value) => value as $type,
+                        value => panic!("expected an integer result, got {:?}", value),
+                    }
+                }
+            })*
+        };
+    }
+
+    int_value!(u8, i32, u32);
+
+    impl<T> FromValue for *const T {
+        fn from_value(value: Value) -> *const T {
+            match value {
+                Value::Pointer(value) => value as *const T,
+                value => panic!("expected a pointer result, got {:?}", value),
+            }
+        }
+    }
+
+    impl<T> FromValue for *mut T {
+        fn from_value(value: Value) -> *mut T {
+            match value {
+                Value::Pointer(value) => value as *mut T,
+                value => panic!("expected a pointer result, got {:?}", value),
+            }
+        }
+    }
+
+    pub trait Emulator {
+        fn call(&self, function: &'static str, arguments: &[Argument]) -> Value;
+    }
+}
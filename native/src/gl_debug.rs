@@ -0,0 +1,154 @@
+macro_rules! gl_check {
+    ($function:ident($($argument:expr),* $(,)?)) => {{
+        let gl = $crate::gl_backend::current();
+        #[allow(clippy::let_unit_value)]
+        let result = gl.$function($($argument),*);
+        if cfg!(debug_assertions) {
+            $crate::gl_debug::check_error(&*gl, stringify!($function), file!(), line!());
+        }
+        result
+    }};
+}
+
+use crate::gl_backend::Gl;
+use crate::log;
+use libGLESv3_sys::{GLenum, GL_EXTENSIONS, GL_NO_ERROR};
+use std::ffi::CStr;
+
+#[cfg(target_os = "android")]
+pub use self::platform::enable_debug_output;
+
+pub fn enum_name(value: GLenum) -> String {
+    match libGLESv3_sys::gl_enum_name(value) {
+        Some(name) => String::from(name),
+        None => format!("{:#06x}", value),
+    }
+}
+
+pub fn has_extension(name: &str) -> bool {
+    let extensions = unsafe { gl_check!(glGetString(GL_EXTENSIONS)) };
+    if extensions.is_null() {
+        return false;
+    }
+    unsafe { CStr::from_ptr(extensions as *const _) }
+        .to_string_lossy()
+        .split_whitespace()
+        .any(|extension| extension == name)
+}
+
+pub fn check_error(gl: &dyn Gl, function: &str, file: &str, line: u32) {
+    loop {
+        let error = unsafe { gl.glGetError() };
+        if error == GL_NO_ERROR {
+            break;
+        }
+        log::loge(&format!(
+            "{} failed at {}:{}: {}",
+            function,
+            file,
+            line,
+            enum_name(error)
+        ));
+    }
+}
+
+#[cfg(target_os = "android")]
+mod platform {
+    use super::{enum_name, has_extension};
+    use crate::log;
+    use libGLESv3_sys::{
+        GLchar, GLenum, GLsizei, GLuint, GL_DEBUG_OUTPUT_KHR, GL_DEBUG_OUTPUT_SYNCHRONOUS_KHR,
+        GL_DEBUG_SEVERITY_HIGH_KHR, GL_DEBUG_SEVERITY_LOW_KHR, GL_DEBUG_SEVERITY_MEDIUM_KHR,
+        PFNGLDEBUGMESSAGECALLBACKKHRPROC,
+    };
+    use std::ffi::{c_void, CStr};
+    use std::{mem, ptr, slice};
+
+    pub fn enable_debug_output() {
+        unsafe {
+            if !has_extension("GL_KHR_debug") {
+                log::logi("KHR_debug is not supported");
+                return;
+            }
+            let debug_message_callback: PFNGLDEBUGMESSAGECALLBACKKHRPROC = mem::transmute(
+                libEGL_sys::eglGetProcAddress(b"glDebugMessageCallbackKHR\0".as_ptr() as *const _),
+            );
+            let debug_message_callback = match debug_message_callback {
+                Some(debug_message_callback) => debug_message_callback,
+                None => {
+                    log::loge("can't get glDebugMessageCallbackKHR");
+                    return;
+                }
+            };
+            log::logi("enable GL debug output");
+            gl_check!(glEnable(GL_DEBUG_OUTPUT_KHR));
+            if cfg!(debug_assertions) {
+                gl_check!(glEnable(GL_DEBUG_OUTPUT_SYNCHRONOUS_KHR));
+            }
+            debug_message_callback(Some(log_debug_message), ptr::null());
+        }
+    }
+
+    unsafe extern "C" fn log_debug_message(
+        source: GLenum,
+        type_: GLenum,
+        id: GLuint,
+        severity: GLenum,
+        length: GLsizei,
+        message: *const GLchar,
+        _: *const c_void,
+    ) {
+        let message = if length < 0 {
+            CStr::from_ptr(message).to_string_lossy()
+        } else {
+            String::from_utf8_lossy(slice::from_raw_parts(message as *const u8, length as usize))
+        };
+        let text = format!(
+            "GL {} {} {}: {}",
+            enum_name(source),
+            enum_name(type_),
+            id,
+            message
+        );
+        match severity {
+            GL_DEBUG_SEVERITY_HIGH_KHR => log::loge(&text),
+            GL_DEBUG_SEVERITY_MEDIUM_KHR => log::logw(&text),
+            GL_DEBUG_SEVERITY_LOW_KHR => log::logi(&text),
+            _ => log::logv(&text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libGLESv3_sys::{
+        GL_DRAW_FRAMEBUFFER_BINDING, GL_FRAMEBUFFER_BINDING, GL_FRAMEBUFFER_COMPLETE,
+        GL_HALF_FLOAT, GL_HALF_FLOAT_OES, GL_INVALID_ENUM, GL_INVALID_OPERATION, GL_TEXTURE_2D,
+        GL_TRUE,
+    };
+
+    #[test]
+    fn names_known_enums() {
+        assert_eq!(enum_name(GL_INVALID_ENUM), "GL_INVALID_ENUM");
+        assert_eq!(enum_name(GL_INVALID_OPERATION), "GL_INVALID_OPERATION");
+        assert_eq!(
+            enum_name(GL_FRAMEBUFFER_COMPLETE),
+            "GL_FRAMEBUFFER_COMPLETE"
+        );
+        assert_eq!(enum_name(GL_TEXTURE_2D), "GL_TEXTURE_2D");
+        assert_eq!(enum_name(GL_HALF_FLOAT), "GL_HALF_FLOAT");
+        assert_eq!(enum_name(GL_HALF_FLOAT_OES), "GL_HALF_FLOAT_OES");
+        assert_eq!(enum_name(0x42), "0x0042");
+    }
+
+    #[test]
+    fn names_aliased_values_after_their_first_definition() {
+        assert_eq!(GL_DRAW_FRAMEBUFFER_BINDING, GL_FRAMEBUFFER_BINDING);
+        assert_eq!(
+            enum_name(GL_DRAW_FRAMEBUFFER_BINDING),
+            "GL_FRAMEBUFFER_BINDING"
+        );
+        assert_eq!(enum_name(GL_TRUE), "GL_TRUE");
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Capability {
+    Blend,
+    CullFace,
+    DepthTest,
+    PolygonOffsetFill,
+    ScissorTest,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 5] = [
+        Capability::Blend,
+        Capability::CullFace,
+        Capability::DepthTest,
+        Capability::PolygonOffsetFill,
+        Capability::ScissorTest,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlendFunc {
+    pub src_rgb: u32,
+    pub dst_rgb: u32,
+    pub src_alpha: u32,
+    pub dst_alpha: u32,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Object {
+    Program,
+    VertexArray,
+    Framebuffer,
+    Texture,
+    Sampler,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StateChange {
+    Enable(Capability, bool),
+    UseProgram(u32),
+    BindVertexArray(u32),
+    BindDrawFramebuffer(u32),
+    BindReadFramebuffer(u32),
+    ActiveTexture(u32),
+    BindTexture(u32, u32),
+    BindSampler(u32, u32),
+    Viewport([i32; 4]),
+    Scissor([i32; 4]),
+    BlendFunc(BlendFunc),
+    DepthFunc(u32),
+    DepthMask(bool),
+    PolygonOffset(f32, f32),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GlState {
+    pub capabilities: BTreeMap<Capability, bool>,
+    pub program: Option<u32>,
+    pub vertex_array: Option<u32>,
+    pub draw_framebuffer: Option<u32>,
+    pub read_framebuffer: Option<u32>,
+    pub active_texture: Option<u32>,
+    pub textures: BTreeMap<(u32, u32), u32>,
+    pub samplers: BTreeMap<u32, u32>,
+    pub viewport: Option<[i32; 4]>,
+    pub scissor: Option<[i32; 4]>,
+    pub blend_func: Option<BlendFunc>,
+    pub depth_func: Option<u32>,
+    pub depth_mask: Option<bool>,
+    pub polygon_offset: Option<(f32, f32)>,
+}
+
+impl GlState {
+    pub fn apply(&mut self, change: StateChange) -> bool {
+        match change {
+            StateChange::Enable(capability, enabled) => {
+                update_entry(&mut self.capabilities, capability, enabled)
+            }
+            StateChange::UseProgram(program) => update(&mut self.program, program),
+            StateChange::BindVertexArray(vertex_array) => {
+                update(&mut self.vertex_array, vertex_array)
+            }
+            StateChange::BindDrawFramebuffer(framebuffer) => {
+                update(&mut self.draw_framebuffer, framebuffer)
+            }
+            StateChange::BindReadFramebuffer(framebuffer) => {
+                update(&mut self.read_framebuffer, framebuffer)
+            }
+            StateChange::ActiveTexture(unit) => update(&mut self.active_texture, unit),
+            StateChange::BindTexture(target, texture) => match self.active_texture {
+                Some(unit) => update_entry(&mut self.textures, (unit, target), texture),
+                None => true,
+            },
+            StateChange::BindSampler(unit, sampler) => {
+                update_entry(&mut self.samplers, unit, sampler)
+            }
+            StateChange::Viewport(viewport) => update(&mut self.viewport, viewport),
+            StateChange::Scissor(scissor) => update(&mut self.scissor, scissor),
+            StateChange::BlendFunc(blend_func) => update(&mut self.blend_func, blend_func),
+            StateChange::DepthFunc(depth_func) => update(&mut self.depth_func, depth_func),
+            StateChange::DepthMask(depth_mask) => update(&mut self.depth_mask, depth_mask),
+            StateChange::PolygonOffset(factor, units) => {
+                update(&mut self.polygon_offset, (factor, units))
+            }
+        }
+    }
+
+    pub fn texture(&self, unit: u32, target: u32) -> Option<u32> {
+        self.textures.get(&(unit, target)).cloned()
+    }
+
+    pub fn forget(&mut self, object: Object, name: u32) {
+        match object {
+            Object::Program => {
+                if self.program == Some(name) {
+                    self.program = None;
+                }
+            }
+            Object::VertexArray => unbind(&mut self.vertex_array, name),
+            Object::Framebuffer => {
+                unbind(&mut self.draw_framebuffer, name);
+                unbind(&mut self.read_framebuffer, name);
+            }
+            Object::Texture => unbind_entries(&mut self.textures, name),
+            Object::Sampler => unbind_entries(&mut self.samplers, name),
+        }
+    }
+
+    pub fn invalidate(&mut self) {
+        *self = GlState::default();
+    }
+
+    pub fn mismatches(&self, actual: &GlState) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        for (capability, enabled) in self.capabilities.iter() {
+            check(
+                &mut mismatches,
+                &format!("{:?}", capability),
+                Some(enabled),
+                actual.capabilities.get(capability),
+            );
+        }
+        check(&mut mismatches, "program", self.program, actual.program);
+        check(
+            &mut mismatches,
+            "vertex array",
+            self.vertex_array,
+            actual.vertex_array,
+        );
+        check(
+            &mut mismatches,
+            "draw frame buffer",
+            self.draw_framebuffer,
+            actual.draw_framebuffer,
+        );
+        check(
+            &mut mismatches,
+            "read frame buffer",
+            self.read_framebuffer,
+            actual.read_framebuffer,
+        );
+        check(
+            &mut mismatches,
+            "active texture",
+            self.active_texture,
+            actual.active_texture,
+        );
+        for ((unit, target), texture) in self.textures.iter() {
+            check(
+                &mut mismatches,
+                &format!("texture {:#x} on unit {}", target, unit),
+                Some(texture),
+                actual.textures.get(&(*unit, *target)),
+            );
+        }
+        for (unit, sampler) in self.samplers.iter() {
+            check(
+                &mut mismatches,
+                &format!("sampler on unit {}", unit),
+                Some(sampler),
+                actual.samplers.get(unit),
+            );
+        }
+        check(&mut mismatches, "viewport", self.viewport, actual.viewport);
+        check(&mut mismatches, "scissor", self.scissor, actual.scissor);
+        check(
+            &mut mismatches,
+            "blend func",
+            self.blend_func,
+            actual.blend_func,
+        );
+        check(
+            &mut mismatches,
+            "depth func",
+            self.depth_func,
+            actual.depth_func,
+        );
+        check(
+            &mut mismatches,
+            "depth mask",
+            self.depth_mask,
+            actual.depth_mask,
+        );
+        check(
+            &mut mismatches,
+            "polygon offset",
+            self.polygon_offset,
+            actual.polygon_offset,
+        );
+        mismatches
+    }
+}
+
+fn update<T: PartialEq>(slot: &mut Option<T>, value: T) -> bool {
+    if slot.as_ref() == Some(&value) {
+        return false;
+    }
+    *slot = Some(value);
+    true
+}
+
+fn update_entry<K: Ord, T: PartialEq>(map: &mut BTreeMap<K, T>, key: K, value: T) -> bool {
+    if map.get(&key) == Some(&value) {
+        return false;
+    }
+    map.insert(key, value);
+    true
+}
+
+fn unbind(slot: &mut Option<u32>, name: u32) {
+    if *slot == Some(name) {
+        *slot = Some(0);
+    }
+}
+
+fn unbind_entries<K>(map: &mut BTreeMap<K, u32>, name: u32) {
+    for value in map.values_mut() {
+        if *value == name {
+            *value = 0;
+        }
+    }
+}
+
+fn check<T: Debug + PartialEq>(
+    mismatches: &mut Vec<String>,
+    name: &str,
+    expected: Option<T>,
+    actual: Option<T>,
+) {
+    match expected {
+        Some(expected) if actual.as_ref() != Some(&expected) => {
+            mismatches.push(format!("{} is {:?}, expected {:?}", name, actual, expected));
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXTURE_2D: u32 = 0x0de1;
+    const TEXTURE_CUBE_MAP: u32 = 0x8513;
+
+    #[test]
+    fn skips_redundant_changes() {
+        let mut state = GlState::default();
+        assert!(state.apply(StateChange::Enable(Capability::CullFace, true)));
+        assert!(!state.apply(StateChange::Enable(Capability::CullFace, true)));
+        assert!(state.apply(StateChange::Enable(Capability::DepthTest, true)));
+        assert!(state.apply(StateChange::Enable(Capability::CullFace, false)));
+        assert!(state.apply(StateChange::UseProgram(3)));
+        assert!(!state.apply(StateChange::UseProgram(3)));
+        assert!(state.apply(StateChange::Viewport([0, 0, 64, 64])));
+        assert!(!state.apply(StateChange::Viewport([0, 0, 64, 64])));
+        assert!(state.apply(StateChange::Viewport([0, 0, 32, 64])));
+        assert!(state.apply(StateChange::PolygonOffset(2.0, 1.0)));
+        assert!(!state.apply(StateChange::PolygonOffset(2.0, 1.0)));
+    }
+
+    #[test]
+    fn tracks_textures_per_unit_and_target() {
+        let mut state = GlState::default();
+        assert!(state.apply(StateChange::BindTexture(TEXTURE_2D, 5)));
+        assert!(state.apply(StateChange::BindTexture(TEXTURE_2D, 5)));
+        assert!(state.apply(StateChange::ActiveTexture(0)));
+        assert!(state.apply(StateChange::BindTexture(TEXTURE_2D, 5)));
+        assert!(!state.apply(StateChange::BindTexture(TEXTURE_2D, 5)));
+        assert!(state.apply(StateChange::BindTexture(TEXTURE_CUBE_MAP, 5)));
+        assert!(state.apply(StateChange::ActiveTexture(1)));
+        assert!(state.apply(StateChange::BindTexture(TEXTURE_2D, 5)));
+        assert_eq!(state.texture(0, TEXTURE_2D), Some(5));
+        assert_eq!(state.texture(1, TEXTURE_2D), Some(5));
+        assert_eq!(state.texture(2, TEXTURE_2D), None);
+        assert!(state.apply(StateChange::BindSampler(1, 7)));
+        assert!(!state.apply(StateChange::BindSampler(1, 7)));
+        assert!(state.apply(StateChange::BindSampler(0, 7)));
+    }
+
+    #[test]
+    fn forgets_deleted_objects() {
+        let mut state = GlState::default();
+        state.apply(StateChange::UseProgram(2));
+        state.apply(StateChange::BindVertexArray(3));
+        state.apply(StateChange::BindDrawFramebuffer(4));
+        state.apply(StateChange::ActiveTexture(2));
+        state.apply(StateChange::BindTexture(TEXTURE_2D, 5));
+        state.forget(Object::VertexArray, 3);
+        state.forget(Object::Framebuffer, 4);
+        state.forget(Object::Texture, 5);
+        state.forget(Object::Program, 2);
+        assert!(!state.apply(StateChange::BindVertexArray(0)));
+        assert!(!state.apply(StateChange::BindDrawFramebuffer(0)));
+        assert!(!state.apply(StateChange::BindTexture(TEXTURE_2D, 0)));
+        assert!(state.apply(StateChange::UseProgram(2)));
+        assert!(state.apply(StateChange::BindVertexArray(3)));
+
+        state.invalidate();
+        assert_eq!(state, GlState::default());
+        assert!(state.apply(StateChange::BindVertexArray(3)));
+    }
+
+    #[test]
+    fn reports_mismatches_for_known_state_only() {
+        let mut shadow = GlState::default();
+        shadow.apply(StateChange::Enable(Capability::ScissorTest, true));
+        shadow.apply(StateChange::UseProgram(3));
+        shadow.apply(StateChange::ActiveTexture(1));
+        shadow.apply(StateChange::BindTexture(TEXTURE_2D, 5));
+
+        let mut actual = shadow.clone();
+        actual.depth_func = Some(0x0203);
+        assert!(shadow.mismatches(&actual).is_empty());
+
+        actual.program = Some(4);
+        actual.textures.insert((1, TEXTURE_2D), 6);
+        actual.capabilities.remove(&Capability::ScissorTest);
+        assert_eq!(
+            shadow.mismatches(&actual),
+            vec![
+                String::from("ScissorTest is None, expected true"),
+                String::from("program is Some(4), expected 3"),
+                String::from("texture 0xde1 on unit 1 is Some(6), expected 5"),
+            ]
+        );
+    }
+}
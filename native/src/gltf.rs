@@ -0,0 +1,1328 @@
+use crate::json;
+use crate::json::Value;
+use crate::vertex_layout::{Format, Semantic, VertexLayout};
+use std::str;
+
+const GLB_MAGIC: u32 = 0x4654_6c67;
+const GLB_CHUNK_JSON: u32 = 0x4e4f_534a;
+const GLB_CHUNK_BIN: u32 = 0x004e_4942;
+
+const BYTE: u32 = 5120;
+const UNSIGNED_BYTE: u32 = 5121;
+const SHORT: u32 = 5122;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+const TRIANGLES: usize = 4;
+const REPEAT: u32 = 10497;
+
+// Accessors without a buffer view are zero-filled, so nothing in the file bounds their count.
+const MAX_UNBACKED_COUNT: usize = 1 << 20;
+
+pub struct Document {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    pub textures: Vec<Texture>,
+    pub samplers: Vec<Sampler>,
+    pub images: Vec<Image>,
+    pub nodes: Vec<Node>,
+    pub scenes: Vec<Scene>,
+    pub scene: Option<usize>,
+}
+
+pub struct Mesh {
+    pub name: Option<String>,
+    pub primitives: Vec<Primitive>,
+}
+
+pub struct Primitive {
+    pub layout: VertexLayout,
+    pub vertices: Vec<u8>,
+    pub vertex_count: usize,
+    pub indices: IndexData,
+    pub material: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IndexData {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: Option<String>,
+    pub base_color_factor: [f32; 4],
+    pub base_color_texture: Option<TextureRef>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub metallic_roughness_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureRef>,
+    pub occlusion_strength: f32,
+    pub emissive_texture: Option<TextureRef>,
+    pub emissive_factor: [f32; 3],
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material {
+            name: None,
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_texture: None,
+            emissive_factor: [0.0, 0.0, 0.0],
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TextureRef {
+    pub texture: usize,
+    pub tex_coord: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Texture {
+    pub source: Option<usize>,
+    pub sampler: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Sampler {
+    pub mag_filter: Option<u32>,
+    pub min_filter: Option<u32>,
+    pub wrap_s: u32,
+    pub wrap_t: u32,
+}
+
+pub struct Image {
+    pub name: Option<String>,
+    pub mime_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub name: Option<String>,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub transform: Transform,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transform {
+    Matrix([f32; 16]),
+    Trs {
+        translation: [f32; 3],
+        rotation: [f32; 4],
+        scale: [f32; 3],
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scene {
+    pub name: Option<String>,
+    pub nodes: Vec<usize>,
+}
+
+pub fn load<F>(bytes: &[u8], mut load_uri: F) -> Result<Document, String>
+where
+    F: FnMut(&str) -> Result<Vec<u8>, String>,
+{
+    let (json_bytes, bin) = if bytes.len() >= 4 && read_u32(bytes, 0) == GLB_MAGIC {
+        parse_glb(bytes)?
+    } else {
+        (bytes, None)
+    };
+    let json_string = str::from_utf8(json_bytes).map_err(|_| "JSON is not valid UTF-8")?;
+    let root = json::parse(json_string)?;
+
+    let version = root
+        .get("asset")
+        .and_then(|asset| asset.get("version"))
+        .and_then(|version| version.as_str())
+        .ok_or("missing asset version")?;
+    if !version.starts_with("2.") {
+        return Err(format!("unsupported glTF version {}", version));
+    }
+
+    let mut buffers = Vec::new();
+    for (index, buffer) in array(&root, "buffers")?.iter().enumerate() {
+        let byte_length = required_usize(buffer, "byteLength")?;
+        let data = match buffer.get("uri").and_then(|uri| uri.as_str()) {
+            Some(uri) => load_buffer_uri(uri, &mut load_uri)?,
+            None if index == 0 => bin
+                .map(|bin| bin.to_vec())
+                .ok_or("buffer 0 has no uri and there is no BIN chunk")?,
+            None => return Err(format!("buffer {} has no uri", index)),
+        };
+        if data.len() < byte_length {
+            return Err(format!(
+                "buffer {} has {} bytes, expected {}",
+                index,
+                data.len(),
+                byte_length
+            ));
+        }
+        buffers.push(data);
+    }
+
+    let context = Context {
+        root: &root,
+        buffers,
+    };
+    let document = Document {
+        meshes: context.parse_meshes()?,
+        materials: context.parse_materials()?,
+        textures: context.parse_textures()?,
+        samplers: context.parse_samplers()?,
+        images: context.parse_images(&mut load_uri)?,
+        nodes: context.parse_nodes()?,
+        scenes: context.parse_scenes()?,
+        scene: optional_usize(&root, "scene")?,
+    };
+    validate(&document)?;
+    Ok(document)
+}
+
+struct Context<'a> {
+    root: &'a Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+struct Accessor {
+    buffer_view: Option<usize>,
+    byte_offset: usize,
+    component_type: u32,
+    normalized: bool,
+    count: usize,
+    columns: usize,
+    rows: usize,
+}
+
+impl Accessor {
+    fn components(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    fn column_stride(&self) -> usize {
+        let size = self.rows * component_size(self.component_type);
+        if self.columns > 1 {
+            align(size, 4)
+        } else {
+            size
+        }
+    }
+
+    fn element_size(&self) -> usize {
+        self.column_stride() * self.columns
+    }
+
+    fn read_element(&self, data: &[u8], offset: usize, values: &mut [f64]) {
+        let component_size = component_size(self.component_type);
+        for column in 0..self.columns {
+            for row in 0..self.rows {
+                values[column * self.rows + row] = read_component(
+                    data,
+                    offset + column * self.column_stride() + row * component_size,
+                    self.component_type,
+                    self.normalized,
+                );
+            }
+        }
+    }
+}
+
+impl<'a> Context<'a> {
+    fn parse_meshes(&self) -> Result<Vec<Mesh>, String> {
+        let mut meshes = Vec::new();
+        for (index, mesh) in array(self.root, "meshes")?.iter().enumerate() {
+            let mut primitives = Vec::new();
+            for primitive in array(mesh, "primitives")?.iter() {
+                primitives.push(
+                    self.parse_primitive(primitive)
+                        .map_err(|error| format!("mesh {}: {}", index, error))?,
+                );
+            }
+            meshes.push(Mesh {
+                name: optional_string(mesh, "name"),
+                primitives,
+            });
+        }
+        Ok(meshes)
+    }
+
+    fn parse_primitive(&self, primitive: &Value) -> Result<Primitive, String> {
+        let mode = optional_usize(primitive, "mode")?.unwrap_or(TRIANGLES);
+        if mode != TRIANGLES {
+            return Err(format!("unsupported primitive mode {}", mode));
+        }
+        let attributes = primitive
+            .get("attributes")
+            .ok_or("primitive has no attributes")?;
+
+        let channels = [
+            ("POSITION", Semantic::Position, Format::FLOAT3, &[3][..]),
+            ("NORMAL", Semantic::Normal, Format::FLOAT3, &[3][..]),
+            ("TANGENT", Semantic::Tangent, Format::FLOAT4, &[4][..]),
+            ("TEXCOORD_0", Semantic::TexCoord0, Format::FLOAT2, &[2][..]),
+            ("TEXCOORD_1", Semantic::TexCoord1, Format::FLOAT2, &[2][..]),
+            ("COLOR_0", Semantic::Color, Format::FLOAT4, &[3, 4][..]),
+        ];
+        let mut streams = Vec::new();
+        let mut vertex_count = None;
+        for (name, semantic, format, components) in channels.iter().cloned() {
+            let accessor = match optional_usize(attributes, name)? {
+                Some(accessor) => accessor,
+                None if semantic == Semantic::Position => {
+                    return Err(String::from("primitive has no POSITION attribute"))
+                }
+                None => continue,
+            };
+            let (count, accessor_components, values) = self.read_accessor(accessor)?;
+            if !components.contains(&accessor_components) {
+                return Err(format!(
+                    "attribute {} has {} components",
+                    name, accessor_components
+                ));
+            }
+            match vertex_count {
+                Some(vertex_count) if vertex_count != count => {
+                    return Err(format!(
+                        "attribute {} has {} elements, expected {}",
+                        name, count, vertex_count
+                    ))
+                }
+                _ => vertex_count = Some(count),
+            }
+            streams.push((semantic, format, accessor_components, values));
+        }
+        let vertex_count = vertex_count.unwrap();
+
+        let layout = VertexLayout::packed(
+            &streams
+                .iter()
+                .map(|(semantic, format, _, _)| (*semantic, *format, false))
+                .collect::<Vec<_>>(),
+        );
+        let size = vertex_count
+            .checked_mul(layout.stride())
+            .ok_or("primitive is too large")?;
+        let mut vertices = Vec::with_capacity(size);
+        for vertex in 0..vertex_count {
+            for (_, format, components, values) in streams.iter() {
+                for component in 0..format.component_count {
+                    let value = if component < *components {
+                        values[vertex * components + component] as f32
+                    } else {
+                        1.0
+                    };
+                    vertices.extend_from_slice(&value.to_ne_bytes());
+                }
+            }
+        }
+
+        let indices: Vec<u32> = match optional_usize(primitive, "indices")? {
+            Some(index) => {
+                let accessor = self.accessor(index)?;
+                if accessor.components() != 1 || accessor.normalized {
+                    return Err(String::from("indices must be unnormalized scalars"));
+                }
+                match accessor.component_type {
+                    UNSIGNED_BYTE | UNSIGNED_SHORT | UNSIGNED_INT => {}
+                    component_type => {
+                        return Err(format!("invalid index component type {}", component_type))
+                    }
+                }
+                let (_, _, values) = self.read_accessor(index)?;
+                values.iter().map(|value| *value as u32).collect()
+            }
+            None => (0..vertex_count as u32).collect(),
+        };
+        if !indices.len().is_multiple_of(3) {
+            return Err(format!(
+                "index count {} is not a multiple of 3",
+                indices.len()
+            ));
+        }
+        if let Some(index) = indices
+            .iter()
+            .find(|index| **index as usize >= vertex_count)
+        {
+            return Err(format!(
+                "index {} is out of range for {} vertices",
+                index, vertex_count
+            ));
+        }
+        let indices = if vertex_count <= 0x10000 {
+            IndexData::U16(indices.iter().map(|index| *index as u16).collect())
+        } else {
+            IndexData::U32(indices)
+        };
+
+        Ok(Primitive {
+            layout,
+            vertices,
+            vertex_count,
+            indices,
+            material: optional_usize(primitive, "material")?,
+        })
+    }
+
+    fn accessor(&self, index: usize) -> Result<Accessor, String> {
+        let accessor = array(self.root, "accessors")?
+            .get(index)
+            .ok_or_else(|| format!("accessor {} doesn't exist", index))?;
+        let component_type = required_usize(accessor, "componentType")? as u32;
+        let normalized = optional_bool(accessor, "normalized")?.unwrap_or(false);
+        if normalized && (component_type == FLOAT || component_type == UNSIGNED_INT) {
+            return Err(format!(
+                "accessor {} can't normalize component type {}",
+                index, component_type
+            ));
+        }
+        let (columns, rows) = match accessor.get("type").and_then(|type_| type_.as_str()) {
+            Some("SCALAR") => (1, 1),
+            Some("VEC2") => (1, 2),
+            Some("VEC3") => (1, 3),
+            Some("VEC4") => (1, 4),
+            Some("MAT2") => (2, 2),
+            Some("MAT3") => (3, 3),
+            Some("MAT4") => (4, 4),
+            _ => return Err(format!("accessor {} has an invalid type", index)),
+        };
+        let accessor = Accessor {
+            buffer_view: optional_usize(accessor, "bufferView")?,
+            byte_offset: optional_usize(accessor, "byteOffset")?.unwrap_or(0),
+            component_type,
+            normalized,
+            count: required_usize(accessor, "count")?,
+            columns,
+            rows,
+        };
+        if component_size(component_type) == 0 {
+            return Err(format!(
+                "accessor {} has invalid component type {}",
+                index, component_type
+            ));
+        }
+        if accessor.count == 0 {
+            return Err(format!("accessor {} is empty", index));
+        }
+        if !accessor
+            .byte_offset
+            .is_multiple_of(component_size(component_type))
+        {
+            return Err(format!("accessor {} is misaligned", index));
+        }
+        Ok(accessor)
+    }
+
+    fn read_accessor(&self, index: usize) -> Result<(usize, usize, Vec<f64>), String> {
+        let accessor = self.accessor(index)?;
+        let components = accessor.components();
+        let element_size = accessor.element_size();
+        let view = match accessor.buffer_view {
+            Some(buffer_view) => {
+                let (data, byte_stride) = self.buffer_view(buffer_view)?;
+                let stride = byte_stride.unwrap_or(element_size);
+                if stride < element_size {
+                    return Err(format!(
+                        "accessor {} has stride {} smaller than its elements",
+                        index, stride
+                    ));
+                }
+                let end = (accessor.count - 1)
+                    .checked_mul(stride)
+                    .and_then(|offset| offset.checked_add(accessor.byte_offset))
+                    .and_then(|offset| offset.checked_add(element_size));
+                if end.is_none_or(|end| end > data.len()) {
+                    return Err(format!("accessor {} is out of bounds", index));
+                }
+                Some((data, stride))
+            }
+            None if accessor.count > MAX_UNBACKED_COUNT => {
+                return Err(format!("accessor {} is too large", index));
+            }
+            None => None,
+        };
+        let mut values = vec![
+            0.0;
+            accessor
+                .count
+                .checked_mul(components)
+                .ok_or_else(|| format!("accessor {} is too large", index))?
+        ];
+        if let Some((data, stride)) = view {
+            for element in 0..accessor.count {
+                accessor.read_element(
+                    data,
+                    accessor.byte_offset + element * stride,
+                    &mut values[element * components..(element + 1) * components],
+                );
+            }
+        }
+
+        if let Some(sparse) = array(self.root, "accessors")?[index].get("sparse") {
+            let count = required_usize(sparse, "count")?;
+            let sparse_indices = sparse.get("indices").ok_or("sparse has no indices")?;
+            let sparse_values = sparse.get("values").ok_or("sparse has no values")?;
+
+            let index_type = required_usize(sparse_indices, "componentType")? as u32;
+            match index_type {
+                UNSIGNED_BYTE | UNSIGNED_SHORT | UNSIGNED_INT => {}
+                _ => {
+                    return Err(format!(
+                        "accessor {} has invalid sparse index type {}",
+                        index, index_type
+                    ))
+                }
+            }
+            let (index_data, _) =
+                self.buffer_view(required_usize(sparse_indices, "bufferView")?)?;
+            let index_offset = optional_usize(sparse_indices, "byteOffset")?.unwrap_or(0);
+            let (value_data, _) = self.buffer_view(required_usize(sparse_values, "bufferView")?)?;
+            let value_offset = optional_usize(sparse_values, "byteOffset")?.unwrap_or(0);
+            if range_end(index_offset, count, component_size(index_type))
+                .is_none_or(|end| end > index_data.len())
+                || range_end(value_offset, count, element_size)
+                    .is_none_or(|end| end > value_data.len())
+            {
+                return Err(format!("accessor {} has out of bounds sparse data", index));
+            }
+
+            let mut previous = None;
+            for element in 0..count {
+                let target = read_component(
+                    index_data,
+                    index_offset + element * component_size(index_type),
+                    index_type,
+                    false,
+                ) as usize;
+                if target >= accessor.count || previous.is_some_and(|previous| target <= previous) {
+                    return Err(format!(
+                        "accessor {} has invalid sparse index {}",
+                        index, target
+                    ));
+                }
+                previous = Some(target);
+                accessor.read_element(
+                    value_data,
+                    value_offset + element * element_size,
+                    &mut values[target * components..(target + 1) * components],
+                );
+            }
+        }
+
+        Ok((accessor.count, components, values))
+    }
+
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>), String> {
+        let buffer_view = array(self.root, "bufferViews")?
+            .get(index)
+            .ok_or_else(|| format!("buffer view {} doesn't exist", index))?;
+        let buffer = required_usize(buffer_view, "buffer")?;
+        let byte_offset = optional_usize(buffer_view, "byteOffset")?.unwrap_or(0);
+        let byte_length = required_usize(buffer_view, "byteLength")?;
+        let byte_stride = optional_usize(buffer_view, "byteStride")?;
+        if let Some(byte_stride) = byte_stride {
+            if !(4..=252).contains(&byte_stride) || !byte_stride.is_multiple_of(4) {
+                return Err(format!(
+                    "buffer view {} has invalid stride {}",
+                    index, byte_stride
+                ));
+            }
+        }
+        let data = self
+            .buffers
+            .get(buffer)
+            .zip(byte_offset.checked_add(byte_length))
+            .and_then(|(buffer, end)| buffer.get(byte_offset..end))
+            .ok_or_else(|| format!("buffer view {} is out of bounds", index))?;
+        Ok((data, byte_stride))
+    }
+
+    fn parse_materials(&self) -> Result<Vec<Material>, String> {
+        let mut materials = Vec::new();
+        for material in array(self.root, "materials")?.iter() {
+            let mut result = Material {
+                name: optional_string(material, "name"),
+                ..Material::default()
+            };
+            if let Some(pbr) = material.get("pbrMetallicRoughness") {
+                if let Some(factor) = optional_floats(pbr, "baseColorFactor")? {
+                    result.base_color_factor = factor;
+                }
+                result.base_color_texture = texture_ref(pbr, "baseColorTexture")?;
+                result.metallic_factor = optional_f32(pbr, "metallicFactor")?.unwrap_or(1.0);
+                result.roughness_factor = optional_f32(pbr, "roughnessFactor")?.unwrap_or(1.0);
+                result.metallic_roughness_texture = texture_ref(pbr, "metallicRoughnessTexture")?;
+            }
+            result.normal_texture = texture_ref(material, "normalTexture")?;
+            if let Some(normal_texture) = material.get("normalTexture") {
+                result.normal_scale = optional_f32(normal_texture, "scale")?.unwrap_or(1.0);
+            }
+            result.occlusion_texture = texture_ref(material, "occlusionTexture")?;
+            if let Some(occlusion_texture) = material.get("occlusionTexture") {
+                result.occlusion_strength =
+                    optional_f32(occlusion_texture, "strength")?.unwrap_or(1.0);
+            }
+            result.emissive_texture = texture_ref(material, "emissiveTexture")?;
+            if let Some(factor) = optional_floats(material, "emissiveFactor")? {
+                result.emissive_factor = factor;
+            }
+            result.alpha_mode = match material.get("alphaMode").and_then(|mode| mode.as_str()) {
+                None | Some("OPAQUE") => AlphaMode::Opaque,
+                Some("MASK") => AlphaMode::Mask,
+                Some("BLEND") => AlphaMode::Blend,
+                Some(mode) => return Err(format!("invalid alpha mode {}", mode)),
+            };
+            result.alpha_cutoff = optional_f32(material, "alphaCutoff")?.unwrap_or(0.5);
+            result.double_sided = optional_bool(material, "doubleSided")?.unwrap_or(false);
+            materials.push(result);
+        }
+        Ok(materials)
+    }
+
+    fn parse_textures(&self) -> Result<Vec<Texture>, String> {
+        let mut textures = Vec::new();
+        for texture in array(self.root, "textures")?.iter() {
+            textures.push(Texture {
+                source: optional_usize(texture, "source")?,
+                sampler: optional_usize(texture, "sampler")?,
+            });
+        }
+        Ok(textures)
+    }
+
+    fn parse_samplers(&self) -> Result<Vec<Sampler>, String> {
+        let mut samplers = Vec::new();
+        for sampler in array(self.root, "samplers")?.iter() {
+            samplers.push(Sampler {
+                mag_filter: optional_usize(sampler, "magFilter")?.map(|filter| filter as u32),
+                min_filter: optional_usize(sampler, "minFilter")?.map(|filter| filter as u32),
+                wrap_s: optional_usize(sampler, "wrapS")?.map_or(REPEAT, |wrap| wrap as u32),
+                wrap_t: optional_usize(sampler, "wrapT")?.map_or(REPEAT, |wrap| wrap as u32),
+            });
+        }
+        Ok(samplers)
+    }
+
+    fn parse_images<F>(&self, load_uri: &mut F) -> Result<Vec<Image>, String>
+    where
+        F: FnMut(&str) -> Result<Vec<u8>, String>,
+    {
+        let mut images = Vec::new();
+        for (index, image) in array(self.root, "images")?.iter().enumerate() {
+            let data = match (
+                image.get("uri").and_then(|uri| uri.as_str()),
+                optional_usize(image, "bufferView")?,
+            ) {
+                (Some(uri), None) => load_buffer_uri(uri, load_uri)?,
+                (None, Some(buffer_view)) => self.buffer_view(buffer_view)?.0.to_vec(),
+                _ => {
+                    return Err(format!(
+                        "image {} must have either a uri or a buffer view",
+                        index
+                    ))
+                }
+            };
+            images.push(Image {
+                name: optional_string(image, "name"),
+                mime_type: optional_string(image, "mimeType"),
+                data,
+            });
+        }
+        Ok(images)
+    }
+
+    fn parse_nodes(&self) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        for node in array(self.root, "nodes")?.iter() {
+            let transform = match optional_floats::<[f32; 16]>(node, "matrix")? {
+                Some(matrix) => Transform::Matrix(matrix),
+                None => Transform::Trs {
+                    translation: optional_floats(node, "translation")?.unwrap_or([0.0; 3]),
+                    rotation: optional_floats(node, "rotation")?.unwrap_or([0.0, 0.0, 0.0, 1.0]),
+                    scale: optional_floats(node, "scale")?.unwrap_or([1.0; 3]),
+                },
+            };
+            nodes.push(Node {
+                name: optional_string(node, "name"),
+                children: indices(node, "children")?,
+                mesh: optional_usize(node, "mesh")?,
+                transform,
+            });
+        }
+        Ok(nodes)
+    }
+
+    fn parse_scenes(&self) -> Result<Vec<Scene>, String> {
+        let mut scenes = Vec::new();
+        for scene in array(self.root, "scenes")?.iter() {
+            scenes.push(Scene {
+                name: optional_string(scene, "name"),
+                nodes: indices(scene, "nodes")?,
+            });
+        }
+        Ok(scenes)
+    }
+}
+
+fn validate(document: &Document) -> Result<(), String> {
+    for (index, mesh) in document.meshes.iter().enumerate() {
+        for primitive in mesh.primitives.iter() {
+            if let Some(material) = primitive.material {
+                if material >= document.materials.len() {
+                    return Err(format!(
+                        "mesh {} refers to missing material {}",
+                        index, material
+                    ));
+                }
+            }
+        }
+    }
+    for (index, material) in document.materials.iter().enumerate() {
+        let texture_refs = [
+            material.base_color_texture,
+            material.metallic_roughness_texture,
+            material.normal_texture,
+            material.occlusion_texture,
+            material.emissive_texture,
+        ];
+        for texture_ref in texture_refs.iter().filter_map(|texture_ref| *texture_ref) {
+            if texture_ref.texture >= document.textures.len() {
+                return Err(format!(
+                    "material {} refers to missing texture {}",
+                    index, texture_ref.texture
+                ));
+            }
+        }
+    }
+    for (index, texture) in document.textures.iter().enumerate() {
+        if texture
+            .source
+            .is_some_and(|source| source >= document.images.len())
+            || texture
+                .sampler
+                .is_some_and(|sampler| sampler >= document.samplers.len())
+        {
+            return Err(format!(
+                "texture {} refers to a missing image or sampler",
+                index
+            ));
+        }
+    }
+
+    let mut parents = vec![None; document.nodes.len()];
+    for (index, node) in document.nodes.iter().enumerate() {
+        if node.mesh.is_some_and(|mesh| mesh >= document.meshes.len()) {
+            return Err(format!("node {} refers to a missing mesh", index));
+        }
+        for child in node.children.iter().cloned() {
+            if child >= document.nodes.len() {
+                return Err(format!("node {} refers to missing child {}", index, child));
+            }
+            if parents[child].is_some() {
+                return Err(format!("node {} has more than one parent", child));
+            }
+            parents[child] = Some(index);
+        }
+    }
+    for index in 0..document.nodes.len() {
+        let mut ancestor = parents[index];
+        let mut depth = 0;
+        while let Some(parent) = ancestor {
+            depth += 1;
+            if parent == index || depth > document.nodes.len() {
+                return Err(format!("node {} is part of a cycle", index));
+            }
+            ancestor = parents[parent];
+        }
+    }
+    for (index, scene) in document.scenes.iter().enumerate() {
+        for node in scene.nodes.iter().cloned() {
+            if node >= document.nodes.len() || parents[node].is_some() {
+                return Err(format!("scene {} has invalid root node {}", index, node));
+            }
+        }
+    }
+    if document
+        .scene
+        .is_some_and(|scene| scene >= document.scenes.len())
+    {
+        return Err(String::from("default scene doesn't exist"));
+    }
+    Ok(())
+}
+
+fn parse_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    if bytes.len() < 20 {
+        return Err(String::from("truncated GLB header"));
+    }
+    let version = read_u32(bytes, 4);
+    if version != 2 {
+        return Err(format!("unsupported GLB version {}", version));
+    }
+    let length = read_u32(bytes, 8) as usize;
+    if length > bytes.len() {
+        return Err(String::from("truncated GLB file"));
+    }
+
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunk_length = read_u32(bytes, offset) as usize;
+        let chunk_type = read_u32(bytes, offset + 4);
+        let chunk = bytes
+            .get(offset + 8..offset + 8 + chunk_length)
+            .ok_or("truncated GLB chunk")?;
+        match chunk_type {
+            GLB_CHUNK_JSON if json.is_none() => json = Some(chunk),
+            GLB_CHUNK_BIN if bin.is_none() => bin = Some(chunk),
+            _ => {}
+        }
+        offset += 8 + align(chunk_length, 4);
+    }
+    Ok((json.ok_or("GLB file has no JSON chunk")?, bin))
+}
+
+fn load_buffer_uri<F>(uri: &str, load_uri: &mut F) -> Result<Vec<u8>, String>
+where
+    F: FnMut(&str) -> Result<Vec<u8>, String>,
+{
+    if uri.starts_with("data:") {
+        let comma = uri.find(',').ok_or("malformed data uri")?;
+        if !uri[..comma].ends_with(";base64") {
+            return Err(String::from("data uri is not base64 encoded"));
+        }
+        decode_base64(&uri[comma + 1..])
+    } else {
+        load_uri(uri)
+    }
+}
+
+fn decode_base64(string: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(string.len() * 3 / 4);
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+    for byte in string.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return Err(format!("invalid base64 character {:?}", byte as char)),
+        };
+        accumulator = (accumulator << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((accumulator >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+fn component_size(component_type: u32) -> usize {
+    match component_type {
+        BYTE | UNSIGNED_BYTE => 1,
+        SHORT | UNSIGNED_SHORT => 2,
+        UNSIGNED_INT | FLOAT => 4,
+        _ => 0,
+    }
+}
+
+fn read_component(data: &[u8], offset: usize, component_type: u32, normalized: bool) -> f64 {
+    match component_type {
+        BYTE => {
+            let value = data[offset] as i8 as f64;
+            if normalized {
+                (value / 127.0).max(-1.0)
+            } else {
+                value
+            }
+        }
+        UNSIGNED_BYTE => {
+            let value = data[offset] as f64;
+            if normalized {
+                value / 255.0
+            } else {
+                value
+            }
+        }
+        SHORT => {
+            let value = i16::from_le_bytes([data[offset], data[offset + 1]]) as f64;
+            if normalized {
+                (value / 32767.0).max(-1.0)
+            } else {
+                value
+            }
+        }
+        UNSIGNED_SHORT => {
+            let value = u16::from_le_bytes([data[offset], data[offset + 1]]) as f64;
+            if normalized {
+                value / 65535.0
+            } else {
+                value
+            }
+        }
+        UNSIGNED_INT => read_u32(data, offset) as f64,
+        FLOAT => f32::from_bits(read_u32(data, offset)) as f64,
+        _ => unreachable!(),
+    }
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> Result<&'a [Value], String> {
+    match value.get(key) {
+        Some(array) => array
+            .as_array()
+            .ok_or_else(|| format!("{} must be an array", key)),
+        None => Ok(&[]),
+    }
+}
+
+fn indices(value: &Value, key: &str) -> Result<Vec<usize>, String> {
+    array(value, key)?
+        .iter()
+        .map(|index| {
+            index
+                .as_usize()
+                .ok_or_else(|| format!("{} must contain indices", key))
+        })
+        .collect()
+}
+
+fn optional_usize(value: &Value, key: &str) -> Result<Option<usize>, String> {
+    match value.get(key) {
+        Some(number) => number
+            .as_usize()
+            .map(Some)
+            .ok_or_else(|| format!("{} must be a non-negative integer", key)),
+        None => Ok(None),
+    }
+}
+
+fn required_usize(value: &Value, key: &str) -> Result<usize, String> {
+    optional_usize(value, key)?.ok_or_else(|| format!("missing {}", key))
+}
+
+fn optional_f32(value: &Value, key: &str) -> Result<Option<f32>, String> {
+    match value.get(key) {
+        Some(number) => number
+            .as_f64()
+            .map(|number| Some(number as f32))
+            .ok_or_else(|| format!("{} must be a number", key)),
+        None => Ok(None),
+    }
+}
+
+fn optional_bool(value: &Value, key: &str) -> Result<Option<bool>, String> {
+    match value.get(key) {
+        Some(boolean) => boolean
+            .as_bool()
+            .map(Some)
+            .ok_or_else(|| format!("{} must be a boolean", key)),
+        None => Ok(None),
+    }
+}
+
+fn optional_string(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(|string| string.as_str())
+        .map(String::from)
+}
+
+fn optional_floats<T>(value: &Value, key: &str) -> Result<Option<T>, String>
+where
+    T: Default + AsMut<[f32]>,
+{
+    let values = match value.get(key) {
+        Some(values) => values
+            .as_array()
+            .ok_or_else(|| format!("{} must be an array", key))?,
+        None => return Ok(None),
+    };
+    let mut result = T::default();
+    if values.len() != result.as_mut().len() {
+        return Err(format!(
+            "{} must have {} elements",
+            key,
+            result.as_mut().len()
+        ));
+    }
+    for (target, value) in result.as_mut().iter_mut().zip(values.iter()) {
+        *target = value
+            .as_f64()
+            .ok_or_else(|| format!("{} must contain numbers", key))? as f32;
+    }
+    Ok(Some(result))
+}
+
+fn texture_ref(value: &Value, key: &str) -> Result<Option<TextureRef>, String> {
+    match value.get(key) {
+        Some(texture_info) => Ok(Some(TextureRef {
+            texture: required_usize(texture_info, "index")?,
+            tex_coord: optional_usize(texture_info, "texCoord")?.unwrap_or(0),
+        })),
+        None => Ok(None),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+fn range_end(offset: usize, count: usize, size: usize) -> Option<usize> {
+    count
+        .checked_mul(size)
+        .and_then(|size| size.checked_add(offset))
+}
+
+fn align(offset: usize, alignment: usize) -> usize {
+    offset.div_ceil(alignment) * alignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLB_JSON: &str = r#"{
+        "asset": {"version": "2.0"},
+        "buffers": [{"byteLength": 44}],
+        "bufferViews": [
+            {"buffer": 0, "byteLength": 36},
+            {"buffer": 0, "byteOffset": 36, "byteLength": 6}
+        ],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}
+        ],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]}],
+        "nodes": [{"mesh": 0}],
+        "scenes": [{"nodes": [0]}],
+        "scene": 0
+    }"#;
+
+    const INTERLEAVED_JSON: &str = r#"{
+        "asset": {"version": "2.0"},
+        "buffers": [{"uri": "interleaved.bin", "byteLength": 60}],
+        "bufferViews": [{"buffer": 0, "byteLength": 60, "byteStride": 20}],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {
+                "bufferView": 0,
+                "byteOffset": 12,
+                "componentType": 5123,
+                "normalized": true,
+                "count": 3,
+                "type": "VEC2"
+            },
+            {
+                "bufferView": 0,
+                "byteOffset": 16,
+                "componentType": 5121,
+                "normalized": true,
+                "count": 3,
+                "type": "VEC4"
+            }
+        ],
+        "meshes": [{
+            "primitives": [{"attributes": {"POSITION": 0, "TEXCOORD_0": 1, "COLOR_0": 2}}]
+        }]
+    }"#;
+
+    const SPARSE_JSON: &str = r#"{
+        "asset": {"version": "2.0"},
+        "buffers": [{"uri": "sparse.bin", "byteLength": 16}],
+        "bufferViews": [
+            {"buffer": 0, "byteLength": 1},
+            {"buffer": 0, "byteOffset": 4, "byteLength": 12}
+        ],
+        "accessors": [{
+            "componentType": 5126,
+            "count": 3,
+            "type": "VEC3",
+            "sparse": {
+                "count": 1,
+                "indices": {"bufferView": 0, "componentType": 5121},
+                "values": {"bufferView": 1}
+            }
+        }],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}]
+    }"#;
+
+    fn floats_to_bytes(values: &[f32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    fn vertex_floats(primitive: &Primitive) -> Vec<f32> {
+        primitive
+            .vertices
+            .chunks(4)
+            .map(|chunk| f32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect()
+    }
+
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(align(json.len(), 4), b' ');
+        let mut bin = bin.to_vec();
+        bin.resize(align(bin.len(), 4), 0);
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        let mut bytes = Vec::with_capacity(length);
+        for value in [
+            GLB_MAGIC,
+            2,
+            length as u32,
+            json.len() as u32,
+            GLB_CHUNK_JSON,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&json);
+        bytes.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&GLB_CHUNK_BIN.to_le_bytes());
+        bytes.extend_from_slice(&bin);
+        bytes
+    }
+
+    fn glb_triangle() -> Vec<u8> {
+        let mut bin = floats_to_bytes(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        for index in [2u16, 1, 0] {
+            bin.extend_from_slice(&index.to_le_bytes());
+        }
+        glb(GLB_JSON, &bin)
+    }
+
+    fn interleaved_buffer() -> Vec<u8> {
+        let mut bin = Vec::new();
+        for vertex in 0..3u16 {
+            bin.extend_from_slice(&floats_to_bytes(&[vertex as f32, 0.0, 0.0]));
+            bin.extend_from_slice(&(vertex * 0x7fff).to_le_bytes());
+            bin.extend_from_slice(&u16::MAX.to_le_bytes());
+            bin.extend_from_slice(&[255, 0, 51, 255]);
+        }
+        bin
+    }
+
+    fn load_interleaved(json: &str) -> Result<Document, String> {
+        load(json.as_bytes(), |uri| {
+            assert_eq!(uri, "interleaved.bin");
+            Ok(interleaved_buffer())
+        })
+    }
+
+    fn load_sparse(json: &str, target: u8) -> Result<Document, String> {
+        load(json.as_bytes(), |_| {
+            let mut bin = vec![target, 0, 0, 0];
+            bin.extend_from_slice(&floats_to_bytes(&[1.0, 2.0, 3.0]));
+            Ok(bin)
+        })
+    }
+
+    fn no_uris(uri: &str) -> Result<Vec<u8>, String> {
+        Err(format!("unexpected uri {}", uri))
+    }
+
+    #[test]
+    fn loads_glb_chunks() {
+        let document = load(&glb_triangle(), no_uris).unwrap();
+        let primitive = &document.meshes[0].primitives[0];
+        assert_eq!(primitive.vertex_count, 3);
+        assert_eq!(primitive.layout.stride(), 12);
+        assert_eq!(
+            vertex_floats(primitive),
+            [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]
+        );
+        assert_eq!(primitive.indices, IndexData::U16(vec![2, 1, 0]));
+        assert_eq!(document.nodes[0].mesh, Some(0));
+        assert_eq!(document.scene, Some(0));
+    }
+
+    #[test]
+    fn rejects_malformed_glb() {
+        let bytes = glb_triangle();
+        assert_eq!(
+            load(&bytes[..16], no_uris).err(),
+            Some(String::from("truncated GLB header"))
+        );
+
+        let mut truncated = bytes.clone();
+        truncated.truncate(bytes.len() - 4);
+        assert_eq!(
+            load(&truncated, no_uris).err(),
+            Some(String::from("truncated GLB file"))
+        );
+
+        let mut chunk_length = bytes.clone();
+        chunk_length[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            load(&chunk_length, no_uris).err(),
+            Some(String::from("truncated GLB chunk"))
+        );
+
+        let mut version = bytes.clone();
+        version[4] = 1;
+        assert_eq!(
+            load(&version, no_uris).err(),
+            Some(String::from("unsupported GLB version 1"))
+        );
+
+        let mut no_bin = glb(GLB_JSON, &[]);
+        no_bin.truncate(no_bin.len() - 8);
+        let length = no_bin.len() as u32;
+        no_bin[8..12].copy_from_slice(&length.to_le_bytes());
+        assert_eq!(
+            load(&no_bin, no_uris).err(),
+            Some(String::from(
+                "buffer 0 has no uri and there is no BIN chunk"
+            ))
+        );
+    }
+
+    #[test]
+    fn reads_interleaved_normalized_attributes() {
+        let document = load_interleaved(INTERLEAVED_JSON).unwrap();
+        let primitive = &document.meshes[0].primitives[0];
+        assert_eq!(primitive.layout.stride(), 36);
+        assert_eq!(primitive.indices, IndexData::U16(vec![0, 1, 2]));
+        let floats = vertex_floats(primitive);
+        let vertex = |index: usize| &floats[index * 9..(index + 1) * 9];
+        assert_eq!(vertex(0), [0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.2, 1.0]);
+        assert_eq!(vertex(1)[0..3], [1.0, 0.0, 0.0]);
+        assert!((vertex(1)[3] - 0.5).abs() < 1e-4);
+        assert_eq!(vertex(2)[0..3], [2.0, 0.0, 0.0]);
+        assert!((vertex(2)[3] - 1.0).abs() < 1e-4);
+        assert_eq!(vertex(2)[4], 1.0);
+    }
+
+    #[test]
+    fn rejects_out_of_range_buffer_access() {
+        assert_eq!(
+            load_interleaved(&INTERLEAVED_JSON.replace(
+                "\"count\": 3, \"type\": \"VEC3\"",
+                "\"count\": 4, \"type\": \"VEC3\""
+            ))
+            .err(),
+            Some(String::from("mesh 0: accessor 0 is out of bounds"))
+        );
+        assert_eq!(
+            load_interleaved(&INTERLEAVED_JSON.replace("\"byteStride\": 20", "\"byteStride\": 2"))
+                .err(),
+            Some(String::from("mesh 0: buffer view 0 has invalid stride 2"))
+        );
+        assert_eq!(
+            load_interleaved(
+                &INTERLEAVED_JSON.replace("\"byteLength\": 60,", "\"byteLength\": 1e300,")
+            )
+            .err(),
+            Some(String::from("mesh 0: buffer view 0 is out of bounds"))
+        );
+        assert_eq!(
+            load_interleaved(
+                &INTERLEAVED_JSON.replace("\"byteOffset\": 16", "\"byteOffset\": 1e300")
+            )
+            .err(),
+            Some(String::from("mesh 0: accessor 2 is out of bounds"))
+        );
+    }
+
+    #[test]
+    fn applies_sparse_accessors() {
+        let document = load_sparse(SPARSE_JSON, 2).unwrap();
+        assert_eq!(
+            vertex_floats(&document.meshes[0].primitives[0]),
+            [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 3.0]
+        );
+        assert_eq!(
+            load_sparse(SPARSE_JSON, 3).err(),
+            Some(String::from(
+                "mesh 0: accessor 0 has invalid sparse index 3"
+            ))
+        );
+        assert_eq!(
+            load_sparse(&SPARSE_JSON.replace("\"count\": 1,", "\"count\": 2,"), 0).err(),
+            Some(String::from(
+                "mesh 0: accessor 0 has out of bounds sparse data"
+            ))
+        );
+        assert_eq!(
+            load_sparse(
+                &SPARSE_JSON.replace("\"count\": 3,", "\"count\": 1000000000000,"),
+                2
+            )
+            .err(),
+            Some(String::from("mesh 0: accessor 0 is too large"))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_documents() {
+        for json in [
+            "",
+            "{\"asset\":",
+            "{\"asset\": {\"version\": \"2.0\"}} trailing",
+        ] {
+            assert!(load(json.as_bytes(), no_uris).is_err());
+        }
+        assert!(load(&[0xff, 0xfe], no_uris).is_err());
+        assert_eq!(
+            load(br#"{"asset": {"version": "1.0"}}"#, no_uris).err(),
+            Some(String::from("unsupported glTF version 1.0"))
+        );
+        assert_eq!(
+            load_interleaved(&INTERLEAVED_JSON.replace("\"COLOR_0\": 2", "\"COLOR_0\": 7")).err(),
+            Some(String::from("mesh 0: accessor 7 doesn't exist"))
+        );
+        assert_eq!(
+            load_interleaved(&INTERLEAVED_JSON.replace("\"COLOR_0\": 2", "\"COLOR_0\": -1")).err(),
+            Some(String::from(
+                "mesh 0: COLOR_0 must be a non-negative integer"
+            ))
+        );
+        assert_eq!(
+            load(
+                &glb(&GLB_JSON.replace("\"mesh\": 0", "\"mesh\": 1"), &[0; 44]),
+                no_uris
+            )
+            .err(),
+            Some(String::from("node 0 refers to a missing mesh"))
+        );
+    }
+}
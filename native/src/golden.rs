@@ -0,0 +1,101 @@
+use crate::image::Image;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn assert_matches_golden(
+    name: &str,
+    image: &Image,
+    channel_tolerance: u8,
+    pixel_tolerance: f32,
+) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let path = root.join("golden").join(format!("{}.png", name));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        write_png(&path, image);
+        return;
+    }
+    let reference = fs::read(&path)
+        .map_err(|error| format!("can't read {}: {}", path.display(), error))
+        .and_then(|bytes| Image::decode(&bytes))
+        .unwrap_or_else(|error| panic!("{} (run with UPDATE_GOLDEN=1 to create it)", error));
+    let mismatch = compare(&reference, image, channel_tolerance);
+    if mismatch > pixel_tolerance {
+        let actual_path: PathBuf = root
+            .join("target")
+            .join("golden")
+            .join(format!("{}.png", name));
+        write_png(&actual_path, image);
+        panic!(
+            "{} differs from {} in {:.2}% of pixels (tolerance {:.2}%), actual image written to {}",
+            name,
+            path.display(),
+            mismatch * 100.0,
+            pixel_tolerance * 100.0,
+            actual_path.display()
+        );
+    }
+}
+
+fn compare(reference: &Image, image: &Image, channel_tolerance: u8) -> f32 {
+    if reference.width != image.width || reference.height != image.height {
+        return 1.0;
+    }
+    let pixel_count = image.width * image.height;
+    if pixel_count == 0 {
+        return 0.0;
+    }
+    let mismatched = reference
+        .data
+        .chunks_exact(4)
+        .zip(image.data.chunks_exact(4))
+        .filter(|(a, b)| {
+            a.iter()
+                .zip(b.iter())
+                .any(|(a, b)| (*a as i16 - *b as i16).abs() > channel_tolerance as i16)
+        })
+        .count();
+    mismatched as f32 / pixel_count as f32
+}
+
+fn write_png(path: &Path, image: &Image) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, image.encode_png().unwrap())
+        .unwrap_or_else(|error| panic!("can't write {}: {}", path.display(), error));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(pixels: &[[u8; 4]]) -> Image {
+        Image {
+            width: pixels.len(),
+            height: 1,
+            data: pixels.iter().flatten().cloned().collect(),
+        }
+    }
+
+    #[test]
+    fn counts_pixels_outside_channel_tolerance() {
+        let reference = image(&[[0, 0, 0, 255], [100, 100, 100, 255]]);
+        assert_eq!(compare(&reference, &reference, 0), 0.0);
+        assert_eq!(
+            compare(
+                &reference,
+                &image(&[[2, 0, 0, 255], [100, 100, 100, 255]]),
+                2
+            ),
+            0.0
+        );
+        assert_eq!(
+            compare(
+                &reference,
+                &image(&[[3, 0, 0, 255], [100, 100, 100, 255]]),
+                2
+            ),
+            0.5
+        );
+        assert_eq!(compare(&reference, &image(&[[0, 0, 0, 255]]), 255), 1.0);
+    }
+}
@@ -0,0 +1,398 @@
+use crate::gl;
+use crate::gl::Context;
+use crate::gl_debug;
+use libGLESv3_sys::{
+    GLint, GLuint, GLuint64, GL_GPU_DISJOINT_EXT, GL_QUERY_RESULT, GL_QUERY_RESULT_AVAILABLE,
+    GL_TIME_ELAPSED_EXT,
+};
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::mem;
+use std::time::Duration;
+
+const MAX_REGIONS_PER_FRAME: usize = 8;
+const MAX_FRAMES_IN_FLIGHT: usize = 4;
+const LOG_INTERVAL: usize = 300;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RegionTiming {
+    pub name: &'static str,
+    pub duration: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameTimings {
+    pub frame_index: u64,
+    pub regions: Vec<RegionTiming>,
+}
+
+struct PendingFrame {
+    frame_index: u64,
+    regions: Vec<(&'static str, usize)>,
+}
+
+pub struct QueryRing {
+    free: Vec<usize>,
+    current: PendingFrame,
+    pending: VecDeque<PendingFrame>,
+    dropped_region_count: usize,
+    disjoint_frame_count: usize,
+}
+
+impl QueryRing {
+    pub fn new(capacity: usize) -> QueryRing {
+        QueryRing {
+            free: (0..capacity).rev().collect(),
+            current: PendingFrame {
+                frame_index: 0,
+                regions: Vec::new(),
+            },
+            pending: VecDeque::new(),
+            dropped_region_count: 0,
+            disjoint_frame_count: 0,
+        }
+    }
+
+    pub fn dropped_region_count(&self) -> usize {
+        self.dropped_region_count
+    }
+
+    pub fn disjoint_frame_count(&self) -> usize {
+        self.disjoint_frame_count
+    }
+
+    pub fn pending_frame_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn begin_frame(&mut self, frame_index: u64) {
+        let regions = mem::take(&mut self.current.regions);
+        self.release(regions);
+        self.current.frame_index = frame_index;
+    }
+
+    pub fn allocate(&mut self, name: &'static str) -> Option<usize> {
+        match self.free.pop() {
+            Some(slot) => {
+                self.current.regions.push((name, slot));
+                Some(slot)
+            }
+            None => {
+                self.dropped_region_count += 1;
+                None
+            }
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        if self.current.regions.is_empty() {
+            return;
+        }
+        self.pending.push_back(PendingFrame {
+            frame_index: self.current.frame_index,
+            regions: mem::take(&mut self.current.regions),
+        });
+    }
+
+    pub fn collect<A, R>(
+        &mut self,
+        disjoint: bool,
+        mut is_available: A,
+        mut result: R,
+    ) -> Vec<FrameTimings>
+    where
+        A: FnMut(usize) -> bool,
+        R: FnMut(usize) -> Duration,
+    {
+        if disjoint {
+            self.disjoint_frame_count += self.pending.len();
+            while let Some(frame) = self.pending.pop_front() {
+                self.release(frame.regions);
+            }
+            return Vec::new();
+        }
+        let mut timings = Vec::new();
+        while let Some(frame) = self.pending.front() {
+            if !frame.regions.iter().all(|(_, slot)| is_available(*slot)) {
+                break;
+            }
+            let frame = self.pending.pop_front().unwrap();
+            timings.push(FrameTimings {
+                frame_index: frame.frame_index,
+                regions: frame
+                    .regions
+                    .iter()
+                    .map(|(name, slot)| RegionTiming {
+                        name,
+                        duration: result(*slot),
+                    })
+                    .collect(),
+            });
+            self.release(frame.regions);
+        }
+        timings
+    }
+
+    fn release(&mut self, regions: Vec<(&'static str, usize)>) {
+        self.free
+            .extend(regions.into_iter().rev().map(|(_, slot)| slot));
+    }
+}
+
+pub struct GpuProfiler {
+    queries: Vec<gl::Query>,
+    ring: QueryRing,
+    frame_index: u64,
+    active_region: bool,
+    latest_timings: Option<FrameTimings>,
+    totals: Vec<(&'static str, Duration)>,
+    total_frame_count: usize,
+}
+
+impl GpuProfiler {
+    pub fn new(context: &Context) -> GpuProfiler {
+        let capacity = if gl_debug::has_extension("GL_EXT_disjoint_timer_query") {
+            MAX_REGIONS_PER_FRAME * MAX_FRAMES_IN_FLIGHT
+        } else {
+            logi!("EXT_disjoint_timer_query is not supported");
+            0
+        };
+        GpuProfiler {
+            queries: (0..capacity).map(|_| gl::Query::new(context)).collect(),
+            ring: QueryRing::new(capacity),
+            frame_index: 0,
+            active_region: false,
+            latest_timings: None,
+            totals: Vec::new(),
+            total_frame_count: 0,
+        }
+    }
+
+    pub fn latest_timings(&self) -> Option<&FrameTimings> {
+        self.latest_timings.as_ref()
+    }
+
+    pub fn begin_frame(&mut self) {
+        if self.ring.pending_frame_count() > 0 {
+            let queries = &self.queries;
+            let mut disjoint: GLint = 0;
+            let timings = unsafe {
+                gl_check!(glGetIntegerv(GL_GPU_DISJOINT_EXT, &mut disjoint));
+                self.ring.collect(
+                    disjoint != 0,
+                    |slot| get_query_object(&queries[slot], GL_QUERY_RESULT_AVAILABLE) != 0,
+                    |slot| Duration::from_nanos(get_query_result(&queries[slot])),
+                )
+            };
+            if disjoint != 0 {
+                logw!("GPU timer queries were disjoint, discarding timings");
+            }
+            for timings in timings {
+                self.accumulate(&timings);
+                self.latest_timings = Some(timings);
+            }
+        }
+        self.ring.begin_frame(self.frame_index);
+    }
+
+    pub fn begin_region(&mut self, name: &'static str) {
+        if self.active_region {
+            panic!("can't begin GPU region {} inside another region", name);
+        }
+        if let Some(slot) = self.ring.allocate(name) {
+            unsafe {
+                gl_check!(glBeginQuery(GL_TIME_ELAPSED_EXT, self.queries[slot].name()));
+            }
+            self.active_region = true;
+        }
+    }
+
+    pub fn end_region(&mut self) {
+        if self.active_region {
+            unsafe {
+                gl_check!(glEndQuery(GL_TIME_ELAPSED_EXT));
+            }
+            self.active_region = false;
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        self.end_region();
+        self.ring.end_frame();
+        self.frame_index += 1;
+    }
+
+    fn accumulate(&mut self, timings: &FrameTimings) {
+        for region in timings.regions.iter() {
+            match self
+                .totals
+                .iter_mut()
+                .find(|(name, _)| *name == region.name)
+            {
+                Some((_, total)) => *total += region.duration,
+                None => self.totals.push((region.name, region.duration)),
+            }
+        }
+        self.total_frame_count += 1;
+        if self.total_frame_count < LOG_INTERVAL {
+            return;
+        }
+        let mut text = String::new();
+        for (name, total) in self.totals.iter() {
+            let average = total.as_secs_f64() * 1000.0 / self.total_frame_count as f64;
+            write!(text, ", {} {:.2} ms", name, average).unwrap();
+        }
+        logi!(
+            "average GPU time over {} frames{} (dropped {} regions, {} disjoint frames)",
+            self.total_frame_count,
+            text,
+            self.ring.dropped_region_count(),
+            self.ring.disjoint_frame_count()
+        );
+        self.totals.clear();
+        self.total_frame_count = 0;
+    }
+}
+
+unsafe fn get_query_object(query: &gl::Query, name: u32) -> GLuint {
+    let mut value = 0;
+    gl_check!(glGetQueryObjectuiv(query.name(), name, &mut value));
+    value
+}
+
+unsafe fn get_query_result(query: &gl::Query) -> GLuint64 {
+    let mut value = 0;
+    gl_check!(glGetQueryObjectui64vEXT(
+        query.name(),
+        GL_QUERY_RESULT,
+        &mut value
+    ));
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_gl::{MockGl, QUERY_ELAPSED_TIME};
+    use std::rc::Rc;
+
+    fn nanoseconds(slot: usize) -> Duration {
+        Duration::from_nanos(slot as u64 * 100)
+    }
+
+    #[test]
+    fn collects_frames_in_order_once_available() {
+        let mut ring = QueryRing::new(4);
+        for frame_index in 0..2 {
+            ring.begin_frame(frame_index);
+            ring.allocate("left eye").unwrap();
+            ring.allocate("right eye").unwrap();
+            ring.end_frame();
+        }
+        assert!(ring
+            .collect(false, |slot| slot != 1, nanoseconds)
+            .is_empty());
+        let timings = ring.collect(false, |_| true, nanoseconds);
+        assert_eq!(
+            timings,
+            vec![
+                FrameTimings {
+                    frame_index: 0,
+                    regions: vec![
+                        RegionTiming {
+                            name: "left eye",
+                            duration: Duration::from_nanos(0),
+                        },
+                        RegionTiming {
+                            name: "right eye",
+                            duration: Duration::from_nanos(100),
+                        },
+                    ],
+                },
+                FrameTimings {
+                    frame_index: 1,
+                    regions: vec![
+                        RegionTiming {
+                            name: "left eye",
+                            duration: Duration::from_nanos(200),
+                        },
+                        RegionTiming {
+                            name: "right eye",
+                            duration: Duration::from_nanos(300),
+                        },
+                    ],
+                },
+            ]
+        );
+        assert_eq!(ring.pending_frame_count(), 0);
+    }
+
+    #[test]
+    fn drops_regions_when_pool_is_exhausted() {
+        let mut ring = QueryRing::new(3);
+        ring.begin_frame(0);
+        ring.allocate("shadow").unwrap();
+        ring.allocate("left eye").unwrap();
+        ring.end_frame();
+        ring.begin_frame(1);
+        assert!(ring.allocate("shadow").is_some());
+        assert!(ring.allocate("left eye").is_none());
+        ring.end_frame();
+        assert_eq!(ring.dropped_region_count(), 1);
+
+        assert_eq!(ring.collect(false, |_| true, nanoseconds).len(), 2);
+        ring.begin_frame(2);
+        for _ in 0..3 {
+            assert!(ring.allocate("region").is_some());
+        }
+    }
+
+    #[test]
+    fn discards_pending_frames_on_disjoint() {
+        let mut ring = QueryRing::new(2);
+        for frame_index in 0..2 {
+            ring.begin_frame(frame_index);
+            ring.allocate("eye").unwrap();
+            ring.end_frame();
+        }
+        assert!(ring.collect(true, |_| true, nanoseconds).is_empty());
+        assert_eq!(ring.disjoint_frame_count(), 2);
+        assert_eq!(ring.pending_frame_count(), 0);
+        ring.begin_frame(2);
+        assert!(ring.allocate("eye").is_some());
+        assert!(ring.allocate("eye").is_some());
+    }
+
+    #[test]
+    fn releases_slots_of_unfinished_frames() {
+        let mut ring = QueryRing::new(1);
+        ring.begin_frame(0);
+        ring.allocate("eye").unwrap();
+        ring.begin_frame(1);
+        assert!(ring.allocate("eye").is_some());
+    }
+
+    #[test]
+    fn times_regions_with_queries() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut profiler = GpuProfiler::new(&context);
+        for _ in 0..2 {
+            profiler.begin_frame();
+            profiler.begin_region("left eye");
+            profiler.end_region();
+            profiler.begin_region("right eye");
+            profiler.end_region();
+            profiler.end_frame();
+        }
+        let timings = profiler.latest_timings().unwrap();
+        assert_eq!(timings.frame_index, 0);
+        let names: Vec<&str> = timings.regions.iter().map(|region| region.name).collect();
+        assert_eq!(names, ["left eye", "right eye"]);
+        assert!(timings
+            .regions
+            .iter()
+            .all(|region| region.duration == Duration::from_nanos(QUERY_ELAPSED_TIME)));
+        assert!(gl.errors().is_empty());
+    }
+}
@@ -0,0 +1,333 @@
+use crate::frame_stats::FrameStats;
+use crate::gl::Context;
+use crate::image::Image;
+use crate::swap_chain::SwapChain;
+use libGLESv3_sys::{GLsizei, GL_RGBA, GL_TEXTURE_2D, GL_UNSIGNED_BYTE};
+
+pub const HUD_WIDTH: GLsizei = 256;
+pub const HUD_HEIGHT: GLsizei = 128;
+
+const BACKGROUND_COLOR: [u8; 4] = [0, 0, 0, 160];
+const TEXT_COLOR: [u8; 4] = [255, 255, 255, 255];
+const ON_TIME_COLOR: [u8; 4] = [64, 220, 64, 255];
+const LATE_COLOR: [u8; 4] = [240, 64, 64, 255];
+const BUDGET_COLOR: [u8; 4] = [255, 255, 255, 128];
+
+const MARGIN: usize = 4;
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+const GLYPH_SCALE: usize = 2;
+const LINE_HEIGHT: usize = 14;
+const GRAPH_TOP: usize = MARGIN + 2 * LINE_HEIGHT + 2;
+
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT]); 44] = [
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('%', [0b101, 0b001, 0b010, 0b100, 0b101]),
+    ('(', [0b001, 0b010, 0b010, 0b010, 0b001]),
+    (')', [0b100, 0b010, 0b010, 0b010, 0b100]),
+];
+
+pub struct Hud {
+    gl: Context,
+    visible: bool,
+    swap_chain: SwapChain,
+    image: Image,
+}
+
+impl Hud {
+    pub fn new(gl: &Context) -> Hud {
+        Hud {
+            gl: gl.clone(),
+            visible: false,
+            swap_chain: SwapChain::new(gl, HUD_WIDTH, HUD_HEIGHT, 1),
+            image: Image {
+                width: HUD_WIDTH as usize,
+                height: HUD_HEIGHT as usize,
+                data: vec![0; HUD_WIDTH as usize * HUD_HEIGHT as usize * 4],
+            },
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        logi!("{} performance hud", if visible { "show" } else { "hide" });
+        self.visible = visible;
+    }
+
+    pub fn toggle(&mut self) {
+        self.set_visible(!self.visible);
+    }
+
+    pub fn swap_chain(&self) -> &SwapChain {
+        &self.swap_chain
+    }
+
+    pub fn update(&mut self, stats: &FrameStats, eye_width: GLsizei, eye_height: GLsizei) {
+        draw(&mut self.image, stats, eye_width, eye_height);
+        let row_size = self.image.width * 4;
+        let data: Vec<u8> = self
+            .image
+            .data
+            .chunks_exact(row_size)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        let texture = self.swap_chain.color_texture(self.swap_chain.index());
+        self.gl.state().bind_texture(0, GL_TEXTURE_2D, texture);
+        unsafe {
+            gl_check!(glTexSubImage2D(
+                GL_TEXTURE_2D,
+                0,
+                0,
+                0,
+                HUD_WIDTH,
+                HUD_HEIGHT,
+                GL_RGBA,
+                GL_UNSIGNED_BYTE,
+                data.as_ptr() as *const _,
+            ));
+        }
+        self.gl.state().bind_texture(0, GL_TEXTURE_2D, 0);
+        self.swap_chain.advance();
+    }
+}
+
+pub fn draw(image: &mut Image, stats: &FrameStats, eye_width: GLsizei, eye_height: GLsizei) {
+    let (width, height) = (image.width, image.height);
+    image.data.iter_mut().for_each(|byte| *byte = 0);
+    fill_rect(image, 1, 1, width - 2, height - 2, BACKGROUND_COLOR);
+
+    let cpu_frame_time = stats.latest().map_or(0.0, |sample| sample.cpu_frame_time());
+    draw_text(
+        image,
+        MARGIN,
+        MARGIN,
+        &format!(
+            "{:.0} FPS  CPU {:.1} MS",
+            stats.frame_rate().unwrap_or(0.0),
+            cpu_frame_time * 1000.0
+        ),
+    );
+    draw_text(
+        image,
+        MARGIN,
+        MARGIN + LINE_HEIGHT,
+        &format!(
+            "DROPPED {}  EYE {}X{}",
+            stats.recent_dropped_frame_count(),
+            eye_width,
+            eye_height
+        ),
+    );
+
+    let graph_width = width - 2 * MARGIN;
+    let graph_height = height - MARGIN - GRAPH_TOP;
+    let graph_bottom = GRAPH_TOP + graph_height;
+    let budget = stats.display_period();
+    let samples: Vec<f64> = stats.cpu_frame_times().samples().collect();
+    let visible = &samples[samples.len().saturating_sub(graph_width)..];
+    let left = MARGIN + graph_width - visible.len();
+    for (index, frame_time) in visible.iter().enumerate() {
+        let scale = (frame_time / (2.0 * budget)).clamp(0.0, 1.0);
+        let bar_height = (scale * graph_height as f64).round() as usize;
+        let color = if *frame_time <= budget {
+            ON_TIME_COLOR
+        } else {
+            LATE_COLOR
+        };
+        fill_rect(
+            image,
+            left + index,
+            graph_bottom - bar_height,
+            1,
+            bar_height,
+            color,
+        );
+    }
+    fill_rect(
+        image,
+        MARGIN,
+        graph_bottom - graph_height / 2,
+        graph_width,
+        1,
+        BUDGET_COLOR,
+    );
+}
+
+fn draw_text(image: &mut Image, x: usize, y: usize, text: &str) {
+    for (index, char) in text.chars().enumerate() {
+        let rows = match GLYPHS.iter().find(|(glyph, _)| *glyph == char) {
+            Some((_, rows)) => rows,
+            None => continue,
+        };
+        let left = x + index * (GLYPH_WIDTH + 1) * GLYPH_SCALE;
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                    fill_rect(
+                        image,
+                        left + column * GLYPH_SCALE,
+                        y + row * GLYPH_SCALE,
+                        GLYPH_SCALE,
+                        GLYPH_SCALE,
+                        TEXT_COLOR,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn fill_rect(image: &mut Image, x: usize, y: usize, width: usize, height: usize, color: [u8; 4]) {
+    let right = (x + width).min(image.width);
+    let bottom = (y + height).min(image.height);
+    for row in y.min(bottom)..bottom {
+        for column in x.min(right)..right {
+            let offset = (row * image.width + column) * 4;
+            image.data[offset..offset + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_stats::FrameSample;
+    use crate::mock_gl::MockGl;
+    use std::rc::Rc;
+
+    const PERIOD: f64 = 1.0 / 72.0;
+
+    fn pixel(image: &Image, x: usize, y: usize) -> [u8; 4] {
+        let offset = (y * image.width + x) * 4;
+        [
+            image.data[offset],
+            image.data[offset + 1],
+            image.data[offset + 2],
+            image.data[offset + 3],
+        ]
+    }
+
+    fn stats(frame_times: &[f64]) -> FrameStats {
+        let mut stats = FrameStats::new(72.0);
+        for (index, frame_time) in frame_times.iter().enumerate() {
+            let predicted_display_time = index as f64 * PERIOD;
+            stats.record(FrameSample {
+                frame_index: index as u64,
+                frame_start_time: predicted_display_time - 0.03,
+                predicted_display_time,
+                submit_time: predicted_display_time - 0.03 + frame_time,
+                app_latency: None,
+                compositor_latency: None,
+            });
+        }
+        stats
+    }
+
+    fn image() -> Image {
+        Image {
+            width: HUD_WIDTH as usize,
+            height: HUD_HEIGHT as usize,
+            data: vec![0; HUD_WIDTH as usize * HUD_HEIGHT as usize * 4],
+        }
+    }
+
+    #[test]
+    fn draws_scaled_glyphs() {
+        let mut image = image();
+        draw_text(&mut image, 10, 20, "1");
+        assert_eq!(pixel(&image, 12, 20), TEXT_COLOR);
+        assert_eq!(pixel(&image, 13, 21), TEXT_COLOR);
+        assert_eq!(pixel(&image, 10, 20), [0, 0, 0, 0]);
+        assert_eq!(pixel(&image, 10, 22), TEXT_COLOR);
+        assert_eq!(pixel(&image, 14, 22), [0, 0, 0, 0]);
+        assert_eq!(pixel(&image, 14, 28), TEXT_COLOR);
+    }
+
+    #[test]
+    fn graphs_frame_times_against_budget() {
+        let mut image = image();
+        let stats = stats(&[0.005, 0.02]);
+        draw(&mut image, &stats, 1440, 1584);
+        let (width, height) = (image.width, image.height);
+        let bottom = height - MARGIN - 1;
+        assert_eq!(pixel(&image, 0, 0), [0, 0, 0, 0]);
+        assert_eq!(pixel(&image, width - 1, height - 1), [0, 0, 0, 0]);
+        assert_eq!(pixel(&image, width - MARGIN - 2, bottom), ON_TIME_COLOR);
+        assert_eq!(pixel(&image, width - MARGIN - 1, bottom), LATE_COLOR);
+        assert_eq!(
+            pixel(&image, width - MARGIN - 1, GRAPH_TOP + 2),
+            BACKGROUND_COLOR
+        );
+        assert_eq!(pixel(&image, width - MARGIN - 3, bottom), BACKGROUND_COLOR);
+        assert_eq!(
+            pixel(&image, MARGIN, GRAPH_TOP + (bottom + 1 - GRAPH_TOP) / 2),
+            BUDGET_COLOR
+        );
+    }
+
+    #[test]
+    fn uploads_into_swap_chain() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut hud = Hud::new(&context);
+        assert!(!hud.is_visible());
+        hud.toggle();
+        assert!(hud.is_visible());
+        gl.clear_calls();
+        hud.update(&stats(&[0.01]), 1440, 1584);
+        assert_eq!(hud.swap_chain().index(), 1);
+        assert_eq!(
+            gl.calls()
+                .iter()
+                .filter(|call| call.function == "glTexSubImage2D")
+                .count(),
+            1
+        );
+        assert!(gl.errors().is_empty());
+    }
+}
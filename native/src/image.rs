@@ -0,0 +1,123 @@
+use png::{BitDepth, ColorType, Encoder, Transformations};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            decode_png(bytes)
+        } else if bytes.starts_with(&[0xff, 0xd8]) {
+            decode_jpeg(bytes)
+        } else {
+            Err(String::from("unknown image format"))
+        }
+    }
+
+    pub fn encode_png(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        let mut encoder = Encoder::new(&mut bytes, self.width as u32, self.height as u32);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.data))
+            .map_err(|error| format!("can't encode PNG: {}", error))?;
+        Ok(bytes)
+    }
+
+    pub fn mip_level_count(&self) -> usize {
+        mip_level_count(self.width, self.height)
+    }
+}
+
+pub fn mip_level_count(width: usize, height: usize) -> usize {
+    let mut size = width.max(height).max(1);
+    let mut count = 1;
+    while size > 1 {
+        size /= 2;
+        count += 1;
+    }
+    count
+}
+
+fn decode_png(bytes: &[u8]) -> Result<Image, String> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|error| format!("can't decode PNG: {}", error))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(|error| format!("can't decode PNG: {}", error))?;
+    let pixels = &buffer[..info.buffer_size()];
+    let data = match info.color_type {
+        ColorType::Grayscale => expand(pixels, 1, |pixel| [pixel[0], pixel[0], pixel[0], 255]),
+        ColorType::GrayscaleAlpha => {
+            expand(pixels, 2, |pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+        }
+        ColorType::Rgb => expand(pixels, 3, |pixel| [pixel[0], pixel[1], pixel[2], 255]),
+        ColorType::Rgba => pixels.to_vec(),
+        ColorType::Indexed => return Err(String::from("can't expand indexed PNG")),
+    };
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        data,
+    })
+}
+
+fn decode_jpeg(bytes: &[u8]) -> Result<Image, String> {
+    let mut decoder = jpeg_decoder::Decoder::new(bytes);
+    let pixels = decoder
+        .decode()
+        .map_err(|error| format!("can't decode JPEG: {}", error))?;
+    let info = decoder.info().unwrap();
+    let data = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => {
+            expand(&pixels, 1, |pixel| [pixel[0], pixel[0], pixel[0], 255])
+        }
+        jpeg_decoder::PixelFormat::RGB24 => {
+            expand(&pixels, 3, |pixel| [pixel[0], pixel[1], pixel[2], 255])
+        }
+        pixel_format => return Err(format!("unsupported JPEG pixel format {:?}", pixel_format)),
+    };
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        data,
+    })
+}
+
+fn expand<F>(pixels: &[u8], channels: usize, f: F) -> Vec<u8>
+where
+    F: Fn(&[u8]) -> [u8; 4],
+{
+    let mut data = Vec::with_capacity(pixels.len() / channels * 4);
+    for pixel in pixels.chunks_exact(channels) {
+        data.extend_from_slice(&f(pixel));
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_png() {
+        let image = Image {
+            width: 3,
+            height: 2,
+            data: (0..24).map(|value| value * 10).collect(),
+        };
+        let bytes = image.encode_png().unwrap();
+        assert!(bytes.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(Image::decode(&bytes).unwrap(), image);
+    }
+}
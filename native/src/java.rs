@@ -0,0 +1,81 @@
+use jni::sys::{jobject, jstring, jvalue, JNIEnv};
+use std::ffi::{CStr, CString};
+use std::path::PathBuf;
+use std::ptr;
+
+pub unsafe fn get_files_dir(env: *mut JNIEnv, activity: jobject) -> PathBuf {
+    let file = call_object_method(env, activity, "getFilesDir", "()Ljava/io/File;");
+    let files_dir = get_absolute_path(env, file);
+    delete_local_ref(env, file);
+    files_dir
+}
+
+pub unsafe fn get_external_files_dir(env: *mut JNIEnv, activity: jobject) -> PathBuf {
+    let file = call_object_method_with_arguments(
+        env,
+        activity,
+        "getExternalFilesDir",
+        "(Ljava/lang/String;)Ljava/io/File;",
+        &[jvalue { l: ptr::null_mut() }],
+    );
+    if file.is_null() {
+        panic!("can't get external files dir");
+    }
+    let external_files_dir = get_absolute_path(env, file);
+    delete_local_ref(env, file);
+    external_files_dir
+}
+
+pub unsafe fn call_object_method(
+    env: *mut JNIEnv,
+    object: jobject,
+    name: &str,
+    signature: &str,
+) -> jobject {
+    call_object_method_with_arguments(env, object, name, signature, &[])
+}
+
+pub unsafe fn call_object_method_with_arguments(
+    env: *mut JNIEnv,
+    object: jobject,
+    name: &str,
+    signature: &str,
+    arguments: &[jvalue],
+) -> jobject {
+    let class = ((**env).GetObjectClass.unwrap())(env, object);
+    let method = ((**env).GetMethodID.unwrap())(
+        env,
+        class,
+        CString::new(name).unwrap().as_ptr(),
+        CString::new(signature).unwrap().as_ptr(),
+    );
+    delete_local_ref(env, class);
+    if method.is_null() {
+        panic!("can't find method {} {}", name, signature);
+    }
+    let result = ((**env).CallObjectMethodA.unwrap())(env, object, method, arguments.as_ptr());
+    if ((**env).ExceptionCheck.unwrap())(env) != 0 {
+        ((**env).ExceptionDescribe.unwrap())(env);
+        ((**env).ExceptionClear.unwrap())(env);
+        panic!("can't call method {} {}", name, signature);
+    }
+    result
+}
+
+pub unsafe fn get_string(env: *mut JNIEnv, string: jstring) -> String {
+    let chars = ((**env).GetStringUTFChars.unwrap())(env, string, ptr::null_mut());
+    let result = String::from(CStr::from_ptr(chars).to_str().unwrap());
+    ((**env).ReleaseStringUTFChars.unwrap())(env, string, chars);
+    result
+}
+
+pub unsafe fn delete_local_ref(env: *mut JNIEnv, object: jobject) {
+    ((**env).DeleteLocalRef.unwrap())(env, object);
+}
+
+unsafe fn get_absolute_path(env: *mut JNIEnv, file: jobject) -> PathBuf {
+    let path = call_object_method(env, file, "getAbsolutePath", "()Ljava/lang/String;");
+    let result = PathBuf::from(get_string(env, path));
+    delete_local_ref(env, path);
+    result
+}
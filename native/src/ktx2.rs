@@ -0,0 +1,406 @@
+const IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Rgba8 {
+        srgb: bool,
+    },
+    Etc2Rgb8 {
+        srgb: bool,
+    },
+    Etc2Rgb8A1 {
+        srgb: bool,
+    },
+    Etc2Rgba8 {
+        srgb: bool,
+    },
+    EacR11,
+    EacRg11,
+    Astc {
+        block_width: usize,
+        block_height: usize,
+        srgb: bool,
+    },
+}
+
+impl Format {
+    pub fn from_vk_format(vk_format: u32) -> Option<Format> {
+        let srgb = vk_format.is_multiple_of(2);
+        Some(match vk_format {
+            37 => Format::Rgba8 { srgb: false },
+            43 => Format::Rgba8 { srgb: true },
+            147 | 148 => Format::Etc2Rgb8 { srgb },
+            149 | 150 => Format::Etc2Rgb8A1 { srgb },
+            151 | 152 => Format::Etc2Rgba8 { srgb },
+            153 => Format::EacR11,
+            155 => Format::EacRg11,
+            157..=184 => {
+                let (block_width, block_height) = ASTC_BLOCK_SIZES[(vk_format as usize - 157) / 2];
+                Format::Astc {
+                    block_width,
+                    block_height,
+                    srgb,
+                }
+            }
+            _ => return None,
+        })
+    }
+
+    pub fn block_width(self) -> usize {
+        match self {
+            Format::Rgba8 { .. } => 1,
+            Format::Astc { block_width, .. } => block_width,
+            _ => 4,
+        }
+    }
+
+    pub fn block_height(self) -> usize {
+        match self {
+            Format::Rgba8 { .. } => 1,
+            Format::Astc { block_height, .. } => block_height,
+            _ => 4,
+        }
+    }
+
+    pub fn block_size(self) -> usize {
+        match self {
+            Format::Rgba8 { .. } => 4,
+            Format::Etc2Rgb8 { .. } | Format::Etc2Rgb8A1 { .. } | Format::EacR11 => 8,
+            _ => 16,
+        }
+    }
+
+    pub fn is_compressed(self) -> bool {
+        !matches!(self, Format::Rgba8 { .. })
+    }
+
+    pub fn image_size(self, width: usize, height: usize) -> Option<usize> {
+        let blocks_x = width.div_ceil(self.block_width());
+        let blocks_y = height.div_ceil(self.block_height());
+        blocks_x
+            .checked_mul(blocks_y)?
+            .checked_mul(self.block_size())
+    }
+}
+
+const ASTC_BLOCK_SIZES: [(usize, usize); 14] = [
+    (4, 4),
+    (5, 4),
+    (5, 5),
+    (6, 5),
+    (6, 6),
+    (8, 5),
+    (8, 6),
+    (8, 8),
+    (10, 5),
+    (10, 6),
+    (10, 8),
+    (10, 10),
+    (12, 10),
+    (12, 12),
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Level {
+    pub offset: usize,
+    pub length: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Ktx2 {
+    pub format: Format,
+    pub width: usize,
+    pub height: usize,
+    pub layer_count: usize,
+    pub face_count: usize,
+    pub generate_mipmaps: bool,
+    pub levels: Vec<Level>,
+}
+
+impl Ktx2 {
+    pub fn parse(bytes: &[u8]) -> Result<Ktx2, String> {
+        if bytes.len() < HEADER_SIZE || bytes[..12] != IDENTIFIER {
+            return Err(String::from("not a KTX2 file"));
+        }
+        let vk_format = read_u32(bytes, 12);
+        let width = read_u32(bytes, 20) as usize;
+        let height = read_u32(bytes, 24) as usize;
+        let depth = read_u32(bytes, 28) as usize;
+        let layer_count = read_u32(bytes, 32) as usize;
+        let face_count = read_u32(bytes, 36) as usize;
+        let level_count = read_u32(bytes, 40) as usize;
+        let supercompression_scheme = read_u32(bytes, 44);
+
+        let format = Format::from_vk_format(vk_format)
+            .ok_or_else(|| format!("unsupported vkFormat {}", vk_format))?;
+        if supercompression_scheme != 0 {
+            return Err(format!(
+                "unsupported supercompression scheme {}",
+                supercompression_scheme
+            ));
+        }
+        if width == 0 || height == 0 {
+            return Err(String::from("1D textures are not supported"));
+        }
+        if depth != 0 {
+            return Err(String::from("3D textures are not supported"));
+        }
+        match face_count {
+            1 => {}
+            6 if width == height && layer_count == 0 => {}
+            6 if width == height => return Err(String::from("cube arrays are not supported")),
+            _ => return Err(format!("invalid face count {}", face_count)),
+        }
+        let generate_mipmaps = level_count == 0;
+        if generate_mipmaps && format.is_compressed() {
+            return Err(String::from(
+                "can't generate mipmaps for a compressed format",
+            ));
+        }
+        let level_count = level_count.max(1);
+        if level_count > 32 || (width.max(height) >> (level_count - 1)) == 0 {
+            return Err(format!("invalid level count {}", level_count));
+        }
+
+        let index_end = HEADER_SIZE + level_count * LEVEL_INDEX_ENTRY_SIZE;
+        if bytes.len() < index_end {
+            return Err(String::from("truncated level index"));
+        }
+        let mut levels = Vec::with_capacity(level_count);
+        for level in 0..level_count {
+            let entry = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+            let offset = read_u64(bytes, entry) as usize;
+            let length = read_u64(bytes, entry + 8) as usize;
+            let expected = format
+                .image_size((width >> level).max(1), (height >> level).max(1))
+                .and_then(|size| size.checked_mul(layer_count.max(1)))
+                .and_then(|size| size.checked_mul(face_count))
+                .ok_or_else(|| String::from("invalid level size"))?;
+            if length != expected {
+                return Err(format!(
+                    "level {} has {} bytes, expected {}",
+                    level, length, expected
+                ));
+            }
+            if offset < index_end
+                || offset
+                    .checked_add(length)
+                    .is_none_or(|end| end > bytes.len())
+            {
+                return Err(format!("level {} is out of bounds", level));
+            }
+            levels.push(Level { offset, length });
+        }
+
+        Ok(Ktx2 {
+            format,
+            width,
+            height,
+            layer_count,
+            face_count,
+            generate_mipmaps,
+            levels,
+        })
+    }
+
+    pub fn level_size(&self, level: usize) -> (usize, usize) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    pub fn level_data<'a>(&self, bytes: &'a [u8], level: usize) -> &'a [u8] {
+        let level = self.levels[level];
+        &bytes[level.offset..level.offset + level.length]
+    }
+}
+
+pub fn is_ktx2(bytes: &[u8]) -> bool {
+    bytes.starts_with(&IDENTIFIER)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    read_u32(bytes, offset) as u64 | (read_u32(bytes, offset + 4) as u64) << 32
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub const RGBA8: u32 = 37;
+    pub const ETC2_RGB8_SRGB: u32 = 148;
+
+    pub fn ktx2(
+        vk_format: u32,
+        size: u32,
+        level_count: u32,
+        supercompression_scheme: u32,
+    ) -> Vec<u8> {
+        let mut bytes = IDENTIFIER.to_vec();
+        for value in [
+            vk_format,
+            1,
+            size,
+            size,
+            0,
+            0,
+            1,
+            level_count,
+            supercompression_scheme,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.resize(HEADER_SIZE, 0);
+        let format = Format::from_vk_format(vk_format).unwrap();
+        let levels: Vec<usize> = (0..level_count.max(1) as usize)
+            .map(|level| {
+                let size = (size as usize >> level).max(1);
+                format.image_size(size, size).unwrap()
+            })
+            .collect();
+        let mut offset = HEADER_SIZE + levels.len() * LEVEL_INDEX_ENTRY_SIZE;
+        bytes.resize(offset, 0);
+        let mut data = Vec::new();
+        for (level, length) in levels.iter().cloned().enumerate().rev() {
+            data.extend((0..length).map(|_| level as u8));
+            let entry = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+            bytes[entry..entry + 8].copy_from_slice(&(offset as u64).to_le_bytes());
+            bytes[entry + 8..entry + 16].copy_from_slice(&(length as u64).to_le_bytes());
+            bytes[entry + 16..entry + 24].copy_from_slice(&(length as u64).to_le_bytes());
+            offset += length;
+        }
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+
+    #[test]
+    fn parses_mip_levels() {
+        let bytes = ktx2(RGBA8, 4, 3, 0);
+        let ktx2 = Ktx2::parse(&bytes).unwrap();
+        assert!(is_ktx2(&bytes));
+        assert_eq!(ktx2.format, Format::Rgba8 { srgb: false });
+        assert_eq!((ktx2.width, ktx2.height), (4, 4));
+        assert_eq!(ktx2.face_count, 1);
+        assert!(!ktx2.generate_mipmaps);
+        assert_eq!(
+            ktx2.levels,
+            [
+                Level {
+                    offset: 172,
+                    length: 64
+                },
+                Level {
+                    offset: 156,
+                    length: 16
+                },
+                Level {
+                    offset: 152,
+                    length: 4
+                },
+            ]
+        );
+        assert_eq!(ktx2.level_size(2), (1, 1));
+        assert_eq!(ktx2.level_data(&bytes, 1), [1; 16]);
+
+        let ktx2 = Ktx2::parse(&self::ktx2(RGBA8, 4, 0, 0)).unwrap();
+        assert!(ktx2.generate_mipmaps);
+        assert_eq!(ktx2.levels.len(), 1);
+    }
+
+    #[test]
+    fn parses_compressed_formats() {
+        let ktx2 = Ktx2::parse(&self::ktx2(ETC2_RGB8_SRGB, 8, 4, 0)).unwrap();
+        assert_eq!(ktx2.format, Format::Etc2Rgb8 { srgb: true });
+        let lengths: Vec<usize> = ktx2.levels.iter().map(|level| level.length).collect();
+        assert_eq!(lengths, [32, 8, 8, 8]);
+        assert_eq!(
+            Ktx2::parse(&self::ktx2(ETC2_RGB8_SRGB, 8, 0, 0)),
+            Err(String::from(
+                "can't generate mipmaps for a compressed format"
+            ))
+        );
+        assert_eq!(
+            Format::from_vk_format(184),
+            Some(Format::Astc {
+                block_width: 12,
+                block_height: 12,
+                srgb: true
+            })
+        );
+        assert_eq!(
+            Format::from_vk_format(157).unwrap().image_size(5, 4),
+            Some(32)
+        );
+        assert_eq!(Format::from_vk_format(1), None);
+    }
+
+    #[test]
+    fn rejects_bad_identifier() {
+        let mut bytes = ktx2(RGBA8, 4, 1, 0);
+        bytes[1] = b'X';
+        assert!(!is_ktx2(&bytes));
+        assert_eq!(Ktx2::parse(&bytes), Err(String::from("not a KTX2 file")));
+        assert_eq!(
+            Ktx2::parse(&IDENTIFIER),
+            Err(String::from("not a KTX2 file"))
+        );
+    }
+
+    #[test]
+    fn rejects_supercompression() {
+        assert_eq!(
+            Ktx2::parse(&ktx2(RGBA8, 4, 1, 2)),
+            Err(String::from("unsupported supercompression scheme 2"))
+        );
+    }
+
+    #[test]
+    fn rejects_levels_past_end_of_file() {
+        let bytes = ktx2(RGBA8, 4, 3, 0);
+        assert_eq!(
+            Ktx2::parse(&bytes[..bytes.len() - 1]),
+            Err(String::from("level 0 is out of bounds"))
+        );
+        assert_eq!(
+            Ktx2::parse(&bytes[..HEADER_SIZE + LEVEL_INDEX_ENTRY_SIZE]),
+            Err(String::from("truncated level index"))
+        );
+
+        let mut bytes = bytes;
+        let entry = HEADER_SIZE + 2 * LEVEL_INDEX_ENTRY_SIZE;
+        bytes[entry..entry + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            Ktx2::parse(&bytes),
+            Err(String::from("level 2 is out of bounds"))
+        );
+        bytes[entry..entry + 8].copy_from_slice(&(HEADER_SIZE as u64).to_le_bytes());
+        assert_eq!(
+            Ktx2::parse(&bytes),
+            Err(String::from("level 2 is out of bounds"))
+        );
+        bytes[entry + 8..entry + 16].copy_from_slice(&8u64.to_le_bytes());
+        assert_eq!(
+            Ktx2::parse(&bytes),
+            Err(String::from("level 2 has 8 bytes, expected 4"))
+        );
+    }
+
+    #[test]
+    fn rejects_overflowing_level_sizes() {
+        let mut bytes = ktx2(RGBA8, 4, 1, 0);
+        bytes[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+        bytes[24..28].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(Ktx2::parse(&bytes), Err(String::from("invalid level size")));
+    }
+}
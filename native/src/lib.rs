@@ -1,21 +1,72 @@
 #[macro_use]
-mod log;
+pub mod gl_debug;
+#[macro_use]
+pub mod log;
+#[macro_use]
+pub mod profiler;
+#[macro_use]
+pub mod std140;
+#[macro_use]
+pub mod vertex_layout;
+#[cfg(target_os = "android")]
 mod app;
+#[cfg(target_os = "android")]
 mod app_thread;
+pub mod assets;
+pub mod batcher;
+#[cfg(target_os = "android")]
 mod egl;
-mod geometry;
-mod program;
-mod swap_chain;
+pub mod environment;
+pub mod frame_stats;
+pub mod frustum;
+pub mod geometry;
+pub mod gl;
+pub mod gl_backend;
+pub mod gl_state;
+pub mod gltf;
+#[cfg(test)]
+mod golden;
+pub mod gpu_profiler;
+pub mod hud;
+pub mod image;
+#[cfg(target_os = "android")]
+mod java;
+pub mod json;
+pub mod ktx2;
+pub mod light;
+pub mod material;
+pub mod math;
+#[cfg(test)]
+mod mock_gl;
+pub mod model;
+pub mod preprocessor;
+pub mod program;
+pub mod program_cache;
+pub mod render_pass;
+pub mod render_target;
+pub mod renderer;
+pub mod sampler;
+pub mod scene;
+pub mod screenshot;
+pub mod shader_library;
+pub mod shader_watcher;
+pub mod shadow;
+pub mod shadow_map;
+#[cfg(test)]
+mod soft_gl;
+pub mod state_cache;
+pub mod swap_chain;
+pub mod texture;
+pub mod uniform_ring;
 
-use crate::app::App;
+#[cfg(target_os = "android")]
 use crate::app_thread::AppThread;
-use crate::egl::EGL;
-use crate::geometry::Geometry;
-use crate::program::Program;
-use crate::swap_chain::SwapChain;
+#[cfg(target_os = "android")]
 use jni::sys::{jlong, jobject, JNIEnv};
+#[cfg(target_os = "android")]
 use std::panic;
 
+#[cfg(target_os = "android")]
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_onCreate(
     env: *mut JNIEnv,
@@ -28,6 +79,7 @@ pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_onCreate(
     Box::into_raw(app_thread) as jlong
 }
 
+#[cfg(target_os = "android")]
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_onStart(
     _: *mut JNIEnv,
@@ -39,6 +91,7 @@ pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_onStart(
     app_thread.on_start();
 }
 
+#[cfg(target_os = "android")]
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_onResume(
     _: *mut JNIEnv,
@@ -50,6 +103,7 @@ pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_onResume(
     app_thread.on_resume();
 }
 
+#[cfg(target_os = "android")]
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_onPause(
     _: *mut JNIEnv,
@@ -61,6 +115,7 @@ pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_onPause(
     app_thread.on_pause();
 }
 
+#[cfg(target_os = "android")]
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_onStop(
     _: *mut JNIEnv,
@@ -72,6 +127,7 @@ pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_onStop(
     app_thread.on_stop();
 }
 
+#[cfg(target_os = "android")]
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_onDestroy(
     _: *mut JNIEnv,
@@ -84,6 +140,7 @@ pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_onDestroy(
     Box::from_raw(app_thread);
 }
 
+#[cfg(target_os = "android")]
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_surfaceCreated(
     env: *mut JNIEnv,
@@ -96,6 +153,7 @@ pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_surfaceCreated(
     app_thread.surface_created(env, surface);
 }
 
+#[cfg(target_os = "android")]
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_surfaceChanged(
     env: *mut JNIEnv,
@@ -108,6 +166,7 @@ pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_surfaceChanged(
     app_thread.surface_changed(env, surface);
 }
 
+#[cfg(target_os = "android")]
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_surfaceDestroyed(
     _: *mut JNIEnv,
@@ -119,6 +178,7 @@ pub unsafe extern "C" fn Java_com_makepad_rustquest_JNI_surfaceDestroyed(
     app_thread.surface_destroyed();
 }
 
+#[cfg(target_os = "android")]
 fn set_panic_hook() {
     panic::set_hook(Box::new(|panic_info| {
         loge!("{}", panic_info.to_string());
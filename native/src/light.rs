@@ -0,0 +1,319 @@
+use crate::frustum::Frustum;
+use crate::math::Vec3;
+use crate::shadow::ShadowSettings;
+use crate::std140::{Std140Block, Std140Layout, Std140Writer, Type};
+use std::cmp::Ordering;
+
+pub const MAX_LIGHTS: usize = 8;
+
+const LIGHT_FIELDS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+impl LightKind {
+    fn to_f32(self) -> f32 {
+        match self {
+            LightKind::Directional => 0.0,
+            LightKind::Point => 1.0,
+            LightKind::Spot { .. } => 2.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub range: Option<f32>,
+    pub shadow: Option<ShadowSettings>,
+}
+
+impl Light {
+    pub fn directional(direction: Vec3, color: Vec3, intensity: f32) -> Light {
+        Light {
+            kind: LightKind::Directional,
+            color,
+            intensity,
+            position: Vec3::ZERO,
+            direction: direction.normalize(),
+            range: None,
+            shadow: None,
+        }
+    }
+
+    pub fn point(position: Vec3, color: Vec3, intensity: f32, range: Option<f32>) -> Light {
+        Light {
+            kind: LightKind::Point,
+            color,
+            intensity,
+            position,
+            direction: Vec3::new(0.0, 0.0, -1.0),
+            range,
+            shadow: None,
+        }
+    }
+
+    fn is_visible(&self, frustum: &Frustum) -> bool {
+        if self.intensity <= 0.0 {
+            return false;
+        }
+        match (self.kind, self.range) {
+            (LightKind::Directional, _) | (_, None) => true,
+            (_, Some(range)) => frustum.intersects_sphere(self.position, range),
+        }
+    }
+
+    fn importance(&self, viewer: Vec3) -> f32 {
+        match self.kind {
+            LightKind::Directional => f32::INFINITY,
+            _ => {
+                let distance = (self.position - viewer).length();
+                self.intensity / (1.0 + distance * distance)
+            }
+        }
+    }
+
+    fn pack(&self, casts_shadow: bool) -> [[f32; 4]; LIGHT_FIELDS] {
+        let (spot_scale, spot_offset) = match self.kind {
+            LightKind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => {
+                let cos_inner = inner_cone_angle.cos();
+                let cos_outer = outer_cone_angle.cos();
+                let scale = 1.0 / (cos_inner - cos_outer).max(0.001);
+                (scale, -cos_outer * scale)
+            }
+            _ => (0.0, 1.0),
+        };
+        [
+            [
+                self.position.x,
+                self.position.y,
+                self.position.z,
+                self.range.unwrap_or(0.0),
+            ],
+            [
+                self.direction.x,
+                self.direction.y,
+                self.direction.z,
+                self.kind.to_f32(),
+            ],
+            [self.color.x, self.color.y, self.color.z, self.intensity],
+            [
+                spot_scale,
+                spot_offset,
+                if casts_shadow { 1.0 } else { 0.0 },
+                0.0,
+            ],
+        ]
+    }
+}
+
+pub struct LightUniforms {
+    pub environment: [f32; 4],
+    pub light_count: i32,
+    pub lights: [[[f32; 4]; LIGHT_FIELDS]; MAX_LIGHTS],
+}
+
+impl LightUniforms {
+    pub fn pack(
+        lights: &[Light],
+        frustum: &Frustum,
+        viewer: Vec3,
+        environment_intensity: f32,
+        environment_max_level: f32,
+        shadow_caster: Option<usize>,
+    ) -> LightUniforms {
+        let selected = select_lights(lights, frustum, viewer);
+        let mut uniforms = LightUniforms {
+            environment: [environment_intensity, environment_max_level, 0.0, 0.0],
+            light_count: selected.len() as i32,
+            lights: [[[0.0; 4]; LIGHT_FIELDS]; MAX_LIGHTS],
+        };
+        for (packed, index) in uniforms.lights.iter_mut().zip(selected.iter()) {
+            *packed = lights[*index].pack(shadow_caster == Some(*index));
+        }
+        uniforms
+    }
+}
+
+impl Std140Block for LightUniforms {
+    fn layout() -> Std140Layout {
+        Std140Layout::builder()
+            .field("environment", Type::Vec4)
+            .field("light_count", Type::Ivec4)
+            .array("lights", Type::Vec4, MAX_LIGHTS * LIGHT_FIELDS)
+            .build()
+    }
+
+    fn size() -> usize {
+        (2 + MAX_LIGHTS * LIGHT_FIELDS) * Type::Vec4.size()
+    }
+
+    fn write(&self, writer: &mut Std140Writer) {
+        writer.write(&self.environment);
+        writer.write(&[self.light_count, 0, 0, 0]);
+        for light in self.lights.iter() {
+            for field in light.iter() {
+                writer.write(field);
+            }
+        }
+    }
+}
+
+pub fn select_lights(lights: &[Light], frustum: &Frustum, viewer: Vec3) -> Vec<usize> {
+    let mut selected: Vec<usize> = (0..lights.len())
+        .filter(|index| lights[*index].is_visible(frustum))
+        .collect();
+    selected.sort_by(|a, b| {
+        lights[*b]
+            .importance(viewer)
+            .partial_cmp(&lights[*a].importance(viewer))
+            .unwrap_or(Ordering::Equal)
+    });
+    selected.truncate(MAX_LIGHTS);
+    selected
+}
+
+pub fn shadow_caster(lights: &[Light]) -> Option<usize> {
+    lights.iter().position(|light| {
+        light.kind == LightKind::Directional && light.intensity > 0.0 && light.shadow.is_some()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Mat4;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn frustum() -> Frustum {
+        Frustum::from_view_projection(&Mat4::perspective(FRAC_PI_2, 1.0, 0.1, 100.0))
+    }
+
+    fn white_point(x: f32, z: f32, intensity: f32, range: Option<f32>) -> Light {
+        Light::point(Vec3::new(x, 0.0, z), Vec3::ONE, intensity, range)
+    }
+
+    #[test]
+    fn culls_lights_outside_the_frustum() {
+        let lights = [
+            white_point(0.0, -5.0, 1.0, Some(1.0)),
+            white_point(0.0, 5.0, 1.0, Some(1.0)),
+            white_point(0.0, 5.0, 1.0, Some(10.0)),
+            white_point(0.0, 5.0, 1.0, None),
+            Light::directional(Vec3::new(0.0, -1.0, 0.0), Vec3::ONE, 1.0),
+            white_point(0.0, -5.0, 0.0, Some(1.0)),
+        ];
+        let mut selected = select_lights(&lights, &frustum(), Vec3::ZERO);
+        selected.sort();
+        assert_eq!(selected, vec![0, 2, 3, 4]);
+    }
+
+    #[test]
+    fn keeps_the_most_important_lights() {
+        let mut lights: Vec<Light> = (0..12)
+            .map(|index| white_point(0.0, -1.0 - index as f32, 1.0, Some(100.0)))
+            .collect();
+        lights.push(Light::directional(
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::ONE,
+            0.1,
+        ));
+        let selected = select_lights(&lights, &frustum(), Vec3::ZERO);
+        assert_eq!(selected.len(), MAX_LIGHTS);
+        assert_eq!(selected[0], 12);
+        assert_eq!(&selected[1..], &[0, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn picks_a_directional_shadow_caster() {
+        let mut lights = vec![
+            white_point(0.0, -5.0, 1.0, Some(1.0)),
+            Light::directional(Vec3::new(0.0, -1.0, 0.0), Vec3::ONE, 1.0),
+            Light::directional(Vec3::new(0.0, -1.0, 0.0), Vec3::ONE, 1.0),
+        ];
+        assert_eq!(shadow_caster(&lights), None);
+        lights[0].shadow = Some(ShadowSettings::default());
+        lights[2].shadow = Some(ShadowSettings::default());
+        assert_eq!(shadow_caster(&lights), Some(2));
+        lights[1].shadow = Some(ShadowSettings::default());
+        assert_eq!(shadow_caster(&lights), Some(1));
+    }
+
+    #[test]
+    fn packs_lights_into_std140() {
+        let lights = [
+            Light::directional(Vec3::new(0.0, -2.0, 0.0), Vec3::new(1.0, 0.5, 0.25), 3.0),
+            Light {
+                kind: LightKind::Spot {
+                    inner_cone_angle: 0.0,
+                    outer_cone_angle: FRAC_PI_2,
+                },
+                color: Vec3::ONE,
+                intensity: 2.0,
+                position: Vec3::new(1.0, 2.0, -3.0),
+                direction: Vec3::new(0.0, 0.0, -1.0),
+                range: Some(4.0),
+                shadow: None,
+            },
+        ];
+        let uniforms = LightUniforms::pack(&lights, &frustum(), Vec3::ZERO, 0.5, 7.0, Some(0));
+        assert_eq!(LightUniforms::size(), LightUniforms::layout().size());
+        let mut bytes = vec![0xff; LightUniforms::size()];
+        uniforms.write(&mut Std140Writer::new(&mut bytes));
+        let read = |index: usize| {
+            f32::from_le_bytes([
+                bytes[index * 4],
+                bytes[index * 4 + 1],
+                bytes[index * 4 + 2],
+                bytes[index * 4 + 3],
+            ])
+        };
+        let read_i32 = |index: usize| {
+            i32::from_le_bytes([
+                bytes[index * 4],
+                bytes[index * 4 + 1],
+                bytes[index * 4 + 2],
+                bytes[index * 4 + 3],
+            ])
+        };
+        assert_eq!((read(0), read(1)), (0.5, 7.0));
+        assert_eq!(read_i32(4), 2);
+
+        let directional = 8;
+        assert_eq!((read(directional + 5), read(directional + 7)), (-1.0, 0.0));
+        assert_eq!(
+            (
+                read(directional + 8),
+                read(directional + 9),
+                read(directional + 11)
+            ),
+            (1.0, 0.5, 3.0)
+        );
+
+        let spot = directional + 16;
+        assert_eq!(
+            (read(spot), read(spot + 1), read(spot + 2), read(spot + 3)),
+            (1.0, 2.0, -3.0, 4.0)
+        );
+        assert_eq!(read(spot + 7), 2.0);
+        assert!((read(spot + 12) - 1.0).abs() < 1e-5);
+        assert!(read(spot + 13).abs() < 1e-5);
+        assert_eq!(read(directional + 14), 1.0);
+        assert_eq!(read(spot + 14), 0.0);
+        assert_eq!(read(spot + 16), 0.0);
+    }
+}
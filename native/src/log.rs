@@ -2,6 +2,10 @@ macro_rules! loge {
     ($($arg:tt)*) => ($crate::log::loge(&format!($($arg)*)));
 }
 
+macro_rules! logw {
+    ($($arg:tt)*) => ($crate::log::logw(&format!($($arg)*)));
+}
+
 macro_rules! logi {
     ($($arg:tt)*) => ($crate::log::logi(&format!($($arg)*)));
 }
@@ -10,40 +14,74 @@ macro_rules! logv {
     ($($arg:tt)*) => ($crate::log::logv(&format!($($arg)*)));
 }
 
-use liblog_sys::{
-    android_LogPriority_ANDROID_LOG_ERROR, android_LogPriority_ANDROID_LOG_INFO,
-    android_LogPriority_ANDROID_LOG_VERBOSE,
-};
-use std::ffi::CString;
+pub use self::platform::{loge, logi, logv, logw};
 
-const TAG: &'static [u8] = b"rustquest\0";
+#[cfg(target_os = "android")]
+mod platform {
+    use liblog_sys::{
+        android_LogPriority_ANDROID_LOG_ERROR, android_LogPriority_ANDROID_LOG_INFO,
+        android_LogPriority_ANDROID_LOG_VERBOSE, android_LogPriority_ANDROID_LOG_WARN,
+    };
+    use std::ffi::CString;
 
-pub fn loge(str: &str) {
-    unsafe {
-        liblog_sys::__android_log_print(
-            android_LogPriority_ANDROID_LOG_ERROR as i32,
-            TAG.as_ptr(),
-            CString::new(str).unwrap().as_ptr(),
-        );
+    const TAG: &'static [u8] = b"rustquest\0";
+
+    pub fn loge(str: &str) {
+        unsafe {
+            liblog_sys::__android_log_print(
+                android_LogPriority_ANDROID_LOG_ERROR as i32,
+                TAG.as_ptr(),
+                CString::new(str).unwrap().as_ptr(),
+            );
+        }
+    }
+
+    pub fn logw(str: &str) {
+        unsafe {
+            liblog_sys::__android_log_print(
+                android_LogPriority_ANDROID_LOG_WARN as i32,
+                TAG.as_ptr(),
+                CString::new(str).unwrap().as_ptr(),
+            );
+        }
+    }
+
+    pub fn logi(str: &str) {
+        unsafe {
+            liblog_sys::__android_log_print(
+                android_LogPriority_ANDROID_LOG_INFO as i32,
+                TAG.as_ptr(),
+                CString::new(str).unwrap().as_ptr(),
+            );
+        }
     }
-}
 
-pub fn logi(str: &str) {
-    unsafe {
-        liblog_sys::__android_log_print(
-            android_LogPriority_ANDROID_LOG_INFO as i32,
-            TAG.as_ptr(),
-            CString::new(str).unwrap().as_ptr(),
-        );
+    pub fn logv(str: &str) {
+        unsafe {
+            liblog_sys::__android_log_print(
+                android_LogPriority_ANDROID_LOG_VERBOSE as i32,
+                TAG.as_ptr(),
+                CString::new(str).unwrap().as_ptr(),
+            );
+        }
     }
 }
 
-pub fn logv(str: &str) {
-    unsafe {
-        liblog_sys::__android_log_print(
-            android_LogPriority_ANDROID_LOG_VERBOSE as i32,
-            TAG.as_ptr(),
-            CString::new(str).unwrap().as_ptr(),
-        );
+#[cfg(not(target_os = "android"))]
+mod platform {
+    pub fn loge(str: &str) {
+        eprintln!("E rustquest: {}", str);
+    }
+
+    pub fn logw(str: &str) {
+        eprintln!("W rustquest: {}", str);
+    }
+
+    pub fn logi(str: &str) {
+        eprintln!("I rustquest: {}", str);
+    }
+
+    pub fn logv(str: &str) {
+        eprintln!("V rustquest: {}", str);
     }
 }
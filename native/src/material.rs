@@ -0,0 +1,71 @@
+use crate::gltf::{AlphaMode, Material, TextureRef};
+use crate::vertex_layout::{Semantic, VertexLayout};
+
+std140_struct! {
+    pub struct MaterialUniforms {
+        base_color_factor: [f32; 4],
+        emissive_factor: [f32; 4],
+        parameters: [f32; 4],
+    }
+}
+
+impl MaterialUniforms {
+    pub fn new(material: &Material) -> MaterialUniforms {
+        let [r, g, b] = material.emissive_factor;
+        MaterialUniforms {
+            base_color_factor: material.base_color_factor,
+            emissive_factor: [r, g, b, material.alpha_cutoff],
+            parameters: [
+                material.metallic_factor,
+                material.roughness_factor,
+                material.normal_scale,
+                material.occlusion_strength,
+            ],
+        }
+    }
+}
+
+pub fn material_defines(
+    material: &Material,
+    layout: &VertexLayout,
+    has_textures: bool,
+) -> Vec<&'static str> {
+    let mut defines = Vec::new();
+    let has_tex_coord_0 = layout.attribute(Semantic::TexCoord0).is_some();
+    let has_texture = |texture_ref: Option<TextureRef>| {
+        has_textures
+            && has_tex_coord_0
+            && texture_ref.is_some_and(|texture_ref| texture_ref.tex_coord == 0)
+    };
+    if layout.attribute(Semantic::Normal).is_some() {
+        defines.push("HAS_NORMALS");
+    }
+    if layout.attribute(Semantic::Color).is_some() {
+        defines.push("HAS_VERTEX_COLORS");
+    }
+    if has_tex_coord_0 {
+        defines.push("HAS_TEX_COORD_0");
+    }
+    if has_texture(material.base_color_texture) {
+        defines.push("HAS_BASE_COLOR_TEXTURE");
+    }
+    if has_texture(material.metallic_roughness_texture) {
+        defines.push("HAS_METALLIC_ROUGHNESS_TEXTURE");
+    }
+    if has_texture(material.normal_texture) {
+        defines.push("HAS_NORMAL_TEXTURE");
+        if layout.attribute(Semantic::Tangent).is_some() {
+            defines.push("HAS_TANGENTS");
+        }
+    }
+    if has_texture(material.occlusion_texture) {
+        defines.push("HAS_OCCLUSION_TEXTURE");
+    }
+    if has_texture(material.emissive_texture) {
+        defines.push("HAS_EMISSIVE_TEXTURE");
+    }
+    if material.alpha_mode == AlphaMode::Mask {
+        defines.push("ALPHA_MASK");
+    }
+    defines
+}
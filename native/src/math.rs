@@ -0,0 +1,511 @@
+use std::ops::{Add, Mul, Neg, Sub};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+    pub const ONE: Vec3 = Vec3::new(1.0, 1.0, 1.0);
+
+    pub const fn new(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    pub fn dot(self, other: Vec3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Vec3 {
+        self * (1.0 / self.length())
+    }
+
+    pub fn min(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    pub fn max(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        [self.x, self.y, self.z]
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+
+    fn add(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, scale: f32) -> Vec3 {
+        Vec3::new(self.x * scale, self.y * scale, self.z * scale)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Vec3 {
+        Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat::new(0.0, 0.0, 0.0, 1.0);
+
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Quat {
+        Quat { x, y, z, w }
+    }
+
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Quat {
+        let axis = axis.normalize() * (angle * 0.5).sin();
+        Quat::new(axis.x, axis.y, axis.z, (angle * 0.5).cos())
+    }
+
+    pub fn rotate(self, vector: Vec3) -> Vec3 {
+        let axis = Vec3::new(self.x, self.y, self.z);
+        let t = axis.cross(vector) * 2.0;
+        vector + t * self.w + axis.cross(t)
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Quat {
+        Quat::IDENTITY
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+
+    fn mul(self, other: Quat) -> Quat {
+        Quat::new(
+            self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+            self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub columns: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        columns: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub const fn from_columns(columns: [[f32; 4]; 4]) -> Mat4 {
+        Mat4 { columns }
+    }
+
+    pub fn translation(translation: Vec3) -> Mat4 {
+        let mut matrix = Mat4::IDENTITY;
+        matrix.columns[3] = [translation.x, translation.y, translation.z, 1.0];
+        matrix
+    }
+
+    pub fn scale(scale: Vec3) -> Mat4 {
+        let mut matrix = Mat4::IDENTITY;
+        matrix.columns[0][0] = scale.x;
+        matrix.columns[1][1] = scale.y;
+        matrix.columns[2][2] = scale.z;
+        matrix
+    }
+
+    pub fn rotation(rotation: Quat) -> Mat4 {
+        let Quat { x, y, z, w } = rotation;
+        Mat4::from_columns([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y + z * w),
+                2.0 * (x * z - y * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * y - z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z + x * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * z + y * w),
+                2.0 * (y * z - x * w),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+        let focal_length = 1.0 / (fov_y * 0.5).tan();
+        let (z, w) = if far.is_infinite() {
+            (-1.0, -2.0 * near)
+        } else {
+            ((far + near) / (near - far), 2.0 * far * near / (near - far))
+        };
+        Mat4::from_columns([
+            [focal_length / aspect, 0.0, 0.0, 0.0],
+            [0.0, focal_length, 0.0, 0.0],
+            [0.0, 0.0, z, -1.0],
+            [0.0, 0.0, w, 0.0],
+        ])
+    }
+
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat4 {
+        Mat4::from_columns([
+            [2.0 / (right - left), 0.0, 0.0, 0.0],
+            [0.0, 2.0 / (top - bottom), 0.0, 0.0],
+            [0.0, 0.0, 2.0 / (near - far), 0.0],
+            [
+                (left + right) / (left - right),
+                (bottom + top) / (bottom - top),
+                (near + far) / (near - far),
+                1.0,
+            ],
+        ])
+    }
+
+    pub fn look_to(eye: Vec3, direction: Vec3, up: Vec3) -> Mat4 {
+        let z = -direction.normalize();
+        let x = up.cross(z).normalize();
+        let y = z.cross(x);
+        Mat4::from_columns([
+            [x.x, y.x, z.x, 0.0],
+            [x.y, y.y, z.y, 0.0],
+            [x.z, y.z, z.z, 0.0],
+            [-x.dot(eye), -y.dot(eye), -z.dot(eye), 1.0],
+        ])
+    }
+
+    pub fn from_trs(translation: Vec3, rotation: Quat, scale: Vec3) -> Mat4 {
+        let mut matrix = Mat4::rotation(rotation);
+        for (column, scale) in matrix
+            .columns
+            .iter_mut()
+            .zip([scale.x, scale.y, scale.z].iter())
+        {
+            for value in column.iter_mut().take(3) {
+                *value *= scale;
+            }
+        }
+        matrix.columns[3] = [translation.x, translation.y, translation.z, 1.0];
+        matrix
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut matrix = Mat4::IDENTITY;
+        for column in 0..4 {
+            for row in 0..4 {
+                matrix.columns[column][row] = self.columns[row][column];
+            }
+        }
+        matrix
+    }
+
+    pub fn row(&self, row: usize) -> [f32; 4] {
+        [
+            self.columns[0][row],
+            self.columns[1][row],
+            self.columns[2][row],
+            self.columns[3][row],
+        ]
+    }
+
+    pub fn inverse(&self) -> Option<Mat4> {
+        let m = &self.columns;
+        let a = [
+            m[0][0] * m[1][1] - m[0][1] * m[1][0],
+            m[0][0] * m[1][2] - m[0][2] * m[1][0],
+            m[0][0] * m[1][3] - m[0][3] * m[1][0],
+            m[0][1] * m[1][2] - m[0][2] * m[1][1],
+            m[0][1] * m[1][3] - m[0][3] * m[1][1],
+            m[0][2] * m[1][3] - m[0][3] * m[1][2],
+        ];
+        let b = [
+            m[2][0] * m[3][1] - m[2][1] * m[3][0],
+            m[2][0] * m[3][2] - m[2][2] * m[3][0],
+            m[2][0] * m[3][3] - m[2][3] * m[3][0],
+            m[2][1] * m[3][2] - m[2][2] * m[3][1],
+            m[2][1] * m[3][3] - m[2][3] * m[3][1],
+            m[2][2] * m[3][3] - m[2][3] * m[3][2],
+        ];
+        let determinant =
+            a[0] * b[5] - a[1] * b[4] + a[2] * b[3] + a[3] * b[2] - a[4] * b[1] + a[5] * b[0];
+        if determinant.abs() < f32::EPSILON * f32::EPSILON {
+            return None;
+        }
+        let inverse_determinant = 1.0 / determinant;
+        let columns = [
+            [
+                m[1][1] * b[5] - m[1][2] * b[4] + m[1][3] * b[3],
+                -m[0][1] * b[5] + m[0][2] * b[4] - m[0][3] * b[3],
+                m[3][1] * a[5] - m[3][2] * a[4] + m[3][3] * a[3],
+                -m[2][1] * a[5] + m[2][2] * a[4] - m[2][3] * a[3],
+            ],
+            [
+                -m[1][0] * b[5] + m[1][2] * b[2] - m[1][3] * b[1],
+                m[0][0] * b[5] - m[0][2] * b[2] + m[0][3] * b[1],
+                -m[3][0] * a[5] + m[3][2] * a[2] - m[3][3] * a[1],
+                m[2][0] * a[5] - m[2][2] * a[2] + m[2][3] * a[1],
+            ],
+            [
+                m[1][0] * b[4] - m[1][1] * b[2] + m[1][3] * b[0],
+                -m[0][0] * b[4] + m[0][1] * b[2] - m[0][3] * b[0],
+                m[3][0] * a[4] - m[3][1] * a[2] + m[3][3] * a[0],
+                -m[2][0] * a[4] + m[2][1] * a[2] - m[2][3] * a[0],
+            ],
+            [
+                -m[1][0] * b[3] + m[1][1] * b[1] - m[1][2] * b[0],
+                m[0][0] * b[3] - m[0][1] * b[1] + m[0][2] * b[0],
+                -m[3][0] * a[3] + m[3][1] * a[1] - m[3][2] * a[0],
+                m[2][0] * a[3] - m[2][1] * a[1] + m[2][2] * a[0],
+            ],
+        ];
+        let mut inverse = Mat4::from_columns(columns);
+        for column in inverse.columns.iter_mut() {
+            for value in column.iter_mut() {
+                *value *= inverse_determinant;
+            }
+        }
+        Some(inverse)
+    }
+
+    pub fn transform(&self, vector: [f32; 4]) -> [f32; 4] {
+        let mut result = [0.0; 4];
+        for (row, value) in result.iter_mut().enumerate() {
+            *value = (0..4)
+                .map(|column| self.columns[column][row] * vector[column])
+                .sum();
+        }
+        result
+    }
+
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        let c = &self.columns;
+        Vec3::new(
+            c[0][0] * point.x + c[1][0] * point.y + c[2][0] * point.z + c[3][0],
+            c[0][1] * point.x + c[1][1] * point.y + c[2][1] * point.z + c[3][1],
+            c[0][2] * point.x + c[1][2] * point.y + c[2][2] * point.z + c[3][2],
+        )
+    }
+
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        let c = &self.columns;
+        Vec3::new(
+            c[0][0] * vector.x + c[1][0] * vector.y + c[2][0] * vector.z,
+            c[0][1] * vector.x + c[1][1] * vector.y + c[2][1] * vector.z,
+            c[0][2] * vector.x + c[1][2] * vector.y + c[2][2] * vector.z,
+        )
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Mat4 {
+        Mat4::IDENTITY
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut matrix = Mat4::from_columns([[0.0; 4]; 4]);
+        for column in 0..4 {
+            for row in 0..4 {
+                matrix.columns[column][row] = (0..4)
+                    .map(|index| self.columns[index][row] * other.columns[column][index])
+                    .sum();
+            }
+        }
+        matrix
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_trs(self.translation, self.rotation, self.scale)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::IDENTITY
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn from_points<I: IntoIterator<Item = Vec3>>(points: I) -> Option<Aabb> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Aabb::new(first, first), |aabb, point| {
+            Aabb::new(aabb.min.min(point), aabb.max.max(point))
+        }))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn bounding_sphere(&self) -> (Vec3, f32) {
+        (self.center(), self.extents().length())
+    }
+
+    pub fn transform(&self, matrix: &Mat4) -> Aabb {
+        let center = matrix.transform_point(self.center());
+        let extents = self.extents();
+        let c = &matrix.columns;
+        let world_extents = Vec3::new(
+            c[0][0].abs() * extents.x + c[1][0].abs() * extents.y + c[2][0].abs() * extents.z,
+            c[0][1].abs() * extents.x + c[1][1].abs() * extents.y + c[2][1].abs() * extents.z,
+            c[0][2].abs() * extents.x + c[1][2].abs() * extents.y + c[2][2].abs() * extents.z,
+        );
+        Aabb::new(center - world_extents, center + world_extents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_matrix_near(a: &Mat4, b: &Mat4) {
+        for (column_a, column_b) in a.columns.iter().zip(b.columns.iter()) {
+            for (value_a, value_b) in column_a.iter().zip(column_b.iter()) {
+                assert!((value_a - value_b).abs() < 1e-5, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn inverts_matrices() {
+        let matrix = Mat4::from_trs(
+            Vec3::new(1.0, -2.0, 3.0),
+            Quat::from_axis_angle(Vec3::new(1.0, 1.0, 0.0), 0.7),
+            Vec3::new(2.0, 0.5, 1.5),
+        );
+        assert_matrix_near(&(matrix * matrix.inverse().unwrap()), &Mat4::IDENTITY);
+        let projection = Mat4::perspective(FRAC_PI_2, 1.5, 0.1, 100.0);
+        assert_matrix_near(
+            &(projection.inverse().unwrap() * projection),
+            &Mat4::IDENTITY,
+        );
+        assert_eq!(Mat4::scale(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+    }
+
+    #[test]
+    fn rotates_vectors() {
+        let rotation = Quat::from_axis_angle(Vec3::new(0.0, 0.0, 2.0), FRAC_PI_2);
+        let vector = Vec3::new(1.0, 2.0, 3.0);
+        assert!((rotation.rotate(vector) - Vec3::new(-2.0, 1.0, 3.0)).length() < 1e-5);
+        let matrix = Mat4::from_trs(Vec3::ZERO, rotation, Vec3::ONE);
+        assert!((rotation.rotate(vector) - matrix.transform_point(vector)).length() < 1e-5);
+    }
+
+    #[test]
+    fn transforms_aabbs() {
+        let aabb = Aabb::from_points(vec![
+            Vec3::new(-1.0, -1.0, -1.0),
+            Vec3::new(1.0, 2.0, 1.0),
+            Vec3::new(0.0, 0.0, 0.0),
+        ])
+        .unwrap();
+        assert_eq!(aabb.min, Vec3::new(-1.0, -1.0, -1.0));
+        assert_eq!(aabb.max, Vec3::new(1.0, 2.0, 1.0));
+        assert_eq!(
+            aabb.bounding_sphere(),
+            (Vec3::new(0.0, 0.5, 0.0), 4.25f32.sqrt())
+        );
+
+        let matrix = Mat4::from_trs(
+            Vec3::new(10.0, 0.0, 0.0),
+            Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), FRAC_PI_2),
+            Vec3::ONE,
+        );
+        let transformed = aabb.transform(&matrix);
+        assert!((transformed.min - Vec3::new(8.0, -1.0, -1.0)).length() < 1e-5);
+        assert!((transformed.max - Vec3::new(11.0, 1.0, 1.0)).length() < 1e-5);
+    }
+}
@@ -0,0 +1,738 @@
+use crate::gl_backend::{Argument, Emulator, Value};
+use libGLESv3_sys::{
+    GLenum, GLfloat, GLint, GLintptr, GLsizei, GLsizeiptr, GLuint, GLuint64, GL_ACTIVE_ATTRIBUTES,
+    GL_ACTIVE_ATTRIBUTE_MAX_LENGTH, GL_ACTIVE_TEXTURE, GL_ACTIVE_UNIFORMS,
+    GL_ACTIVE_UNIFORM_MAX_LENGTH, GL_ALREADY_SIGNALED, GL_BLEND_DST_ALPHA, GL_BLEND_DST_RGB,
+    GL_BLEND_SRC_ALPHA, GL_BLEND_SRC_RGB, GL_COLOR_ATTACHMENT0, GL_COMPILE_STATUS,
+    GL_CURRENT_PROGRAM, GL_DEPTH24_STENCIL8, GL_DEPTH32F_STENCIL8, GL_DEPTH_ATTACHMENT,
+    GL_DEPTH_COMPONENT16, GL_DEPTH_COMPONENT24, GL_DEPTH_COMPONENT32F, GL_DEPTH_FUNC,
+    GL_DEPTH_WRITEMASK, GL_DRAW_FRAMEBUFFER, GL_DRAW_FRAMEBUFFER_BINDING, GL_EXTENSIONS, GL_FALSE,
+    GL_FRAMEBUFFER, GL_FRAMEBUFFER_COMPLETE, GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT,
+    GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT, GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE,
+    GL_GPU_DISJOINT_EXT, GL_INFO_LOG_LENGTH, GL_INVALID_ENUM, GL_INVALID_FRAMEBUFFER_OPERATION,
+    GL_INVALID_INDEX, GL_INVALID_OPERATION, GL_INVALID_VALUE, GL_LESS, GL_LINK_STATUS, GL_NO_ERROR,
+    GL_ONE, GL_POLYGON_OFFSET_FACTOR, GL_POLYGON_OFFSET_UNITS, GL_PROGRAM_BINARY_LENGTH,
+    GL_QUERY_RESULT, GL_QUERY_RESULT_AVAILABLE, GL_READ_FRAMEBUFFER, GL_READ_FRAMEBUFFER_BINDING,
+    GL_RENDERER, GL_SAMPLER_BINDING, GL_SCISSOR_BOX, GL_TEXTURE0, GL_TEXTURE_2D,
+    GL_TEXTURE_2D_ARRAY, GL_TEXTURE_3D, GL_TEXTURE_BINDING_2D, GL_TEXTURE_BINDING_2D_ARRAY,
+    GL_TEXTURE_BINDING_3D, GL_TEXTURE_BINDING_CUBE_MAP, GL_TEXTURE_CUBE_MAP, GL_TRUE,
+    GL_UNIFORM_BUFFER_OFFSET_ALIGNMENT, GL_VENDOR, GL_VERSION, GL_VERTEX_ARRAY_BINDING,
+    GL_VIEWPORT, GL_ZERO,
+};
+use std::cell::{Ref, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+use std::slice;
+
+const UNIFORM_BUFFER_OFFSET_ALIGNMENT: GLint = 256;
+pub const QUERY_ELAPSED_TIME: GLuint64 = 5_000_000_000;
+pub const PROGRAM_BINARY_FORMAT: GLenum = 1;
+pub const PROGRAM_BINARY: &[u8] = b"mock program binary";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+    pub function: &'static str,
+    pub arguments: Vec<Argument>,
+    pub enums: Vec<GLenum>,
+}
+
+impl Call {
+    pub fn argument(&self, index: usize) -> Argument {
+        self.arguments[index]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Error {
+    pub function: &'static str,
+    pub error: GLenum,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Attachment {
+    Texture(GLuint),
+    Renderbuffer(GLuint),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Storage {
+    pub internal_format: GLenum,
+    pub samples: GLsizei,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Object {
+    Buffer,
+    VertexArray,
+    Texture(Option<Storage>),
+    Sampler,
+    Renderbuffer(Option<Storage>),
+    Framebuffer(HashMap<GLenum, Attachment>),
+    Shader,
+    Program,
+    Query,
+    Sync,
+}
+
+pub struct State {
+    pub calls: Vec<Call>,
+    pub errors: Vec<Error>,
+    pub pending_errors: VecDeque<GLenum>,
+    pub next_name: GLuint,
+    pub objects: HashMap<GLuint, Object>,
+    pub buffers: HashMap<GLenum, GLuint>,
+    pub uniform_buffers: HashMap<GLuint, (GLuint, GLintptr, GLsizeiptr)>,
+    pub vertex_array: GLuint,
+    pub draw_framebuffer: GLuint,
+    pub read_framebuffer: GLuint,
+    pub renderbuffer: GLuint,
+    pub active_texture: GLuint,
+    pub textures: HashMap<(GLuint, GLenum), GLuint>,
+    pub samplers: HashMap<GLuint, GLuint>,
+    pub program: GLuint,
+    pub uniforms: Vec<&'static str>,
+    pub unlinked_programs: HashSet<GLuint>,
+    pub capabilities: HashSet<GLenum>,
+    pub viewport: [GLint; 4],
+    pub scissor: [GLint; 4],
+    pub blend_func: [GLenum; 4],
+    pub depth_func: GLenum,
+    pub depth_mask: bool,
+    pub polygon_offset: (GLfloat, GLfloat),
+    pub queries: HashMap<GLenum, GLuint>,
+}
+
+pub struct MockGl {
+    state: RefCell<State>,
+}
+
+impl MockGl {
+    pub fn new() -> MockGl {
+        MockGl {
+            state: RefCell::new(State {
+                calls: Vec::new(),
+                errors: Vec::new(),
+                pending_errors: VecDeque::new(),
+                next_name: 1,
+                objects: HashMap::new(),
+                buffers: HashMap::new(),
+                uniform_buffers: HashMap::new(),
+                vertex_array: 0,
+                draw_framebuffer: 0,
+                read_framebuffer: 0,
+                renderbuffer: 0,
+                active_texture: 0,
+                textures: HashMap::new(),
+                samplers: HashMap::new(),
+                program: 0,
+                uniforms: Vec::new(),
+                unlinked_programs: HashSet::new(),
+                capabilities: HashSet::new(),
+                viewport: [0; 4],
+                scissor: [0; 4],
+                blend_func: [GL_ONE, GL_ZERO, GL_ONE, GL_ZERO],
+                depth_func: GL_LESS,
+                depth_mask: true,
+                polygon_offset: (0.0, 0.0),
+                queries: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn set_uniforms(&self, uniforms: &[&'static str]) {
+        self.state.borrow_mut().uniforms = uniforms.to_vec();
+    }
+
+    pub fn state(&self) -> Ref<'_, State> {
+        self.state.borrow()
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.borrow().calls.clone()
+    }
+
+    pub fn functions(&self) -> Vec<&'static str> {
+        self.state
+            .borrow()
+            .calls
+            .iter()
+            .map(|call| call.function)
+            .collect()
+    }
+
+    pub fn clear_calls(&self) {
+        self.state.borrow_mut().calls.clear();
+    }
+
+    pub fn errors(&self) -> Vec<Error> {
+        self.state.borrow().errors.clone()
+    }
+
+    pub fn object_count(&self) -> usize {
+        self.state.borrow().objects.len()
+    }
+}
+
+impl Emulator for MockGl {
+    fn call(&self, function: &'static str, arguments: &[Argument]) -> Value {
+        let mut state = self.state.borrow_mut();
+        if function == "glGetError" {
+            let error = state.pending_errors.pop_front().unwrap_or(GL_NO_ERROR);
+            return Value::Int(error as i64);
+        }
+        let enums = match function {
+            "glDrawBuffers" => unsafe { read_enums(arguments[0], arguments[1]) },
+            "glInvalidateFramebuffer" => unsafe { read_enums(arguments[1], arguments[2]) },
+            _ => Vec::new(),
+        };
+        state.calls.push(Call {
+            function,
+            arguments: arguments.to_vec(),
+            enums,
+        });
+        let result = unsafe { state.emulate(function, arguments) };
+        match result {
+            Ok(value) => value,
+            Err((error, value)) => {
+                state.errors.push(Error { function, error });
+                state.pending_errors.push_back(error);
+                value
+            }
+        }
+    }
+}
+
+impl State {
+    unsafe fn emulate(
+        &mut self,
+        function: &'static str,
+        arguments: &[Argument],
+    ) -> Result<Value, (GLenum, Value)> {
+        let int = |index: usize| arguments[index].int();
+        let uint = |index: usize| arguments[index].int() as GLuint;
+        match function {
+            "glGenBuffers" => self.generate(arguments, || Object::Buffer),
+            "glGenVertexArrays" => self.generate(arguments, || Object::VertexArray),
+            "glGenTextures" => self.generate(arguments, || Object::Texture(None)),
+            "glGenSamplers" => self.generate(arguments, || Object::Sampler),
+            "glGenRenderbuffers" => self.generate(arguments, || Object::Renderbuffer(None)),
+            "glGenFramebuffers" => self.generate(arguments, || Object::Framebuffer(HashMap::new())),
+            "glGenQueries" => self.generate(arguments, || Object::Query),
+            "glCreateShader" => Ok(Value::Int(self.create(Object::Shader) as i64)),
+            "glCreateProgram" => Ok(Value::Int(self.create(Object::Program) as i64)),
+            "glFenceSync" => Ok(Value::Pointer(
+                self.create(Object::Sync) as usize as *const _
+            )),
+            "glDeleteBuffers"
+            | "glDeleteVertexArrays"
+            | "glDeleteTextures"
+            | "glDeleteSamplers"
+            | "glDeleteRenderbuffers"
+            | "glDeleteFramebuffers"
+            | "glDeleteQueries" => {
+                let names = arguments[1].pointer::<GLuint>();
+                for index in 0..int(0) as usize {
+                    self.delete(*names.add(index));
+                }
+                Ok(Value::None)
+            }
+            "glDeleteShader" | "glDeleteProgram" => {
+                self.delete(uint(0));
+                Ok(Value::None)
+            }
+            "glDeleteSync" => {
+                self.delete(arguments[0].pointer::<c_void>() as usize as GLuint);
+                Ok(Value::None)
+            }
+            "glClientWaitSync" => Ok(Value::Int(GL_ALREADY_SIGNALED as i64)),
+            "glBindBuffer" => {
+                self.check_name(uint(1), |object| *object == Object::Buffer)?;
+                self.buffers.insert(uint(0), uint(1));
+                Ok(Value::None)
+            }
+            "glBindBufferRange" => {
+                self.check_name(uint(2), |object| *object == Object::Buffer)?;
+                self.buffers.insert(uint(0), uint(2));
+                self.uniform_buffers
+                    .insert(uint(1), (uint(2), int(3) as GLintptr, int(4) as GLsizeiptr));
+                Ok(Value::None)
+            }
+            "glBindVertexArray" => {
+                self.check_name(uint(0), |object| *object == Object::VertexArray)?;
+                self.vertex_array = uint(0);
+                Ok(Value::None)
+            }
+            "glBindFramebuffer" => {
+                self.check_name(uint(1), |object| matches!(object, Object::Framebuffer(_)))?;
+                match uint(0) {
+                    GL_FRAMEBUFFER => {
+                        self.draw_framebuffer = uint(1);
+                        self.read_framebuffer = uint(1);
+                    }
+                    GL_DRAW_FRAMEBUFFER => self.draw_framebuffer = uint(1),
+                    GL_READ_FRAMEBUFFER => self.read_framebuffer = uint(1),
+                    _ => return Err((GL_INVALID_ENUM, Value::None)),
+                }
+                Ok(Value::None)
+            }
+            "glBindRenderbuffer" => {
+                self.check_name(uint(1), |object| matches!(object, Object::Renderbuffer(_)))?;
+                self.renderbuffer = uint(1);
+                Ok(Value::None)
+            }
+            "glActiveTexture" => {
+                self.active_texture = uint(0) - GL_TEXTURE0;
+                Ok(Value::None)
+            }
+            "glBindTexture" => {
+                self.check_name(uint(1), |object| matches!(object, Object::Texture(_)))?;
+                self.textures
+                    .insert((self.active_texture, uint(0)), uint(1));
+                Ok(Value::None)
+            }
+            "glBindSampler" => {
+                self.check_name(uint(1), |object| *object == Object::Sampler)?;
+                self.samplers.insert(uint(0), uint(1));
+                Ok(Value::None)
+            }
+            "glUseProgram" => {
+                self.check_name(uint(0), |object| *object == Object::Program)?;
+                self.program = uint(0);
+                Ok(Value::None)
+            }
+            "glEnable" => {
+                self.capabilities.insert(uint(0));
+                Ok(Value::None)
+            }
+            "glDisable" => {
+                self.capabilities.remove(&uint(0));
+                Ok(Value::None)
+            }
+            "glIsEnabled" => Ok(Value::Int(self.capabilities.contains(&uint(0)) as i64)),
+            "glViewport" => {
+                self.viewport = [
+                    int(0) as GLint,
+                    int(1) as GLint,
+                    int(2) as GLint,
+                    int(3) as GLint,
+                ];
+                Ok(Value::None)
+            }
+            "glScissor" => {
+                self.scissor = [
+                    int(0) as GLint,
+                    int(1) as GLint,
+                    int(2) as GLint,
+                    int(3) as GLint,
+                ];
+                Ok(Value::None)
+            }
+            "glBlendFuncSeparate" => {
+                self.blend_func = [uint(0), uint(1), uint(2), uint(3)];
+                Ok(Value::None)
+            }
+            "glDepthFunc" => {
+                self.depth_func = uint(0);
+                Ok(Value::None)
+            }
+            "glDepthMask" => {
+                self.depth_mask = int(0) != 0;
+                Ok(Value::None)
+            }
+            "glPolygonOffset" => {
+                self.polygon_offset = (arguments[0].float(), arguments[1].float());
+                Ok(Value::None)
+            }
+            "glGetIntegerv" => self.get_integer(uint(0), arguments[1].pointer::<GLint>()),
+            "glGetFloatv" => {
+                let value = match uint(0) {
+                    GL_POLYGON_OFFSET_FACTOR => self.polygon_offset.0,
+                    GL_POLYGON_OFFSET_UNITS => self.polygon_offset.1,
+                    _ => return Err((GL_INVALID_ENUM, Value::None)),
+                };
+                *arguments[1].pointer::<GLfloat>() = value;
+                Ok(Value::None)
+            }
+            "glGetBooleanv" => {
+                if uint(0) != GL_DEPTH_WRITEMASK {
+                    return Err((GL_INVALID_ENUM, Value::None));
+                }
+                *arguments[1].pointer::<u8>() = self.depth_mask as u8;
+                Ok(Value::None)
+            }
+            "glTexStorage2D" | "glTexStorage3D" => {
+                let texture = self.textures.get(&(self.active_texture, uint(0))).cloned();
+                let storage = Storage {
+                    internal_format: uint(2),
+                    samples: 1,
+                };
+                match texture.and_then(|texture| self.objects.get_mut(&texture)) {
+                    Some(Object::Texture(slot @ None)) => *slot = Some(storage),
+                    _ => return Err((GL_INVALID_OPERATION, Value::None)),
+                }
+                Ok(Value::None)
+            }
+            "glRenderbufferStorage" | "glRenderbufferStorageMultisample" => {
+                let storage = if function == "glRenderbufferStorage" {
+                    Storage {
+                        internal_format: uint(1),
+                        samples: 1,
+                    }
+                } else {
+                    Storage {
+                        internal_format: uint(2),
+                        samples: (int(1) as GLsizei).max(1),
+                    }
+                };
+                match self.objects.get_mut(&self.renderbuffer) {
+                    Some(Object::Renderbuffer(slot)) => *slot = Some(storage),
+                    _ => return Err((GL_INVALID_OPERATION, Value::None)),
+                }
+                Ok(Value::None)
+            }
+            "glFramebufferTexture2D" => {
+                let attachment = match uint(3) {
+                    0 => None,
+                    texture => Some(Attachment::Texture(texture)),
+                };
+                self.attach(uint(0), uint(1), attachment)
+            }
+            "glFramebufferRenderbuffer" => {
+                let attachment = match uint(3) {
+                    0 => None,
+                    renderbuffer => Some(Attachment::Renderbuffer(renderbuffer)),
+                };
+                self.attach(uint(0), uint(1), attachment)
+            }
+            "glCheckFramebufferStatus" => match self.framebuffer_binding(uint(0)) {
+                Some(framebuffer) => Ok(Value::Int(self.framebuffer_status(framebuffer) as i64)),
+                None => Err((GL_INVALID_ENUM, Value::Int(0))),
+            },
+            "glGetShaderiv" => {
+                let value = match uint(1) {
+                    GL_COMPILE_STATUS => GL_TRUE as GLint,
+                    GL_INFO_LOG_LENGTH => 0,
+                    _ => return Err((GL_INVALID_ENUM, Value::None)),
+                };
+                *arguments[2].pointer::<GLint>() = value;
+                Ok(Value::None)
+            }
+            "glGetProgramiv" => {
+                let value = match uint(1) {
+                    GL_LINK_STATUS if self.unlinked_programs.contains(&uint(0)) => {
+                        GL_FALSE as GLint
+                    }
+                    GL_LINK_STATUS => GL_TRUE as GLint,
+                    GL_ACTIVE_UNIFORMS => self.uniforms.len() as GLint,
+                    GL_ACTIVE_UNIFORM_MAX_LENGTH => self
+                        .uniforms
+                        .iter()
+                        .map(|uniform| uniform.len() as GLint + 1)
+                        .max()
+                        .unwrap_or(0),
+                    GL_PROGRAM_BINARY_LENGTH => PROGRAM_BINARY.len() as GLint,
+                    GL_INFO_LOG_LENGTH | GL_ACTIVE_ATTRIBUTES | GL_ACTIVE_ATTRIBUTE_MAX_LENGTH => 0,
+                    _ => return Err((GL_INVALID_ENUM, Value::None)),
+                };
+                *arguments[2].pointer::<GLint>() = value;
+                Ok(Value::None)
+            }
+            "glGetProgramBinary" => {
+                if int(1) < PROGRAM_BINARY.len() as i64 {
+                    return Err((GL_INVALID_OPERATION, Value::None));
+                }
+                *arguments[2].pointer::<GLsizei>() = PROGRAM_BINARY.len() as GLsizei;
+                *arguments[3].pointer::<GLenum>() = PROGRAM_BINARY_FORMAT;
+                std::ptr::copy_nonoverlapping(
+                    PROGRAM_BINARY.as_ptr(),
+                    arguments[4].pointer::<u8>(),
+                    PROGRAM_BINARY.len(),
+                );
+                Ok(Value::None)
+            }
+            "glProgramBinary" => {
+                let binary = slice::from_raw_parts(arguments[2].pointer::<u8>(), int(3) as usize);
+                if uint(1) == PROGRAM_BINARY_FORMAT && binary == PROGRAM_BINARY {
+                    self.unlinked_programs.remove(&uint(0));
+                } else {
+                    self.unlinked_programs.insert(uint(0));
+                }
+                Ok(Value::None)
+            }
+            "glGetActiveUniform" => {
+                let uniform = match self.uniforms.get(uint(1) as usize) {
+                    Some(uniform) => uniform.as_bytes(),
+                    None => return Err((GL_INVALID_VALUE, Value::None)),
+                };
+                let length = uniform.len().min(int(2) as usize - 1);
+                let name = arguments[6].pointer::<u8>();
+                std::ptr::copy_nonoverlapping(uniform.as_ptr(), name, length);
+                *name.add(length) = 0;
+                *arguments[3].pointer::<GLsizei>() = length as GLsizei;
+                *arguments[4].pointer::<GLint>() = 1;
+                Ok(Value::None)
+            }
+            "glGetUniformBlockIndex" => Ok(Value::Int(GL_INVALID_INDEX as i64)),
+            "glGetUniformLocation" => {
+                let name = CStr::from_ptr(arguments[1].pointer::<c_char>());
+                let location = self
+                    .uniforms
+                    .iter()
+                    .position(|uniform| uniform.as_bytes() == name.to_bytes());
+                Ok(Value::Int(location.map_or(-1, |location| location as i64)))
+            }
+            "glGetString" => {
+                let string: &'static [u8] = match uint(0) {
+                    GL_VENDOR => b"rustquest\0",
+                    GL_RENDERER => b"mock\0",
+                    GL_VERSION => b"OpenGL ES 3.0 mock\0",
+                    GL_EXTENSIONS => b"GL_EXT_disjoint_timer_query\0",
+                    _ => return Err((GL_INVALID_ENUM, Value::Pointer(std::ptr::null()))),
+                };
+                Ok(Value::Pointer(string.as_ptr() as *const _))
+            }
+            "glBeginQuery" => {
+                if uint(1) == 0 || self.queries.contains_key(&uint(0)) {
+                    return Err((GL_INVALID_OPERATION, Value::None));
+                }
+                self.check_name(uint(1), |object| *object == Object::Query)?;
+                self.queries.insert(uint(0), uint(1));
+                Ok(Value::None)
+            }
+            "glEndQuery" => match self.queries.remove(&uint(0)) {
+                Some(_) => Ok(Value::None),
+                None => Err((GL_INVALID_OPERATION, Value::None)),
+            },
+            "glGetQueryObjectuiv" | "glGetQueryObjectui64vEXT" => {
+                if self.objects.get(&uint(0)) != Some(&Object::Query)
+                    || self.queries.values().any(|query| *query == uint(0))
+                {
+                    return Err((GL_INVALID_OPERATION, Value::None));
+                }
+                let value = match uint(1) {
+                    GL_QUERY_RESULT_AVAILABLE => GL_TRUE as GLuint64,
+                    GL_QUERY_RESULT => QUERY_ELAPSED_TIME,
+                    _ => return Err((GL_INVALID_ENUM, Value::None)),
+                };
+                if function == "glGetQueryObjectuiv" {
+                    *arguments[2].pointer::<GLuint>() = value as GLuint;
+                } else {
+                    *arguments[2].pointer::<GLuint64>() = value;
+                }
+                Ok(Value::None)
+            }
+            "glReadPixels" => {
+                if self.framebuffer_status(self.read_framebuffer) != GL_FRAMEBUFFER_COMPLETE {
+                    return Err((GL_INVALID_FRAMEBUFFER_OPERATION, Value::None));
+                }
+                Ok(Value::None)
+            }
+            "glMapBufferRange" => match self.buffers.get(&uint(0)) {
+                Some(buffer) if *buffer != 0 => Ok(Value::Pointer(std::ptr::null())),
+                _ => Err((GL_INVALID_OPERATION, Value::Pointer(std::ptr::null()))),
+            },
+            "glUnmapBuffer" => match self.buffers.get(&uint(0)) {
+                Some(buffer) if *buffer != 0 => Ok(Value::Int(GL_TRUE as i64)),
+                _ => Err((GL_INVALID_OPERATION, Value::Int(0))),
+            },
+            "glDrawElementsInstanced" => {
+                if self.program == 0 || self.vertex_array == 0 {
+                    return Err((GL_INVALID_OPERATION, Value::None));
+                }
+                if self.framebuffer_status(self.draw_framebuffer) != GL_FRAMEBUFFER_COMPLETE {
+                    return Err((GL_INVALID_FRAMEBUFFER_OPERATION, Value::None));
+                }
+                Ok(Value::None)
+            }
+            _ => Ok(Value::None),
+        }
+    }
+
+    unsafe fn generate<F: Fn() -> Object>(
+        &mut self,
+        arguments: &[Argument],
+        object: F,
+    ) -> Result<Value, (GLenum, Value)> {
+        let names = arguments[1].pointer::<GLuint>();
+        for index in 0..arguments[0].int() as usize {
+            *names.add(index) = self.create(object());
+        }
+        Ok(Value::None)
+    }
+
+    fn create(&mut self, object: Object) -> GLuint {
+        let name = self.next_name;
+        self.next_name += 1;
+        self.objects.insert(name, object);
+        name
+    }
+
+    fn delete(&mut self, name: GLuint) {
+        if name == 0 || self.objects.remove(&name).is_none() {
+            return;
+        }
+        let unbind = |binding: &mut GLuint| {
+            if *binding == name {
+                *binding = 0;
+            }
+        };
+        self.buffers.values_mut().for_each(unbind);
+        self.textures.values_mut().for_each(unbind);
+        self.samplers.values_mut().for_each(unbind);
+        self.uniform_buffers
+            .retain(|_, (buffer, _, _)| *buffer != name);
+        unbind(&mut self.vertex_array);
+        unbind(&mut self.draw_framebuffer);
+        unbind(&mut self.read_framebuffer);
+        unbind(&mut self.renderbuffer);
+    }
+
+    fn check_name<F: Fn(&Object) -> bool>(
+        &self,
+        name: GLuint,
+        is_kind: F,
+    ) -> Result<(), (GLenum, Value)> {
+        match self.objects.get(&name) {
+            _ if name == 0 => Ok(()),
+            Some(object) if is_kind(object) => Ok(()),
+            _ => Err((GL_INVALID_OPERATION, Value::None)),
+        }
+    }
+
+    unsafe fn get_integer(&self, name: GLenum, data: *mut GLint) -> Result<Value, (GLenum, Value)> {
+        let texture = |target: GLenum| {
+            self.textures
+                .get(&(self.active_texture, target))
+                .cloned()
+                .unwrap_or(0) as GLint
+        };
+        let values: Vec<GLint> = match name {
+            GL_CURRENT_PROGRAM => vec![self.program as GLint],
+            GL_VERTEX_ARRAY_BINDING => vec![self.vertex_array as GLint],
+            GL_DRAW_FRAMEBUFFER_BINDING => vec![self.draw_framebuffer as GLint],
+            GL_READ_FRAMEBUFFER_BINDING => vec![self.read_framebuffer as GLint],
+            GL_ACTIVE_TEXTURE => vec![(GL_TEXTURE0 + self.active_texture) as GLint],
+            GL_TEXTURE_BINDING_2D => vec![texture(GL_TEXTURE_2D)],
+            GL_TEXTURE_BINDING_2D_ARRAY => vec![texture(GL_TEXTURE_2D_ARRAY)],
+            GL_TEXTURE_BINDING_3D => vec![texture(GL_TEXTURE_3D)],
+            GL_TEXTURE_BINDING_CUBE_MAP => vec![texture(GL_TEXTURE_CUBE_MAP)],
+            GL_SAMPLER_BINDING => vec![self
+                .samplers
+                .get(&self.active_texture)
+                .cloned()
+                .unwrap_or(0) as GLint],
+            GL_VIEWPORT => self.viewport.to_vec(),
+            GL_SCISSOR_BOX => self.scissor.to_vec(),
+            GL_BLEND_SRC_RGB => vec![self.blend_func[0] as GLint],
+            GL_BLEND_DST_RGB => vec![self.blend_func[1] as GLint],
+            GL_BLEND_SRC_ALPHA => vec![self.blend_func[2] as GLint],
+            GL_BLEND_DST_ALPHA => vec![self.blend_func[3] as GLint],
+            GL_DEPTH_FUNC => vec![self.depth_func as GLint],
+            GL_UNIFORM_BUFFER_OFFSET_ALIGNMENT => vec![UNIFORM_BUFFER_OFFSET_ALIGNMENT],
+            GL_GPU_DISJOINT_EXT => vec![0],
+            _ => return Err((GL_INVALID_ENUM, Value::None)),
+        };
+        for (index, value) in values.into_iter().enumerate() {
+            *data.add(index) = value;
+        }
+        Ok(Value::None)
+    }
+
+    fn framebuffer_binding(&self, target: GLenum) -> Option<GLuint> {
+        match target {
+            GL_FRAMEBUFFER | GL_DRAW_FRAMEBUFFER => Some(self.draw_framebuffer),
+            GL_READ_FRAMEBUFFER => Some(self.read_framebuffer),
+            _ => None,
+        }
+    }
+
+    fn attach(
+        &mut self,
+        target: GLenum,
+        attachment_point: GLenum,
+        attachment: Option<Attachment>,
+    ) -> Result<Value, (GLenum, Value)> {
+        let framebuffer = match self.framebuffer_binding(target) {
+            Some(framebuffer) => framebuffer,
+            None => return Err((GL_INVALID_ENUM, Value::None)),
+        };
+        let valid = match attachment {
+            Some(Attachment::Texture(name)) => {
+                matches!(self.objects.get(&name), Some(Object::Texture(_)))
+            }
+            Some(Attachment::Renderbuffer(name)) => {
+                matches!(self.objects.get(&name), Some(Object::Renderbuffer(_)))
+            }
+            None => true,
+        };
+        if !valid {
+            return Err((GL_INVALID_OPERATION, Value::None));
+        }
+        match self.objects.get_mut(&framebuffer) {
+            Some(Object::Framebuffer(attachments)) => {
+                match attachment {
+                    Some(attachment) => attachments.insert(attachment_point, attachment),
+                    None => attachments.remove(&attachment_point),
+                };
+                Ok(Value::None)
+            }
+            _ => Err((GL_INVALID_OPERATION, Value::None)),
+        }
+    }
+
+    fn framebuffer_status(&self, framebuffer: GLuint) -> GLenum {
+        let attachments = match self.objects.get(&framebuffer) {
+            Some(Object::Framebuffer(attachments)) => attachments,
+            _ => return GL_FRAMEBUFFER_COMPLETE,
+        };
+        if attachments.is_empty() {
+            return GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT;
+        }
+        let mut samples = None;
+        for (attachment_point, attachment) in attachments.iter() {
+            let storage = match attachment {
+                Attachment::Texture(name) => match self.objects.get(name) {
+                    Some(Object::Texture(storage)) => *storage,
+                    _ => None,
+                },
+                Attachment::Renderbuffer(name) => match self.objects.get(name) {
+                    Some(Object::Renderbuffer(storage)) => *storage,
+                    _ => None,
+                },
+            };
+            let storage = match storage {
+                Some(storage) => storage,
+                None => return GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT,
+            };
+            let is_depth = is_depth_format(storage.internal_format);
+            let attachable = match *attachment_point {
+                GL_DEPTH_ATTACHMENT => is_depth,
+                GL_COLOR_ATTACHMENT0 => !is_depth,
+                _ => false,
+            };
+            if !attachable {
+                return GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT;
+            }
+            if *samples.get_or_insert(storage.samples) != storage.samples {
+                return GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE;
+            }
+        }
+        GL_FRAMEBUFFER_COMPLETE
+    }
+}
+
+unsafe fn read_enums(count: Argument, enums: Argument) -> Vec<GLenum> {
+    slice::from_raw_parts(enums.pointer::<GLenum>(), count.int() as usize).to_vec()
+}
+
+pub fn is_depth_format(internal_format: GLenum) -> bool {
+    matches!(
+        internal_format,
+        GL_DEPTH_COMPONENT16
+            | GL_DEPTH_COMPONENT24
+            | GL_DEPTH_COMPONENT32F
+            | GL_DEPTH24_STENCIL8
+            | GL_DEPTH32F_STENCIL8
+    )
+}
@@ -0,0 +1,251 @@
+use crate::geometry::{Geometry, Indices};
+use crate::gl::Context;
+use crate::gltf;
+use crate::gltf::{Document, IndexData, Material, Node, Scene, TextureRef};
+use crate::program::{
+    BASE_COLOR_TEXTURE_UNIT, EMISSIVE_TEXTURE_UNIT, METALLIC_ROUGHNESS_TEXTURE_UNIT,
+    NORMAL_TEXTURE_UNIT, OCCLUSION_TEXTURE_UNIT,
+};
+use crate::sampler::{Sampler, SamplerState};
+use crate::texture::Texture;
+use libGLESv3_sys::{GLenum, GLuint};
+
+pub struct Model {
+    pub meshes: Vec<Vec<Primitive>>,
+    pub materials: Vec<Material>,
+    pub nodes: Vec<Node>,
+    pub scenes: Vec<Scene>,
+    pub scene: Option<usize>,
+    pub textures: MaterialTextures,
+}
+
+pub struct MaterialTextures {
+    textures: Vec<gltf::Texture>,
+    images: Vec<Texture>,
+    samplers: Vec<Sampler>,
+    default_sampler: Sampler,
+}
+
+pub struct Primitive {
+    pub geometry: Geometry,
+    pub material: Option<usize>,
+}
+
+impl Model {
+    pub fn upload(context: &Context, document: Document) -> Result<Model, String> {
+        let meshes = document
+            .meshes
+            .iter()
+            .map(|mesh| {
+                mesh.primitives
+                    .iter()
+                    .map(|primitive| Primitive {
+                        geometry: Geometry::from_bytes(
+                            context,
+                            &primitive.vertices,
+                            &primitive.layout,
+                            match &primitive.indices {
+                                IndexData::U16(indices) => Indices::U16(indices),
+                                IndexData::U32(indices) => Indices::U32(indices),
+                            },
+                        ),
+                        material: primitive.material,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut srgb = vec![false; document.images.len()];
+        for material in document.materials.iter() {
+            for texture_ref in [material.base_color_texture, material.emissive_texture]
+                .iter()
+                .filter_map(|texture_ref| *texture_ref)
+            {
+                if let Some(source) = document.textures[texture_ref.texture].source {
+                    srgb[source] = true;
+                }
+            }
+        }
+        let mut images = Vec::with_capacity(document.images.len());
+        for (index, image) in document.images.iter().enumerate() {
+            images.push(
+                Texture::decode(context, &image.data, srgb[index], true)
+                    .map_err(|error| format!("image {}: {}", index, error))?,
+            );
+        }
+
+        let samplers = document
+            .samplers
+            .iter()
+            .map(|sampler| {
+                let default = SamplerState::default();
+                Sampler::new(
+                    context,
+                    SamplerState {
+                        min_filter: sampler.min_filter.unwrap_or(default.min_filter) as GLenum,
+                        mag_filter: sampler.mag_filter.unwrap_or(default.mag_filter) as GLenum,
+                        wrap_s: sampler.wrap_s as GLenum,
+                        wrap_t: sampler.wrap_t as GLenum,
+                        wrap_r: default.wrap_r,
+                        compare_func: None,
+                    },
+                )
+            })
+            .collect();
+
+        Ok(Model {
+            meshes,
+            materials: document.materials,
+            nodes: document.nodes,
+            scenes: document.scenes,
+            scene: document.scene,
+            textures: MaterialTextures::new(context, document.textures, images, samplers),
+        })
+    }
+}
+
+impl MaterialTextures {
+    pub fn new(
+        context: &Context,
+        textures: Vec<gltf::Texture>,
+        images: Vec<Texture>,
+        samplers: Vec<Sampler>,
+    ) -> MaterialTextures {
+        MaterialTextures {
+            textures,
+            images,
+            samplers,
+            default_sampler: Sampler::new(context, SamplerState::default()),
+        }
+    }
+
+    pub fn bind_texture(&self, texture_ref: TextureRef, unit: GLuint) -> bool {
+        let texture = self.textures[texture_ref.texture];
+        let image = match texture.source {
+            Some(source) => &self.images[source],
+            None => return false,
+        };
+        image.bind(unit);
+        match texture.sampler {
+            Some(sampler) => self.samplers[sampler].bind(unit),
+            None => self.default_sampler.bind(unit),
+        }
+        true
+    }
+
+    pub fn bind_material_textures(&self, material: &Material) {
+        for (texture_ref, unit) in [
+            (material.base_color_texture, BASE_COLOR_TEXTURE_UNIT),
+            (
+                material.metallic_roughness_texture,
+                METALLIC_ROUGHNESS_TEXTURE_UNIT,
+            ),
+            (material.normal_texture, NORMAL_TEXTURE_UNIT),
+            (material.occlusion_texture, OCCLUSION_TEXTURE_UNIT),
+            (material.emissive_texture, EMISSIVE_TEXTURE_UNIT),
+        ]
+        .iter()
+        .cloned()
+        {
+            if let Some(texture_ref) = texture_ref {
+                self.bind_texture(texture_ref, unit);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Image;
+    use crate::mock_gl::MockGl;
+    use libGLESv3_sys::{
+        GL_CLAMP_TO_EDGE, GL_LINEAR, GL_REPEAT, GL_SRGB8_ALPHA8, GL_TEXTURE_2D, GL_UNSIGNED_SHORT,
+    };
+    use std::rc::Rc;
+
+    const MODEL_JSON: &str = r#"{
+        "asset": {"version": "2.0"},
+        "buffers": [{"uri": "model.bin", "byteLength": 48}],
+        "bufferViews": [
+            {"buffer": 0, "byteLength": 36},
+            {"buffer": 0, "byteOffset": 36, "byteLength": 12}
+        ],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 1, "componentType": 5125, "count": 3, "type": "SCALAR"}
+        ],
+        "meshes": [{
+            "name": "triangle",
+            "primitives": [{"attributes": {"POSITION": 0}, "indices": 1, "material": 0}]
+        }],
+        "materials": [{"pbrMetallicRoughness": {"baseColorTexture": {"index": 0}}}],
+        "textures": [{"source": 0, "sampler": 0}],
+        "samplers": [{"magFilter": 9729, "wrapS": 33071}],
+        "images": [{"name": "albedo", "uri": "albedo.png", "mimeType": "image/png"}]
+    }"#;
+
+    #[test]
+    fn uploads_documents_and_binds_material_textures() {
+        let mut bin = Vec::new();
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0].iter() {
+            bin.extend_from_slice(&value.to_le_bytes());
+        }
+        for index in [0u32, 1, 2].iter() {
+            bin.extend_from_slice(&index.to_le_bytes());
+        }
+        let png = Image {
+            width: 2,
+            height: 2,
+            data: vec![255; 16],
+        }
+        .encode_png()
+        .unwrap();
+        let document = gltf::load(MODEL_JSON.as_bytes(), |uri| match uri {
+            "model.bin" => Ok(bin.clone()),
+            "albedo.png" => Ok(png.clone()),
+            _ => Err(format!("unknown uri {}", uri)),
+        })
+        .unwrap();
+        assert_eq!(document.meshes[0].name.as_deref(), Some("triangle"));
+        assert_eq!(document.images[0].name.as_deref(), Some("albedo"));
+        assert_eq!(document.images[0].mime_type.as_deref(), Some("image/png"));
+
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let model = Model::upload(&context, document).unwrap();
+        assert_eq!(
+            (model.nodes.len(), model.scenes.len(), model.scene),
+            (0, 0, None)
+        );
+        assert_eq!(model.meshes[0][0].material, Some(0));
+        assert_eq!(model.meshes[0][0].geometry.index_type(), GL_UNSIGNED_SHORT);
+        assert_eq!(model.meshes[0][0].geometry.count(), 3);
+        assert_eq!(model.textures.images[0].levels(), 2);
+        let storage = gl
+            .calls()
+            .into_iter()
+            .find(|call| call.function == "glTexStorage2D")
+            .unwrap();
+        assert_eq!(storage.argument(2).int(), GL_SRGB8_ALPHA8 as i64);
+        assert!(gl.functions().contains(&"glGenerateMipmap"));
+        let state = model.textures.samplers[0].state();
+        assert_eq!(state.mag_filter, GL_LINEAR);
+        assert_eq!((state.wrap_s, state.wrap_t), (GL_CLAMP_TO_EDGE, GL_REPEAT));
+
+        context.state().invalidate();
+        gl.clear_calls();
+        model.textures.bind_material_textures(&model.materials[0]);
+        let calls = gl.calls();
+        assert!(calls.iter().any(|call| {
+            call.function == "glBindTexture"
+                && call.argument(0).int() == GL_TEXTURE_2D as i64
+                && call.argument(1).int() == model.textures.images[0].texture() as i64
+        }));
+        assert!(calls.iter().any(|call| {
+            call.function == "glBindSampler"
+                && call.argument(0).int() == BASE_COLOR_TEXTURE_UNIT as i64
+        }));
+        assert!(gl.errors().is_empty());
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Define {
+    pub name: String,
+    pub value: String,
+}
+
+impl Define {
+    pub fn parse(string: &str) -> Result<Define, String> {
+        let (name, value) = match string.find('=') {
+            Some(index) => (string[..index].trim(), string[index + 1..].trim()),
+            None => (string.trim(), "1"),
+        };
+        if !is_identifier(name) {
+            return Err(format!("invalid define name {:?}", name));
+        }
+        Ok(Define {
+            name: String::from(name),
+            value: String::from(value),
+        })
+    }
+}
+
+impl fmt::Display for Define {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.value == "1" {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{}={}", self.name, self.value)
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct VariantKey {
+    program: String,
+    defines: Vec<Define>,
+}
+
+impl VariantKey {
+    pub fn new(program: &str, defines: &[&str]) -> Result<VariantKey, String> {
+        let mut parsed: Vec<Define> = Vec::with_capacity(defines.len());
+        for define in defines.iter() {
+            let define = Define::parse(define)?;
+            match parsed.iter_mut().find(|other| other.name == define.name) {
+                Some(other) => *other = define,
+                None => parsed.push(define),
+            }
+        }
+        parsed.sort();
+        Ok(VariantKey {
+            program: String::from(program),
+            defines: parsed,
+        })
+    }
+
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    pub fn defines(&self) -> &[Define] {
+        &self.defines
+    }
+}
+
+impl fmt::Display for VariantKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+        if !self.defines.is_empty() {
+            write!(f, "[")?;
+            for (index, define) in self.defines.iter().enumerate() {
+                if index > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}", define)?;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Preprocessed {
+    pub source: String,
+    pub files: Vec<String>,
+}
+
+pub fn preprocess<F>(name: &str, defines: &[Define], mut load: F) -> Result<Preprocessed, String>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let source = load(name).ok_or_else(|| format!("can't find shader source {:?}", name))?;
+    let mut preprocessor = Preprocessor {
+        load: &mut load,
+        output: String::new(),
+        files: vec![String::from(name)],
+        stack: vec![String::from(name)],
+        included: HashSet::new(),
+    };
+    preprocessor.included.insert(String::from(name));
+
+    let mut lines = source.lines().enumerate().peekable();
+    while let Some((_, line)) = lines.peek() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("//") {
+            lines.next();
+            continue;
+        }
+        if trimmed.starts_with("#version") {
+            preprocessor.output.push_str(trimmed);
+            preprocessor.output.push('\n');
+            lines.next();
+        }
+        break;
+    }
+    for define in defines.iter() {
+        preprocessor
+            .output
+            .push_str(&format!("#define {} {}\n", define.name, define.value));
+    }
+    if let Some((line_number, _)) = lines.peek() {
+        preprocessor
+            .output
+            .push_str(&format!("#line {} 0\n", line_number + 1));
+    }
+    for (line_number, line) in lines {
+        preprocessor.process_line(name, 0, line_number, line)?;
+    }
+
+    Ok(Preprocessed {
+        source: preprocessor.output,
+        files: preprocessor.files,
+    })
+}
+
+struct Preprocessor<'a, F> {
+    load: &'a mut F,
+    output: String,
+    files: Vec<String>,
+    stack: Vec<String>,
+    included: HashSet<String>,
+}
+
+impl<'a, F> Preprocessor<'a, F>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    fn process_line(
+        &mut self,
+        file: &str,
+        file_index: usize,
+        line_number: usize,
+        line: &str,
+    ) -> Result<(), String> {
+        let trimmed = line.trim();
+        if trimmed.starts_with("#version") {
+            return Err(format!(
+                "{}:{}: #version must be the first directive of the main file",
+                file,
+                line_number + 1
+            ));
+        }
+        if !trimmed.starts_with("#include") {
+            self.output.push_str(line);
+            self.output.push('\n');
+            return Ok(());
+        }
+
+        let argument = trimmed["#include".len()..].trim();
+        if argument.len() < 2 || !argument.starts_with('"') || !argument.ends_with('"') {
+            return Err(format!(
+                "{}:{}: malformed #include directive",
+                file,
+                line_number + 1
+            ));
+        }
+        let name = &argument[1..argument.len() - 1];
+        if self.stack.iter().any(|other| other == name) {
+            return Err(format!(
+                "{}:{}: recursive #include of {:?}",
+                file,
+                line_number + 1,
+                name
+            ));
+        }
+        if !self.included.insert(String::from(name)) {
+            self.output.push('\n');
+            return Ok(());
+        }
+
+        let source = (self.load)(name).ok_or_else(|| {
+            format!(
+                "{}:{}: can't find included shader source {:?}",
+                file,
+                line_number + 1,
+                name
+            )
+        })?;
+        let included_index = self.files.len();
+        self.files.push(String::from(name));
+        self.stack.push(String::from(name));
+        self.output
+            .push_str(&format!("#line 1 {}\n", included_index));
+        for (included_line_number, included_line) in source.lines().enumerate() {
+            self.process_line(name, included_index, included_line_number, included_line)?;
+        }
+        self.stack.pop();
+        self.output
+            .push_str(&format!("#line {} {}\n", line_number + 2, file_index));
+        Ok(())
+    }
+}
+
+fn is_identifier(string: &str) -> bool {
+    let mut chars = string.chars();
+    match chars.next() {
+        Some(char) if char.is_ascii_alphabetic() || char == '_' => {}
+        _ => return false,
+    }
+    chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources<'a>(files: &'a [(&'a str, &'a str)]) -> impl FnMut(&str) -> Option<String> + 'a {
+        move |name| {
+            files
+                .iter()
+                .find(|(file, _)| *file == name)
+                .map(|(_, source)| String::from(*source))
+        }
+    }
+
+    #[test]
+    fn resolves_includes_with_line_directives() {
+        let files = [
+            (
+                "main.frag",
+                "#version 300 es\nfloat a;\n#include \"common.glsl\"\nfloat b;\n",
+            ),
+            (
+                "common.glsl",
+                "float c;\n#include \"util.glsl\"\nfloat d;\n",
+            ),
+            ("util.glsl", "float e;\n"),
+        ];
+        let defines = [Define::parse("COUNT=4").unwrap()];
+        let preprocessed = preprocess("main.frag", &defines, sources(&files)).unwrap();
+        assert_eq!(
+            preprocessed.source,
+            "#version 300 es\n\
+             #define COUNT 4\n\
+             #line 2 0\n\
+             float a;\n\
+             #line 1 1\n\
+             float c;\n\
+             #line 1 2\n\
+             float e;\n\
+             #line 3 1\n\
+             float d;\n\
+             #line 4 0\n\
+             float b;\n"
+        );
+        assert_eq!(
+            preprocessed.files,
+            ["main.frag", "common.glsl", "util.glsl"]
+        );
+    }
+
+    #[test]
+    fn includes_each_file_once() {
+        let files = [
+            (
+                "main.vert",
+                "#include \"common.glsl\"\n#include \"common.glsl\"\n",
+            ),
+            ("common.glsl", "float c;\n"),
+        ];
+        let preprocessed = preprocess("main.vert", &[], sources(&files)).unwrap();
+        assert_eq!(
+            preprocessed.source,
+            "#line 1 0\n#line 1 1\nfloat c;\n#line 2 0\n\n"
+        );
+    }
+
+    #[test]
+    fn rejects_recursive_includes() {
+        let files = [("main.vert", "#include \"main.vert\"\n")];
+        let error = preprocess("main.vert", &[], sources(&files)).err().unwrap();
+        assert!(
+            error.contains("recursive #include of \"main.vert\""),
+            "{}",
+            error
+        );
+
+        let files = [
+            ("main.vert", "#include \"a.glsl\"\n"),
+            ("a.glsl", "#include \"b.glsl\"\n"),
+            ("b.glsl", "\n#include \"a.glsl\"\n"),
+        ];
+        let error = preprocess("main.vert", &[], sources(&files)).err().unwrap();
+        assert_eq!(error, "b.glsl:2: recursive #include of \"a.glsl\"");
+    }
+
+    #[test]
+    fn rejects_missing_and_malformed_includes() {
+        let files = [("main.vert", "#include \"missing.glsl\"\n")];
+        let error = preprocess("main.vert", &[], sources(&files)).err().unwrap();
+        assert_eq!(
+            error,
+            "main.vert:1: can't find included shader source \"missing.glsl\""
+        );
+
+        let files = [("main.vert", "#include <missing.glsl>\n")];
+        let error = preprocess("main.vert", &[], sources(&files)).err().unwrap();
+        assert_eq!(error, "main.vert:1: malformed #include directive");
+    }
+
+    #[test]
+    fn normalizes_variant_keys() {
+        let key = VariantKey::new("pbr", &["B", "A=2", "B"]).unwrap();
+        assert_eq!(key, VariantKey::new("pbr", &["A=2", "B"]).unwrap());
+        assert_eq!(key, VariantKey::new("pbr", &["A=1", "B", "A=2"]).unwrap());
+        assert_ne!(key, VariantKey::new("pbr", &["A", "B"]).unwrap());
+        assert_ne!(key, VariantKey::new("shadow", &["A=2", "B"]).unwrap());
+        assert_eq!(key.to_string(), "pbr[A=2,B]");
+        assert!(VariantKey::new("pbr", &["1A"]).is_err());
+    }
+}
@@ -0,0 +1,396 @@
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::profiler::Scope::new(concat!($name, "\0"));
+    };
+}
+
+use crate::json::Value;
+use std::cell::UnsafeCell;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const THREAD_BUFFER_CAPACITY: usize = 32 * 1024;
+
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+static THREAD_BUFFERS: Mutex<Vec<Arc<ThreadBuffer>>> = Mutex::new(Vec::new());
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+thread_local! {
+    static THREAD_BUFFER: Arc<ThreadBuffer> = register_thread();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event {
+    pub name: &'static str,
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThreadCapture {
+    pub id: u64,
+    pub name: String,
+    pub events: Vec<Event>,
+    pub dropped_event_count: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Capture {
+    pub threads: Vec<ThreadCapture>,
+}
+
+impl Capture {
+    pub fn event_count(&self) -> usize {
+        self.threads.iter().map(|thread| thread.events.len()).sum()
+    }
+
+    pub fn to_chrome_trace(&self) -> String {
+        let pid = Value::Number(process::id() as f64);
+        let mut events = Vec::new();
+        for thread in self.threads.iter() {
+            let tid = Value::Number(thread.id as f64);
+            events.push(Value::Object(vec![
+                ("name".to_string(), Value::String("thread_name".to_string())),
+                ("ph".to_string(), Value::String("M".to_string())),
+                ("pid".to_string(), pid.clone()),
+                ("tid".to_string(), tid.clone()),
+                (
+                    "args".to_string(),
+                    Value::Object(vec![(
+                        "name".to_string(),
+                        Value::String(thread.name.clone()),
+                    )]),
+                ),
+            ]));
+            for event in thread.events.iter() {
+                events.push(Value::Object(vec![
+                    ("name".to_string(), Value::String(event.name.to_string())),
+                    ("cat".to_string(), Value::String("rustquest".to_string())),
+                    ("ph".to_string(), Value::String("X".to_string())),
+                    ("ts".to_string(), Value::Number(microseconds(event.start))),
+                    (
+                        "dur".to_string(),
+                        Value::Number(microseconds(event.duration)),
+                    ),
+                    ("pid".to_string(), pid.clone()),
+                    ("tid".to_string(), tid.clone()),
+                ]));
+            }
+        }
+        Value::Object(vec![
+            ("traceEvents".to_string(), Value::Array(events)),
+            (
+                "displayTimeUnit".to_string(),
+                Value::String("ms".to_string()),
+            ),
+        ])
+        .to_string()
+    }
+
+    pub fn write_chrome_trace(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_chrome_trace()).map_err(|error| error.to_string())
+    }
+}
+
+pub struct Scope {
+    name: &'static str,
+    start: Option<Instant>,
+    traced: bool,
+}
+
+impl Scope {
+    pub fn new(name: &'static str) -> Scope {
+        debug_assert!(name.ends_with('\0'), "scope name must be NUL-terminated");
+        let traced = platform::begin_section(name);
+        Scope {
+            name: name.trim_end_matches('\0'),
+            start: if CAPTURING.load(Ordering::Relaxed) {
+                Some(Instant::now())
+            } else {
+                None
+            },
+            traced,
+        }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            let end = Instant::now();
+            let event = Event {
+                name: self.name,
+                start: start.saturating_duration_since(epoch()),
+                duration: end - start,
+            };
+            THREAD_BUFFER.with(|buffer| buffer.push(event));
+        }
+        if self.traced {
+            platform::end_section();
+        }
+    }
+}
+
+struct ThreadBuffer {
+    id: u64,
+    name: String,
+    generation: AtomicU64,
+    length: AtomicUsize,
+    dropped_event_count: AtomicUsize,
+    events: Box<[UnsafeCell<Event>]>,
+}
+
+unsafe impl Sync for ThreadBuffer {}
+
+impl ThreadBuffer {
+    fn new(id: u64, name: String, capacity: usize) -> ThreadBuffer {
+        ThreadBuffer {
+            id,
+            name,
+            generation: AtomicU64::new(GENERATION.load(Ordering::Acquire)),
+            length: AtomicUsize::new(0),
+            dropped_event_count: AtomicUsize::new(0),
+            events: (0..capacity)
+                .map(|_| {
+                    UnsafeCell::new(Event {
+                        name: "",
+                        start: Duration::ZERO,
+                        duration: Duration::ZERO,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    fn push(&self, event: Event) {
+        let generation = GENERATION.load(Ordering::Acquire);
+        if self.generation.load(Ordering::Relaxed) != generation {
+            self.length.store(0, Ordering::Relaxed);
+            self.dropped_event_count.store(0, Ordering::Relaxed);
+            self.generation.store(generation, Ordering::Release);
+        }
+        let length = self.length.load(Ordering::Relaxed);
+        if length == self.events.len() {
+            self.dropped_event_count.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        unsafe {
+            *self.events[length].get() = event;
+        }
+        self.length.store(length + 1, Ordering::Release);
+    }
+
+    fn capture(&self, generation: u64) -> Option<ThreadCapture> {
+        if self.generation.load(Ordering::Acquire) != generation {
+            return None;
+        }
+        let length = self.length.load(Ordering::Acquire);
+        Some(ThreadCapture {
+            id: self.id,
+            name: self.name.clone(),
+            events: self.events[..length]
+                .iter()
+                .map(|event| unsafe { *event.get() })
+                .collect(),
+            dropped_event_count: self.dropped_event_count.load(Ordering::Relaxed),
+        })
+    }
+}
+
+pub fn is_capturing() -> bool {
+    CAPTURING.load(Ordering::Relaxed)
+}
+
+pub fn start_capture() {
+    let _thread_buffers = THREAD_BUFFERS.lock().unwrap();
+    epoch();
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+    CAPTURING.store(true, Ordering::Relaxed);
+}
+
+pub fn stop_capture() -> Capture {
+    let thread_buffers = THREAD_BUFFERS.lock().unwrap();
+    CAPTURING.store(false, Ordering::Relaxed);
+    let generation = GENERATION.load(Ordering::Acquire);
+    Capture {
+        threads: thread_buffers
+            .iter()
+            .filter_map(|buffer| buffer.capture(generation))
+            .filter(|thread| !thread.events.is_empty())
+            .collect(),
+    }
+}
+
+fn register_thread() -> Arc<ThreadBuffer> {
+    let id = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+    let name = thread::current()
+        .name()
+        .map_or_else(|| format!("thread {}", id), |name| name.to_string());
+    let buffer = Arc::new(ThreadBuffer::new(id, name, THREAD_BUFFER_CAPACITY));
+    THREAD_BUFFERS.lock().unwrap().push(buffer.clone());
+    buffer
+}
+
+fn epoch() -> Instant {
+    *EPOCH.get_or_init(Instant::now)
+}
+
+fn microseconds(duration: Duration) -> f64 {
+    duration.as_nanos() as f64 / 1000.0
+}
+
+#[cfg(target_os = "android")]
+mod platform {
+    pub fn begin_section(name: &'static str) -> bool {
+        unsafe {
+            if !libandroid_sys::ATrace_isEnabled() {
+                return false;
+            }
+            libandroid_sys::ATrace_beginSection(name.as_ptr() as *const _);
+        }
+        true
+    }
+
+    pub fn end_section() {
+        unsafe {
+            libandroid_sys::ATrace_endSection();
+        }
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+mod platform {
+    pub fn begin_section(_name: &'static str) -> bool {
+        false
+    }
+
+    pub fn end_section() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+    use std::env;
+
+    fn event(name: &'static str, start: u64, duration: u64) -> Event {
+        Event {
+            name,
+            start: Duration::from_micros(start),
+            duration: Duration::from_micros(duration),
+        }
+    }
+
+    fn thread_capture<'a>(capture: &'a Capture, name: &str) -> &'a ThreadCapture {
+        capture
+            .threads
+            .iter()
+            .find(|thread| thread.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn records_scopes_per_thread() {
+        {
+            profile_scope!("before capture");
+        }
+        assert!(!is_capturing());
+        start_capture();
+        assert!(is_capturing());
+        {
+            profile_scope!("outer");
+            {
+                profile_scope!("inner");
+            }
+        }
+        thread::Builder::new()
+            .name("profiler worker".to_string())
+            .spawn(|| {
+                profile_scope!("worker");
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        let capture = stop_capture();
+        assert!(!is_capturing());
+        {
+            profile_scope!("after capture");
+        }
+
+        let current = thread::current();
+        let thread = thread_capture(&capture, current.name().unwrap());
+        let names: Vec<&str> = thread.events.iter().map(|event| event.name).collect();
+        assert_eq!(names, ["inner", "outer"]);
+        let (inner, outer) = (thread.events[0], thread.events[1]);
+        assert!(outer.start <= inner.start);
+        assert!(inner.start + inner.duration <= outer.start + outer.duration);
+
+        let worker = thread_capture(&capture, "profiler worker");
+        assert_ne!(worker.id, thread.id);
+        assert_eq!(worker.events.len(), 1);
+        assert_eq!(worker.events[0].name, "worker");
+
+        start_capture();
+        let capture = stop_capture();
+        assert!(capture
+            .threads
+            .iter()
+            .all(|thread| thread.name != "profiler worker"));
+    }
+
+    #[test]
+    fn drops_events_when_buffer_is_full() {
+        let buffer = ThreadBuffer::new(1, "main".to_string(), 2);
+        for index in 0..3 {
+            buffer.push(event("event", index, 1));
+        }
+        let generation = buffer.generation.load(Ordering::Acquire);
+        let capture = buffer.capture(generation).unwrap();
+        assert_eq!(capture.events, [event("event", 0, 1), event("event", 1, 1)]);
+        assert_eq!(capture.dropped_event_count, 1);
+    }
+
+    #[test]
+    fn exports_chrome_trace() {
+        let capture = Capture {
+            threads: vec![ThreadCapture {
+                id: 3,
+                name: "app \"main\"".to_string(),
+                events: vec![event("render", 1500, 250)],
+                dropped_event_count: 0,
+            }],
+        };
+        assert_eq!(capture.event_count(), 1);
+        let path = env::temp_dir().join(format!("rustquest-trace-{}.json", process::id()));
+        capture.write_chrome_trace(&path).unwrap();
+        let trace = json::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        let events = trace.get("traceEvents").unwrap().as_array().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].get("ph").unwrap().as_str(), Some("M"));
+        assert_eq!(
+            events[0]
+                .get("args")
+                .and_then(|args| args.get("name"))
+                .and_then(|name| name.as_str()),
+            Some("app \"main\"")
+        );
+        let render = &events[1];
+        assert_eq!(render.get("name").unwrap().as_str(), Some("render"));
+        assert_eq!(render.get("ph").unwrap().as_str(), Some("X"));
+        assert_eq!(render.get("ts").unwrap().as_f64(), Some(1500.0));
+        assert_eq!(render.get("dur").unwrap().as_f64(), Some(250.0));
+        assert_eq!(render.get("tid").unwrap().as_usize(), Some(3));
+        assert_eq!(
+            render.get("pid").unwrap().as_usize(),
+            Some(process::id() as usize)
+        );
+    }
+}
@@ -1,110 +1,224 @@
+use crate::gl::{Context, ProgramHandle, Shader};
+use crate::program_cache::{ProgramBinary, ProgramCache};
+use crate::vertex_layout::{Semantic, INSTANCE_ATTRIBUTES};
 use libGLESv3_sys::{
-    GLenum, GLint, GLuint, GL_COMPILE_STATUS, GL_FALSE, GL_FRAGMENT_SHADER, GL_INFO_LOG_LENGTH,
-    GL_LINK_STATUS, GL_VERTEX_SHADER,
+    GLchar, GLenum, GLint, GLsizei, GLuint, GL_ACTIVE_ATTRIBUTES, GL_ACTIVE_ATTRIBUTE_MAX_LENGTH,
+    GL_ACTIVE_UNIFORMS, GL_ACTIVE_UNIFORM_MAX_LENGTH, GL_COMPILE_STATUS, GL_FALSE,
+    GL_FRAGMENT_SHADER, GL_INFO_LOG_LENGTH, GL_INVALID_INDEX, GL_LINK_STATUS,
+    GL_PROGRAM_BINARY_LENGTH, GL_PROGRAM_BINARY_RETRIEVABLE_HINT, GL_RENDERER, GL_TRUE, GL_VERSION,
+    GL_VERTEX_SHADER,
 };
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::ptr;
 
-const VERTEX_SHADER: &'static str = r#"
-    #version 300 es
-
-    uniform mat4 uModelMatrix;
-    uniform mat4 uViewMatrix;
-    uniform mat4 uProjectionMatrix;
-
-    in vec3 aPosition;
-    in vec3 aColor;
-
-    out vec3 vColor;
+pub struct Program {
+    program: ProgramHandle,
+    _vertex_shader: Option<Shader>,
+    _fragment_shader: Option<Shader>,
+    attrib_names: Vec<String>,
+    uniform_locations: HashMap<String, GLint>,
+}
 
-    void main() {
-        gl_Position = uProjectionMatrix * (uViewMatrix * (uModelMatrix * vec4(0.1 * aPosition, 1.0)));
-        vColor = aColor;
-    }
-"#;
+pub const VIEW_BLOCK_BINDING: GLuint = 0;
+pub const OBJECT_BLOCK_BINDING: GLuint = 1;
+pub const MATERIAL_BLOCK_BINDING: GLuint = 2;
+pub const LIGHT_BLOCK_BINDING: GLuint = 3;
+pub const SHADOW_BLOCK_BINDING: GLuint = 4;
 
-const FRAGMENT_SHADER: &'static str = r#"
-    #version 300 es
+const UNIFORM_BLOCK_BINDINGS: &[(&str, GLuint)] = &[
+    ("View", VIEW_BLOCK_BINDING),
+    ("Object", OBJECT_BLOCK_BINDING),
+    ("Material", MATERIAL_BLOCK_BINDING),
+    ("Lights", LIGHT_BLOCK_BINDING),
+    ("Shadow", SHADOW_BLOCK_BINDING),
+];
 
-    in highp vec3 vColor;
+pub const BASE_COLOR_TEXTURE_UNIT: GLuint = 0;
+pub const METALLIC_ROUGHNESS_TEXTURE_UNIT: GLuint = 1;
+pub const NORMAL_TEXTURE_UNIT: GLuint = 2;
+pub const OCCLUSION_TEXTURE_UNIT: GLuint = 3;
+pub const EMISSIVE_TEXTURE_UNIT: GLuint = 4;
+pub const ENVIRONMENT_MAP_UNIT: GLuint = 5;
+pub const SHADOW_MAP_UNIT: GLuint = 6;
 
-    out highp vec4 fragColor;
+const SAMPLER_UNITS: &[(&str, GLuint)] = &[
+    ("uBaseColorTexture", BASE_COLOR_TEXTURE_UNIT),
+    ("uMetallicRoughnessTexture", METALLIC_ROUGHNESS_TEXTURE_UNIT),
+    ("uNormalTexture", NORMAL_TEXTURE_UNIT),
+    ("uOcclusionTexture", OCCLUSION_TEXTURE_UNIT),
+    ("uEmissiveTexture", EMISSIVE_TEXTURE_UNIT),
+    ("uEnvironmentMap", ENVIRONMENT_MAP_UNIT),
+    ("uShadowMap", SHADOW_MAP_UNIT),
+];
 
-    void main() {
-        fragColor = vec4(vColor, 1.0);
+impl Program {
+    pub fn from_sources(context: &Context, vertex_source: &str, fragment_source: &str) -> Program {
+        Program::try_from_sources(context, vertex_source, fragment_source)
+            .unwrap_or_else(|error| panic!("{}", error))
     }
-"#;
 
-pub struct Program {
-    program: GLuint,
-    vertex_shader: GLuint,
-    fragment_shader: GLuint,
-    uniform_locations: HashMap<String, GLint>,
-}
-
-impl Program {
-    pub fn new() -> Program {
+    pub fn try_from_sources(
+        context: &Context,
+        vertex_source: &str,
+        fragment_source: &str,
+    ) -> Result<Program, String> {
         unsafe {
-            let vertex_shader = compile_shader(GL_VERTEX_SHADER, VERTEX_SHADER);
-            let fragment_shader = compile_shader(GL_FRAGMENT_SHADER, FRAGMENT_SHADER);
-
-            logi!("link program");
-            let program = libGLESv3_sys::glCreateProgram();
-            libGLESv3_sys::glAttachShader(program, vertex_shader);
-            libGLESv3_sys::glAttachShader(program, fragment_shader);
-            let attrib_names = ["aPosition", "aColor"];
-            for (index, name) in attrib_names.iter().cloned().enumerate() {
-                libGLESv3_sys::glBindAttribLocation(
-                    program,
-                    index as GLuint,
-                    CString::new(name).unwrap().as_ptr(),
-                );
-            }
-            libGLESv3_sys::glLinkProgram(program);
-            let mut status = 0;
-            libGLESv3_sys::glGetProgramiv(program, GL_LINK_STATUS, &mut status);
-            if status == GL_FALSE as GLint {
-                let mut length = 0;
-                libGLESv3_sys::glGetProgramiv(program, GL_INFO_LOG_LENGTH, &mut length);
-                let mut log = Vec::with_capacity(length as usize);
-                libGLESv3_sys::glGetProgramInfoLog(
-                    program,
-                    length,
-                    ptr::null_mut(),
-                    log.as_mut_ptr(),
-                );
-                log.set_len(length as usize);
-                panic!(
-                    "can't link program: {}",
-                    CStr::from_ptr(log.as_ptr()).to_str().unwrap()
-                );
+            let vertex_shader = compile_shader(context, GL_VERTEX_SHADER, vertex_source)?;
+            let fragment_shader = compile_shader(context, GL_FRAGMENT_SHADER, fragment_source)?;
+            let program = link_program(context, &vertex_shader, &fragment_shader)?;
+            Ok(Program::from_linked(
+                program,
+                Some(vertex_shader),
+                Some(fragment_shader),
+            ))
+        }
+    }
+
+    pub fn from_sources_cached(
+        context: &Context,
+        cache: &mut ProgramCache,
+        vertex_source: &str,
+        fragment_source: &str,
+    ) -> Program {
+        let attrib_names: Vec<&str> = Semantic::ALL
+            .iter()
+            .map(|semantic| semantic.attrib_name())
+            .chain(INSTANCE_ATTRIBUTES.iter().map(|(name, _)| *name))
+            .collect();
+        let key = cache.key(&[vertex_source, fragment_source, &attrib_names.join(",")]);
+        if let Some(binary) = cache.get(key) {
+            logi!("load program binary {:016x}", key);
+            match unsafe { load_program_binary(context, binary) } {
+                Some(program) => return unsafe { Program::from_linked(program, None, None) },
+                None => {
+                    logi!("driver rejected program binary {:016x}", key);
+                    cache.remove(key);
+                }
             }
+        }
+        let program = Program::from_sources(context, vertex_source, fragment_source);
+        if let Some(binary) = unsafe { get_program_binary(program.program()) } {
+            logi!("store program binary {:016x}", key);
+            cache.insert(key, binary);
+        }
+        program
+    }
 
-            logi!("get uniform locations");
-            let mut uniform_locations = HashMap::new();
-            let uniform_names = ["uModelMatrix", "uViewMatrix", "uProjectionMatrix"];
-            for name in uniform_names.iter().cloned() {
-                uniform_locations.insert(
-                    String::from(name),
-                    libGLESv3_sys::glGetUniformLocation(
-                        program,
-                        CString::new(name).unwrap().as_ptr(),
-                    ),
-                );
+    unsafe fn from_linked(
+        handle: ProgramHandle,
+        vertex_shader: Option<Shader>,
+        fragment_shader: Option<Shader>,
+    ) -> Program {
+        let program = handle.name();
+        logi!("get active attribs");
+        let mut attrib_count = 0;
+        gl_check!(glGetProgramiv(
+            program,
+            GL_ACTIVE_ATTRIBUTES,
+            &mut attrib_count
+        ));
+        let mut max_length = 0;
+        gl_check!(glGetProgramiv(
+            program,
+            GL_ACTIVE_ATTRIBUTE_MAX_LENGTH,
+            &mut max_length
+        ));
+        let mut attrib_names = Vec::with_capacity(attrib_count as usize);
+        for index in 0..attrib_count {
+            let mut name = vec![0 as GLchar; max_length as usize];
+            let mut length: GLsizei = 0;
+            let mut size: GLint = 0;
+            let mut type_: GLenum = 0;
+            gl_check!(glGetActiveAttrib(
+                program,
+                index as GLuint,
+                max_length,
+                &mut length,
+                &mut size,
+                &mut type_,
+                name.as_mut_ptr(),
+            ));
+            let name = CStr::from_ptr(name.as_ptr()).to_str().unwrap();
+            if !name.starts_with("gl_") {
+                logv!("found attrib {}", name);
+                attrib_names.push(String::from(name));
             }
+        }
 
-            Program {
+        logi!("get uniform locations");
+        let mut uniform_count = 0;
+        gl_check!(glGetProgramiv(
+            program,
+            GL_ACTIVE_UNIFORMS,
+            &mut uniform_count
+        ));
+        let mut max_length = 0;
+        gl_check!(glGetProgramiv(
+            program,
+            GL_ACTIVE_UNIFORM_MAX_LENGTH,
+            &mut max_length
+        ));
+        let mut uniform_locations = HashMap::new();
+        for index in 0..uniform_count {
+            let mut name = vec![0 as GLchar; max_length as usize];
+            let mut length: GLsizei = 0;
+            let mut size: GLint = 0;
+            let mut type_: GLenum = 0;
+            gl_check!(glGetActiveUniform(
+                program,
+                index as GLuint,
+                max_length,
+                &mut length,
+                &mut size,
+                &mut type_,
+                name.as_mut_ptr(),
+            ));
+            let name = CStr::from_ptr(name.as_ptr()).to_str().unwrap();
+            let location = gl_check!(glGetUniformLocation(
                 program,
-                vertex_shader,
-                fragment_shader,
-                uniform_locations,
+                CString::new(name).unwrap().as_ptr()
+            ));
+            logv!("found uniform {} at location {}", name, location);
+            uniform_locations.insert(String::from(name.trim_end_matches("[0]")), location);
+        }
+
+        logi!("bind uniform blocks");
+        for (name, binding) in UNIFORM_BLOCK_BINDINGS.iter().cloned() {
+            let index = gl_check!(glGetUniformBlockIndex(
+                program,
+                CString::new(name).unwrap().as_ptr(),
+            ));
+            if index != GL_INVALID_INDEX {
+                logv!("bind uniform block {} to binding {}", name, binding);
+                gl_check!(glUniformBlockBinding(program, index, binding));
             }
         }
+
+        logi!("bind samplers");
+        handle.context().state().use_program(program);
+        for (name, unit) in SAMPLER_UNITS.iter().cloned() {
+            if let Some(location) = uniform_locations.get(name).cloned() {
+                logv!("bind sampler {} to unit {}", name, unit);
+                gl_check!(glUniform1i(location, unit as GLint));
+            }
+        }
+
+        Program {
+            program: handle,
+            _vertex_shader: vertex_shader,
+            _fragment_shader: fragment_shader,
+            attrib_names,
+            uniform_locations,
+        }
     }
 
     pub fn program(&self) -> GLuint {
-        self.program
+        self.program.name()
+    }
+
+    pub fn attrib_names(&self) -> &[String] {
+        &self.attrib_names
     }
 
     pub fn uniform_location(&self, name: &str) -> GLint {
@@ -112,40 +226,202 @@ impl Program {
     }
 }
 
-impl Drop for Program {
-    fn drop(&mut self) {
-        unsafe {
-            logi!("delete program");
-            libGLESv3_sys::glDeleteProgram(self.program);
+pub fn get_driver_string() -> String {
+    unsafe {
+        let renderer = CStr::from_ptr(gl_check!(glGetString(GL_RENDERER)) as *const _);
+        let version = CStr::from_ptr(gl_check!(glGetString(GL_VERSION)) as *const _);
+        format!(
+            "{} {}",
+            renderer.to_string_lossy(),
+            version.to_string_lossy()
+        )
+    }
+}
 
-            logi!("delete fragment shader");
-            libGLESv3_sys::glDeleteShader(self.fragment_shader);
+unsafe fn link_program(
+    context: &Context,
+    vertex_shader: &Shader,
+    fragment_shader: &Shader,
+) -> Result<ProgramHandle, String> {
+    logi!("link program");
+    let handle = ProgramHandle::new(context);
+    let program = handle.name();
+    gl_check!(glAttachShader(program, vertex_shader.name()));
+    gl_check!(glAttachShader(program, fragment_shader.name()));
+    for semantic in Semantic::ALL.iter() {
+        gl_check!(glBindAttribLocation(
+            program,
+            semantic.location(),
+            CString::new(semantic.attrib_name()).unwrap().as_ptr(),
+        ));
+    }
+    for (name, location) in INSTANCE_ATTRIBUTES.iter().cloned() {
+        gl_check!(glBindAttribLocation(
+            program,
+            location,
+            CString::new(name).unwrap().as_ptr(),
+        ));
+    }
+    gl_check!(glProgramParameteri(
+        program,
+        GL_PROGRAM_BINARY_RETRIEVABLE_HINT,
+        GL_TRUE as GLint,
+    ));
+    gl_check!(glLinkProgram(program));
+    let mut status = 0;
+    gl_check!(glGetProgramiv(program, GL_LINK_STATUS, &mut status));
+    if status == GL_FALSE as GLint {
+        let mut length = 0;
+        gl_check!(glGetProgramiv(program, GL_INFO_LOG_LENGTH, &mut length));
+        let mut log = Vec::with_capacity(length as usize);
+        gl_check!(glGetProgramInfoLog(
+            program,
+            length,
+            ptr::null_mut(),
+            log.as_mut_ptr()
+        ));
+        log.set_len(length as usize);
+        return Err(format!(
+            "can't link program: {}",
+            CStr::from_ptr(log.as_ptr()).to_str().unwrap()
+        ));
+    }
+    Ok(handle)
+}
 
-            logi!("delete vertex shader");
-            libGLESv3_sys::glDeleteShader(self.vertex_shader);
-        }
+unsafe fn get_program_binary(program: GLuint) -> Option<ProgramBinary> {
+    let mut length = 0;
+    gl_check!(glGetProgramiv(
+        program,
+        GL_PROGRAM_BINARY_LENGTH,
+        &mut length
+    ));
+    if length == 0 {
+        return None;
+    }
+    let mut data = vec![0u8; length as usize];
+    let mut written = 0;
+    let mut format = 0;
+    gl_check!(glGetProgramBinary(
+        program,
+        length,
+        &mut written,
+        &mut format,
+        data.as_mut_ptr() as *mut _,
+    ));
+    data.truncate(written as usize);
+    Some(ProgramBinary { format, data })
+}
+
+unsafe fn load_program_binary(context: &Context, binary: &ProgramBinary) -> Option<ProgramHandle> {
+    let handle = ProgramHandle::new(context);
+    gl_check!(glProgramBinary(
+        handle.name(),
+        binary.format,
+        binary.data.as_ptr() as *const _,
+        binary.data.len() as GLsizei,
+    ));
+    let mut status = 0;
+    gl_check!(glGetProgramiv(handle.name(), GL_LINK_STATUS, &mut status));
+    if status == GL_FALSE as GLint {
+        return None;
     }
+    Some(handle)
 }
 
-unsafe fn compile_shader(type_: GLenum, string: &str) -> GLuint {
+unsafe fn compile_shader(context: &Context, type_: GLenum, string: &str) -> Result<Shader, String> {
     logi!("compile shader");
-    let shader = libGLESv3_sys::glCreateShader(type_);
+    let handle = Shader::new(context, type_);
+    let shader = handle.name();
     let string = CString::new(string).unwrap();
     let strings = [string.as_ptr()];
-    libGLESv3_sys::glShaderSource(shader, 1, strings.as_ptr(), ptr::null_mut());
-    libGLESv3_sys::glCompileShader(shader);
+    gl_check!(glShaderSource(shader, 1, strings.as_ptr(), ptr::null_mut()));
+    gl_check!(glCompileShader(shader));
     let mut status = 0;
-    libGLESv3_sys::glGetShaderiv(shader, GL_COMPILE_STATUS, &mut status);
+    gl_check!(glGetShaderiv(shader, GL_COMPILE_STATUS, &mut status));
     if status == GL_FALSE as GLint {
         let mut length = 0;
-        libGLESv3_sys::glGetShaderiv(shader, GL_INFO_LOG_LENGTH, &mut length);
+        gl_check!(glGetShaderiv(shader, GL_INFO_LOG_LENGTH, &mut length));
         let mut log = Vec::with_capacity(length as usize);
-        libGLESv3_sys::glGetShaderInfoLog(shader, length, ptr::null_mut(), log.as_mut_ptr());
+        gl_check!(glGetShaderInfoLog(
+            shader,
+            length,
+            ptr::null_mut(),
+            log.as_mut_ptr()
+        ));
         log.set_len(length as usize);
-        panic!(
+        return Err(format!(
             "can't compile shader: {}",
             CStr::from_ptr(log.as_ptr()).to_str().unwrap()
+        ));
+    }
+    Ok(handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_gl::MockGl;
+    use std::rc::Rc;
+
+    #[test]
+    fn binds_attrib_locations_before_linking() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let program = Program::try_from_sources(&context, "vertex", "fragment").unwrap();
+        let functions = gl.functions();
+        let link = functions
+            .iter()
+            .position(|function| *function == "glLinkProgram")
+            .unwrap();
+        let bind_attrib_locations: Vec<usize> = functions
+            .iter()
+            .enumerate()
+            .filter(|(_, function)| **function == "glBindAttribLocation")
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(
+            bind_attrib_locations.len(),
+            Semantic::ALL.len() + INSTANCE_ATTRIBUTES.len()
         );
+        assert!(bind_attrib_locations.iter().all(|index| *index < link));
+        assert_eq!(
+            functions
+                .iter()
+                .take(link)
+                .filter(|function| **function == "glCompileShader")
+                .count(),
+            2
+        );
+        assert_ne!(program.program(), 0);
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn binds_samplers_to_their_texture_units() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        gl.set_uniforms(&["uModel", "uShadowMap", "uLights[0]"]);
+        let program = Program::try_from_sources(&context, "vertex", "fragment").unwrap();
+        assert_eq!(program.uniform_location("uModel"), 0);
+        assert_eq!(program.uniform_location("uShadowMap"), 1);
+        assert_eq!(program.uniform_location("uLights"), 2);
+        assert_eq!(program.uniform_location("uMissing"), -1);
+        let samplers: Vec<(i64, i64)> = gl
+            .calls()
+            .iter()
+            .filter(|call| call.function == "glUniform1i")
+            .map(|call| (call.argument(0).int(), call.argument(1).int()))
+            .collect();
+        assert_eq!(samplers, [(1, SHADOW_MAP_UNIT as i64)]);
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn describes_the_driver() {
+        let gl = Rc::new(MockGl::new());
+        let _context = Context::with_backend(gl.clone());
+        assert_eq!(get_driver_string(), "mock OpenGL ES 3.0 mock");
+        assert!(gl.errors().is_empty());
     }
-    shader
 }
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 4] = b"RQPC";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 12;
+const INDEX_ENTRY_SIZE: usize = 24;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramBinary {
+    pub format: u32,
+    pub data: Vec<u8>,
+}
+
+pub struct ProgramCache {
+    path: PathBuf,
+    driver: String,
+    binaries: HashMap<u64, ProgramBinary>,
+}
+
+impl ProgramCache {
+    pub fn open(path: &Path, driver: &str) -> ProgramCache {
+        let binaries = match fs::read(path) {
+            Ok(bytes) => match decode(&bytes) {
+                Ok(binaries) => binaries,
+                Err(error) => {
+                    logi!("discard program cache {}: {}", path.display(), error);
+                    HashMap::new()
+                }
+            },
+            Err(_) => HashMap::new(),
+        };
+        logi!(
+            "opened program cache {} with {} entries",
+            path.display(),
+            binaries.len()
+        );
+        ProgramCache {
+            path: PathBuf::from(path),
+            driver: String::from(driver),
+            binaries,
+        }
+    }
+
+    pub fn key(&self, sources: &[&str]) -> u64 {
+        let mut hash = FNV_OFFSET_BASIS;
+        for part in Some(self.driver.as_str()).iter().chain(sources.iter()) {
+            hash = fnv1a(hash, &(part.len() as u64).to_le_bytes());
+            hash = fnv1a(hash, part.as_bytes());
+        }
+        hash
+    }
+
+    pub fn get(&self, key: u64) -> Option<&ProgramBinary> {
+        self.binaries.get(&key)
+    }
+
+    pub fn insert(&mut self, key: u64, binary: ProgramBinary) {
+        self.binaries.insert(key, binary);
+        self.save();
+    }
+
+    pub fn remove(&mut self, key: u64) {
+        if self.binaries.remove(&key).is_some() {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        if let Err(error) = write_atomically(&self.path, &encode(&self.binaries)) {
+            loge!(
+                "can't save program cache {}: {}",
+                self.path.display(),
+                error
+            );
+        }
+    }
+}
+
+pub fn encode(binaries: &HashMap<u64, ProgramBinary>) -> Vec<u8> {
+    let mut keys: Vec<u64> = binaries.keys().cloned().collect();
+    keys.sort();
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(keys.len() as u32).to_le_bytes());
+    let mut offset = HEADER_SIZE + keys.len() * INDEX_ENTRY_SIZE;
+    for key in keys.iter() {
+        let binary = &binaries[key];
+        bytes.extend_from_slice(&key.to_le_bytes());
+        bytes.extend_from_slice(&binary.format.to_le_bytes());
+        bytes.extend_from_slice(&(offset as u32).to_le_bytes());
+        bytes.extend_from_slice(&(binary.data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&checksum(&binary.data).to_le_bytes());
+        offset += binary.data.len();
+    }
+    for key in keys.iter() {
+        bytes.extend_from_slice(&binaries[key].data);
+    }
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> Result<HashMap<u64, ProgramBinary>, String> {
+    if bytes.len() < HEADER_SIZE || &bytes[0..4] != MAGIC {
+        return Err(String::from("not a program cache"));
+    }
+    let version = read_u32(bytes, 4);
+    if version != VERSION {
+        return Err(format!("unsupported version {}", version));
+    }
+    let count = read_u32(bytes, 8) as usize;
+    if bytes.len() < HEADER_SIZE + count * INDEX_ENTRY_SIZE {
+        return Err(String::from("truncated index"));
+    }
+
+    let mut binaries = HashMap::with_capacity(count);
+    for index in 0..count {
+        let entry = HEADER_SIZE + index * INDEX_ENTRY_SIZE;
+        let key = read_u64(bytes, entry);
+        let format = read_u32(bytes, entry + 8);
+        let offset = read_u32(bytes, entry + 12) as usize;
+        let length = read_u32(bytes, entry + 16) as usize;
+        let expected_checksum = read_u32(bytes, entry + 20);
+        let data = bytes
+            .get(offset..offset + length)
+            .ok_or_else(|| format!("entry {:016x} is out of bounds", key))?;
+        if checksum(data) != expected_checksum {
+            return Err(format!("entry {:016x} is corrupt", key));
+        }
+        binaries.insert(
+            key,
+            ProgramBinary {
+                format,
+                data: data.to_vec(),
+            },
+        );
+    }
+    Ok(binaries)
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes.iter() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    let hash = fnv1a(FNV_OFFSET_BASIS, bytes);
+    (hash ^ (hash >> 32)) as u32
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut buffer = [0; 4];
+    buffer.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(buffer)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut buffer = [0; 8];
+    buffer.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(buffer)
+}
+
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let temporary_path = path.with_extension("tmp");
+    fs::write(&temporary_path, bytes)?;
+    fs::rename(&temporary_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn binaries() -> HashMap<u64, ProgramBinary> {
+        let mut binaries = HashMap::new();
+        binaries.insert(
+            2,
+            ProgramBinary {
+                format: 7,
+                data: vec![1, 2, 3],
+            },
+        );
+        binaries.insert(
+            1,
+            ProgramBinary {
+                format: 8,
+                data: vec![4, 5, 6, 7, 8],
+            },
+        );
+        binaries
+    }
+
+    #[test]
+    fn round_trips() {
+        let bytes = encode(&binaries());
+        assert_eq!(&bytes[0..4], MAGIC);
+        assert_eq!(bytes.len(), HEADER_SIZE + 2 * INDEX_ENTRY_SIZE + 8);
+        assert_eq!(decode(&bytes).unwrap(), binaries());
+        assert!(decode(&encode(&HashMap::new())).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_magic_and_version() {
+        let mut bytes = encode(&binaries());
+        bytes[0] = b'X';
+        assert_eq!(decode(&bytes), Err(String::from("not a program cache")));
+
+        let mut bytes = encode(&binaries());
+        bytes[4..8].copy_from_slice(&2u32.to_le_bytes());
+        assert_eq!(decode(&bytes), Err(String::from("unsupported version 2")));
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let bytes = encode(&binaries());
+        assert!(decode(&bytes[..HEADER_SIZE - 1]).is_err());
+        assert_eq!(
+            decode(&bytes[..HEADER_SIZE + INDEX_ENTRY_SIZE]),
+            Err(String::from("truncated index"))
+        );
+        assert!(decode(&bytes[..bytes.len() - 1])
+            .err()
+            .unwrap()
+            .contains("out of bounds"));
+    }
+
+    #[test]
+    fn treats_corrupt_entries_as_misses() {
+        let mut bytes = encode(&binaries());
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        assert!(decode(&bytes).err().unwrap().contains("is corrupt"));
+
+        let path = env::temp_dir().join(format!("rustquest-program-cache-{}.bin", process::id()));
+        fs::write(&path, &bytes).unwrap();
+        let cache = ProgramCache::open(&path, "driver");
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(2).is_none());
+
+        fs::write(&path, encode(&binaries())).unwrap();
+        let mut cache = ProgramCache::open(&path, "driver");
+        assert_eq!(cache.get(1), binaries().get(&1));
+        cache.remove(1);
+        let cache = ProgramCache::open(&path, "driver");
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.get(2), binaries().get(&2));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keys_depend_on_driver_and_sources() {
+        let path = Path::new("unused");
+        let cache = ProgramCache {
+            path: PathBuf::from(path),
+            driver: String::from("driver a"),
+            binaries: HashMap::new(),
+        };
+        let other_driver = ProgramCache {
+            driver: String::from("driver b"),
+            ..ProgramCache {
+                path: PathBuf::from(path),
+                driver: String::new(),
+                binaries: HashMap::new(),
+            }
+        };
+        let key = cache.key(&["vertex", "fragment"]);
+        assert_eq!(key, cache.key(&["vertex", "fragment"]));
+        assert_ne!(key, cache.key(&["vertexfragment", ""]));
+        assert_ne!(key, other_driver.key(&["vertex", "fragment"]));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::gl_state::Capability;
+use crate::render_target::RenderTarget;
+use libGLESv3_sys::{GLbitfield, GLsizei, GL_COLOR_BUFFER_BIT, GL_DEPTH_BUFFER_BIT};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoadOp<T> {
+    Load,
+    Clear(T),
+    DontCare,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StoreOp {
+    Store,
+    StoreWithBorder([f32; 4]),
+    DontCare,
+}
+
+impl StoreOp {
+    fn stores(self) -> bool {
+        self != StoreOp::DontCare
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderPass {
+    pub name: &'static str,
+    pub color_load: LoadOp<[f32; 4]>,
+    pub color_store: StoreOp,
+    pub depth_load: LoadOp<f32>,
+    pub depth_store: StoreOp,
+}
+
+impl RenderPass {
+    pub fn begin(&self, target: &RenderTarget) {
+        logv!("begin render pass {}", self.name);
+        target.bind();
+        {
+            let mut state = target.context().state();
+            state.enable(Capability::ScissorTest);
+            state.scissor(0, 0, target.width(), target.height());
+            state.viewport(0, 0, target.width(), target.height());
+            if let LoadOp::Clear(_) = self.depth_load {
+                state.depth_mask(true);
+            }
+        }
+        unsafe {
+            target.invalidate(
+                self.color_load == LoadOp::DontCare,
+                self.depth_load == LoadOp::DontCare,
+            );
+            let mut mask: GLbitfield = 0;
+            if let LoadOp::Clear([r, g, b, a]) = self.color_load {
+                gl_check!(glClearColor(r, g, b, a));
+                mask |= GL_COLOR_BUFFER_BIT;
+            }
+            if let LoadOp::Clear(depth) = self.depth_load {
+                gl_check!(glClearDepthf(depth));
+                mask |= GL_DEPTH_BUFFER_BIT;
+            }
+            if mask != 0 {
+                gl_check!(glClear(mask));
+            }
+        }
+    }
+
+    pub fn end(&self, target: &RenderTarget) {
+        unsafe {
+            if let StoreOp::StoreWithBorder(color) = self.color_store {
+                clear_border(target, color);
+            }
+            let (store_color, store_depth) = (self.color_store.stores(), self.depth_store.stores());
+            if target.samples() > 1 {
+                target.resolve(store_color, store_depth);
+                target.invalidate(true, true);
+            } else {
+                target.invalidate(!store_color, !store_depth);
+            }
+        }
+        logv!("end render pass {}", self.name);
+    }
+}
+
+unsafe fn clear_border(target: &RenderTarget, color: [f32; 4]) {
+    let (width, height) = (target.width(), target.height());
+    let [r, g, b, a] = color;
+    let mut state = target.context().state();
+    gl_check!(glClearColor(r, g, b, a));
+    let rects: [(GLsizei, GLsizei, GLsizei, GLsizei); 4] = [
+        (0, 0, 1, height),
+        (width - 1, 0, 1, height),
+        (0, 0, width, 1),
+        (0, height - 1, width, 1),
+    ];
+    for (x, y, width, height) in rects.iter().cloned() {
+        state.scissor(x, y, width, height);
+        gl_check!(glClear(GL_COLOR_BUFFER_BIT));
+    }
+    state.scissor(0, 0, width, height);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gl::Context;
+    use crate::mock_gl::MockGl;
+    use libGLESv3_sys::{
+        GLenum, GL_COLOR_ATTACHMENT0, GL_DEPTH_ATTACHMENT, GL_DEPTH_COMPONENT24, GL_RGBA8,
+    };
+    use std::rc::Rc;
+
+    const OVERLAY_PASS: RenderPass = RenderPass {
+        name: "overlay",
+        color_load: LoadOp::Load,
+        color_store: StoreOp::Store,
+        depth_load: LoadOp::Clear(1.0),
+        depth_store: StoreOp::DontCare,
+    };
+
+    fn invalidated(gl: &MockGl) -> Vec<Vec<GLenum>> {
+        gl.calls()
+            .iter()
+            .filter(|call| call.function == "glInvalidateFramebuffer")
+            .map(|call| call.enums.clone())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_loaded_color_and_discards_depth() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let target = RenderTarget::builder(&context, 16, 8)
+            .color_texture(GL_RGBA8)
+            .depth_renderbuffer(GL_DEPTH_COMPONENT24)
+            .build()
+            .unwrap();
+
+        gl.clear_calls();
+        OVERLAY_PASS.begin(&target);
+        assert!(invalidated(&gl).is_empty());
+        let clears: Vec<i64> = gl
+            .calls()
+            .iter()
+            .filter(|call| call.function == "glClear")
+            .map(|call| call.argument(0).int())
+            .collect();
+        assert_eq!(clears, [GL_DEPTH_BUFFER_BIT as i64]);
+        assert_eq!(gl.state().scissor, [0, 0, 16, 8]);
+
+        gl.clear_calls();
+        OVERLAY_PASS.end(&target);
+        assert_eq!(invalidated(&gl), [vec![GL_DEPTH_ATTACHMENT]]);
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn discards_multisampled_attachments_after_resolving() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let target = RenderTarget::builder(&context, 16, 8)
+            .color_texture(GL_RGBA8)
+            .depth_renderbuffer(GL_DEPTH_COMPONENT24)
+            .samples(4)
+            .build()
+            .unwrap();
+
+        OVERLAY_PASS.begin(&target);
+        gl.clear_calls();
+        OVERLAY_PASS.end(&target);
+        let functions = gl.functions();
+        let blit = functions
+            .iter()
+            .position(|function| *function == "glBlitFramebuffer")
+            .unwrap();
+        assert!(!functions[..blit].contains(&"glInvalidateFramebuffer"));
+        assert_eq!(
+            invalidated(&gl),
+            [vec![GL_COLOR_ATTACHMENT0, GL_DEPTH_ATTACHMENT]]
+        );
+        assert!(gl.errors().is_empty());
+    }
+}
@@ -0,0 +1,463 @@
+use crate::gl;
+use crate::gl::Context;
+use crate::gl_debug;
+use crate::texture::{Texture, TextureKind};
+use libGLESv3_sys::{
+    GLenum, GLsizei, GLuint, GL_COLOR_ATTACHMENT0, GL_COLOR_BUFFER_BIT, GL_DEPTH_ATTACHMENT,
+    GL_DEPTH_BUFFER_BIT, GL_DRAW_FRAMEBUFFER, GL_FRAMEBUFFER_COMPLETE, GL_NEAREST, GL_NONE,
+    GL_RENDERBUFFER, GL_TEXTURE_2D,
+};
+
+enum Attachment {
+    Texture(Texture),
+    External(GLuint),
+    Renderbuffer(gl::Renderbuffer),
+}
+
+impl Attachment {
+    fn attach(&self, attachment: GLenum) {
+        unsafe {
+            match self {
+                Attachment::Texture(texture) => gl_check!(glFramebufferTexture2D(
+                    GL_DRAW_FRAMEBUFFER,
+                    attachment,
+                    GL_TEXTURE_2D,
+                    texture.texture(),
+                    0,
+                )),
+                Attachment::External(texture) => gl_check!(glFramebufferTexture2D(
+                    GL_DRAW_FRAMEBUFFER,
+                    attachment,
+                    GL_TEXTURE_2D,
+                    *texture,
+                    0,
+                )),
+                Attachment::Renderbuffer(renderbuffer) => gl_check!(glFramebufferRenderbuffer(
+                    GL_DRAW_FRAMEBUFFER,
+                    attachment,
+                    GL_RENDERBUFFER,
+                    renderbuffer.name(),
+                )),
+            }
+        }
+    }
+
+    fn texture(&self) -> Option<&Texture> {
+        match self {
+            Attachment::Texture(texture) => Some(texture),
+            _ => None,
+        }
+    }
+}
+
+enum AttachmentSource {
+    Texture(GLenum),
+    External(GLuint),
+    Renderbuffer(GLenum),
+}
+
+pub struct RenderTargetBuilder {
+    context: Context,
+    width: GLsizei,
+    height: GLsizei,
+    samples: GLsizei,
+    color: Option<(AttachmentSource, GLenum)>,
+    depth: Option<(AttachmentSource, GLenum)>,
+}
+
+impl RenderTargetBuilder {
+    pub fn color_texture(mut self, internal_format: GLenum) -> RenderTargetBuilder {
+        self.color = Some((AttachmentSource::Texture(internal_format), internal_format));
+        self
+    }
+
+    pub fn color_external(
+        mut self,
+        texture: GLuint,
+        internal_format: GLenum,
+    ) -> RenderTargetBuilder {
+        self.color = Some((AttachmentSource::External(texture), internal_format));
+        self
+    }
+
+    pub fn color_renderbuffer(mut self, internal_format: GLenum) -> RenderTargetBuilder {
+        self.color = Some((
+            AttachmentSource::Renderbuffer(internal_format),
+            internal_format,
+        ));
+        self
+    }
+
+    pub fn depth_texture(mut self, internal_format: GLenum) -> RenderTargetBuilder {
+        self.depth = Some((AttachmentSource::Texture(internal_format), internal_format));
+        self
+    }
+
+    pub fn depth_renderbuffer(mut self, internal_format: GLenum) -> RenderTargetBuilder {
+        self.depth = Some((
+            AttachmentSource::Renderbuffer(internal_format),
+            internal_format,
+        ));
+        self
+    }
+
+    pub fn samples(mut self, samples: GLsizei) -> RenderTargetBuilder {
+        self.samples = samples.max(1);
+        self
+    }
+
+    pub fn build(self) -> Result<RenderTarget, String> {
+        let context = &self.context;
+        let (width, height, samples) = (self.width, self.height, self.samples);
+        let create = |source: AttachmentSource| match source {
+            AttachmentSource::Texture(internal_format) => Attachment::Texture(Texture::new(
+                context,
+                TextureKind::Texture2D,
+                internal_format,
+                width,
+                height,
+                1,
+                1,
+            )),
+            AttachmentSource::External(texture) => Attachment::External(texture),
+            AttachmentSource::Renderbuffer(internal_format) => Attachment::Renderbuffer(
+                create_renderbuffer(context, internal_format, width, height, 1),
+            ),
+        };
+        let multisample = |internal_format: GLenum| {
+            Attachment::Renderbuffer(create_renderbuffer(
+                context,
+                internal_format,
+                width,
+                height,
+                samples,
+            ))
+        };
+
+        let (mut color, mut depth, mut resolve_color, mut resolve_depth) = (None, None, None, None);
+        if samples > 1 {
+            if let Some((source, internal_format)) = self.color {
+                color = Some(multisample(internal_format));
+                match source {
+                    AttachmentSource::Renderbuffer(_) => {}
+                    source => resolve_color = Some(create(source)),
+                }
+            }
+            if let Some((source, internal_format)) = self.depth {
+                depth = Some(multisample(internal_format));
+                match source {
+                    AttachmentSource::Renderbuffer(_) => {}
+                    source => resolve_depth = Some(create(source)),
+                }
+            }
+        } else {
+            color = self.color.map(|(source, _)| create(source));
+            depth = self.depth.map(|(source, _)| create(source));
+        }
+
+        let frame_buffer = create_frame_buffer(context, color.as_ref(), depth.as_ref())?;
+        let resolve_frame_buffer = if resolve_color.is_some() || resolve_depth.is_some() {
+            Some(create_frame_buffer(
+                context,
+                resolve_color.as_ref(),
+                resolve_depth.as_ref(),
+            )?)
+        } else {
+            None
+        };
+        Ok(RenderTarget {
+            frame_buffer,
+            resolve_frame_buffer,
+            width,
+            height,
+            samples,
+            color,
+            depth,
+            resolve_color,
+            resolve_depth,
+        })
+    }
+}
+
+pub struct RenderTarget {
+    frame_buffer: gl::Framebuffer,
+    resolve_frame_buffer: Option<gl::Framebuffer>,
+    width: GLsizei,
+    height: GLsizei,
+    samples: GLsizei,
+    color: Option<Attachment>,
+    depth: Option<Attachment>,
+    resolve_color: Option<Attachment>,
+    resolve_depth: Option<Attachment>,
+}
+
+impl RenderTarget {
+    pub fn builder(context: &Context, width: GLsizei, height: GLsizei) -> RenderTargetBuilder {
+        RenderTargetBuilder {
+            context: context.clone(),
+            width,
+            height,
+            samples: 1,
+            color: None,
+            depth: None,
+        }
+    }
+
+    pub fn context(&self) -> &Context {
+        self.frame_buffer.context()
+    }
+
+    pub fn width(&self) -> GLsizei {
+        self.width
+    }
+
+    pub fn height(&self) -> GLsizei {
+        self.height
+    }
+
+    pub fn samples(&self) -> GLsizei {
+        self.samples
+    }
+
+    pub fn frame_buffer(&self) -> GLuint {
+        self.frame_buffer.name()
+    }
+
+    pub fn has_color(&self) -> bool {
+        self.color.is_some()
+    }
+
+    pub fn has_depth(&self) -> bool {
+        self.depth.is_some()
+    }
+
+    pub fn color_texture(&self) -> Option<&Texture> {
+        self.resolve_color
+            .as_ref()
+            .or(self.color.as_ref())
+            .and_then(Attachment::texture)
+    }
+
+    pub fn depth_texture(&self) -> Option<&Texture> {
+        self.resolve_depth
+            .as_ref()
+            .or(self.depth.as_ref())
+            .and_then(Attachment::texture)
+    }
+
+    pub fn bind(&self) {
+        self.context()
+            .state()
+            .bind_draw_framebuffer(self.frame_buffer.name());
+    }
+
+    pub fn bind_read(&self) {
+        let frame_buffer = self
+            .resolve_frame_buffer
+            .as_ref()
+            .unwrap_or(&self.frame_buffer);
+        self.context()
+            .state()
+            .bind_read_framebuffer(frame_buffer.name());
+    }
+
+    pub fn resolve(&self, color: bool, depth: bool) {
+        let resolve_frame_buffer = match self.resolve_frame_buffer.as_ref() {
+            Some(resolve_frame_buffer) => resolve_frame_buffer,
+            None => return,
+        };
+        let mut mask = 0;
+        if color && self.resolve_color.is_some() {
+            mask |= GL_COLOR_BUFFER_BIT;
+        }
+        if depth && self.resolve_depth.is_some() {
+            mask |= GL_DEPTH_BUFFER_BIT;
+        }
+        if mask == 0 {
+            return;
+        }
+        let mut state = self.context().state();
+        state.bind_read_framebuffer(self.frame_buffer.name());
+        state.bind_draw_framebuffer(resolve_frame_buffer.name());
+        unsafe {
+            gl_check!(glBlitFramebuffer(
+                0,
+                0,
+                self.width,
+                self.height,
+                0,
+                0,
+                self.width,
+                self.height,
+                mask,
+                GL_NEAREST,
+            ));
+        }
+        state.bind_read_framebuffer(0);
+        state.bind_draw_framebuffer(self.frame_buffer.name());
+    }
+
+    pub fn invalidate(&self, color: bool, depth: bool) {
+        let mut attachments = Vec::with_capacity(2);
+        if color && self.color.is_some() {
+            attachments.push(GL_COLOR_ATTACHMENT0);
+        }
+        if depth && self.depth.is_some() {
+            attachments.push(GL_DEPTH_ATTACHMENT);
+        }
+        if attachments.is_empty() {
+            return;
+        }
+        unsafe {
+            gl_check!(glInvalidateFramebuffer(
+                GL_DRAW_FRAMEBUFFER,
+                attachments.len() as GLsizei,
+                attachments.as_ptr(),
+            ));
+        }
+    }
+}
+
+fn create_renderbuffer(
+    context: &Context,
+    internal_format: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+    samples: GLsizei,
+) -> gl::Renderbuffer {
+    unsafe {
+        let renderbuffer = gl::Renderbuffer::new(context);
+        gl_check!(glBindRenderbuffer(GL_RENDERBUFFER, renderbuffer.name()));
+        if samples > 1 {
+            gl_check!(glRenderbufferStorageMultisample(
+                GL_RENDERBUFFER,
+                samples,
+                internal_format,
+                width,
+                height,
+            ));
+        } else {
+            gl_check!(glRenderbufferStorage(
+                GL_RENDERBUFFER,
+                internal_format,
+                width,
+                height
+            ));
+        }
+        gl_check!(glBindRenderbuffer(GL_RENDERBUFFER, 0));
+        renderbuffer
+    }
+}
+
+fn create_frame_buffer(
+    context: &Context,
+    color: Option<&Attachment>,
+    depth: Option<&Attachment>,
+) -> Result<gl::Framebuffer, String> {
+    unsafe {
+        let frame_buffer = gl::Framebuffer::new(context);
+        context.state().bind_draw_framebuffer(frame_buffer.name());
+        match color {
+            Some(color) => color.attach(GL_COLOR_ATTACHMENT0),
+            None => {
+                let draw_buffers = [GL_NONE];
+                gl_check!(glDrawBuffers(1, draw_buffers.as_ptr()));
+                gl_check!(glReadBuffer(GL_NONE));
+            }
+        }
+        if let Some(depth) = depth {
+            depth.attach(GL_DEPTH_ATTACHMENT);
+        }
+        let status = gl_check!(glCheckFramebufferStatus(GL_DRAW_FRAMEBUFFER));
+        context.state().bind_draw_framebuffer(0);
+        if status != GL_FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "can't initialize frame buffer: {}",
+                gl_debug::enum_name(status)
+            ));
+        }
+        Ok(frame_buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_gl::{self, MockGl, Storage};
+    use libGLESv3_sys::{GL_DEPTH_COMPONENT24, GL_RGBA8};
+    use std::rc::Rc;
+
+    fn attachment_storage(gl: &MockGl, frame_buffer: GLuint, attachment: GLenum) -> Storage {
+        let state = gl.state();
+        let name = match &state.objects[&frame_buffer] {
+            mock_gl::Object::Framebuffer(attachments) => match attachments[&attachment] {
+                mock_gl::Attachment::Texture(name) | mock_gl::Attachment::Renderbuffer(name) => {
+                    name
+                }
+            },
+            object => panic!("{:?} is not a framebuffer", object),
+        };
+        match &state.objects[&name] {
+            mock_gl::Object::Texture(Some(storage))
+            | mock_gl::Object::Renderbuffer(Some(storage)) => *storage,
+            object => panic!("{:?} has no storage", object),
+        }
+    }
+
+    #[test]
+    fn renders_into_renderbuffers() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let target = RenderTarget::builder(&context, 16, 8)
+            .color_renderbuffer(GL_RGBA8)
+            .depth_renderbuffer(GL_DEPTH_COMPONENT24)
+            .build()
+            .unwrap();
+        assert!(target.has_color());
+        assert!(target.has_depth());
+        assert!(target.color_texture().is_none());
+        assert!(target.depth_texture().is_none());
+        assert_eq!(
+            attachment_storage(&gl, target.frame_buffer(), GL_COLOR_ATTACHMENT0),
+            Storage {
+                internal_format: GL_RGBA8,
+                samples: 1,
+            }
+        );
+
+        target.bind();
+        assert_eq!(gl.state().draw_framebuffer, target.frame_buffer());
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn resolves_multisampled_attachments_into_textures() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let target = RenderTarget::builder(&context, 16, 8)
+            .color_texture(GL_RGBA8)
+            .depth_renderbuffer(GL_DEPTH_COMPONENT24)
+            .samples(4)
+            .build()
+            .unwrap();
+        assert!(target.color_texture().is_some());
+        assert!(target.depth_texture().is_none());
+        assert_eq!(
+            attachment_storage(&gl, target.frame_buffer(), GL_DEPTH_ATTACHMENT).samples,
+            4
+        );
+
+        target.bind();
+        gl.clear_calls();
+        target.resolve(true, true);
+        let blits: Vec<i64> = gl
+            .calls()
+            .iter()
+            .filter(|call| call.function == "glBlitFramebuffer")
+            .map(|call| call.argument(8).int())
+            .collect();
+        assert_eq!(blits, [GL_COLOR_BUFFER_BIT as i64]);
+        assert_eq!(gl.state().draw_framebuffer, target.frame_buffer());
+        assert!(gl.errors().is_empty());
+    }
+}
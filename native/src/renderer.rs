@@ -0,0 +1,729 @@
+use crate::batcher::Batcher;
+use crate::environment::Environment;
+use crate::frustum::Frustum;
+use crate::geometry::Geometry;
+use crate::gl::Context;
+use crate::gl_state::Capability;
+use crate::gltf::Material;
+use crate::gpu_profiler::{FrameTimings, GpuProfiler};
+use crate::image::Image;
+use crate::light;
+use crate::light::{Light, LightUniforms};
+use crate::material;
+use crate::material::MaterialUniforms;
+use crate::math::{Mat4, Vec3};
+use crate::model::MaterialTextures;
+use crate::program::{
+    ENVIRONMENT_MAP_UNIT, LIGHT_BLOCK_BINDING, MATERIAL_BLOCK_BINDING, SHADOW_BLOCK_BINDING,
+    SHADOW_MAP_UNIT, VIEW_BLOCK_BINDING,
+};
+use crate::render_pass::{LoadOp, RenderPass, StoreOp};
+use crate::scene::Scene;
+use crate::screenshot::{CaptureEyes, ScreenshotCapture};
+use crate::shader_library::ShaderLibrary;
+use crate::shadow;
+use crate::shadow::ShadowFrustum;
+use crate::shadow_map::ShadowMap;
+use crate::swap_chain::SwapChain;
+use crate::uniform_ring::UniformRing;
+use libGLESv3_sys::GLsizei;
+
+const UNIFORM_RING_FRAME_SIZE: usize = 64 * 1024;
+const UNIFORM_RING_FRAME_COUNT: usize = 3;
+const EYE_REGION_NAMES: [&str; 2] = ["left eye", "right eye"];
+const EYE_SAMPLES: GLsizei = 1;
+
+const SHADOW_PASS: RenderPass = RenderPass {
+    name: "shadow",
+    color_load: LoadOp::DontCare,
+    color_store: StoreOp::DontCare,
+    depth_load: LoadOp::Clear(1.0),
+    depth_store: StoreOp::Store,
+};
+
+const EYE_PASS: RenderPass = RenderPass {
+    name: "eye",
+    color_load: LoadOp::Clear([0.1, 0.1, 0.1, 0.0]),
+    color_store: StoreOp::StoreWithBorder([0.0, 0.0, 0.0, 1.0]),
+    depth_load: LoadOp::Clear(1.0),
+    depth_store: StoreOp::DontCare,
+};
+
+std140_struct! {
+    struct ViewUniforms {
+        view_matrix: [[f32; 4]; 4],
+        projection_matrix: [[f32; 4]; 4],
+        camera_position: [f32; 4],
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Eye {
+    pub view_matrix: Mat4,
+    pub projection_matrix: Mat4,
+}
+
+pub struct Renderer {
+    gl: Context,
+    swap_chains: [SwapChain; 2],
+    shader_library: ShaderLibrary,
+    uniform_ring: UniformRing,
+    meshes: Vec<Geometry>,
+    batcher: Batcher,
+    shadow_batcher: Batcher,
+    materials: Vec<Material>,
+    textures: Option<MaterialTextures>,
+    lights: Vec<Light>,
+    environment: Environment,
+    shadow_map: Option<ShadowMap>,
+    screenshots: ScreenshotCapture,
+    gpu_profiler: GpuProfiler,
+}
+
+impl Renderer {
+    pub fn new(
+        gl: &Context,
+        shader_library: ShaderLibrary,
+        environment: Environment,
+        width: GLsizei,
+        height: GLsizei,
+    ) -> Renderer {
+        Renderer {
+            gl: gl.clone(),
+            swap_chains: [
+                SwapChain::new(gl, width, height, EYE_SAMPLES),
+                SwapChain::new(gl, width, height, EYE_SAMPLES),
+            ],
+            shader_library,
+            uniform_ring: UniformRing::new(gl, UNIFORM_RING_FRAME_SIZE, UNIFORM_RING_FRAME_COUNT),
+            meshes: Vec::new(),
+            batcher: Batcher::new(),
+            shadow_batcher: Batcher::new(),
+            materials: Vec::new(),
+            textures: None,
+            lights: Vec::new(),
+            environment,
+            shadow_map: None,
+            screenshots: ScreenshotCapture::new(),
+            gpu_profiler: GpuProfiler::new(gl),
+        }
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.gl
+    }
+
+    pub fn swap_chains(&self) -> &[SwapChain; 2] {
+        &self.swap_chains
+    }
+
+    pub fn add_mesh(&mut self, mesh: Geometry) -> usize {
+        self.meshes.push(mesh);
+        self.meshes.len() - 1
+    }
+
+    pub fn set_materials(&mut self, materials: Vec<Material>) {
+        self.materials = materials;
+    }
+
+    pub fn set_textures(&mut self, textures: MaterialTextures) {
+        self.textures = Some(textures);
+    }
+
+    pub fn set_lights(&mut self, lights: Vec<Light>) {
+        self.lights = lights;
+    }
+
+    pub fn reload_shaders(&mut self) {
+        self.shader_library.reload();
+    }
+
+    pub fn request_screenshot(&mut self, eyes: CaptureEyes) {
+        self.screenshots.request(eyes);
+    }
+
+    pub fn poll_screenshots(&mut self) -> Vec<Image> {
+        self.screenshots.poll()
+    }
+
+    pub fn gpu_timings(&self) -> Option<&FrameTimings> {
+        self.gpu_profiler.latest_timings()
+    }
+
+    pub fn render_frame(&mut self, scene: &Scene, eyes: &[Eye; 2]) {
+        profile_scope!("render");
+        self.gpu_profiler.begin_frame();
+        self.uniform_ring.begin_frame();
+        let mut view_ranges = Vec::with_capacity(eyes.len());
+        let mut eye_frustums = Vec::with_capacity(eyes.len());
+        let mut eye_positions = Vec::with_capacity(eyes.len());
+        for eye in eyes.iter() {
+            let eye_position = eye
+                .view_matrix
+                .inverse()
+                .map_or(Vec3::ZERO, |matrix| matrix.transform_point(Vec3::ZERO));
+            view_ranges.push(self.uniform_ring.push(&ViewUniforms {
+                view_matrix: eye.view_matrix.columns,
+                projection_matrix: eye.projection_matrix.columns,
+                camera_position: [eye_position.x, eye_position.y, eye_position.z, 1.0],
+            }));
+            eye_positions.push(eye_position);
+            eye_frustums.push(Frustum::from_view_projection(
+                &(eye.projection_matrix * eye.view_matrix),
+            ));
+        }
+        let frustum = Frustum::stereo(&eye_frustums[0], &eye_frustums[1]);
+
+        let lights = &self.lights;
+        let shadow = light::shadow_caster(lights).and_then(|index| {
+            let light = &lights[index];
+            let settings = light.shadow?;
+            let mut points = Vec::with_capacity(16);
+            for eye in eyes.iter() {
+                points.extend(
+                    shadow::view_corners(
+                        &eye.view_matrix,
+                        &eye.projection_matrix,
+                        settings.distance,
+                    )?
+                    .iter(),
+                );
+            }
+            let shadow_frustum = ShadowFrustum::fit(light.direction, &points, &settings)?;
+            Some((index, settings, shadow_frustum))
+        });
+        if let Some((_, settings, _)) = shadow {
+            if self
+                .shadow_map
+                .as_ref()
+                .is_none_or(|shadow_map| shadow_map.resolution() != settings.resolution)
+            {
+                self.shadow_map = Some(ShadowMap::new(&self.gl, settings.resolution));
+            }
+        }
+        let shadow_ranges = shadow.map(|(_, settings, shadow_frustum)| {
+            (
+                self.uniform_ring.push(&ViewUniforms {
+                    view_matrix: shadow_frustum.view_matrix.columns,
+                    projection_matrix: shadow_frustum.projection_matrix.columns,
+                    camera_position: [0.0, 0.0, 0.0, 1.0],
+                }),
+                self.uniform_ring.push(&shadow_frustum.uniforms(&settings)),
+            )
+        });
+
+        let light_range = self.uniform_ring.push(&LightUniforms::pack(
+            &self.lights,
+            &frustum,
+            (eye_positions[0] + eye_positions[1]) * 0.5,
+            self.environment.intensity(),
+            self.environment.max_level(),
+            shadow.map(|(index, _, _)| index),
+        ));
+        let default_material_range = self
+            .uniform_ring
+            .push(&MaterialUniforms::new(&Material::default()));
+        let mut material_ranges = Vec::with_capacity(self.materials.len());
+        for material in self.materials.iter() {
+            material_ranges.push(self.uniform_ring.push(&MaterialUniforms::new(material)));
+        }
+
+        self.batcher.clear();
+        self.shadow_batcher.clear();
+        let shadow_frustum = shadow.map(|(_, _, shadow_frustum)| {
+            Frustum::from_view_projection(&shadow_frustum.view_projection())
+        });
+        let batcher = &mut self.batcher;
+        let shadow_batcher = &mut self.shadow_batcher;
+        let meshes = &self.meshes;
+        let mut culled_count = 0;
+        scene.visit_drawables(|_, world_matrix, drawable| {
+            let bounds = meshes[drawable.mesh]
+                .bounds()
+                .map(|bounds| bounds.transform(world_matrix));
+            let visible = |frustum: &Frustum| {
+                bounds
+                    .as_ref()
+                    .is_none_or(|bounds| frustum.intersects_aabb(bounds))
+            };
+            if shadow_frustum.as_ref().is_some_and(visible) {
+                shadow_batcher.add(
+                    drawable.mesh,
+                    drawable.material,
+                    world_matrix,
+                    [1.0, 1.0, 1.0, 1.0],
+                );
+            }
+            if !visible(&frustum) {
+                culled_count += 1;
+                return;
+            }
+            batcher.add(
+                drawable.mesh,
+                drawable.material,
+                world_matrix,
+                [1.0, 1.0, 1.0, 1.0],
+            );
+        });
+        logv!(
+            "draw {} instances, culled {}",
+            self.batcher.instance_count(),
+            culled_count
+        );
+        self.uniform_ring.flush();
+
+        self.gl.state().enable(Capability::CullFace);
+        self.gl.state().enable(Capability::DepthTest);
+        if let (Some(shadow_map), Some((_, settings, _)), Some((shadow_view_range, _))) =
+            (self.shadow_map.as_ref(), shadow, shadow_ranges)
+        {
+            profile_scope!("shadow pass");
+            self.shadow_batcher.upload(&mut self.meshes);
+            self.gpu_profiler.begin_region("shadow");
+            SHADOW_PASS.begin(shadow_map.render_target());
+            self.gl.state().enable(Capability::PolygonOffsetFill);
+            self.gl.state().polygon_offset(settings.slope_bias, 1.0);
+            self.uniform_ring
+                .bind(VIEW_BLOCK_BINDING, shadow_view_range);
+            let program = self.shader_library.program("shadow", &["INSTANCED"]);
+            self.gl.state().use_program(program.program());
+            self.shadow_batcher.draw(&self.meshes, |_, _| {});
+            self.gl.state().disable(Capability::PolygonOffsetFill);
+            SHADOW_PASS.end(shadow_map.render_target());
+            self.gpu_profiler.end_region();
+        }
+        self.batcher.upload(&mut self.meshes);
+
+        let default_material = Material::default();
+        for (index, swap_chain) in self.swap_chains.iter_mut().enumerate() {
+            profile_scope!("eye pass");
+            self.gpu_profiler.begin_region(EYE_REGION_NAMES[index]);
+            EYE_PASS.begin(swap_chain.render_target());
+            self.uniform_ring
+                .bind(VIEW_BLOCK_BINDING, view_ranges[index]);
+            self.uniform_ring.bind(LIGHT_BLOCK_BINDING, light_range);
+            self.environment.bind(ENVIRONMENT_MAP_UNIT);
+            if let (Some(shadow_map), Some((_, shadow_range))) =
+                (self.shadow_map.as_ref(), shadow_ranges)
+            {
+                self.uniform_ring.bind(SHADOW_BLOCK_BINDING, shadow_range);
+                shadow_map.bind(SHADOW_MAP_UNIT);
+            }
+            let gl = &self.gl;
+            let shader_library = &mut self.shader_library;
+            let uniform_ring = &self.uniform_ring;
+            let materials = &self.materials;
+            let textures = self.textures.as_ref();
+            self.batcher.draw(&self.meshes, |geometry, material_index| {
+                let material = material_index.map_or(&default_material, |index| &materials[index]);
+                let mut defines =
+                    material::material_defines(material, geometry.layout(), textures.is_some());
+                if shadow_ranges.is_some() {
+                    defines.push("HAS_SHADOWS");
+                }
+                defines.push("INSTANCED");
+                let program = shader_library.program("pbr", &defines);
+                if cfg!(debug_assertions) {
+                    if let Err(error) = geometry.layout().validate(program.attrib_names()) {
+                        panic!("can't draw geometry: {}", error);
+                    }
+                }
+                gl.state().use_program(program.program());
+                uniform_ring.bind(
+                    MATERIAL_BLOCK_BINDING,
+                    material_index.map_or(default_material_range, |index| material_ranges[index]),
+                );
+                if let Some(textures) = textures {
+                    textures.bind_material_textures(material);
+                }
+            });
+            EYE_PASS.end(swap_chain.render_target());
+            self.gpu_profiler.end_region();
+            if self.screenshots.wants(index) {
+                self.screenshots.read_eye(swap_chain.render_target());
+            }
+            unsafe {
+                gl_check!(glFlush());
+            }
+            swap_chain.advance();
+        }
+        self.screenshots.end_frame();
+        self.gpu_profiler.end_frame();
+        self.uniform_ring.end_frame();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gltf;
+    use crate::gltf::TextureRef;
+    use crate::golden;
+    use crate::math::{Quat, Transform};
+    use crate::mock_gl::MockGl;
+    use crate::program::{BASE_COLOR_TEXTURE_UNIT, EMISSIVE_TEXTURE_UNIT};
+    use crate::sampler::{Sampler, SamplerState};
+    use crate::scene::Drawable;
+    use crate::shadow::ShadowSettings;
+    use crate::soft_gl::SoftGl;
+    use crate::texture::Texture;
+    use libGLESv3_sys::{GL_DEPTH_ATTACHMENT, GL_TEXTURE_2D};
+    use std::collections::HashSet;
+    use std::env;
+    use std::f32::consts::FRAC_PI_2;
+    use std::fs;
+    use std::fs::File;
+    use std::process;
+    use std::rc::Rc;
+    use std::time::{Duration, SystemTime};
+
+    const SHADERS: &[(&str, &str)] = &[
+        ("lighting.glsl", include_str!("../shaders/lighting.glsl")),
+        ("pbr.vert", include_str!("../shaders/pbr.vert")),
+        ("pbr.frag", include_str!("../shaders/pbr.frag")),
+        ("shadow.vert", include_str!("../shaders/shadow.vert")),
+        ("shadow.frag", include_str!("../shaders/shadow.frag")),
+    ];
+
+    fn renderer(context: &Context) -> Renderer {
+        let mut shader_library = ShaderLibrary::new(context);
+        for (name, source) in SHADERS.iter() {
+            shader_library.add_source(name, source);
+        }
+        let environment = Environment::procedural(context, 1.0);
+        let mut renderer = Renderer::new(context, shader_library, environment, 64, 64);
+        renderer.add_mesh(Geometry::cube(context));
+        renderer.set_materials(vec![Material::default()]);
+        renderer.set_lights(vec![Light {
+            shadow: Some(ShadowSettings::default()),
+            ..Light::directional(Vec3::new(0.0, -1.0, 0.0), Vec3::ONE, 1.0)
+        }]);
+        renderer
+    }
+
+    fn scene() -> Scene {
+        let mut scene = Scene::new();
+        let node = scene.create_node("cube");
+        scene.set_transform(
+            node,
+            Transform {
+                translation: Vec3::new(0.0, 0.0, -3.0),
+                ..Transform::IDENTITY
+            },
+        );
+        scene.set_drawable(
+            node,
+            Some(Drawable {
+                mesh: 0,
+                material: Some(0),
+            }),
+        );
+        scene.update();
+        scene
+    }
+
+    fn eyes() -> [Eye; 2] {
+        let eye = |x: f32| Eye {
+            view_matrix: Mat4::translation(Vec3::new(-x, 0.0, 0.0)),
+            projection_matrix: Mat4::perspective(FRAC_PI_2, 1.0, 0.1, 100.0),
+        };
+        [eye(-0.03), eye(0.03)]
+    }
+
+    fn scripted_eyes(head_position: Vec3, pitch: f32, interpupillary_distance: f32) -> [Eye; 2] {
+        let head_matrix = Mat4::from_trs(
+            head_position,
+            Quat::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), pitch),
+            Vec3::ONE,
+        );
+        let eye = |x: f32| Eye {
+            view_matrix: (head_matrix * Mat4::translation(Vec3::new(x, 0.0, 0.0)))
+                .inverse()
+                .unwrap(),
+            projection_matrix: Mat4::perspective(FRAC_PI_2, 1.0, 0.1, 100.0),
+        };
+        [
+            eye(-interpupillary_distance * 0.5),
+            eye(interpupillary_distance * 0.5),
+        ]
+    }
+
+    #[test]
+    fn renders_cube_from_scripted_pose() {
+        let gl = Rc::new(SoftGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut renderer = renderer(&context);
+        let mut scene = scene();
+        let floor_node = scene.create_node("floor");
+        scene.set_transform(
+            floor_node,
+            Transform {
+                translation: Vec3::new(0.0, -1.0, -3.0),
+                scale: Vec3::new(4.0, 0.05, 4.0),
+                ..Transform::IDENTITY
+            },
+        );
+        scene.set_drawable(
+            floor_node,
+            Some(Drawable {
+                mesh: 0,
+                material: Some(0),
+            }),
+        );
+        scene.update();
+        let indices: Vec<GLsizei> = renderer
+            .swap_chains()
+            .iter()
+            .map(|swap_chain| swap_chain.index())
+            .collect();
+        renderer.render_frame(
+            &scene,
+            &scripted_eyes(Vec3::new(0.4, 0.8, 0.0), -0.3, 0.064),
+        );
+        assert!(gl.mock().errors().is_empty());
+
+        for ((swap_chain, index), name) in renderer
+            .swap_chains()
+            .iter()
+            .zip(indices)
+            .zip(["scripted_pose_left", "scripted_pose_right"].iter())
+        {
+            let image = gl.texture_image(swap_chain.color_texture(index)).unwrap();
+            golden::assert_matches_golden(name, &image, 2, 0.01);
+        }
+    }
+
+    #[test]
+    fn captures_side_by_side_screenshot() {
+        let gl = Rc::new(SoftGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut renderer = renderer(&context);
+        let scene = scene();
+        renderer.render_frame(&scene, &eyes());
+        assert!(renderer.poll_screenshots().is_empty());
+
+        let index = renderer.swap_chains()[1].index();
+        renderer.request_screenshot(CaptureEyes::Both);
+        renderer.render_frame(&scene, &eyes());
+        renderer.render_frame(&scene, &eyes());
+        let screenshots = renderer.poll_screenshots();
+        assert_eq!(screenshots.len(), 1);
+        let screenshot = &screenshots[0];
+        assert_eq!((screenshot.width, screenshot.height), (128, 64));
+
+        let right_eye = gl
+            .texture_image(renderer.swap_chains()[1].color_texture(index))
+            .unwrap();
+        for (y, row) in right_eye.data.chunks_exact(64 * 4).enumerate() {
+            for (x, pixel) in row.chunks_exact(4).enumerate() {
+                let offset = (y * 128 + 64 + x) * 4;
+                assert_eq!(screenshot.data[offset..offset + 3], pixel[..3]);
+                assert_eq!(screenshot.data[offset + 3], 255);
+            }
+        }
+        assert!(renderer.poll_screenshots().is_empty());
+        assert!(gl.mock().errors().is_empty());
+    }
+
+    #[test]
+    fn exposes_gpu_timings_per_region() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut renderer = renderer(&context);
+        let scene = scene();
+        renderer.render_frame(&scene, &eyes());
+        assert_eq!(renderer.gpu_timings(), None);
+        renderer.render_frame(&scene, &eyes());
+        let timings = renderer.gpu_timings().unwrap();
+        assert_eq!(timings.frame_index, 0);
+        assert_eq!(
+            timings
+                .regions
+                .iter()
+                .map(|region| region.name)
+                .collect::<Vec<_>>(),
+            ["shadow", "left eye", "right eye"]
+        );
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn invalidates_depth_after_drawing_each_eye() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut renderer = renderer(&context);
+        let scene = scene();
+        gl.clear_calls();
+        renderer.render_frame(&scene, &eyes());
+
+        let calls = gl.calls();
+        let draws: Vec<usize> = calls
+            .iter()
+            .enumerate()
+            .filter(|(_, call)| call.function == "glDrawElementsInstanced")
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(draws.len(), 3);
+        let depth_invalidations: Vec<usize> = calls
+            .iter()
+            .enumerate()
+            .filter(|(_, call)| {
+                call.function == "glInvalidateFramebuffer"
+                    && call.enums.contains(&GL_DEPTH_ATTACHMENT)
+            })
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(depth_invalidations.len(), 2);
+        for (draw, invalidation) in draws[1..].iter().zip(depth_invalidations.iter()) {
+            assert!(draw < invalidation);
+        }
+        assert!(gl.errors().is_empty());
+    }
+
+    fn used_programs(gl: &MockGl) -> HashSet<i64> {
+        gl.calls()
+            .iter()
+            .filter(|call| call.function == "glUseProgram")
+            .map(|call| call.argument(0).int())
+            .collect()
+    }
+
+    #[test]
+    fn rebinds_state_after_context_is_invalidated() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut renderer = renderer(&context);
+        let scene = scene();
+        renderer.render_frame(&scene, &eyes());
+        unsafe {
+            gl_check!(glUseProgram(0));
+            gl_check!(glBindVertexArray(0));
+        }
+        renderer.context().state().invalidate();
+        gl.clear_calls();
+        renderer.render_frame(&scene, &eyes());
+        assert!(!used_programs(&gl).is_empty());
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn recompiles_programs_that_include_changed_shaders() {
+        let directory = env::temp_dir().join(format!("rustquest-renderer-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let write = |name: &str, source: &str, seconds: u64| {
+            let path = directory.join(name);
+            fs::write(&path, source).unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+                .unwrap();
+        };
+        for (name, source) in SHADERS.iter() {
+            write(name, source, 1000);
+        }
+
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut renderer = renderer(&context);
+        renderer.shader_library.set_watch_directory(&directory);
+        let scene = scene();
+        renderer.render_frame(&scene, &eyes());
+        let programs = used_programs(&gl);
+
+        let lighting = SHADERS[0].1;
+        write("lighting.glsl", &format!("{}\n", lighting), 2000);
+        gl.clear_calls();
+        renderer.reload_shaders();
+        let linked = gl
+            .calls()
+            .iter()
+            .filter(|call| call.function == "glLinkProgram")
+            .count();
+        gl.clear_calls();
+        renderer.render_frame(&scene, &eyes());
+        let reloaded = used_programs(&gl);
+        assert_eq!(reloaded.len(), programs.len());
+        assert_eq!(reloaded.difference(&programs).count(), linked);
+        assert_eq!(reloaded.intersection(&programs).count(), 1);
+        assert!(gl.errors().is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn advances_swap_chains() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut renderer = renderer(&context);
+        let scene = scene();
+        renderer.render_frame(&scene, &eyes());
+        for swap_chain in renderer.swap_chains().iter() {
+            assert_eq!(swap_chain.index(), 1);
+        }
+    }
+
+    #[test]
+    fn binds_material_textures_before_drawing() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut renderer = renderer(&context);
+        let image = Image {
+            width: 2,
+            height: 2,
+            data: vec![255; 16],
+        };
+        let texture = Texture::from_image(&context, &image, true, false);
+        let texture_name = texture.texture();
+        renderer.set_textures(MaterialTextures::new(
+            &context,
+            vec![gltf::Texture {
+                source: Some(0),
+                sampler: Some(0),
+            }],
+            vec![texture],
+            vec![Sampler::new(&context, SamplerState::default())],
+        ));
+        let texture_ref = Some(TextureRef {
+            texture: 0,
+            tex_coord: 0,
+        });
+        renderer.set_materials(vec![Material {
+            base_color_texture: texture_ref,
+            emissive_texture: texture_ref,
+            ..Material::default()
+        }]);
+        gl.clear_calls();
+        renderer.render_frame(&scene(), &eyes());
+
+        let calls = gl.calls();
+        let eye_draw = calls
+            .iter()
+            .enumerate()
+            .filter(|(_, call)| call.function == "glDrawElementsInstanced")
+            .map(|(index, _)| index)
+            .nth(1)
+            .unwrap();
+        for unit in [BASE_COLOR_TEXTURE_UNIT, EMISSIVE_TEXTURE_UNIT].iter() {
+            assert!(calls[..eye_draw].iter().any(|call| {
+                call.function == "glBindSampler" && call.argument(0).int() == *unit as i64
+            }));
+        }
+        let state = gl.state();
+        assert_eq!(
+            state.textures.get(&(EMISSIVE_TEXTURE_UNIT, GL_TEXTURE_2D)),
+            Some(&texture_name)
+        );
+        assert_eq!(
+            state
+                .textures
+                .get(&(BASE_COLOR_TEXTURE_UNIT, GL_TEXTURE_2D)),
+            Some(&texture_name)
+        );
+        let sampler = state.samplers.get(&BASE_COLOR_TEXTURE_UNIT).cloned();
+        assert!(sampler.is_some_and(|sampler| sampler != 0));
+        assert_eq!(state.samplers.get(&EMISSIVE_TEXTURE_UNIT).cloned(), sampler);
+        drop(state);
+        assert!(gl.errors().is_empty());
+    }
+}
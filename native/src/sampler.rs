@@ -0,0 +1,139 @@
+use crate::gl;
+use crate::gl::Context;
+use libGLESv3_sys::{
+    GLenum, GLint, GLuint, GL_CLAMP_TO_EDGE, GL_COMPARE_REF_TO_TEXTURE, GL_LINEAR,
+    GL_LINEAR_MIPMAP_LINEAR, GL_REPEAT, GL_TEXTURE_COMPARE_FUNC, GL_TEXTURE_COMPARE_MODE,
+    GL_TEXTURE_MAG_FILTER, GL_TEXTURE_MIN_FILTER, GL_TEXTURE_WRAP_R, GL_TEXTURE_WRAP_S,
+    GL_TEXTURE_WRAP_T,
+};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SamplerState {
+    pub min_filter: GLenum,
+    pub mag_filter: GLenum,
+    pub wrap_s: GLenum,
+    pub wrap_t: GLenum,
+    pub wrap_r: GLenum,
+    pub compare_func: Option<GLenum>,
+}
+
+impl SamplerState {
+    pub const CLAMP: SamplerState = SamplerState {
+        min_filter: GL_LINEAR,
+        mag_filter: GL_LINEAR,
+        wrap_s: GL_CLAMP_TO_EDGE,
+        wrap_t: GL_CLAMP_TO_EDGE,
+        wrap_r: GL_CLAMP_TO_EDGE,
+        compare_func: None,
+    };
+}
+
+impl Default for SamplerState {
+    fn default() -> SamplerState {
+        SamplerState {
+            min_filter: GL_LINEAR_MIPMAP_LINEAR,
+            mag_filter: GL_LINEAR,
+            wrap_s: GL_REPEAT,
+            wrap_t: GL_REPEAT,
+            wrap_r: GL_REPEAT,
+            compare_func: None,
+        }
+    }
+}
+
+pub struct Sampler {
+    sampler: gl::Sampler,
+    state: SamplerState,
+}
+
+impl Sampler {
+    pub fn new(context: &Context, state: SamplerState) -> Sampler {
+        unsafe {
+            let handle = gl::Sampler::new(context);
+            let sampler = handle.name();
+            for (name, value) in [
+                (GL_TEXTURE_MIN_FILTER, state.min_filter),
+                (GL_TEXTURE_MAG_FILTER, state.mag_filter),
+                (GL_TEXTURE_WRAP_S, state.wrap_s),
+                (GL_TEXTURE_WRAP_T, state.wrap_t),
+                (GL_TEXTURE_WRAP_R, state.wrap_r),
+            ]
+            .iter()
+            .cloned()
+            {
+                gl_check!(glSamplerParameteri(sampler, name, value as GLint));
+            }
+            if let Some(compare_func) = state.compare_func {
+                gl_check!(glSamplerParameteri(
+                    sampler,
+                    GL_TEXTURE_COMPARE_MODE,
+                    GL_COMPARE_REF_TO_TEXTURE as GLint,
+                ));
+                gl_check!(glSamplerParameteri(
+                    sampler,
+                    GL_TEXTURE_COMPARE_FUNC,
+                    compare_func as GLint,
+                ));
+            }
+            Sampler {
+                sampler: handle,
+                state,
+            }
+        }
+    }
+
+    pub fn state(&self) -> SamplerState {
+        self.state
+    }
+
+    pub fn bind(&self, unit: GLuint) {
+        self.sampler
+            .context()
+            .state()
+            .bind_sampler(unit, self.sampler.name());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_gl::MockGl;
+    use libGLESv3_sys::{GL_LEQUAL, GL_NEAREST};
+    use std::rc::Rc;
+
+    #[test]
+    fn sets_parameters_and_binds_to_units() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let state = SamplerState {
+            mag_filter: GL_NEAREST,
+            compare_func: Some(GL_LEQUAL),
+            ..SamplerState::CLAMP
+        };
+        let sampler = Sampler::new(&context, state);
+        assert_eq!(sampler.state(), state);
+        let parameters: Vec<(GLenum, i64)> = gl
+            .calls()
+            .iter()
+            .filter(|call| call.function == "glSamplerParameteri")
+            .map(|call| (call.argument(1).int() as GLenum, call.argument(2).int()))
+            .collect();
+        assert_eq!(
+            parameters,
+            [
+                (GL_TEXTURE_MIN_FILTER, GL_LINEAR as i64),
+                (GL_TEXTURE_MAG_FILTER, GL_NEAREST as i64),
+                (GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE as i64),
+                (GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE as i64),
+                (GL_TEXTURE_WRAP_R, GL_CLAMP_TO_EDGE as i64),
+                (GL_TEXTURE_COMPARE_MODE, GL_COMPARE_REF_TO_TEXTURE as i64),
+                (GL_TEXTURE_COMPARE_FUNC, GL_LEQUAL as i64),
+            ]
+        );
+
+        sampler.bind(6);
+        let bound = gl.state().samplers[&6];
+        assert_eq!(bound, sampler.sampler.name());
+        assert!(gl.errors().is_empty());
+    }
+}
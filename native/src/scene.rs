@@ -0,0 +1,416 @@
+use crate::math::{Mat4, Transform};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Drawable {
+    pub mesh: usize,
+    pub material: Option<usize>,
+}
+
+pub struct Scene {
+    slots: Vec<Slot>,
+    free_list: Vec<u32>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene {
+            slots: Vec::new(),
+            free_list: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+
+    pub fn create_node(&mut self, name: &str) -> NodeId {
+        let node = Node {
+            name: String::from(name),
+            parent: None,
+            children: Vec::new(),
+            transform: Transform::IDENTITY,
+            world_matrix: Mat4::IDENTITY,
+            dirty: true,
+            drawable: None,
+        };
+        let id = match self.free_list.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+        self.roots.push(id);
+        id
+    }
+
+    pub fn remove_node(&mut self, id: NodeId) {
+        self.detach(id);
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let slot = &mut self.slots[id.index as usize];
+            let node = slot.node.take().unwrap();
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free_list.push(id.index);
+            stack.extend(node.children);
+        }
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.slots
+            .get(id.index as usize)
+            .is_some_and(|slot| slot.generation == id.generation && slot.node.is_some())
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free_list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.node(id).name
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.slots
+            .iter()
+            .enumerate()
+            .find_map(|(index, slot)| match slot.node {
+                Some(ref node) if node.name == name => Some(NodeId {
+                    index: index as u32,
+                    generation: slot.generation,
+                }),
+                _ => None,
+            })
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.node(id).children
+    }
+
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), String> {
+        if let Some(parent) = parent {
+            let mut ancestor = Some(parent);
+            while let Some(node) = ancestor {
+                if node == id {
+                    return Err(format!(
+                        "can't make {} a child of its descendant {}",
+                        self.name(id),
+                        self.name(parent)
+                    ));
+                }
+                ancestor = self.parent(node);
+            }
+        }
+        self.detach(id);
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        let node = self.node_mut(id);
+        node.parent = parent;
+        node.dirty = true;
+        Ok(())
+    }
+
+    pub fn transform(&self, id: NodeId) -> &Transform {
+        &self.node(id).transform
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) {
+        let node = self.node_mut(id);
+        node.transform = transform;
+        node.dirty = true;
+    }
+
+    pub fn drawable(&self, id: NodeId) -> Option<Drawable> {
+        self.node(id).drawable
+    }
+
+    pub fn set_drawable(&mut self, id: NodeId, drawable: Option<Drawable>) {
+        self.node_mut(id).drawable = drawable;
+    }
+
+    pub fn world_matrix(&self, id: NodeId) -> &Mat4 {
+        let node = self.node(id);
+        debug_assert!(!node.dirty, "world matrix of {} is out of date", node.name);
+        &node.world_matrix
+    }
+
+    pub fn update(&mut self) {
+        let mut stack: Vec<(NodeId, Mat4, bool)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|root| (*root, Mat4::IDENTITY, false))
+            .collect();
+        while let Some((id, parent_matrix, parent_dirty)) = stack.pop() {
+            let node = self.node_mut(id);
+            let dirty = parent_dirty || node.dirty;
+            if dirty {
+                node.world_matrix = parent_matrix * node.transform.matrix();
+                node.dirty = false;
+            }
+            let world_matrix = node.world_matrix;
+            stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .map(|child| (*child, world_matrix, dirty)),
+            );
+        }
+    }
+
+    pub fn visit_drawables<F: FnMut(NodeId, &Mat4, Drawable)>(&self, mut f: F) {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().cloned().collect();
+        while let Some(id) = stack.pop() {
+            let node = self.node(id);
+            if let Some(drawable) = node.drawable {
+                f(id, self.world_matrix(id), drawable);
+            }
+            stack.extend(node.children.iter().rev());
+        }
+    }
+
+    fn detach(&mut self, id: NodeId) {
+        match self.node(id).parent {
+            Some(parent) => self.node_mut(parent).children.retain(|child| *child != id),
+            None => self.roots.retain(|root| *root != id),
+        }
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        match self.slots.get(id.index as usize) {
+            Some(Slot {
+                generation,
+                node: Some(node),
+            }) if *generation == id.generation => node,
+            _ => panic!("invalid node {:?}", id),
+        }
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        match self.slots.get_mut(id.index as usize) {
+            Some(Slot {
+                generation,
+                node: Some(node),
+            }) if *generation == id.generation => node,
+            _ => panic!("invalid node {:?}", id),
+        }
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Scene {
+        Scene::new()
+    }
+}
+
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
+
+struct Node {
+    name: String,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    transform: Transform,
+    world_matrix: Mat4,
+    dirty: bool,
+    drawable: Option<Drawable>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Quat, Vec3};
+    use std::f32::consts::FRAC_PI_2;
+
+    fn translation(x: f32, y: f32, z: f32) -> Transform {
+        Transform {
+            translation: Vec3::new(x, y, z),
+            ..Transform::IDENTITY
+        }
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    fn origin(scene: &Scene, id: NodeId) -> Vec3 {
+        scene.world_matrix(id).transform_point(Vec3::ZERO)
+    }
+
+    #[test]
+    fn propagates_transforms_to_children() {
+        let mut scene = Scene::new();
+        let parent = scene.create_node("parent");
+        let child = scene.create_node("child");
+        scene.set_parent(child, Some(parent)).unwrap();
+        scene.set_transform(
+            parent,
+            Transform {
+                translation: Vec3::new(1.0, 0.0, 0.0),
+                rotation: Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), FRAC_PI_2),
+                scale: Vec3::new(2.0, 2.0, 2.0),
+            },
+        );
+        scene.set_transform(child, translation(1.0, 0.0, 0.0));
+        scene.update();
+        assert_near(origin(&scene, parent), Vec3::new(1.0, 0.0, 0.0));
+        assert_near(origin(&scene, child), Vec3::new(1.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn updates_descendants_of_dirty_nodes() {
+        let mut scene = Scene::new();
+        let a = scene.create_node("a");
+        let b = scene.create_node("b");
+        let c = scene.create_node("c");
+        scene.set_parent(b, Some(a)).unwrap();
+        scene.set_parent(c, Some(b)).unwrap();
+        scene.set_transform(b, translation(0.0, 1.0, 0.0));
+        scene.set_transform(c, translation(0.0, 0.0, 1.0));
+        scene.update();
+        assert_near(origin(&scene, c), Vec3::new(0.0, 1.0, 1.0));
+
+        scene.set_transform(a, translation(5.0, 0.0, 0.0));
+        scene.update();
+        assert_near(origin(&scene, b), Vec3::new(5.0, 1.0, 0.0));
+        assert_near(origin(&scene, c), Vec3::new(5.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn reparenting_keeps_local_transform() {
+        let mut scene = Scene::new();
+        let a = scene.create_node("a");
+        let b = scene.create_node("b");
+        let child = scene.create_node("child");
+        scene.set_transform(a, translation(1.0, 0.0, 0.0));
+        scene.set_transform(b, translation(0.0, 1.0, 0.0));
+        scene.set_transform(child, translation(0.0, 0.0, 1.0));
+        scene.set_parent(child, Some(a)).unwrap();
+        scene.update();
+        assert_near(origin(&scene, child), Vec3::new(1.0, 0.0, 1.0));
+
+        scene.set_parent(child, Some(b)).unwrap();
+        scene.update();
+        assert_near(origin(&scene, child), Vec3::new(0.0, 1.0, 1.0));
+        assert!(scene.children(a).is_empty());
+        assert_eq!(scene.children(b), &[child]);
+
+        scene.set_parent(child, None).unwrap();
+        scene.update();
+        assert_near(origin(&scene, child), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(scene.transform(child), &translation(0.0, 0.0, 1.0));
+        assert_eq!(scene.roots(), &[a, b, child]);
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let mut scene = Scene::new();
+        let a = scene.create_node("a");
+        let b = scene.create_node("b");
+        let c = scene.create_node("c");
+        scene.set_parent(b, Some(a)).unwrap();
+        scene.set_parent(c, Some(b)).unwrap();
+        assert!(scene.set_parent(a, Some(c)).is_err());
+        assert!(scene.set_parent(a, Some(a)).is_err());
+        assert_eq!(scene.parent(a), None);
+        assert_eq!(scene.roots(), &[a]);
+    }
+
+    #[test]
+    fn removes_subtrees() {
+        let mut scene = Scene::new();
+        let a = scene.create_node("a");
+        let b = scene.create_node("b");
+        let c = scene.create_node("c");
+        let d = scene.create_node("d");
+        scene.set_parent(b, Some(a)).unwrap();
+        scene.set_parent(c, Some(b)).unwrap();
+        scene.set_parent(d, Some(a)).unwrap();
+        scene.remove_node(b);
+        assert!(!scene.contains(b));
+        assert!(!scene.contains(c));
+        assert!(scene.contains(d));
+        assert_eq!(scene.children(a), &[d]);
+        assert_eq!(scene.len(), 2);
+
+        let e = scene.create_node("e");
+        assert!(scene.contains(e));
+        assert!(!scene.contains(b) && !scene.contains(c));
+        assert_eq!(scene.find("e"), Some(e));
+        assert_eq!(scene.find("b"), None);
+
+        scene.remove_node(a);
+        scene.remove_node(e);
+        assert!(scene.is_empty());
+    }
+
+    #[test]
+    fn visits_drawables_in_hierarchy_order() {
+        let mut scene = Scene::new();
+        let root = scene.create_node("root");
+        let first = scene.create_node("first");
+        let second = scene.create_node("second");
+        scene.set_parent(second, Some(root)).unwrap();
+        scene.set_parent(first, Some(root)).unwrap();
+        scene.set_transform(root, translation(0.0, 2.0, 0.0));
+        scene.set_drawable(
+            first,
+            Some(Drawable {
+                mesh: 1,
+                material: None,
+            }),
+        );
+        scene.set_drawable(
+            second,
+            Some(Drawable {
+                mesh: 0,
+                material: Some(3),
+            }),
+        );
+        scene.update();
+        let mut visited = Vec::new();
+        scene.visit_drawables(|id, world_matrix, drawable| {
+            visited.push((id, drawable.mesh));
+            assert_near(
+                world_matrix.transform_point(Vec3::ZERO),
+                Vec3::new(0.0, 2.0, 0.0),
+            );
+        });
+        assert_eq!(visited, vec![(second, 0), (first, 1)]);
+        assert_eq!(scene.drawable(root), None);
+        assert_eq!(scene.drawable(first).map(|drawable| drawable.mesh), Some(1));
+    }
+}
@@ -0,0 +1,252 @@
+use crate::gl;
+use crate::image::Image;
+use crate::render_target::RenderTarget;
+use libGLESv3_sys::{
+    GLsizei, GLsizeiptr, GLsync, GL_ALREADY_SIGNALED, GL_CONDITION_SATISFIED, GL_MAP_READ_BIT,
+    GL_PIXEL_PACK_BUFFER, GL_RGBA, GL_STREAM_READ, GL_SYNC_FLUSH_COMMANDS_BIT,
+    GL_SYNC_GPU_COMMANDS_COMPLETE, GL_UNSIGNED_BYTE, GL_WAIT_FAILED,
+};
+use std::collections::VecDeque;
+use std::mem;
+use std::ptr;
+use std::slice;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CaptureEyes {
+    Left,
+    Right,
+    Both,
+}
+
+impl CaptureEyes {
+    pub fn includes(self, eye: usize) -> bool {
+        match self {
+            CaptureEyes::Left => eye == 0,
+            CaptureEyes::Right => eye == 1,
+            CaptureEyes::Both => true,
+        }
+    }
+}
+
+struct Readback {
+    buffer: gl::Buffer,
+    width: GLsizei,
+    height: GLsizei,
+}
+
+impl Readback {
+    fn new(target: &RenderTarget) -> Readback {
+        let (width, height) = (target.width(), target.height());
+        let buffer = gl::Buffer::new(target.context());
+        unsafe {
+            gl_check!(glBindBuffer(GL_PIXEL_PACK_BUFFER, buffer.name()));
+            gl_check!(glBufferData(
+                GL_PIXEL_PACK_BUFFER,
+                (width * height * 4) as GLsizeiptr,
+                ptr::null(),
+                GL_STREAM_READ,
+            ));
+            target.bind_read();
+            gl_check!(glReadPixels(
+                0,
+                0,
+                width,
+                height,
+                GL_RGBA,
+                GL_UNSIGNED_BYTE,
+                ptr::null_mut(),
+            ));
+            target.context().state().bind_read_framebuffer(0);
+            gl_check!(glBindBuffer(GL_PIXEL_PACK_BUFFER, 0));
+        }
+        Readback {
+            buffer,
+            width,
+            height,
+        }
+    }
+
+    fn read(&self) -> Image {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let size = width * height * 4;
+        unsafe {
+            gl_check!(glBindBuffer(GL_PIXEL_PACK_BUFFER, self.buffer.name()));
+            let pixels = gl_check!(glMapBufferRange(
+                GL_PIXEL_PACK_BUFFER,
+                0,
+                size as GLsizeiptr,
+                GL_MAP_READ_BIT,
+            )) as *const u8;
+            if pixels.is_null() {
+                panic!("can't map screenshot buffer");
+            }
+            let image = from_bottom_up(width, height, slice::from_raw_parts(pixels, size));
+            gl_check!(glUnmapBuffer(GL_PIXEL_PACK_BUFFER));
+            gl_check!(glBindBuffer(GL_PIXEL_PACK_BUFFER, 0));
+            image
+        }
+    }
+}
+
+struct PendingCapture {
+    readbacks: Vec<Readback>,
+    fence: GLsync,
+}
+
+pub struct ScreenshotCapture {
+    requested: Option<CaptureEyes>,
+    readbacks: Vec<Readback>,
+    pending: VecDeque<PendingCapture>,
+}
+
+impl ScreenshotCapture {
+    pub fn new() -> ScreenshotCapture {
+        ScreenshotCapture {
+            requested: None,
+            readbacks: Vec::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn request(&mut self, eyes: CaptureEyes) {
+        logi!("request screenshot of {:?}", eyes);
+        self.requested = Some(eyes);
+    }
+
+    pub fn wants(&self, eye: usize) -> bool {
+        self.requested.is_some_and(|eyes| eyes.includes(eye))
+    }
+
+    pub fn read_eye(&mut self, target: &RenderTarget) {
+        logv!("read back screenshot eye {}", self.readbacks.len());
+        self.readbacks.push(Readback::new(target));
+    }
+
+    pub fn end_frame(&mut self) {
+        if self.requested.take().is_none() || self.readbacks.is_empty() {
+            return;
+        }
+        let fence = unsafe { gl_check!(glFenceSync(GL_SYNC_GPU_COMMANDS_COMPLETE, 0)) };
+        self.pending.push_back(PendingCapture {
+            readbacks: mem::take(&mut self.readbacks),
+            fence,
+        });
+    }
+
+    pub fn poll(&mut self) -> Vec<Image> {
+        let mut images = Vec::new();
+        while let Some(capture) = self.pending.front() {
+            unsafe {
+                match gl_check!(glClientWaitSync(
+                    capture.fence,
+                    GL_SYNC_FLUSH_COMMANDS_BIT,
+                    0
+                )) {
+                    GL_ALREADY_SIGNALED | GL_CONDITION_SATISFIED => {}
+                    GL_WAIT_FAILED => panic!("can't wait for screenshot fence"),
+                    _ => break,
+                }
+                gl_check!(glDeleteSync(capture.fence));
+            }
+            let capture = self.pending.pop_front().unwrap();
+            let eyes: Vec<Image> = capture
+                .readbacks
+                .iter()
+                .map(|readback| readback.read())
+                .collect();
+            images.push(compose_side_by_side(&eyes));
+        }
+        images
+    }
+}
+
+impl Default for ScreenshotCapture {
+    fn default() -> ScreenshotCapture {
+        ScreenshotCapture::new()
+    }
+}
+
+impl Drop for ScreenshotCapture {
+    fn drop(&mut self) {
+        unsafe {
+            for capture in self.pending.iter() {
+                gl_check!(glDeleteSync(capture.fence));
+            }
+        }
+    }
+}
+
+fn from_bottom_up(width: usize, height: usize, pixels: &[u8]) -> Image {
+    let mut data = Vec::with_capacity(width * height * 4);
+    for row in pixels.chunks_exact(width * 4).take(height).rev() {
+        for pixel in row.chunks_exact(4) {
+            data.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+        }
+    }
+    Image {
+        width,
+        height,
+        data,
+    }
+}
+
+pub fn compose_side_by_side(images: &[Image]) -> Image {
+    let width = images.iter().map(|image| image.width).sum();
+    let height = images.iter().map(|image| image.height).max().unwrap_or(0);
+    let mut data = vec![0; width * height * 4];
+    let mut left = 0;
+    for image in images.iter() {
+        for (y, row) in image.data.chunks_exact(image.width * 4).enumerate() {
+            let start = (y * width + left) * 4;
+            data[start..start + row.len()].copy_from_slice(row);
+        }
+        left += image.width;
+    }
+    Image {
+        width,
+        height,
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: usize, height: usize, value: u8) -> Image {
+        Image {
+            width,
+            height,
+            data: (0..width * height)
+                .flat_map(|index| vec![value, index as u8, 0, 255])
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn composes_side_by_side() {
+        let image = compose_side_by_side(&[image(2, 2, 1), image(1, 1, 2)]);
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(
+            image.data,
+            vec![
+                1, 0, 0, 255, 1, 1, 0, 255, 2, 0, 0, 255, //
+                1, 2, 0, 255, 1, 3, 0, 255, 0, 0, 0, 0,
+            ]
+        );
+    }
+
+    #[test]
+    fn selects_captured_eyes() {
+        assert!(CaptureEyes::Left.includes(0) && !CaptureEyes::Left.includes(1));
+        assert!(!CaptureEyes::Right.includes(0) && CaptureEyes::Right.includes(1));
+        assert!(CaptureEyes::Both.includes(0) && CaptureEyes::Both.includes(1));
+    }
+
+    #[test]
+    fn flips_rows_and_makes_opaque() {
+        let pixels = [1, 2, 3, 0, 4, 5, 6, 0];
+        let image = from_bottom_up(1, 2, &pixels);
+        assert_eq!(image.data, vec![4, 5, 6, 255, 1, 2, 3, 255]);
+    }
+}
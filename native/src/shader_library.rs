@@ -0,0 +1,303 @@
+use crate::gl::Context;
+use crate::preprocessor;
+use crate::preprocessor::VariantKey;
+use crate::program::Program;
+use crate::program_cache::ProgramCache;
+use crate::shader_watcher;
+use crate::shader_watcher::ShaderWatcher;
+use archive::Archive;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::Path;
+use std::time::Instant;
+
+pub struct ShaderLibrary {
+    context: Context,
+    sources: HashMap<String, String>,
+    variants: HashMap<VariantKey, Variant>,
+    program_cache: Option<ProgramCache>,
+    watcher: Option<ShaderWatcher>,
+}
+
+impl ShaderLibrary {
+    pub fn new(context: &Context) -> ShaderLibrary {
+        ShaderLibrary {
+            context: context.clone(),
+            sources: HashMap::new(),
+            variants: HashMap::new(),
+            program_cache: None,
+            watcher: None,
+        }
+    }
+
+    pub fn add_sources_from_archive<B: Deref<Target = [u8]>>(
+        &mut self,
+        archive: &Archive<B>,
+        directory: &str,
+    ) {
+        let prefix = format!("{}/", directory);
+        for entry in archive.entries() {
+            if !entry.name.starts_with(&prefix) {
+                continue;
+            }
+            let name = &entry.name[prefix.len()..];
+            match archive.read_to_string(&entry.name) {
+                Ok(source) => {
+                    logv!("load shader source {}", name);
+                    self.add_source(name, &source);
+                }
+                Err(error) => loge!("can't load shader source {}: {}", name, error),
+            }
+        }
+    }
+
+    pub fn add_source(&mut self, name: &str, source: &str) {
+        self.sources
+            .insert(String::from(name), String::from(source));
+    }
+
+    pub fn set_program_cache(&mut self, program_cache: ProgramCache) {
+        self.program_cache = Some(program_cache);
+    }
+
+    pub fn set_watch_directory(&mut self, directory: &Path) {
+        logi!("watch shader directory {}", directory.display());
+        let watcher = ShaderWatcher::new(directory);
+        for name in watcher.files() {
+            if let Some(source) = watcher.read(&name) {
+                logv!("load shader source {}", name);
+                self.add_source(&name, &source);
+            }
+        }
+        self.watcher = Some(watcher);
+    }
+
+    pub fn program(&mut self, name: &str, defines: &[&str]) -> &Program {
+        let key = VariantKey::new(name, defines)
+            .unwrap_or_else(|error| panic!("can't create program {}: {}", name, error));
+        if !self.variants.contains_key(&key) {
+            logi!("create program {}", key);
+            let (vertex_source, fragment_source, files) = self
+                .preprocess(&key)
+                .unwrap_or_else(|error| panic!("can't preprocess program {}: {}", key, error));
+            let program = match self.program_cache.as_mut() {
+                Some(program_cache) => Program::from_sources_cached(
+                    &self.context,
+                    program_cache,
+                    &vertex_source,
+                    &fragment_source,
+                ),
+                None => Program::from_sources(&self.context, &vertex_source, &fragment_source),
+            };
+            self.variants
+                .insert(key.clone(), Variant { program, files });
+        }
+        &self.variants[&key].program
+    }
+
+    pub fn reload(&mut self) {
+        let changed_files = match self.watcher.as_mut() {
+            Some(watcher) => watcher.poll(Instant::now()),
+            None => return,
+        };
+        if changed_files.is_empty() {
+            return;
+        }
+
+        for name in changed_files.iter() {
+            match self.watcher.as_ref().unwrap().read(name) {
+                Some(source) => {
+                    logi!("reload shader source {}", name);
+                    self.add_source(name, &source);
+                }
+                None => logi!("keep last shader source {}", name),
+            }
+        }
+
+        let keys: Vec<VariantKey> = self
+            .variants
+            .iter()
+            .filter(|(_, variant)| shader_watcher::needs_reload(&variant.files, &changed_files))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            logi!("recompile program {}", key);
+            let result =
+                self.preprocess(&key)
+                    .and_then(|(vertex_source, fragment_source, files)| {
+                        Program::try_from_sources(&self.context, &vertex_source, &fragment_source)
+                            .map(|program| Variant { program, files })
+                    });
+            match result {
+                Ok(variant) => {
+                    self.variants.insert(key, variant);
+                }
+                Err(error) => loge!("can't reload program {}: {}", key, error),
+            }
+        }
+    }
+
+    fn preprocess(&self, key: &VariantKey) -> Result<(String, String, Vec<String>), String> {
+        let load = |name: &str| self.sources.get(name).cloned();
+        let vertex =
+            preprocessor::preprocess(&format!("{}.vert", key.program()), key.defines(), load)?;
+        let fragment =
+            preprocessor::preprocess(&format!("{}.frag", key.program()), key.defines(), load)?;
+        let mut files = vertex.files;
+        for file in fragment.files {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+        Ok((vertex.source, fragment.source, files))
+    }
+}
+
+struct Variant {
+    program: Program,
+    files: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_gl::{MockGl, PROGRAM_BINARY, PROGRAM_BINARY_FORMAT};
+    use crate::program_cache::{self, ProgramBinary};
+    use archive::{ArchiveWriter, Compression};
+    use std::env;
+    use std::fs;
+    use std::fs::File;
+    use std::process;
+    use std::rc::Rc;
+    use std::time::{Duration, SystemTime};
+
+    const VERTEX_SOURCE: &str = "#version 300 es\nvoid main() {\n    gl_Position = vec4(0.0);\n}\n";
+    const FRAGMENT_SOURCE: &str = "#version 300 es\nprecision mediump float;\nout vec4 color;\nvoid main() {\n    color = vec4(1.0);\n}\n";
+
+    #[test]
+    fn reuses_programs_for_equal_variant_keys() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut shader_library = ShaderLibrary::new(&context);
+        shader_library.add_source("test.vert", VERTEX_SOURCE);
+        shader_library.add_source("test.frag", FRAGMENT_SOURCE);
+
+        let program = shader_library.program("test", &["A", "B"]).program();
+        assert_eq!(
+            shader_library.program("test", &["B", "A", "A"]).program(),
+            program
+        );
+        assert_ne!(shader_library.program("test", &["A"]).program(), program);
+        assert_eq!(shader_library.variants.len(), 2);
+        let created = gl
+            .calls()
+            .iter()
+            .filter(|call| call.function == "glCreateProgram")
+            .count();
+        assert_eq!(created, 2);
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn rebuilds_programs_whose_cached_binary_is_rejected() {
+        let path =
+            env::temp_dir().join(format!("rustquest-program-binaries-{}.bin", process::id()));
+        let build = || {
+            let gl = Rc::new(MockGl::new());
+            let context = Context::with_backend(gl.clone());
+            let mut shader_library = ShaderLibrary::new(&context);
+            shader_library.add_source("test.vert", VERTEX_SOURCE);
+            shader_library.add_source("test.frag", FRAGMENT_SOURCE);
+            shader_library.set_program_cache(ProgramCache::open(&path, "mock"));
+            shader_library.program("test", &[]);
+            assert!(gl.errors().is_empty());
+            gl.functions()
+        };
+        let read_binaries = || program_cache::decode(&fs::read(&path).unwrap()).unwrap();
+
+        let functions = build();
+        assert!(functions.contains(&"glLinkProgram"));
+        assert!(functions.contains(&"glGetProgramBinary"));
+        let functions = build();
+        assert!(functions.contains(&"glProgramBinary"));
+        assert!(!functions.contains(&"glLinkProgram"));
+
+        let mut binaries = read_binaries();
+        for binary in binaries.values_mut() {
+            binary.format += 1;
+        }
+        fs::write(&path, program_cache::encode(&binaries)).unwrap();
+        let functions = build();
+        let load = functions
+            .iter()
+            .position(|function| *function == "glProgramBinary")
+            .unwrap();
+        assert!(functions[load..].contains(&"glLinkProgram"));
+        let binaries = read_binaries();
+        assert_eq!(
+            binaries.values().collect::<Vec<_>>(),
+            [&ProgramBinary {
+                format: PROGRAM_BINARY_FORMAT,
+                data: PROGRAM_BINARY.to_vec(),
+            }]
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn loads_sources_from_archive_directory() {
+        let mut writer = ArchiveWriter::new();
+        writer.add(
+            "shaders/test.vert",
+            VERTEX_SOURCE.as_bytes(),
+            Compression::Lz4,
+        );
+        writer.add(
+            "shaders/test.frag",
+            FRAGMENT_SOURCE.as_bytes(),
+            Compression::None,
+        );
+        writer.add("models/test.vert", b"", Compression::None);
+        let archive = Archive::new(writer.finish()).unwrap();
+
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut shader_library = ShaderLibrary::new(&context);
+        shader_library.add_sources_from_archive(&archive, "shaders");
+        assert_eq!(shader_library.sources.len(), 2);
+        assert_eq!(shader_library.sources["test.vert"], VERTEX_SOURCE);
+        shader_library.program("test", &[]);
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn recompiles_programs_when_watched_sources_change() {
+        let directory = env::temp_dir().join(format!("rustquest-shader-library-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let write = |name: &str, source: &str, seconds: u64| {
+            let path = directory.join(name);
+            fs::write(&path, source).unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+                .unwrap();
+        };
+        write("test.vert", VERTEX_SOURCE, 1000);
+        write("test.frag", FRAGMENT_SOURCE, 1000);
+
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut shader_library = ShaderLibrary::new(&context);
+        shader_library.set_watch_directory(&directory);
+        let program = shader_library.program("test", &[]).program();
+
+        write("test.frag", &FRAGMENT_SOURCE.replace("1.0", "0.5"), 2000);
+        shader_library.reload();
+        assert_ne!(shader_library.program("test", &[]).program(), program);
+        assert!(shader_library.sources["test.frag"].contains("0.5"));
+        assert!(gl.errors().is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
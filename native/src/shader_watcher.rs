@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct ShaderWatcher {
+    directory: PathBuf,
+    modified_times: HashMap<String, SystemTime>,
+    last_poll: Option<Instant>,
+}
+
+impl ShaderWatcher {
+    pub fn new(directory: &Path) -> ShaderWatcher {
+        let mut watcher = ShaderWatcher {
+            directory: PathBuf::from(directory),
+            modified_times: HashMap::new(),
+            last_poll: None,
+        };
+        watcher.scan();
+        watcher
+    }
+
+    pub fn files(&self) -> Vec<String> {
+        let mut files: Vec<String> = self.modified_times.keys().cloned().collect();
+        files.sort();
+        files
+    }
+
+    pub fn read(&self, name: &str) -> Option<String> {
+        fs::read_to_string(self.directory.join(name)).ok()
+    }
+
+    pub fn poll(&mut self, now: Instant) -> Vec<String> {
+        if let Some(last_poll) = self.last_poll {
+            if now.duration_since(last_poll) < POLL_INTERVAL {
+                return Vec::new();
+            }
+        }
+        self.last_poll = Some(now);
+        self.scan()
+    }
+
+    pub fn scan(&mut self) -> Vec<String> {
+        let mut modified_times = HashMap::new();
+        if let Ok(entries) = fs::read_dir(&self.directory) {
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let metadata = match entry.metadata() {
+                    Ok(metadata) => metadata,
+                    Err(_) => continue,
+                };
+                if !metadata.is_file() {
+                    continue;
+                }
+                let name = match entry.file_name().into_string() {
+                    Ok(name) => name,
+                    Err(_) => continue,
+                };
+                let modified_time = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                modified_times.insert(name, modified_time);
+            }
+        }
+
+        let mut changed_files: Vec<String> = modified_times
+            .iter()
+            .filter(|(name, modified_time)| self.modified_times.get(*name) != Some(modified_time))
+            .map(|(name, _)| name.clone())
+            .chain(
+                self.modified_times
+                    .keys()
+                    .filter(|name| !modified_times.contains_key(*name))
+                    .cloned(),
+            )
+            .collect();
+        changed_files.sort();
+        self.modified_times = modified_times;
+        changed_files
+    }
+}
+
+pub fn needs_reload(dependencies: &[String], changed_files: &[String]) -> bool {
+    dependencies
+        .iter()
+        .any(|dependency| changed_files.contains(dependency))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preprocessor;
+    use std::env;
+    use std::fs::File;
+    use std::process;
+
+    fn set_modified_time(path: &Path, seconds: u64) {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn reports_programs_depending_on_changed_includes() {
+        let directory = env::temp_dir().join(format!("rustquest-shader-watcher-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("lit.frag"),
+            "#version 300 es\n#include \"lighting.glsl\"\nvoid main() {}\n",
+        )
+        .unwrap();
+        fs::write(directory.join("lighting.glsl"), "float light;\n").unwrap();
+        fs::write(directory.join("unlit.frag"), "void main() {}\n").unwrap();
+        for name in ["lit.frag", "lighting.glsl", "unlit.frag"] {
+            set_modified_time(&directory.join(name), 1000);
+        }
+
+        let mut watcher = ShaderWatcher::new(&directory);
+        assert_eq!(watcher.files(), ["lighting.glsl", "lit.frag", "unlit.frag"]);
+        let lit = preprocessor::preprocess("lit.frag", &[], |name| watcher.read(name))
+            .unwrap()
+            .files;
+        let unlit = preprocessor::preprocess("unlit.frag", &[], |name| watcher.read(name))
+            .unwrap()
+            .files;
+        assert_eq!(lit, ["lit.frag", "lighting.glsl"]);
+
+        let start = Instant::now();
+        assert!(watcher.poll(start).is_empty());
+        set_modified_time(&directory.join("lighting.glsl"), 2000);
+        assert!(watcher.poll(start + POLL_INTERVAL / 2).is_empty());
+        let changed_files = watcher.poll(start + POLL_INTERVAL);
+        assert_eq!(changed_files, ["lighting.glsl"]);
+        assert!(needs_reload(&lit, &changed_files));
+        assert!(!needs_reload(&unlit, &changed_files));
+
+        fs::remove_file(directory.join("unlit.frag")).unwrap();
+        let changed_files = watcher.scan();
+        assert_eq!(changed_files, ["unlit.frag"]);
+        assert!(needs_reload(&unlit, &changed_files));
+        assert!(watcher.scan().is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
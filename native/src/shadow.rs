@@ -0,0 +1,242 @@
+use crate::math::{Mat4, Vec3};
+
+const TEXTURE_MATRIX: Mat4 = Mat4::from_columns([
+    [0.5, 0.0, 0.0, 0.0],
+    [0.0, 0.5, 0.0, 0.0],
+    [0.0, 0.0, 0.5, 0.0],
+    [0.5, 0.5, 0.5, 1.0],
+]);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowSettings {
+    pub resolution: u32,
+    pub depth_bias: f32,
+    pub slope_bias: f32,
+    pub normal_bias: f32,
+    pub distance: f32,
+    pub caster_distance: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> ShadowSettings {
+        ShadowSettings {
+            resolution: 1024,
+            depth_bias: 0.001,
+            slope_bias: 2.0,
+            normal_bias: 0.02,
+            distance: 8.0,
+            caster_distance: 10.0,
+        }
+    }
+}
+
+std140_struct! {
+    pub struct ShadowUniforms {
+        shadow_matrix: [[f32; 4]; 4],
+        parameters: [f32; 4],
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowFrustum {
+    pub view_matrix: Mat4,
+    pub projection_matrix: Mat4,
+}
+
+impl ShadowFrustum {
+    pub fn fit(
+        direction: Vec3,
+        points: &[Vec3],
+        settings: &ShadowSettings,
+    ) -> Option<ShadowFrustum> {
+        if points.is_empty() || direction.length() == 0.0 {
+            return None;
+        }
+        let center =
+            points.iter().fold(Vec3::ZERO, |sum, point| sum + *point) * (1.0 / points.len() as f32);
+        let radius = points
+            .iter()
+            .map(|point| (*point - center).length())
+            .fold(0.0, f32::max)
+            .max(1e-3);
+
+        let direction = direction.normalize();
+        let up = if direction.y.abs() > 0.99 {
+            Vec3::new(0.0, 0.0, 1.0)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        let view_matrix = Mat4::look_to(Vec3::ZERO, direction, up);
+
+        let texel_size = 2.0 * radius / settings.resolution.max(1) as f32;
+        let snap = |value: f32| (value / texel_size).floor() * texel_size;
+        let light_center = view_matrix.transform_point(center);
+        let x = snap(light_center.x);
+        let y = snap(light_center.y);
+        let projection_matrix = Mat4::orthographic(
+            x - radius,
+            x + radius,
+            y - radius,
+            y + radius,
+            -light_center.z - radius - settings.caster_distance,
+            -light_center.z + radius,
+        );
+        Some(ShadowFrustum {
+            view_matrix,
+            projection_matrix,
+        })
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection_matrix * self.view_matrix
+    }
+
+    pub fn uniforms(&self, settings: &ShadowSettings) -> ShadowUniforms {
+        ShadowUniforms {
+            shadow_matrix: (TEXTURE_MATRIX * self.view_projection()).columns,
+            parameters: [
+                settings.depth_bias,
+                settings.normal_bias,
+                1.0 / settings.resolution.max(1) as f32,
+                0.0,
+            ],
+        }
+    }
+}
+
+pub fn view_corners(view: &Mat4, projection: &Mat4, distance: f32) -> Option<[Vec3; 8]> {
+    let inverse_view = view.inverse()?;
+    let inverse_projection = projection.inverse()?;
+    let mut corners = [Vec3::ZERO; 8];
+    for index in 0..4 {
+        let near = inverse_projection.transform([
+            if index & 1 == 0 { -1.0 } else { 1.0 },
+            if index & 2 == 0 { -1.0 } else { 1.0 },
+            -1.0,
+            1.0,
+        ]);
+        if near[3] == 0.0 {
+            return None;
+        }
+        let near = Vec3::new(near[0], near[1], near[2]) * (1.0 / near[3]);
+        let far = near * (distance.max(-near.z) / -near.z);
+        corners[index] = inverse_view.transform_point(near);
+        corners[index + 4] = inverse_view.transform_point(far);
+    }
+    Some(corners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frustum::Frustum;
+    use crate::math::Quat;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn eye(x: f32) -> (Mat4, Mat4) {
+        let view = Mat4::from_trs(
+            Vec3::new(-x, -1.6, 0.0),
+            Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 0.3),
+            Vec3::ONE,
+        );
+        let projection = Mat4::perspective(FRAC_PI_2, 1.0, 0.1, f32::INFINITY);
+        (view, projection)
+    }
+
+    fn stereo_corners(distance: f32) -> Vec<Vec3> {
+        let mut points = Vec::new();
+        for x in [-0.032, 0.032].iter().cloned() {
+            let (view, projection) = eye(x);
+            points.extend(view_corners(&view, &projection, distance).unwrap().iter());
+        }
+        points
+    }
+
+    fn to_ndc(matrix: &Mat4, point: Vec3) -> Vec3 {
+        let clip = matrix.transform([point.x, point.y, point.z, 1.0]);
+        Vec3::new(clip[0], clip[1], clip[2]) * (1.0 / clip[3])
+    }
+
+    #[test]
+    fn computes_view_corners_up_to_a_distance() {
+        let (view, projection) = eye(0.0);
+        let corners = view_corners(&view, &projection, 5.0).unwrap();
+        let frustum = Frustum::from_view_projection(&(projection * view));
+        let center = corners.iter().fold(Vec3::ZERO, |sum, corner| sum + *corner) * 0.125;
+        for (index, corner) in corners.iter().enumerate() {
+            let depth = -view.transform_point(*corner).z;
+            let expected = if index < 4 { 0.1 } else { 5.0 };
+            assert!((depth - expected).abs() < 1e-4, "{} != {}", depth, expected);
+            assert!(frustum.contains_point(*corner + (center - *corner) * 1e-3));
+        }
+    }
+
+    #[test]
+    fn fits_the_stereo_view_volume() {
+        let settings = ShadowSettings::default();
+        let points = stereo_corners(settings.distance);
+        for direction in [
+            Vec3::new(-0.3, -1.0, -0.4),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        ]
+        .iter()
+        .cloned()
+        {
+            let shadow = ShadowFrustum::fit(direction, &points, &settings).unwrap();
+            let view_projection = shadow.view_projection();
+            for point in points.iter() {
+                let ndc = to_ndc(&view_projection, *point);
+                for value in ndc.to_array().iter() {
+                    assert!(value.abs() <= 1.0 + 1e-4, "{:?} outside {:?}", point, ndc);
+                }
+            }
+            let center = points.iter().fold(Vec3::ZERO, |sum, point| sum + *point)
+                * (1.0 / points.len() as f32);
+            let caster = center - direction.normalize() * (settings.distance + 5.0);
+            assert!(to_ndc(&view_projection, caster).z.abs() <= 1.0);
+            let behind = center - direction.normalize() * (settings.distance * 4.0 + 15.0);
+            assert!(to_ndc(&view_projection, behind).z < -1.0);
+        }
+    }
+
+    #[test]
+    fn snaps_to_whole_texels() {
+        let settings = ShadowSettings::default();
+        let points = stereo_corners(settings.distance);
+        let direction = Vec3::new(-0.3, -1.0, -0.4);
+        let texel = |offset: f32| {
+            let moved: Vec<Vec3> = points
+                .iter()
+                .map(|point| *point + Vec3::new(offset, 0.0, offset * 0.5))
+                .collect();
+            let shadow = ShadowFrustum::fit(direction, &moved, &settings).unwrap();
+            let ndc = to_ndc(&shadow.view_projection(), Vec3::new(0.25, 0.0, -0.75));
+            (ndc.x * 0.5 + 0.5) * settings.resolution as f32
+        };
+        let reference = texel(0.0).fract();
+        for step in 1..20 {
+            let fraction = texel(step as f32 * 0.013).fract();
+            let difference = (fraction - reference).abs();
+            assert!(
+                difference.min(1.0 - difference) < 1e-2,
+                "{} != {}",
+                fraction,
+                reference
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_degenerate_input() {
+        let settings = ShadowSettings::default();
+        assert_eq!(
+            ShadowFrustum::fit(Vec3::new(0.0, -1.0, 0.0), &[], &settings),
+            None
+        );
+        assert_eq!(
+            ShadowFrustum::fit(Vec3::ZERO, &[Vec3::ONE], &settings),
+            None
+        );
+    }
+}
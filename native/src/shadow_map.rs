@@ -0,0 +1,44 @@
+use crate::gl::Context;
+use crate::render_target::RenderTarget;
+use crate::sampler::{Sampler, SamplerState};
+use libGLESv3_sys::{GLsizei, GLuint, GL_DEPTH_COMPONENT24, GL_LEQUAL};
+
+pub struct ShadowMap {
+    render_target: RenderTarget,
+    sampler: Sampler,
+}
+
+impl ShadowMap {
+    pub fn new(context: &Context, resolution: u32) -> ShadowMap {
+        let size = resolution as GLsizei;
+        let render_target = RenderTarget::builder(context, size, size)
+            .depth_texture(GL_DEPTH_COMPONENT24)
+            .build()
+            .unwrap_or_else(|error| panic!("can't create shadow map: {}", error));
+        ShadowMap {
+            render_target,
+            sampler: Sampler::new(
+                context,
+                SamplerState {
+                    compare_func: Some(GL_LEQUAL),
+                    ..SamplerState::CLAMP
+                },
+            ),
+        }
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.render_target.width() as u32
+    }
+
+    pub fn render_target(&self) -> &RenderTarget {
+        &self.render_target
+    }
+
+    pub fn bind(&self, unit: GLuint) {
+        if let Some(texture) = self.render_target.depth_texture() {
+            texture.bind(unit);
+        }
+        self.sampler.bind(unit);
+    }
+}
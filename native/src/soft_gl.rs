@@ -0,0 +1,1276 @@
+use crate::gl_backend::{Argument, Emulator, Value};
+use crate::image::Image;
+use crate::light::MAX_LIGHTS;
+use crate::math::{Mat4, Vec3};
+use crate::mock_gl;
+use crate::mock_gl::{Attachment, MockGl, Object, State};
+use crate::program::{
+    LIGHT_BLOCK_BINDING, MATERIAL_BLOCK_BINDING, OBJECT_BLOCK_BINDING, VIEW_BLOCK_BINDING,
+};
+use crate::vertex_layout::{Semantic, INSTANCE_COLOR_LOCATION, INSTANCE_MODEL_MATRIX_LOCATION};
+use libGLESv3_sys::{
+    GLenum, GLuint, GL_ALWAYS, GL_ARRAY_BUFFER, GL_BLEND, GL_BYTE, GL_COLOR_ATTACHMENT0,
+    GL_COLOR_BUFFER_BIT, GL_CULL_FACE, GL_DEPTH_ATTACHMENT, GL_DEPTH_BUFFER_BIT, GL_DEPTH_TEST,
+    GL_DST_ALPHA, GL_ELEMENT_ARRAY_BUFFER, GL_EQUAL, GL_FLOAT, GL_FRAGMENT_SHADER, GL_GEQUAL,
+    GL_GREATER, GL_HALF_FLOAT, GL_INT, GL_LEQUAL, GL_LESS, GL_NEVER, GL_NOTEQUAL, GL_ONE,
+    GL_ONE_MINUS_DST_ALPHA, GL_ONE_MINUS_SRC_ALPHA, GL_PIXEL_PACK_BUFFER, GL_POLYGON_OFFSET_FILL,
+    GL_RGBA, GL_SCISSOR_TEST, GL_SHORT, GL_SRC_ALPHA, GL_TEXTURE_2D, GL_TRIANGLES,
+    GL_UNSIGNED_BYTE, GL_UNSIGNED_INT, GL_UNSIGNED_SHORT, GL_VERTEX_SHADER, GL_ZERO,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::slice;
+
+const MAX_VERTEX_ATTRIBS: usize = 16;
+const AMBIENT: f32 = 0.2;
+const DIELECTRIC_REFLECTANCE: f32 = 0.04;
+const MIN_ROUGHNESS: f32 = 0.05;
+const MAX_SHININESS: f32 = 1024.0;
+const DEPTH_UNIT: f32 = 1.0 / 16_777_216.0;
+const VARYINGS: usize = 10;
+
+pub struct SoftGl {
+    mock: MockGl,
+    device: RefCell<Device>,
+}
+
+impl SoftGl {
+    pub fn new() -> SoftGl {
+        let mut vertex_arrays = HashMap::new();
+        vertex_arrays.insert(0, VertexArray::default());
+        SoftGl {
+            mock: MockGl::new(),
+            device: RefCell::new(Device {
+                buffers: HashMap::new(),
+                surfaces: HashMap::new(),
+                vertex_arrays,
+                shaders: HashMap::new(),
+                programs: HashMap::new(),
+                clear_color: [0.0; 4],
+                clear_depth: 1.0,
+            }),
+        }
+    }
+
+    pub fn mock(&self) -> &MockGl {
+        &self.mock
+    }
+
+    pub fn texture_image(&self, texture: GLuint) -> Option<Image> {
+        let device = self.device.borrow();
+        let surface = device.surfaces.get(&texture)?;
+        let colors = match &surface.texels {
+            Texels::Color(colors) => colors,
+            Texels::Depth(_) => return None,
+        };
+        let mut data = Vec::with_capacity(colors.len() * 4);
+        for row in (0..surface.height).rev() {
+            for color in colors[row * surface.width..][..surface.width].iter() {
+                data.extend_from_slice(color);
+            }
+        }
+        Some(Image {
+            width: surface.width,
+            height: surface.height,
+            data,
+        })
+    }
+}
+
+impl Emulator for SoftGl {
+    fn call(&self, function: &'static str, arguments: &[Argument]) -> Value {
+        let error_count = self.mock.errors().len();
+        let value = self.mock.call(function, arguments);
+        if self.mock.errors().len() == error_count {
+            let state = self.mock.state();
+            return unsafe {
+                self.device
+                    .borrow_mut()
+                    .execute(&state, function, arguments, value)
+            };
+        }
+        value
+    }
+}
+
+struct Device {
+    buffers: HashMap<GLuint, Vec<u8>>,
+    surfaces: HashMap<GLuint, Surface>,
+    vertex_arrays: HashMap<GLuint, VertexArray>,
+    shaders: HashMap<GLuint, Shader>,
+    programs: HashMap<GLuint, Program>,
+    clear_color: [f32; 4],
+    clear_depth: f32,
+}
+
+struct Surface {
+    width: usize,
+    height: usize,
+    texels: Texels,
+}
+
+enum Texels {
+    Color(Vec<[u8; 4]>),
+    Depth(Vec<f32>),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct AttribPointer {
+    buffer: GLuint,
+    size: usize,
+    type_: GLenum,
+    normalized: bool,
+    stride: usize,
+    offset: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+struct VertexArray {
+    pointers: [Option<AttribPointer>; MAX_VERTEX_ATTRIBS],
+    enabled: [bool; MAX_VERTEX_ATTRIBS],
+    divisors: [usize; MAX_VERTEX_ATTRIBS],
+    element_buffer: GLuint,
+}
+
+struct Shader {
+    type_: GLenum,
+    source: String,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Program {
+    vertex_shader: Option<GLuint>,
+    fragment_shader: Option<GLuint>,
+    shading: Option<Shading>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Output {
+    Lit,
+    VertexColor,
+    DepthOnly,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Shading {
+    output: Output,
+    instanced: bool,
+    has_normals: bool,
+    has_vertex_colors: bool,
+}
+
+impl Shading {
+    fn interpret(vertex_source: &str, fragment_source: &str) -> Shading {
+        let output = if fragment_source.contains("uniform Material") {
+            Output::Lit
+        } else if fragment_source
+            .lines()
+            .any(|line| line.trim_start().starts_with("out "))
+        {
+            Output::VertexColor
+        } else {
+            Output::DepthOnly
+        };
+        Shading {
+            output,
+            instanced: is_defined(vertex_source, "INSTANCED"),
+            has_normals: is_defined(vertex_source, "HAS_NORMALS"),
+            has_vertex_colors: output == Output::VertexColor
+                || is_defined(vertex_source, "HAS_VERTEX_COLORS"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ClipVertex {
+    position: [f32; 4],
+    varyings: [f32; VARYINGS],
+}
+
+#[derive(Clone, Copy, Debug)]
+struct WindowVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    inverse_w: f32,
+    varyings: [f32; VARYINGS],
+}
+
+struct Uniforms {
+    view_matrix: Mat4,
+    projection_matrix: Mat4,
+    camera_position: [f32; 4],
+    model_matrix: Mat4,
+    base_color: [f32; 4],
+    emissive: [f32; 4],
+    parameters: [f32; 4],
+    environment: [f32; 4],
+    lights: Vec<[[f32; 4]; 4]>,
+}
+
+struct Target<'a> {
+    color: Option<&'a mut Surface>,
+    depth: Option<&'a mut Surface>,
+    width: usize,
+    rect: [i64; 4],
+}
+
+impl Device {
+    unsafe fn execute(
+        &mut self,
+        state: &State,
+        function: &'static str,
+        arguments: &[Argument],
+        value: Value,
+    ) -> Value {
+        let int = |index: usize| arguments[index].int();
+        let uint = |index: usize| arguments[index].int() as GLuint;
+        match function {
+            "glDeleteBuffers"
+            | "glDeleteTextures"
+            | "glDeleteRenderbuffers"
+            | "glDeleteVertexArrays" => {
+                let names =
+                    slice::from_raw_parts(arguments[1].pointer::<GLuint>(), int(0) as usize);
+                for name in names.iter() {
+                    self.buffers.remove(name);
+                    self.surfaces.remove(name);
+                    if *name != 0 {
+                        self.vertex_arrays.remove(name);
+                    }
+                }
+            }
+            "glGenVertexArrays" => {
+                let names =
+                    slice::from_raw_parts(arguments[1].pointer::<GLuint>(), int(0) as usize);
+                for name in names.iter() {
+                    self.vertex_arrays.insert(*name, VertexArray::default());
+                }
+            }
+            "glBindBuffer" if uint(0) == GL_ELEMENT_ARRAY_BUFFER => {
+                self.vertex_array(state).element_buffer = uint(1);
+            }
+            "glBufferData" => {
+                let buffer = self.buffer_binding(state, uint(0));
+                let size = int(1) as usize;
+                let data = arguments[2].pointer::<u8>();
+                self.buffers.insert(
+                    buffer,
+                    if data.is_null() {
+                        vec![0; size]
+                    } else {
+                        slice::from_raw_parts(data, size).to_vec()
+                    },
+                );
+            }
+            "glBufferSubData" => {
+                let buffer = self.buffer_binding(state, uint(0));
+                let (offset, size) = (int(1) as usize, int(2) as usize);
+                let data = slice::from_raw_parts(arguments[3].pointer::<u8>(), size);
+                if let Some(bytes) = self.buffers.get_mut(&buffer) {
+                    bytes[offset..offset + size].copy_from_slice(data);
+                }
+            }
+            "glVertexAttribPointer" => {
+                let pointer = AttribPointer {
+                    buffer: state.buffers.get(&GL_ARRAY_BUFFER).cloned().unwrap_or(0),
+                    size: int(1) as usize,
+                    type_: uint(2),
+                    normalized: int(3) != 0,
+                    stride: int(4) as usize,
+                    offset: arguments[5].pointer::<u8>() as usize,
+                };
+                self.vertex_array(state).pointers[uint(0) as usize] = Some(pointer);
+            }
+            "glVertexAttribIPointer" => {
+                let pointer = AttribPointer {
+                    buffer: state.buffers.get(&GL_ARRAY_BUFFER).cloned().unwrap_or(0),
+                    size: int(1) as usize,
+                    type_: uint(2),
+                    normalized: false,
+                    stride: int(3) as usize,
+                    offset: arguments[4].pointer::<u8>() as usize,
+                };
+                self.vertex_array(state).pointers[uint(0) as usize] = Some(pointer);
+            }
+            "glEnableVertexAttribArray" => {
+                self.vertex_array(state).enabled[uint(0) as usize] = true;
+            }
+            "glVertexAttribDivisor" => {
+                self.vertex_array(state).divisors[uint(0) as usize] = uint(1) as usize;
+            }
+            "glTexStorage2D" if uint(0) == GL_TEXTURE_2D => {
+                if let Some(texture) = state.textures.get(&(state.active_texture, GL_TEXTURE_2D)) {
+                    self.surfaces.insert(
+                        *texture,
+                        Surface::new(uint(2), int(3) as usize, int(4) as usize),
+                    );
+                }
+            }
+            "glRenderbufferStorage" => {
+                self.surfaces.insert(
+                    state.renderbuffer,
+                    Surface::new(uint(1), int(2) as usize, int(3) as usize),
+                );
+            }
+            "glRenderbufferStorageMultisample" => {
+                self.surfaces.insert(
+                    state.renderbuffer,
+                    Surface::new(uint(2), int(3) as usize, int(4) as usize),
+                );
+            }
+            "glCreateShader" => {
+                if let Value::Int(shader) = value {
+                    self.shaders.insert(
+                        shader as GLuint,
+                        Shader {
+                            type_: uint(0),
+                            source: String::new(),
+                        },
+                    );
+                }
+            }
+            "glShaderSource" => {
+                let strings =
+                    slice::from_raw_parts(arguments[2].pointer::<*const c_char>(), int(1) as usize);
+                if let Some(shader) = self.shaders.get_mut(&uint(0)) {
+                    shader.source = strings
+                        .iter()
+                        .map(|string| CStr::from_ptr(*string).to_string_lossy())
+                        .collect();
+                }
+            }
+            "glAttachShader" => {
+                let type_ = self.shaders.get(&uint(1)).map(|shader| shader.type_);
+                let program = self.programs.entry(uint(0)).or_default();
+                match type_ {
+                    Some(GL_VERTEX_SHADER) => program.vertex_shader = Some(uint(1)),
+                    Some(GL_FRAGMENT_SHADER) => program.fragment_shader = Some(uint(1)),
+                    _ => {}
+                }
+            }
+            "glLinkProgram" => {
+                let shaders = &self.shaders;
+                if let Some(program) = self.programs.get_mut(&uint(0)) {
+                    let source = |shader: Option<GLuint>| {
+                        shader
+                            .and_then(|shader| shaders.get(&shader))
+                            .map_or("", |shader| shader.source.as_str())
+                    };
+                    program.shading = Some(Shading::interpret(
+                        source(program.vertex_shader),
+                        source(program.fragment_shader),
+                    ));
+                }
+            }
+            "glDeleteShader" => {
+                self.shaders.remove(&uint(0));
+            }
+            "glDeleteProgram" => {
+                self.programs.remove(&uint(0));
+            }
+            "glClearColor" => {
+                self.clear_color = [
+                    arguments[0].float(),
+                    arguments[1].float(),
+                    arguments[2].float(),
+                    arguments[3].float(),
+                ];
+            }
+            "glClearDepthf" => self.clear_depth = arguments[0].float(),
+            "glClear" => self.clear(state, uint(0)),
+            "glBlitFramebuffer" => {
+                let rect =
+                    |first: usize| [int(first), int(first + 1), int(first + 2), int(first + 3)];
+                self.blit(state, rect(0), rect(4), uint(8));
+            }
+            "glDrawElementsInstanced" => {
+                if uint(0) != GL_TRIANGLES {
+                    panic!("can't rasterize primitive mode {:#x}", uint(0));
+                }
+                self.draw(
+                    state,
+                    int(1) as usize,
+                    uint(2),
+                    arguments[3].pointer::<u8>() as usize,
+                    int(4) as usize,
+                );
+            }
+            "glReadPixels" => {
+                if uint(4) != GL_RGBA || uint(5) != GL_UNSIGNED_BYTE {
+                    panic!("can't read pixels as {:#x} {:#x}", uint(4), uint(5));
+                }
+                let rect = [int(0), int(1), int(0) + int(2), int(1) + int(3)];
+                let pixels = arguments[6].pointer::<u8>();
+                let pixels = match state.buffers.get(&GL_PIXEL_PACK_BUFFER) {
+                    Some(buffer) if *buffer != 0 => {
+                        self.buffers.get_mut(buffer).unwrap()[pixels as usize..].as_mut_ptr()
+                    }
+                    _ => pixels,
+                };
+                self.read_pixels(state, rect, pixels);
+            }
+            "glMapBufferRange" => {
+                let buffer = self.buffer_binding(state, uint(0));
+                return Value::Pointer(
+                    self.buffers[&buffer][int(1) as usize..].as_ptr() as *const _
+                );
+            }
+            _ => {}
+        }
+        value
+    }
+
+    unsafe fn read_pixels(&self, state: &State, rect: [i64; 4], pixels: *mut u8) {
+        let colors = Device::attachments(state, state.read_framebuffer)
+            .0
+            .and_then(|name| self.surfaces.get(&name));
+        let surface = match colors {
+            Some(surface) => surface,
+            None => return,
+        };
+        let colors = match &surface.texels {
+            Texels::Color(colors) => colors,
+            Texels::Depth(_) => return,
+        };
+        let width = (rect[2] - rect[0]) as usize;
+        for y in rect[1]..rect[3] {
+            for x in rect[0]..rect[2] {
+                if x < 0 || y < 0 || x >= surface.width as i64 || y >= surface.height as i64 {
+                    continue;
+                }
+                let offset = ((y - rect[1]) as usize * width + (x - rect[0]) as usize) * 4;
+                let color = colors[y as usize * surface.width + x as usize];
+                slice::from_raw_parts_mut(pixels.add(offset), 4).copy_from_slice(&color);
+            }
+        }
+    }
+
+    fn vertex_array(&mut self, state: &State) -> &mut VertexArray {
+        self.vertex_arrays.entry(state.vertex_array).or_default()
+    }
+
+    fn buffer_binding(&self, state: &State, target: GLenum) -> GLuint {
+        if target == GL_ELEMENT_ARRAY_BUFFER {
+            self.vertex_arrays
+                .get(&state.vertex_array)
+                .map_or(0, |vertex_array| vertex_array.element_buffer)
+        } else {
+            state.buffers.get(&target).cloned().unwrap_or(0)
+        }
+    }
+
+    fn attachments(state: &State, framebuffer: GLuint) -> (Option<GLuint>, Option<GLuint>) {
+        let attachments = match state.objects.get(&framebuffer) {
+            Some(Object::Framebuffer(attachments)) => attachments,
+            _ => return (None, None),
+        };
+        let name = |attachment_point: GLenum| {
+            attachments
+                .get(&attachment_point)
+                .map(|attachment| match attachment {
+                    Attachment::Texture(name) | Attachment::Renderbuffer(name) => *name,
+                })
+        };
+        (name(GL_COLOR_ATTACHMENT0), name(GL_DEPTH_ATTACHMENT))
+    }
+
+    fn with_target<F: FnOnce(&mut Target)>(&mut self, state: &State, framebuffer: GLuint, f: F) {
+        let (color, depth) = Device::attachments(state, framebuffer);
+        let mut color = color.and_then(|name| self.surfaces.remove(&name).map(|s| (name, s)));
+        let mut depth = depth.and_then(|name| self.surfaces.remove(&name).map(|s| (name, s)));
+        let (width, height) = match (color.as_ref(), depth.as_ref()) {
+            (Some((_, surface)), _) | (None, Some((_, surface))) => (surface.width, surface.height),
+            (None, None) => return,
+        };
+        let mut rect = [0, 0, width as i64, height as i64];
+        if state.capabilities.contains(&GL_SCISSOR_TEST) {
+            rect = intersect(rect, to_rect(state.scissor));
+        }
+        f(&mut Target {
+            color: color.as_mut().map(|(_, surface)| surface),
+            depth: depth.as_mut().map(|(_, surface)| surface),
+            width,
+            rect,
+        });
+        for (name, surface) in color.into_iter().chain(depth) {
+            self.surfaces.insert(name, surface);
+        }
+    }
+
+    fn clear(&mut self, state: &State, mask: GLenum) {
+        let clear_color = to_rgba8(self.clear_color);
+        let clear_depth = self.clear_depth.clamp(0.0, 1.0);
+        let clear_depth_buffer = mask & GL_DEPTH_BUFFER_BIT != 0 && state.depth_mask;
+        self.with_target(state, state.draw_framebuffer, |target| {
+            let rect = target.rect;
+            if mask & GL_COLOR_BUFFER_BIT != 0 {
+                if let Some(surface) = target.color.as_mut() {
+                    surface.fill(rect, |texels, index| {
+                        if let Texels::Color(colors) = texels {
+                            colors[index] = clear_color;
+                        }
+                    });
+                }
+            }
+            if clear_depth_buffer {
+                if let Some(surface) = target.depth.as_mut() {
+                    surface.fill(rect, |texels, index| {
+                        if let Texels::Depth(depths) = texels {
+                            depths[index] = clear_depth;
+                        }
+                    });
+                }
+            }
+        });
+    }
+
+    fn blit(&mut self, state: &State, source: [i64; 4], destination: [i64; 4], mask: GLenum) {
+        let (source_color, source_depth) = Device::attachments(state, state.read_framebuffer);
+        let (destination_color, destination_depth) =
+            Device::attachments(state, state.draw_framebuffer);
+        let mut copies = Vec::new();
+        if mask & GL_COLOR_BUFFER_BIT != 0 {
+            copies.push((source_color, destination_color));
+        }
+        if mask & GL_DEPTH_BUFFER_BIT != 0 {
+            copies.push((source_depth, destination_depth));
+        }
+        for (source_name, destination_name) in copies {
+            let (source_name, destination_name) = match (source_name, destination_name) {
+                (Some(source_name), Some(destination_name)) => (source_name, destination_name),
+                _ => continue,
+            };
+            let source_surface = match self.surfaces.remove(&source_name) {
+                Some(surface) => surface,
+                None => continue,
+            };
+            if let Some(destination_surface) = self.surfaces.get_mut(&destination_name) {
+                destination_surface.copy_from(&source_surface, source, destination);
+            }
+            self.surfaces.insert(source_name, source_surface);
+        }
+    }
+
+    fn draw(
+        &mut self,
+        state: &State,
+        count: usize,
+        index_type: GLenum,
+        offset: usize,
+        instance_count: usize,
+    ) {
+        let shading = match self
+            .programs
+            .get(&state.program)
+            .and_then(|program| program.shading)
+        {
+            Some(shading) => shading,
+            None => panic!("can't draw with unlinked program {}", state.program),
+        };
+        let vertex_array = self.vertex_arrays[&state.vertex_array].clone();
+        let indices = self.indices(&vertex_array, count, index_type, offset);
+        let uniforms = self.uniforms(state);
+        let vertices: Vec<Vec<ClipVertex>> = (0..instance_count)
+            .map(|instance| {
+                let mut cache: HashMap<usize, ClipVertex> = HashMap::new();
+                indices
+                    .iter()
+                    .map(|index| {
+                        *cache.entry(*index).or_insert_with(|| {
+                            self.shade_vertex(&vertex_array, &shading, &uniforms, *index, instance)
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let viewport = state.viewport;
+        let cull = state.capabilities.contains(&GL_CULL_FACE);
+        let depth_test = state.capabilities.contains(&GL_DEPTH_TEST);
+        let blend = state.capabilities.contains(&GL_BLEND);
+        let polygon_offset = if state.capabilities.contains(&GL_POLYGON_OFFSET_FILL) {
+            Some(state.polygon_offset)
+        } else {
+            None
+        };
+        let raster = Raster {
+            viewport,
+            cull,
+            depth_func: if depth_test {
+                state.depth_func
+            } else {
+                GL_ALWAYS
+            },
+            depth_write: depth_test && state.depth_mask,
+            polygon_offset,
+            blend_func: if blend { Some(state.blend_func) } else { None },
+        };
+        self.with_target(state, state.draw_framebuffer, |target| {
+            target.rect = intersect(target.rect, to_rect(viewport));
+            for instance in vertices.iter() {
+                for triangle in instance.chunks_exact(3) {
+                    raster.triangle(target, triangle, |varyings, front_facing| {
+                        shade_fragment(&shading, &uniforms, varyings, front_facing)
+                    });
+                }
+            }
+        });
+    }
+
+    fn indices(
+        &self,
+        vertex_array: &VertexArray,
+        count: usize,
+        index_type: GLenum,
+        offset: usize,
+    ) -> Vec<usize> {
+        let bytes = &self.buffers[&vertex_array.element_buffer][offset..];
+        (0..count)
+            .map(|index| match index_type {
+                GL_UNSIGNED_BYTE => bytes[index] as usize,
+                GL_UNSIGNED_SHORT => {
+                    u16::from_ne_bytes([bytes[index * 2], bytes[index * 2 + 1]]) as usize
+                }
+                GL_UNSIGNED_INT => read_u32(bytes, index * 4) as usize,
+                _ => panic!("unknown index type {:#x}", index_type),
+            })
+            .collect()
+    }
+
+    fn uniform_block(&self, state: &State, binding: GLuint) -> &[u8] {
+        state
+            .uniform_buffers
+            .get(&binding)
+            .and_then(|(buffer, offset, size)| {
+                let bytes = self.buffers.get(buffer)?;
+                bytes.get(*offset as usize..(*offset + *size) as usize)
+            })
+            .unwrap_or(&[])
+    }
+
+    fn uniforms(&self, state: &State) -> Uniforms {
+        let vec4 = |bytes: &[u8], offset: usize| {
+            if bytes.len() < offset + 16 {
+                return [0.0; 4];
+            }
+            [
+                read_f32(bytes, offset),
+                read_f32(bytes, offset + 4),
+                read_f32(bytes, offset + 8),
+                read_f32(bytes, offset + 12),
+            ]
+        };
+        let mat4 = |bytes: &[u8], offset: usize| {
+            Mat4::from_columns([
+                vec4(bytes, offset),
+                vec4(bytes, offset + 16),
+                vec4(bytes, offset + 32),
+                vec4(bytes, offset + 48),
+            ])
+        };
+        let view = self.uniform_block(state, VIEW_BLOCK_BINDING);
+        let object = self.uniform_block(state, OBJECT_BLOCK_BINDING);
+        let material = self.uniform_block(state, MATERIAL_BLOCK_BINDING);
+        let lights = self.uniform_block(state, LIGHT_BLOCK_BINDING);
+        let light_count = if lights.len() >= 32 {
+            (read_u32(lights, 16) as usize).min(MAX_LIGHTS)
+        } else {
+            0
+        };
+        Uniforms {
+            view_matrix: mat4(view, 0),
+            projection_matrix: mat4(view, 64),
+            camera_position: vec4(view, 128),
+            model_matrix: if object.is_empty() {
+                Mat4::IDENTITY
+            } else {
+                mat4(object, 0)
+            },
+            base_color: vec4(material, 0),
+            emissive: vec4(material, 16),
+            parameters: vec4(material, 32),
+            environment: vec4(lights, 0),
+            lights: (0..light_count)
+                .map(|index| {
+                    let offset = 32 + index * 64;
+                    [
+                        vec4(lights, offset),
+                        vec4(lights, offset + 16),
+                        vec4(lights, offset + 32),
+                        vec4(lights, offset + 48),
+                    ]
+                })
+                .collect(),
+        }
+    }
+
+    fn attribute(
+        &self,
+        vertex_array: &VertexArray,
+        location: u32,
+        vertex: usize,
+        instance: usize,
+    ) -> [f32; 4] {
+        let location = location as usize;
+        let pointer = match vertex_array.pointers[location] {
+            Some(pointer) if vertex_array.enabled[location] => pointer,
+            _ => return [0.0, 0.0, 0.0, 1.0],
+        };
+        let index = match vertex_array.divisors[location] {
+            0 => vertex,
+            divisor => instance / divisor,
+        };
+        let component_size = match pointer.type_ {
+            GL_BYTE | GL_UNSIGNED_BYTE => 1,
+            GL_SHORT | GL_UNSIGNED_SHORT | GL_HALF_FLOAT => 2,
+            GL_INT | GL_UNSIGNED_INT | GL_FLOAT => 4,
+            type_ => panic!("unknown attribute type {:#x}", type_),
+        };
+        let stride = if pointer.stride == 0 {
+            pointer.size * component_size
+        } else {
+            pointer.stride
+        };
+        let bytes = &self.buffers[&pointer.buffer][pointer.offset + index * stride..];
+        let mut value = [0.0, 0.0, 0.0, 1.0];
+        for (component, value) in value.iter_mut().enumerate().take(pointer.size) {
+            let offset = component * component_size;
+            *value = match pointer.type_ {
+                GL_FLOAT => read_f32(bytes, offset),
+                GL_HALF_FLOAT => {
+                    half_to_f32(u16::from_ne_bytes([bytes[offset], bytes[offset + 1]]))
+                }
+                GL_UNSIGNED_BYTE => normalize(bytes[offset] as f32, 255.0, pointer.normalized),
+                GL_BYTE => normalize(bytes[offset] as i8 as f32, 127.0, pointer.normalized),
+                GL_UNSIGNED_SHORT => normalize(
+                    u16::from_ne_bytes([bytes[offset], bytes[offset + 1]]) as f32,
+                    65535.0,
+                    pointer.normalized,
+                ),
+                GL_SHORT => normalize(
+                    i16::from_ne_bytes([bytes[offset], bytes[offset + 1]]) as f32,
+                    32767.0,
+                    pointer.normalized,
+                ),
+                GL_UNSIGNED_INT => read_u32(bytes, offset) as f32,
+                _ => read_u32(bytes, offset) as i32 as f32,
+            };
+        }
+        value
+    }
+
+    fn shade_vertex(
+        &self,
+        vertex_array: &VertexArray,
+        shading: &Shading,
+        uniforms: &Uniforms,
+        vertex: usize,
+        instance: usize,
+    ) -> ClipVertex {
+        let attribute = |location: u32| self.attribute(vertex_array, location, vertex, instance);
+        let model_matrix = if shading.instanced {
+            Mat4::from_columns([
+                attribute(INSTANCE_MODEL_MATRIX_LOCATION),
+                attribute(INSTANCE_MODEL_MATRIX_LOCATION + 1),
+                attribute(INSTANCE_MODEL_MATRIX_LOCATION + 2),
+                attribute(INSTANCE_MODEL_MATRIX_LOCATION + 3),
+            ])
+        } else {
+            uniforms.model_matrix
+        };
+        let [x, y, z, _] = attribute(Semantic::Position.location());
+        let position = model_matrix.transform([x, y, z, 1.0]);
+        let clip_position = uniforms
+            .projection_matrix
+            .transform(uniforms.view_matrix.transform(position));
+
+        let normal = if shading.has_normals {
+            let [x, y, z, _] = attribute(Semantic::Normal.location());
+            model_matrix.inverse().map_or(Vec3::ZERO, |inverse| {
+                inverse.transpose().transform_vector(Vec3::new(x, y, z))
+            })
+        } else {
+            Vec3::ZERO
+        };
+        let mut color = if shading.instanced {
+            attribute(INSTANCE_COLOR_LOCATION)
+        } else {
+            [1.0; 4]
+        };
+        if shading.has_vertex_colors {
+            let vertex_color = attribute(Semantic::Color.location());
+            for (color, vertex_color) in color.iter_mut().zip(vertex_color.iter()) {
+                *color *= vertex_color;
+            }
+        }
+        if shading.output == Output::VertexColor {
+            color[3] = 1.0;
+        }
+        ClipVertex {
+            position: clip_position,
+            varyings: [
+                position[0],
+                position[1],
+                position[2],
+                normal.x,
+                normal.y,
+                normal.z,
+                color[0],
+                color[1],
+                color[2],
+                color[3],
+            ],
+        }
+    }
+}
+
+impl Surface {
+    fn new(internal_format: GLenum, width: usize, height: usize) -> Surface {
+        let texels = if mock_gl::is_depth_format(internal_format) {
+            Texels::Depth(vec![1.0; width * height])
+        } else {
+            Texels::Color(vec![[0; 4]; width * height])
+        };
+        Surface {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    fn fill<F: FnMut(&mut Texels, usize)>(&mut self, rect: [i64; 4], mut f: F) {
+        let rect = intersect(rect, [0, 0, self.width as i64, self.height as i64]);
+        for y in rect[1]..rect[3] {
+            for x in rect[0]..rect[2] {
+                f(&mut self.texels, y as usize * self.width + x as usize);
+            }
+        }
+    }
+
+    fn copy_from(&mut self, source: &Surface, from: [i64; 4], to: [i64; 4]) {
+        let (from_width, from_height) = (from[2] - from[0], from[3] - from[1]);
+        let (to_width, to_height) = (to[2] - to[0], to[3] - to[1]);
+        if from_width <= 0 || from_height <= 0 || to_width <= 0 || to_height <= 0 {
+            return;
+        }
+        let bounds = [0, 0, self.width as i64, self.height as i64];
+        let rect = intersect(to, bounds);
+        for y in rect[1]..rect[3] {
+            for x in rect[0]..rect[2] {
+                let source_x = from[0] + (x - to[0]) * from_width / to_width;
+                let source_y = from[1] + (y - to[1]) * from_height / to_height;
+                if source_x < 0
+                    || source_y < 0
+                    || source_x >= source.width as i64
+                    || source_y >= source.height as i64
+                {
+                    continue;
+                }
+                let to_index = y as usize * self.width + x as usize;
+                let from_index = source_y as usize * source.width + source_x as usize;
+                match (&mut self.texels, &source.texels) {
+                    (Texels::Color(to), Texels::Color(from)) => to[to_index] = from[from_index],
+                    (Texels::Depth(to), Texels::Depth(from)) => to[to_index] = from[from_index],
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+struct Raster {
+    viewport: [i32; 4],
+    cull: bool,
+    depth_func: GLenum,
+    depth_write: bool,
+    polygon_offset: Option<(f32, f32)>,
+    blend_func: Option<[GLenum; 4]>,
+}
+
+impl Raster {
+    fn triangle<F>(&self, target: &mut Target, triangle: &[ClipVertex], mut shade: F)
+    where
+        F: FnMut(&[f32; VARYINGS], bool) -> Option<[f32; 4]>,
+    {
+        let polygon = clip_near(triangle);
+        if polygon.len() < 3 {
+            return;
+        }
+        let window: Vec<WindowVertex> = polygon.iter().map(|vertex| self.window(vertex)).collect();
+        for index in 1..window.len() - 1 {
+            self.rasterize(
+                target,
+                [window[0], window[index], window[index + 1]],
+                &mut shade,
+            );
+        }
+    }
+
+    fn window(&self, vertex: &ClipVertex) -> WindowVertex {
+        let [x, y, z, w] = vertex.position;
+        let [viewport_x, viewport_y, width, height] = self.viewport;
+        let inverse_w = 1.0 / w;
+        WindowVertex {
+            x: viewport_x as f32 + (x * inverse_w + 1.0) * 0.5 * width as f32,
+            y: viewport_y as f32 + (y * inverse_w + 1.0) * 0.5 * height as f32,
+            z: (z * inverse_w + 1.0) * 0.5,
+            inverse_w,
+            varyings: vertex.varyings,
+        }
+    }
+
+    fn rasterize<F>(&self, target: &mut Target, vertices: [WindowVertex; 3], shade: &mut F)
+    where
+        F: FnMut(&[f32; VARYINGS], bool) -> Option<[f32; 4]>,
+    {
+        let [v0, v1, v2] = vertices;
+        let area = edge(&v0, &v1, v2.x, v2.y);
+        if area == 0.0 || !area.is_finite() {
+            return;
+        }
+        let front_facing = area > 0.0;
+        if self.cull && !front_facing {
+            return;
+        }
+        let depth_offset = self.polygon_offset.map_or(0.0, |(factor, units)| {
+            let dzdx = ((v1.z - v0.z) * (v2.y - v0.y) - (v2.z - v0.z) * (v1.y - v0.y)) / area;
+            let dzdy = ((v2.z - v0.z) * (v1.x - v0.x) - (v1.z - v0.z) * (v2.x - v0.x)) / area;
+            factor * dzdx.abs().max(dzdy.abs()) + units * DEPTH_UNIT
+        });
+
+        let min_x = v0.x.min(v1.x).min(v2.x).floor() as i64;
+        let min_y = v0.y.min(v1.y).min(v2.y).floor() as i64;
+        let max_x = v0.x.max(v1.x).max(v2.x).ceil() as i64 + 1;
+        let max_y = v0.y.max(v1.y).max(v2.y).ceil() as i64 + 1;
+        let rect = intersect(target.rect, [min_x, min_y, max_x, max_y]);
+        let sign = area.signum();
+        for y in rect[1]..rect[3] {
+            for x in rect[0]..rect[2] {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let w0 = edge(&v1, &v2, px, py) * sign;
+                let w1 = edge(&v2, &v0, px, py) * sign;
+                let w2 = edge(&v0, &v1, px, py) * sign;
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+                let total = w0 + w1 + w2;
+                let (b0, b1, b2) = (w0 / total, w1 / total, w2 / total);
+                let depth = (b0 * v0.z + b1 * v1.z + b2 * v2.z + depth_offset).clamp(0.0, 1.0);
+                let index = y as usize * target.width + x as usize;
+                if let Some(Texels::Depth(depths)) =
+                    target.depth.as_mut().map(|surface| &mut surface.texels)
+                {
+                    if !depth_passes(self.depth_func, depth, depths[index]) {
+                        continue;
+                    }
+                    if self.depth_write {
+                        depths[index] = depth;
+                    }
+                }
+                let colors = match target.color.as_mut().map(|surface| &mut surface.texels) {
+                    Some(Texels::Color(colors)) => colors,
+                    _ => continue,
+                };
+                let (p0, p1, p2) = (b0 * v0.inverse_w, b1 * v1.inverse_w, b2 * v2.inverse_w);
+                let inverse_w = p0 + p1 + p2;
+                let mut varyings = [0.0; VARYINGS];
+                for (index, varying) in varyings.iter_mut().enumerate() {
+                    *varying = (p0 * v0.varyings[index]
+                        + p1 * v1.varyings[index]
+                        + p2 * v2.varyings[index])
+                        / inverse_w;
+                }
+                if let Some(color) = shade(&varyings, front_facing) {
+                    colors[index] = match self.blend_func {
+                        Some(blend_func) => to_rgba8(blend(blend_func, color, colors[index])),
+                        None => to_rgba8(color),
+                    };
+                }
+            }
+        }
+    }
+}
+
+fn shade_fragment(
+    shading: &Shading,
+    uniforms: &Uniforms,
+    varyings: &[f32; VARYINGS],
+    front_facing: bool,
+) -> Option<[f32; 4]> {
+    let color = [varyings[6], varyings[7], varyings[8], varyings[9]];
+    match shading.output {
+        Output::DepthOnly => None,
+        Output::VertexColor => Some(color),
+        Output::Lit => {
+            let position = Vec3::new(varyings[0], varyings[1], varyings[2]);
+            let mut normal = Vec3::new(varyings[3], varyings[4], varyings[5]).normalize();
+            if !front_facing {
+                normal = -normal;
+            }
+            let camera_position = Vec3::new(
+                uniforms.camera_position[0],
+                uniforms.camera_position[1],
+                uniforms.camera_position[2],
+            );
+            let view_direction = (camera_position - position).normalize();
+            let base_color = Vec3::new(
+                uniforms.base_color[0] * color[0],
+                uniforms.base_color[1] * color[1],
+                uniforms.base_color[2] * color[2],
+            );
+            let metallic = uniforms.parameters[0].clamp(0.0, 1.0);
+            let roughness = uniforms.parameters[1].clamp(MIN_ROUGHNESS, 1.0);
+            let alpha = roughness * roughness;
+            let shininess = (2.0 / (alpha * alpha) - 2.0).clamp(1.0, MAX_SHININESS);
+            let diffuse_color = base_color * (1.0 - metallic);
+            let specular_color =
+                Vec3::ONE * (DIELECTRIC_REFLECTANCE * (1.0 - metallic)) + base_color * metallic;
+
+            let mut rgb = (diffuse_color + specular_color) * (AMBIENT * uniforms.environment[0]);
+            for light in uniforms.lights.iter() {
+                let (light_direction, radiance) = light_radiance(light, position);
+                let n_dot_l = normal.dot(light_direction).max(0.0);
+                if n_dot_l == 0.0 {
+                    continue;
+                }
+                let half_vector = (light_direction + view_direction).normalize();
+                let n_dot_h = normal.dot(half_vector).max(0.0);
+                let specular = (shininess + 8.0) / (8.0 * PI) * n_dot_h.powf(shininess);
+                let reflected = diffuse_color * (1.0 / PI) + specular_color * specular;
+                rgb = rgb + multiply(reflected, radiance) * n_dot_l;
+            }
+            rgb = rgb
+                + Vec3::new(
+                    uniforms.emissive[0],
+                    uniforms.emissive[1],
+                    uniforms.emissive[2],
+                );
+            let tone_map = |value: f32| (value / (1.0 + value)).powf(1.0 / 2.2);
+            Some([
+                tone_map(rgb.x),
+                tone_map(rgb.y),
+                tone_map(rgb.z),
+                uniforms.base_color[3] * color[3],
+            ])
+        }
+    }
+}
+
+fn light_radiance(light: &[[f32; 4]; 4], position: Vec3) -> (Vec3, Vec3) {
+    let [position_range, direction_type, color_intensity, parameters] = light;
+    let direction = Vec3::new(direction_type[0], direction_type[1], direction_type[2]);
+    let (light_direction, attenuation) = if direction_type[3] as i32 == 0 {
+        (-direction.normalize(), 1.0)
+    } else {
+        let to_light =
+            Vec3::new(position_range[0], position_range[1], position_range[2]) - position;
+        let distance = to_light.length();
+        let light_direction = to_light * (1.0 / distance);
+        let mut attenuation = 1.0 / (distance * distance).max(1e-4);
+        if position_range[3] > 0.0 {
+            let ratio = distance / position_range[3];
+            attenuation *= (1.0 - ratio * ratio * ratio * ratio).clamp(0.0, 1.0);
+        }
+        if direction_type[3] as i32 == 2 {
+            let cos_angle = direction.normalize().dot(-light_direction);
+            let spot = (cos_angle * parameters[0] + parameters[1]).clamp(0.0, 1.0);
+            attenuation *= spot * spot;
+        }
+        (light_direction, attenuation)
+    };
+    let color = Vec3::new(color_intensity[0], color_intensity[1], color_intensity[2]);
+    (light_direction, color * (color_intensity[3] * attenuation))
+}
+
+fn multiply(a: Vec3, b: Vec3) -> Vec3 {
+    Vec3::new(a.x * b.x, a.y * b.y, a.z * b.z)
+}
+
+fn clip_near(triangle: &[ClipVertex]) -> Vec<ClipVertex> {
+    let distance = |vertex: &ClipVertex| vertex.position[2] + vertex.position[3];
+    let mut polygon = Vec::with_capacity(4);
+    for index in 0..triangle.len() {
+        let current = &triangle[index];
+        let next = &triangle[(index + 1) % triangle.len()];
+        let (current_distance, next_distance) = (distance(current), distance(next));
+        if current_distance >= 0.0 {
+            polygon.push(*current);
+        }
+        if (current_distance >= 0.0) != (next_distance >= 0.0) {
+            let t = current_distance / (current_distance - next_distance);
+            let mut vertex = *current;
+            for (value, next) in vertex.position.iter_mut().zip(next.position.iter()) {
+                *value += (next - *value) * t;
+            }
+            for (value, next) in vertex.varyings.iter_mut().zip(next.varyings.iter()) {
+                *value += (next - *value) * t;
+            }
+            polygon.push(vertex);
+        }
+    }
+    polygon
+}
+
+fn edge(a: &WindowVertex, b: &WindowVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+fn depth_passes(depth_func: GLenum, depth: f32, stored: f32) -> bool {
+    match depth_func {
+        GL_NEVER => false,
+        GL_LESS => depth < stored,
+        GL_EQUAL => depth == stored,
+        GL_LEQUAL => depth <= stored,
+        GL_GREATER => depth > stored,
+        GL_NOTEQUAL => depth != stored,
+        GL_GEQUAL => depth >= stored,
+        _ => true,
+    }
+}
+
+fn blend(blend_func: [GLenum; 4], source: [f32; 4], destination: [u8; 4]) -> [f32; 4] {
+    let destination = [
+        destination[0] as f32 / 255.0,
+        destination[1] as f32 / 255.0,
+        destination[2] as f32 / 255.0,
+        destination[3] as f32 / 255.0,
+    ];
+    let factor = |factor: GLenum| match factor {
+        GL_ZERO => 0.0,
+        GL_ONE => 1.0,
+        GL_SRC_ALPHA => source[3],
+        GL_ONE_MINUS_SRC_ALPHA => 1.0 - source[3],
+        GL_DST_ALPHA => destination[3],
+        GL_ONE_MINUS_DST_ALPHA => 1.0 - destination[3],
+        _ => panic!("unsupported blend factor {:#x}", factor),
+    };
+    let [source_rgb, destination_rgb, source_alpha, destination_alpha] = blend_func;
+    let mut result = [0.0; 4];
+    for (channel, value) in result.iter_mut().enumerate() {
+        let (source_factor, destination_factor) = if channel < 3 {
+            (factor(source_rgb), factor(destination_rgb))
+        } else {
+            (factor(source_alpha), factor(destination_alpha))
+        };
+        *value = source[channel] * source_factor + destination[channel] * destination_factor;
+    }
+    result
+}
+
+fn is_defined(source: &str, name: &str) -> bool {
+    source.lines().any(|line| {
+        let mut words = line.split_whitespace();
+        words.next() == Some("#define") && words.next() == Some(name)
+    })
+}
+
+fn intersect(a: [i64; 4], b: [i64; 4]) -> [i64; 4] {
+    [
+        a[0].max(b[0]),
+        a[1].max(b[1]),
+        a[2].min(b[2]),
+        a[3].min(b[3]),
+    ]
+}
+
+fn to_rect(rect: [i32; 4]) -> [i64; 4] {
+    let [x, y, width, height] = rect;
+    [
+        x as i64,
+        y as i64,
+        x as i64 + width as i64,
+        y as i64 + height as i64,
+    ]
+}
+
+fn to_rgba8(color: [f32; 4]) -> [u8; 4] {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [
+        channel(color[0]),
+        channel(color[1]),
+        channel(color[2]),
+        channel(color[3]),
+    ]
+}
+
+fn normalize(value: f32, max: f32, normalized: bool) -> f32 {
+    if normalized {
+        (value / max).max(-1.0)
+    } else {
+        value
+    }
+}
+
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        31 => {
+            if mantissa == 0.0 {
+                f32::INFINITY
+            } else {
+                f32::NAN
+            }
+        }
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+    f32::from_bits(read_u32(bytes, offset))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gl::Context;
+    use crate::gl_state::Capability;
+    use crate::render_target::RenderTarget;
+    use libGLESv3_sys::{GL_DEPTH_COMPONENT24, GL_RGBA8};
+    use std::rc::Rc;
+
+    #[test]
+    fn clears_inside_scissor() {
+        let gl = Rc::new(SoftGl::new());
+        let context = Context::with_backend(gl.clone());
+        let target = RenderTarget::builder(&context, 4, 4)
+            .color_texture(GL_RGBA8)
+            .depth_renderbuffer(GL_DEPTH_COMPONENT24)
+            .build()
+            .unwrap();
+        target.bind();
+        unsafe {
+            gl_check!(glClearColor(1.0, 0.0, 0.0, 1.0));
+            gl_check!(glClear(GL_COLOR_BUFFER_BIT));
+            context.state().enable(Capability::ScissorTest);
+            context.state().scissor(1, 0, 2, 1);
+            gl_check!(glClearColor(0.0, 1.0, 0.0, 1.0));
+            gl_check!(glClear(GL_COLOR_BUFFER_BIT));
+        }
+        let image = gl
+            .texture_image(target.color_texture().unwrap().texture())
+            .unwrap();
+        let pixel = |x: usize, y: usize| &image.data[(y * image.width + x) * 4..][..4];
+        assert_eq!(pixel(0, 3), [255, 0, 0, 255]);
+        assert_eq!(pixel(1, 3), [0, 255, 0, 255]);
+        assert_eq!(pixel(2, 3), [0, 255, 0, 255]);
+        assert_eq!(pixel(3, 3), [255, 0, 0, 255]);
+        assert_eq!(pixel(1, 2), [255, 0, 0, 255]);
+        assert!(gl.mock().errors().is_empty());
+    }
+}
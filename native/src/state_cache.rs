@@ -0,0 +1,387 @@
+use crate::gl_state::{BlendFunc, Capability, GlState, Object, StateChange};
+use libGLESv3_sys::{
+    GLboolean, GLenum, GLfloat, GLint, GLsizei, GLuint, GL_ACTIVE_TEXTURE, GL_BLEND,
+    GL_BLEND_DST_ALPHA, GL_BLEND_DST_RGB, GL_BLEND_SRC_ALPHA, GL_BLEND_SRC_RGB, GL_CULL_FACE,
+    GL_CURRENT_PROGRAM, GL_DEPTH_FUNC, GL_DEPTH_TEST, GL_DEPTH_WRITEMASK, GL_DRAW_FRAMEBUFFER,
+    GL_DRAW_FRAMEBUFFER_BINDING, GL_POLYGON_OFFSET_FACTOR, GL_POLYGON_OFFSET_FILL,
+    GL_POLYGON_OFFSET_UNITS, GL_READ_FRAMEBUFFER, GL_READ_FRAMEBUFFER_BINDING, GL_SAMPLER_BINDING,
+    GL_SCISSOR_BOX, GL_SCISSOR_TEST, GL_TEXTURE0, GL_TEXTURE_2D, GL_TEXTURE_2D_ARRAY,
+    GL_TEXTURE_3D, GL_TEXTURE_BINDING_2D, GL_TEXTURE_BINDING_2D_ARRAY, GL_TEXTURE_BINDING_3D,
+    GL_TEXTURE_BINDING_CUBE_MAP, GL_TEXTURE_CUBE_MAP, GL_VERTEX_ARRAY_BINDING, GL_VIEWPORT,
+};
+
+pub struct StateCache {
+    shadow: GlState,
+    validate: bool,
+}
+
+impl StateCache {
+    pub fn new(validate: bool) -> StateCache {
+        StateCache {
+            shadow: GlState::default(),
+            validate,
+        }
+    }
+
+    pub fn enable(&mut self, capability: Capability) {
+        self.set(StateChange::Enable(capability, true));
+    }
+
+    pub fn disable(&mut self, capability: Capability) {
+        self.set(StateChange::Enable(capability, false));
+    }
+
+    pub fn use_program(&mut self, program: GLuint) {
+        self.set(StateChange::UseProgram(program));
+    }
+
+    pub fn bind_vertex_array(&mut self, vertex_array: GLuint) {
+        self.set(StateChange::BindVertexArray(vertex_array));
+    }
+
+    pub fn bind_draw_framebuffer(&mut self, framebuffer: GLuint) {
+        self.set(StateChange::BindDrawFramebuffer(framebuffer));
+    }
+
+    pub fn bind_read_framebuffer(&mut self, framebuffer: GLuint) {
+        self.set(StateChange::BindReadFramebuffer(framebuffer));
+    }
+
+    pub fn bind_texture(&mut self, unit: GLuint, target: GLenum, texture: GLuint) {
+        if self.shadow.texture(unit, target) == Some(texture) {
+            return;
+        }
+        self.set(StateChange::ActiveTexture(unit));
+        self.set(StateChange::BindTexture(target, texture));
+    }
+
+    pub fn bind_sampler(&mut self, unit: GLuint, sampler: GLuint) {
+        self.set(StateChange::BindSampler(unit, sampler));
+    }
+
+    pub fn viewport(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        self.set(StateChange::Viewport([x, y, width, height]));
+    }
+
+    pub fn scissor(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        self.set(StateChange::Scissor([x, y, width, height]));
+    }
+
+    pub fn blend_func(&mut self, blend_func: BlendFunc) {
+        self.set(StateChange::BlendFunc(blend_func));
+    }
+
+    pub fn depth_func(&mut self, depth_func: GLenum) {
+        self.set(StateChange::DepthFunc(depth_func));
+    }
+
+    pub fn depth_mask(&mut self, depth_mask: bool) {
+        self.set(StateChange::DepthMask(depth_mask));
+    }
+
+    pub fn polygon_offset(&mut self, factor: GLfloat, units: GLfloat) {
+        self.set(StateChange::PolygonOffset(factor, units));
+    }
+
+    pub fn forget(&mut self, object: Object, name: GLuint) {
+        self.shadow.forget(object, name);
+    }
+
+    pub fn invalidate(&mut self) {
+        logv!("invalidate GL state cache");
+        self.shadow.invalidate();
+    }
+
+    pub fn validate(&self) {
+        let mismatches = self.shadow.mismatches(&unsafe { query(&self.shadow) });
+        if !mismatches.is_empty() {
+            panic!("GL state cache is out of sync: {}", mismatches.join(", "));
+        }
+    }
+
+    fn set(&mut self, change: StateChange) {
+        if !self.shadow.apply(change) {
+            return;
+        }
+        unsafe {
+            execute(change);
+        }
+        if self.validate {
+            self.validate();
+        }
+    }
+}
+
+unsafe fn execute(change: StateChange) {
+    match change {
+        StateChange::Enable(capability, true) => {
+            gl_check!(glEnable(get_capability_enum(capability)))
+        }
+        StateChange::Enable(capability, false) => {
+            gl_check!(glDisable(get_capability_enum(capability)))
+        }
+        StateChange::UseProgram(program) => gl_check!(glUseProgram(program)),
+        StateChange::BindVertexArray(vertex_array) => {
+            gl_check!(glBindVertexArray(vertex_array))
+        }
+        StateChange::BindDrawFramebuffer(framebuffer) => {
+            gl_check!(glBindFramebuffer(GL_DRAW_FRAMEBUFFER, framebuffer))
+        }
+        StateChange::BindReadFramebuffer(framebuffer) => {
+            gl_check!(glBindFramebuffer(GL_READ_FRAMEBUFFER, framebuffer))
+        }
+        StateChange::ActiveTexture(unit) => gl_check!(glActiveTexture(GL_TEXTURE0 + unit)),
+        StateChange::BindTexture(target, texture) => gl_check!(glBindTexture(target, texture)),
+        StateChange::BindSampler(unit, sampler) => gl_check!(glBindSampler(unit, sampler)),
+        StateChange::Viewport([x, y, width, height]) => {
+            gl_check!(glViewport(x, y, width, height))
+        }
+        StateChange::Scissor([x, y, width, height]) => {
+            gl_check!(glScissor(x, y, width, height))
+        }
+        StateChange::BlendFunc(blend_func) => gl_check!(glBlendFuncSeparate(
+            blend_func.src_rgb,
+            blend_func.dst_rgb,
+            blend_func.src_alpha,
+            blend_func.dst_alpha,
+        )),
+        StateChange::DepthFunc(depth_func) => gl_check!(glDepthFunc(depth_func)),
+        StateChange::DepthMask(depth_mask) => gl_check!(glDepthMask(depth_mask as GLboolean)),
+        StateChange::PolygonOffset(factor, units) => gl_check!(glPolygonOffset(factor, units)),
+    }
+}
+
+unsafe fn query(shadow: &GlState) -> GlState {
+    let integer = |name: GLenum| {
+        let mut value: GLint = 0;
+        gl_check!(glGetIntegerv(name, &mut value));
+        value
+    };
+    let rect = |name: GLenum| {
+        let mut value: [GLint; 4] = [0; 4];
+        gl_check!(glGetIntegerv(name, value.as_mut_ptr()));
+        value
+    };
+    let float = |name: GLenum| {
+        let mut value: GLfloat = 0.0;
+        gl_check!(glGetFloatv(name, &mut value));
+        value
+    };
+
+    let mut actual = GlState::default();
+    for capability in Capability::ALL.iter().cloned() {
+        actual.capabilities.insert(
+            capability,
+            gl_check!(glIsEnabled(get_capability_enum(capability))) != 0,
+        );
+    }
+    actual.program = Some(integer(GL_CURRENT_PROGRAM) as GLuint);
+    actual.vertex_array = Some(integer(GL_VERTEX_ARRAY_BINDING) as GLuint);
+    actual.draw_framebuffer = Some(integer(GL_DRAW_FRAMEBUFFER_BINDING) as GLuint);
+    actual.read_framebuffer = Some(integer(GL_READ_FRAMEBUFFER_BINDING) as GLuint);
+    let active_texture = integer(GL_ACTIVE_TEXTURE) as GLuint - GL_TEXTURE0;
+    actual.active_texture = Some(active_texture);
+    for (unit, target) in shadow.textures.keys().cloned() {
+        if let Some(binding) = get_texture_binding_enum(target) {
+            gl_check!(glActiveTexture(GL_TEXTURE0 + unit));
+            actual
+                .textures
+                .insert((unit, target), integer(binding) as GLuint);
+        }
+    }
+    for unit in shadow.samplers.keys().cloned() {
+        gl_check!(glActiveTexture(GL_TEXTURE0 + unit));
+        actual
+            .samplers
+            .insert(unit, integer(GL_SAMPLER_BINDING) as GLuint);
+    }
+    gl_check!(glActiveTexture(GL_TEXTURE0 + active_texture));
+    actual.viewport = Some(rect(GL_VIEWPORT));
+    actual.scissor = Some(rect(GL_SCISSOR_BOX));
+    actual.blend_func = Some(BlendFunc {
+        src_rgb: integer(GL_BLEND_SRC_RGB) as GLenum,
+        dst_rgb: integer(GL_BLEND_DST_RGB) as GLenum,
+        src_alpha: integer(GL_BLEND_SRC_ALPHA) as GLenum,
+        dst_alpha: integer(GL_BLEND_DST_ALPHA) as GLenum,
+    });
+    actual.depth_func = Some(integer(GL_DEPTH_FUNC) as GLenum);
+    let mut depth_mask: GLboolean = 0;
+    gl_check!(glGetBooleanv(GL_DEPTH_WRITEMASK, &mut depth_mask));
+    actual.depth_mask = Some(depth_mask != 0);
+    actual.polygon_offset = Some((
+        float(GL_POLYGON_OFFSET_FACTOR),
+        float(GL_POLYGON_OFFSET_UNITS),
+    ));
+    actual
+}
+
+fn get_capability_enum(capability: Capability) -> GLenum {
+    match capability {
+        Capability::Blend => GL_BLEND,
+        Capability::CullFace => GL_CULL_FACE,
+        Capability::DepthTest => GL_DEPTH_TEST,
+        Capability::PolygonOffsetFill => GL_POLYGON_OFFSET_FILL,
+        Capability::ScissorTest => GL_SCISSOR_TEST,
+    }
+}
+
+fn get_texture_binding_enum(target: GLenum) -> Option<GLenum> {
+    match target {
+        GL_TEXTURE_2D => Some(GL_TEXTURE_BINDING_2D),
+        GL_TEXTURE_2D_ARRAY => Some(GL_TEXTURE_BINDING_2D_ARRAY),
+        GL_TEXTURE_3D => Some(GL_TEXTURE_BINDING_3D),
+        GL_TEXTURE_CUBE_MAP => Some(GL_TEXTURE_BINDING_CUBE_MAP),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gl::Context;
+    use crate::mock_gl::MockGl;
+    use libGLESv3_sys::{GL_LESS, GL_ONE, GL_ONE_MINUS_SRC_ALPHA, GL_SRC_ALPHA};
+    use std::rc::Rc;
+
+    const BLEND_FUNC: BlendFunc = BlendFunc {
+        src_rgb: GL_SRC_ALPHA,
+        dst_rgb: GL_ONE_MINUS_SRC_ALPHA,
+        src_alpha: GL_ONE,
+        dst_alpha: GL_ONE_MINUS_SRC_ALPHA,
+    };
+
+    struct Names {
+        programs: [GLuint; 2],
+        vertex_array: GLuint,
+        framebuffer: GLuint,
+        texture: GLuint,
+        sampler: GLuint,
+    }
+
+    impl Names {
+        fn new() -> Names {
+            let mut names = Names {
+                programs: [0; 2],
+                vertex_array: 0,
+                framebuffer: 0,
+                texture: 0,
+                sampler: 0,
+            };
+            unsafe {
+                names.programs = [gl_check!(glCreateProgram()), gl_check!(glCreateProgram())];
+                gl_check!(glGenVertexArrays(1, &mut names.vertex_array));
+                gl_check!(glGenFramebuffers(1, &mut names.framebuffer));
+                gl_check!(glGenTextures(1, &mut names.texture));
+                gl_check!(glGenSamplers(1, &mut names.sampler));
+            }
+            names
+        }
+    }
+
+    fn set_state(cache: &mut StateCache, names: &Names) {
+        cache.enable(Capability::DepthTest);
+        cache.disable(Capability::Blend);
+        cache.use_program(names.programs[0]);
+        cache.bind_vertex_array(names.vertex_array);
+        cache.bind_draw_framebuffer(names.framebuffer);
+        cache.bind_texture(1, GL_TEXTURE_2D, names.texture);
+        cache.bind_sampler(1, names.sampler);
+        cache.viewport(0, 0, 64, 32);
+        cache.scissor(8, 8, 16, 16);
+        cache.blend_func(BLEND_FUNC);
+        cache.depth_func(GL_LESS);
+        cache.depth_mask(false);
+        cache.polygon_offset(1.0, 2.0);
+    }
+
+    fn functions(gl: &MockGl) -> Vec<&'static str> {
+        let functions = gl.calls().iter().map(|call| call.function).collect();
+        gl.clear_calls();
+        functions
+    }
+
+    #[test]
+    fn skips_redundant_state_changes() {
+        let gl = Rc::new(MockGl::new());
+        let _context = Context::with_backend(gl.clone());
+        let names = Names::new();
+        let mut cache = StateCache::new(false);
+        gl.clear_calls();
+
+        set_state(&mut cache, &names);
+        assert_eq!(
+            functions(&gl),
+            [
+                "glEnable",
+                "glDisable",
+                "glUseProgram",
+                "glBindVertexArray",
+                "glBindFramebuffer",
+                "glActiveTexture",
+                "glBindTexture",
+                "glBindSampler",
+                "glViewport",
+                "glScissor",
+                "glBlendFuncSeparate",
+                "glDepthFunc",
+                "glDepthMask",
+                "glPolygonOffset",
+            ]
+        );
+        set_state(&mut cache, &names);
+        assert!(functions(&gl).is_empty());
+
+        cache.bind_texture(0, GL_TEXTURE_2D, names.texture);
+        assert_eq!(functions(&gl), ["glActiveTexture", "glBindTexture"]);
+        cache.bind_texture(1, GL_TEXTURE_2D, names.texture);
+        assert!(functions(&gl).is_empty());
+        cache.use_program(names.programs[1]);
+        assert_eq!(functions(&gl), ["glUseProgram"]);
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn reemits_state_after_invalidate() {
+        let gl = Rc::new(MockGl::new());
+        let _context = Context::with_backend(gl.clone());
+        let names = Names::new();
+        let mut cache = StateCache::new(false);
+        set_state(&mut cache, &names);
+        gl.clear_calls();
+
+        cache.invalidate();
+        set_state(&mut cache, &names);
+        assert_eq!(functions(&gl).len(), 14);
+
+        cache.forget(Object::Texture, names.texture);
+        cache.forget(Object::Program, names.programs[0]);
+        set_state(&mut cache, &names);
+        assert_eq!(functions(&gl), ["glUseProgram", "glBindTexture"]);
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn validates_against_driver_state() {
+        let gl = Rc::new(MockGl::new());
+        let _context = Context::with_backend(gl.clone());
+        let names = Names::new();
+        let mut cache = StateCache::new(true);
+        set_state(&mut cache, &names);
+        cache.validate();
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "GL state cache is out of sync")]
+    fn detects_state_changed_behind_its_back() {
+        let gl = Rc::new(MockGl::new());
+        let _context = Context::with_backend(gl.clone());
+        let names = Names::new();
+        let mut cache = StateCache::new(false);
+        cache.use_program(names.programs[0]);
+        unsafe {
+            gl_check!(glUseProgram(names.programs[1]));
+        }
+        cache.validate();
+    }
+}
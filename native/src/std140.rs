@@ -0,0 +1,462 @@
+macro_rules! std140_struct {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident: $type:ty,)*
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $($field_vis $field: $type,)*
+        }
+
+        impl $crate::std140::Std140Block for $name {
+            fn layout() -> $crate::std140::Std140Layout {
+                $crate::std140::Std140Layout::builder()
+                    $(.field(
+                        stringify!($field),
+                        <$type as $crate::std140::Std140Value>::TYPE,
+                    ))*
+                    .build()
+            }
+
+            fn size() -> usize {
+                let mut offset = 0;
+                $(
+                    let type_ = <$type as $crate::std140::Std140Value>::TYPE;
+                    offset = $crate::std140::align(offset, type_.alignment()) + type_.size();
+                )*
+                $crate::std140::align(offset, 16)
+            }
+
+            fn write(&self, writer: &mut $crate::std140::Std140Writer) {
+                $(writer.write(&self.$field);)*
+            }
+        }
+    };
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Type {
+    Float,
+    Int,
+    Uint,
+    Bool,
+    Vec2,
+    Vec3,
+    Vec4,
+    Ivec2,
+    Ivec3,
+    Ivec4,
+    Uvec2,
+    Uvec3,
+    Uvec4,
+    Mat2,
+    Mat3,
+    Mat4,
+}
+
+impl Type {
+    pub fn alignment(self) -> usize {
+        match self {
+            Type::Float | Type::Int | Type::Uint | Type::Bool => 4,
+            Type::Vec2 | Type::Ivec2 | Type::Uvec2 => 8,
+            _ => 16,
+        }
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            Type::Float | Type::Int | Type::Uint | Type::Bool => 4,
+            Type::Vec2 | Type::Ivec2 | Type::Uvec2 => 8,
+            Type::Vec3 | Type::Ivec3 | Type::Uvec3 => 12,
+            Type::Vec4 | Type::Ivec4 | Type::Uvec4 => 16,
+            Type::Mat2 => 32,
+            Type::Mat3 => 48,
+            Type::Mat4 => 64,
+        }
+    }
+
+    pub fn array_stride(self) -> usize {
+        align(self.size(), 16)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Field {
+    pub name: &'static str,
+    pub offset: usize,
+    pub size: usize,
+    pub stride: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Std140Layout {
+    fields: Vec<Field>,
+    alignment: usize,
+    size: usize,
+}
+
+impl Std140Layout {
+    pub fn builder() -> Std140LayoutBuilder {
+        Std140LayoutBuilder {
+            fields: Vec::new(),
+            alignment: 16,
+            offset: 0,
+        }
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+pub struct Std140LayoutBuilder {
+    fields: Vec<Field>,
+    alignment: usize,
+    offset: usize,
+}
+
+impl Std140LayoutBuilder {
+    pub fn field(self, name: &'static str, type_: Type) -> Std140LayoutBuilder {
+        self.push(name, type_.alignment(), type_.size(), 0)
+    }
+
+    pub fn array(self, name: &'static str, type_: Type, count: usize) -> Std140LayoutBuilder {
+        let stride = type_.array_stride();
+        self.push(name, stride, stride * count, stride)
+    }
+
+    pub fn structure(self, name: &'static str, layout: &Std140Layout) -> Std140LayoutBuilder {
+        self.push(name, layout.alignment, layout.size, 0)
+    }
+
+    pub fn structure_array(
+        self,
+        name: &'static str,
+        layout: &Std140Layout,
+        count: usize,
+    ) -> Std140LayoutBuilder {
+        let stride = align(layout.size, layout.alignment);
+        self.push(name, layout.alignment, stride * count, stride)
+    }
+
+    pub fn build(self) -> Std140Layout {
+        Std140Layout {
+            size: align(self.offset, self.alignment),
+            fields: self.fields,
+            alignment: self.alignment,
+        }
+    }
+
+    fn push(
+        mut self,
+        name: &'static str,
+        alignment: usize,
+        size: usize,
+        stride: usize,
+    ) -> Std140LayoutBuilder {
+        let offset = align(self.offset, alignment);
+        self.fields.push(Field {
+            name,
+            offset,
+            size,
+            stride,
+        });
+        self.alignment = self.alignment.max(align(alignment, 16));
+        self.offset = offset + size;
+        self
+    }
+}
+
+pub trait Std140Value {
+    const TYPE: Type;
+
+    fn write(&self, bytes: &mut [u8]);
+}
+
+impl Std140Value for f32 {
+    const TYPE: Type = Type::Float;
+
+    fn write(&self, bytes: &mut [u8]) {
+        bytes[0..4].copy_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl Std140Value for i32 {
+    const TYPE: Type = Type::Int;
+
+    fn write(&self, bytes: &mut [u8]) {
+        bytes[0..4].copy_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl Std140Value for u32 {
+    const TYPE: Type = Type::Uint;
+
+    fn write(&self, bytes: &mut [u8]) {
+        bytes[0..4].copy_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl Std140Value for bool {
+    const TYPE: Type = Type::Bool;
+
+    fn write(&self, bytes: &mut [u8]) {
+        (*self as u32).write(bytes);
+    }
+}
+
+impl Std140Value for [f32; 2] {
+    const TYPE: Type = Type::Vec2;
+
+    fn write(&self, bytes: &mut [u8]) {
+        write_floats(self, bytes);
+    }
+}
+
+impl Std140Value for [f32; 3] {
+    const TYPE: Type = Type::Vec3;
+
+    fn write(&self, bytes: &mut [u8]) {
+        write_floats(self, bytes);
+    }
+}
+
+impl Std140Value for [f32; 4] {
+    const TYPE: Type = Type::Vec4;
+
+    fn write(&self, bytes: &mut [u8]) {
+        write_floats(self, bytes);
+    }
+}
+
+macro_rules! impl_integer_vector {
+    ($($type:ty => $std140_type:ident),*) => {
+        $(
+            impl Std140Value for $type {
+                const TYPE: Type = Type::$std140_type;
+
+                fn write(&self, bytes: &mut [u8]) {
+                    for (index, value) in self.iter().enumerate() {
+                        value.write(&mut bytes[index * 4..]);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_vector!(
+    [i32; 2] => Ivec2,
+    [i32; 3] => Ivec3,
+    [i32; 4] => Ivec4,
+    [u32; 2] => Uvec2,
+    [u32; 3] => Uvec3,
+    [u32; 4] => Uvec4
+);
+
+impl Std140Value for [[f32; 2]; 2] {
+    const TYPE: Type = Type::Mat2;
+
+    fn write(&self, bytes: &mut [u8]) {
+        for (index, column) in self.iter().enumerate() {
+            write_floats(column, &mut bytes[index * 16..]);
+        }
+    }
+}
+
+impl Std140Value for [[f32; 3]; 3] {
+    const TYPE: Type = Type::Mat3;
+
+    fn write(&self, bytes: &mut [u8]) {
+        for (index, column) in self.iter().enumerate() {
+            write_floats(column, &mut bytes[index * 16..]);
+        }
+    }
+}
+
+impl Std140Value for [[f32; 4]; 4] {
+    const TYPE: Type = Type::Mat4;
+
+    fn write(&self, bytes: &mut [u8]) {
+        for (index, column) in self.iter().enumerate() {
+            write_floats(column, &mut bytes[index * 16..]);
+        }
+    }
+}
+
+pub trait Std140Block {
+    fn layout() -> Std140Layout;
+
+    fn size() -> usize;
+
+    fn write(&self, writer: &mut Std140Writer);
+}
+
+pub struct Std140Writer<'a> {
+    bytes: &'a mut [u8],
+    offset: usize,
+}
+
+impl<'a> Std140Writer<'a> {
+    pub fn new(bytes: &'a mut [u8]) -> Std140Writer<'a> {
+        Std140Writer { bytes, offset: 0 }
+    }
+
+    pub fn write<T: Std140Value>(&mut self, value: &T) {
+        self.offset = align(self.offset, T::TYPE.alignment());
+        value.write(&mut self.bytes[self.offset..]);
+        self.offset += T::TYPE.size();
+    }
+}
+
+pub fn align(offset: usize, alignment: usize) -> usize {
+    offset.div_ceil(alignment) * alignment
+}
+
+fn write_floats(values: &[f32], bytes: &mut [u8]) {
+    for (index, value) in values.iter().enumerate() {
+        value.write(&mut bytes[index * 4..]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    std140_struct! {
+        struct Light {
+            direction: [f32; 3],
+            intensity: f32,
+            color: [f32; 3],
+        }
+    }
+
+    std140_struct! {
+        struct Transform {
+            scale: f32,
+            model: [[f32; 4]; 4],
+            normal: [[f32; 3]; 3],
+            offset: [f32; 2],
+        }
+    }
+
+    fn offsets(layout: &Std140Layout) -> Vec<(&'static str, usize, usize)> {
+        layout
+            .fields()
+            .iter()
+            .map(|field| (field.name, field.offset, field.size))
+            .collect()
+    }
+
+    #[test]
+    fn packs_float_after_vec3() {
+        assert_eq!(
+            offsets(&Light::layout()),
+            [
+                ("direction", 0, 12),
+                ("intensity", 12, 4),
+                ("color", 16, 12)
+            ]
+        );
+        assert_eq!(Light::size(), 32);
+        assert_eq!(Light::layout().size(), 32);
+
+        let mut bytes = [0xff; 32];
+        Light {
+            direction: [1.0, 2.0, 3.0],
+            intensity: 4.0,
+            color: [5.0, 6.0, 7.0],
+        }
+        .write(&mut Std140Writer::new(&mut bytes));
+        let floats: Vec<f32> = bytes
+            .chunks(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        assert_eq!(floats[..7], [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+    }
+
+    #[test]
+    fn aligns_matrices_to_vec4() {
+        assert_eq!(
+            offsets(&Transform::layout()),
+            [
+                ("scale", 0, 4),
+                ("model", 16, 64),
+                ("normal", 80, 48),
+                ("offset", 128, 8),
+            ]
+        );
+        assert_eq!(Transform::size(), 144);
+        assert_eq!(Transform::layout().size(), 144);
+
+        let mut bytes = [0; 144];
+        let mut normal = [[0.0; 3]; 3];
+        normal[1][2] = 9.0;
+        Transform {
+            scale: 0.0,
+            model: [[0.0; 4]; 4],
+            normal,
+            offset: [0.0; 2],
+        }
+        .write(&mut Std140Writer::new(&mut bytes));
+        assert_eq!(bytes[80 + 16 + 8..80 + 16 + 12], 9.0f32.to_le_bytes());
+    }
+
+    #[test]
+    fn rounds_array_strides_up_to_vec4() {
+        let layout = Std140Layout::builder()
+            .field("count", Type::Int)
+            .array("weights", Type::Float, 3)
+            .array("positions", Type::Vec3, 2)
+            .field("last", Type::Float)
+            .build();
+        assert_eq!(
+            offsets(&layout),
+            [
+                ("count", 0, 4),
+                ("weights", 16, 48),
+                ("positions", 64, 32),
+                ("last", 96, 4),
+            ]
+        );
+        assert_eq!(layout.field("weights").unwrap().stride, 16);
+        assert_eq!(layout.field("positions").unwrap().stride, 16);
+        assert_eq!(layout.size(), 112);
+    }
+
+    #[test]
+    fn aligns_nested_structures() {
+        let inner = Std140Layout::builder()
+            .field("value", Type::Float)
+            .field("offset", Type::Vec2)
+            .build();
+        assert_eq!(inner.size(), 16);
+        let layout = Std140Layout::builder()
+            .field("flag", Type::Bool)
+            .structure("inner", &inner)
+            .field("scale", Type::Float)
+            .structure_array("items", &inner, 2)
+            .build();
+        assert_eq!(
+            offsets(&layout),
+            [
+                ("flag", 0, 4),
+                ("inner", 16, 16),
+                ("scale", 32, 4),
+                ("items", 48, 32),
+            ]
+        );
+        assert_eq!(layout.field("items").unwrap().stride, 16);
+        assert_eq!(layout.size(), 80);
+    }
+}
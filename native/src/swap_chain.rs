@@ -1,127 +1,77 @@
+use crate::gl::Context;
+use crate::render_target::RenderTarget;
 use libGLESv3_sys::{
-    GLenum, GLint, GLsizei, GLuint, GL_CLAMP_TO_EDGE, GL_COLOR_ATTACHMENT0, GL_DEPTH_ATTACHMENT,
-    GL_DEPTH_COMPONENT24, GL_DRAW_FRAMEBUFFER, GL_FRAMEBUFFER_COMPLETE,
-    GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT, GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT,
-    GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE, GL_FRAMEBUFFER_UNDEFINED, GL_FRAMEBUFFER_UNSUPPORTED,
-    GL_LINEAR, GL_RENDERBUFFER, GL_RGBA8, GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER,
-    GL_TEXTURE_MIN_FILTER, GL_TEXTURE_WRAP_S, GL_TEXTURE_WRAP_T,
+    GLint, GLsizei, GLuint, GL_CLAMP_TO_EDGE, GL_DEPTH_COMPONENT24, GL_LINEAR, GL_RGBA8,
+    GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_TEXTURE_MIN_FILTER, GL_TEXTURE_WRAP_S,
+    GL_TEXTURE_WRAP_T,
 };
-use libvrapi_sys::{ovrTextureSwapChain, ovrTextureType__VRAPI_TEXTURE_TYPE_2D};
+#[cfg(target_os = "android")]
+use libvrapi_sys::ovrTextureSwapChain;
 
 pub struct SwapChain {
     length: GLsizei,
     width: GLsizei,
     height: GLsizei,
-    color_swap_chain: *mut ovrTextureSwapChain,
-    depth_buffers: Vec<GLuint>,
-    frame_buffers: Vec<GLuint>,
+    render_targets: Vec<RenderTarget>,
+    color_swap_chain: platform::ColorSwapChain,
     index: GLsizei,
 }
 
 impl SwapChain {
-    pub fn new(width: GLsizei, height: GLsizei) -> SwapChain {
+    pub fn new(context: &Context, width: GLsizei, height: GLsizei, samples: GLsizei) -> SwapChain {
         unsafe {
             logi!("create color swap chain");
-            let color_swap_chain = libvrapi_sys::vrapi_CreateTextureSwapChain3(
-                ovrTextureType__VRAPI_TEXTURE_TYPE_2D,
-                GL_RGBA8 as i64,
-                width,
-                height,
-                1,
-                3,
-            );
-            if color_swap_chain.is_null() {
-                panic!("can't create color swap chain");
-            }
-
-            let length = libvrapi_sys::vrapi_GetTextureSwapChainLength(color_swap_chain) as GLsizei;
+            let color_swap_chain = platform::ColorSwapChain::new(context, width, height);
+            let length = color_swap_chain.length();
 
             for index in 0..length {
                 logv!("initialize color texture {}", index);
-                libGLESv3_sys::glBindTexture(
-                    GL_TEXTURE_2D,
-                    libvrapi_sys::vrapi_GetTextureSwapChainHandle(color_swap_chain, index),
-                );
-                libGLESv3_sys::glTexParameteri(
+                context
+                    .state()
+                    .bind_texture(0, GL_TEXTURE_2D, color_swap_chain.texture(index));
+                gl_check!(glTexParameteri(
                     GL_TEXTURE_2D,
                     GL_TEXTURE_MIN_FILTER,
                     GL_LINEAR as GLint,
-                );
-                libGLESv3_sys::glTexParameteri(
+                ));
+                gl_check!(glTexParameteri(
                     GL_TEXTURE_2D,
                     GL_TEXTURE_MAG_FILTER,
                     GL_LINEAR as GLint,
-                );
-                libGLESv3_sys::glTexParameteri(
+                ));
+                gl_check!(glTexParameteri(
                     GL_TEXTURE_2D,
                     GL_TEXTURE_WRAP_S,
                     GL_CLAMP_TO_EDGE as GLint,
-                );
-                libGLESv3_sys::glTexParameteri(
+                ));
+                gl_check!(glTexParameteri(
                     GL_TEXTURE_2D,
                     GL_TEXTURE_WRAP_T,
                     GL_CLAMP_TO_EDGE as GLint,
-                );
-                libGLESv3_sys::glBindTexture(GL_TEXTURE_2D, 0);
-            }
-
-            logi!("generate depth buffers");
-            let mut depth_buffers = Vec::with_capacity(length as usize);
-            libGLESv3_sys::glGenRenderbuffers(length, depth_buffers.as_mut_ptr());
-            depth_buffers.set_len(length as usize);
-            for index in 0..length {
-                logv!("initialize depth buffer {}", index);
-                libGLESv3_sys::glBindRenderbuffer(GL_RENDERBUFFER, depth_buffers[index as usize]);
-                libGLESv3_sys::glRenderbufferStorage(
-                    GL_RENDERBUFFER,
-                    GL_DEPTH_COMPONENT24,
-                    width,
-                    height,
-                );
-                libGLESv3_sys::glBindRenderbuffer(GL_RENDERBUFFER, 0);
+                ));
+                context.state().bind_texture(0, GL_TEXTURE_2D, 0);
             }
 
-            logi!("generate frame buffers");
-            let mut frame_buffers = Vec::with_capacity(length as usize);
-            libGLESv3_sys::glGenFramebuffers(length, frame_buffers.as_mut_ptr());
-            frame_buffers.set_len(length as usize);
+            let mut render_targets = Vec::with_capacity(length as usize);
             for index in 0..length {
-                logv!("initialize frame buffer {}", index);
-                libGLESv3_sys::glBindFramebuffer(
-                    GL_DRAW_FRAMEBUFFER,
-                    frame_buffers[index as usize],
-                );
-                libGLESv3_sys::glFramebufferTexture2D(
-                    GL_DRAW_FRAMEBUFFER,
-                    GL_COLOR_ATTACHMENT0,
-                    GL_TEXTURE_2D,
-                    libvrapi_sys::vrapi_GetTextureSwapChainHandle(color_swap_chain, index),
-                    0,
-                );
-                libGLESv3_sys::glFramebufferRenderbuffer(
-                    GL_DRAW_FRAMEBUFFER,
-                    GL_DEPTH_ATTACHMENT,
-                    GL_RENDERBUFFER,
-                    depth_buffers[index as usize],
-                );
-                let status = libGLESv3_sys::glCheckFramebufferStatus(GL_DRAW_FRAMEBUFFER);
-                if status != GL_FRAMEBUFFER_COMPLETE {
-                    panic!(
-                        "can't initialize framebuffer {}: {}",
-                        index,
-                        get_framebuffer_status_string(status)
-                    );
-                }
-                libGLESv3_sys::glBindFramebuffer(GL_DRAW_FRAMEBUFFER, 0);
+                logv!("initialize render target {}", index);
+                let render_target = RenderTarget::builder(context, width, height)
+                    .color_external(color_swap_chain.texture(index), GL_RGBA8)
+                    .depth_renderbuffer(GL_DEPTH_COMPONENT24)
+                    .samples(samples)
+                    .build()
+                    .unwrap_or_else(|error| {
+                        panic!("can't initialize render target {}: {}", index, error)
+                    });
+                render_targets.push(render_target);
             }
 
             SwapChain {
                 length,
                 width,
                 height,
+                render_targets,
                 color_swap_chain,
-                depth_buffers,
-                frame_buffers,
                 index: 0,
             }
         }
@@ -135,47 +85,141 @@ impl SwapChain {
         self.height
     }
 
+    #[cfg(target_os = "android")]
     pub fn color_swap_chain(&self) -> *mut ovrTextureSwapChain {
-        self.color_swap_chain
+        self.color_swap_chain.handle()
     }
 
-    pub fn frame_buffer(&self, index: usize) -> GLuint {
-        self.frame_buffers[index]
+    pub fn render_target(&self) -> &RenderTarget {
+        &self.render_targets[self.index as usize]
     }
 
     pub fn index(&self) -> GLsizei {
         self.index
     }
 
+    pub fn color_texture(&self, index: GLsizei) -> GLuint {
+        self.color_swap_chain.texture(index)
+    }
+
     pub fn advance(&mut self) {
         self.index = (self.index + 1) % self.length;
     }
 }
 
-impl Drop for SwapChain {
-    fn drop(&mut self) {
-        unsafe {
-            logi!("delete frame buffers");
-            libGLESv3_sys::glDeleteFramebuffers(self.length, self.frame_buffers.as_ptr());
+#[cfg(target_os = "android")]
+mod platform {
+    use crate::gl::Context;
+    use libGLESv3_sys::{GLsizei, GLuint, GL_RGBA8};
+    use libvrapi_sys::{ovrTextureSwapChain, ovrTextureType__VRAPI_TEXTURE_TYPE_2D};
+
+    pub struct ColorSwapChain {
+        swap_chain: *mut ovrTextureSwapChain,
+    }
+
+    impl ColorSwapChain {
+        pub fn new(_: &Context, width: GLsizei, height: GLsizei) -> ColorSwapChain {
+            let swap_chain = unsafe {
+                libvrapi_sys::vrapi_CreateTextureSwapChain3(
+                    ovrTextureType__VRAPI_TEXTURE_TYPE_2D,
+                    GL_RGBA8 as i64,
+                    width,
+                    height,
+                    1,
+                    3,
+                )
+            };
+            if swap_chain.is_null() {
+                panic!("can't create color swap chain");
+            }
+            ColorSwapChain { swap_chain }
+        }
+
+        pub fn length(&self) -> GLsizei {
+            unsafe { libvrapi_sys::vrapi_GetTextureSwapChainLength(self.swap_chain) as GLsizei }
+        }
 
-            logi!("delete depth buffers");
-            libGLESv3_sys::glDeleteRenderbuffers(self.length, self.depth_buffers.as_ptr());
+        pub fn texture(&self, index: GLsizei) -> GLuint {
+            unsafe { libvrapi_sys::vrapi_GetTextureSwapChainHandle(self.swap_chain, index) }
+        }
 
+        pub fn handle(&self) -> *mut ovrTextureSwapChain {
+            self.swap_chain
+        }
+    }
+
+    impl Drop for ColorSwapChain {
+        fn drop(&mut self) {
             logi!("destroy color swap chain");
-            libvrapi_sys::vrapi_DestroyTextureSwapChain(self.color_swap_chain);
+            unsafe {
+                libvrapi_sys::vrapi_DestroyTextureSwapChain(self.swap_chain);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+mod platform {
+    use crate::gl::Context;
+    use crate::texture::{Texture, TextureKind};
+    use libGLESv3_sys::{GLsizei, GLuint, GL_RGBA8};
+
+    const LENGTH: usize = 3;
+
+    pub struct ColorSwapChain {
+        textures: Vec<Texture>,
+    }
+
+    impl ColorSwapChain {
+        pub fn new(context: &Context, width: GLsizei, height: GLsizei) -> ColorSwapChain {
+            ColorSwapChain {
+                textures: (0..LENGTH)
+                    .map(|_| {
+                        Texture::new(
+                            context,
+                            TextureKind::Texture2D,
+                            GL_RGBA8,
+                            width,
+                            height,
+                            1,
+                            1,
+                        )
+                    })
+                    .collect(),
+            }
+        }
+
+        pub fn length(&self) -> GLsizei {
+            self.textures.len() as GLsizei
+        }
+
+        pub fn texture(&self, index: GLsizei) -> GLuint {
+            self.textures[index as usize].texture()
         }
     }
 }
 
-fn get_framebuffer_status_string(status: GLenum) -> &'static str {
-    match status as u32 {
-        GL_FRAMEBUFFER_UNDEFINED => "GL_FRAMEBUFFER_UNDEFINED",
-        GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT",
-        GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => {
-            "GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT"
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_gl::MockGl;
+    use std::rc::Rc;
+
+    #[test]
+    fn creates_complete_render_targets() {
+        for samples in [1, 4].iter().cloned() {
+            let gl = Rc::new(MockGl::new());
+            let context = Context::with_backend(gl.clone());
+            let mut swap_chain = SwapChain::new(&context, 64, 32, samples);
+            assert_eq!((swap_chain.width(), swap_chain.height()), (64, 32));
+            assert_eq!(swap_chain.length, 3);
+            assert_eq!(swap_chain.render_targets.len(), 3);
+            assert_eq!(swap_chain.render_target().samples(), samples);
+            for _ in 0..3 {
+                swap_chain.advance();
+            }
+            assert_eq!(swap_chain.index(), 0);
+            assert!(gl.errors().is_empty());
         }
-        GL_FRAMEBUFFER_UNSUPPORTED => "GL_FRAMEBUFFER_UNSUPPORTED",
-        GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE",
-        _ => panic!(),
     }
 }
@@ -0,0 +1,525 @@
+use crate::gl;
+use crate::gl::Context;
+use crate::image::Image;
+use crate::ktx2;
+use crate::ktx2::{Format, Ktx2};
+use libGLESv3_sys::{
+    GL_COMPRESSED_RGBA_ASTC_10x10_KHR, GL_COMPRESSED_RGBA_ASTC_10x5_KHR,
+    GL_COMPRESSED_RGBA_ASTC_10x6_KHR, GL_COMPRESSED_RGBA_ASTC_10x8_KHR,
+    GL_COMPRESSED_RGBA_ASTC_12x10_KHR, GL_COMPRESSED_RGBA_ASTC_12x12_KHR,
+    GL_COMPRESSED_RGBA_ASTC_4x4_KHR, GL_COMPRESSED_RGBA_ASTC_5x4_KHR,
+    GL_COMPRESSED_RGBA_ASTC_5x5_KHR, GL_COMPRESSED_RGBA_ASTC_6x5_KHR,
+    GL_COMPRESSED_RGBA_ASTC_6x6_KHR, GL_COMPRESSED_RGBA_ASTC_8x5_KHR,
+    GL_COMPRESSED_RGBA_ASTC_8x6_KHR, GL_COMPRESSED_RGBA_ASTC_8x8_KHR,
+    GL_COMPRESSED_SRGB8_ALPHA8_ASTC_10x10_KHR, GL_COMPRESSED_SRGB8_ALPHA8_ASTC_10x5_KHR,
+    GL_COMPRESSED_SRGB8_ALPHA8_ASTC_10x6_KHR, GL_COMPRESSED_SRGB8_ALPHA8_ASTC_10x8_KHR,
+    GL_COMPRESSED_SRGB8_ALPHA8_ASTC_12x10_KHR, GL_COMPRESSED_SRGB8_ALPHA8_ASTC_12x12_KHR,
+    GL_COMPRESSED_SRGB8_ALPHA8_ASTC_4x4_KHR, GL_COMPRESSED_SRGB8_ALPHA8_ASTC_5x4_KHR,
+    GL_COMPRESSED_SRGB8_ALPHA8_ASTC_5x5_KHR, GL_COMPRESSED_SRGB8_ALPHA8_ASTC_6x5_KHR,
+    GL_COMPRESSED_SRGB8_ALPHA8_ASTC_6x6_KHR, GL_COMPRESSED_SRGB8_ALPHA8_ASTC_8x5_KHR,
+    GL_COMPRESSED_SRGB8_ALPHA8_ASTC_8x6_KHR, GL_COMPRESSED_SRGB8_ALPHA8_ASTC_8x8_KHR, GLenum,
+    GLsizei, GLuint, GL_COMPRESSED_R11_EAC, GL_COMPRESSED_RG11_EAC, GL_COMPRESSED_RGB8_ETC2,
+    GL_COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2, GL_COMPRESSED_RGBA8_ETC2_EAC,
+    GL_COMPRESSED_SRGB8_ALPHA8_ETC2_EAC, GL_COMPRESSED_SRGB8_ETC2,
+    GL_COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2, GL_RGBA, GL_RGBA8, GL_SRGB8_ALPHA8,
+    GL_TEXTURE_2D, GL_TEXTURE_2D_ARRAY, GL_TEXTURE_CUBE_MAP, GL_TEXTURE_CUBE_MAP_POSITIVE_X,
+    GL_UNSIGNED_BYTE,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TextureKind {
+    Texture2D,
+    Texture2DArray,
+    Cube,
+}
+
+impl TextureKind {
+    pub fn target(self) -> GLenum {
+        match self {
+            TextureKind::Texture2D => GL_TEXTURE_2D,
+            TextureKind::Texture2DArray => GL_TEXTURE_2D_ARRAY,
+            TextureKind::Cube => GL_TEXTURE_CUBE_MAP,
+        }
+    }
+}
+
+pub struct Texture {
+    texture: gl::Texture,
+    kind: TextureKind,
+    internal_format: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+    layers: GLsizei,
+    levels: GLsizei,
+}
+
+impl Texture {
+    pub fn new(
+        context: &Context,
+        kind: TextureKind,
+        internal_format: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        layers: GLsizei,
+        levels: GLsizei,
+    ) -> Texture {
+        unsafe {
+            let texture = gl::Texture::new(context);
+            context
+                .state()
+                .bind_texture(0, kind.target(), texture.name());
+            match kind {
+                TextureKind::Texture2D | TextureKind::Cube => gl_check!(glTexStorage2D(
+                    kind.target(),
+                    levels,
+                    internal_format,
+                    width,
+                    height,
+                )),
+                TextureKind::Texture2DArray => gl_check!(glTexStorage3D(
+                    kind.target(),
+                    levels,
+                    internal_format,
+                    width,
+                    height,
+                    layers,
+                )),
+            }
+            Texture {
+                texture,
+                kind,
+                internal_format,
+                width,
+                height,
+                layers,
+                levels,
+            }
+        }
+    }
+
+    pub fn from_image(context: &Context, image: &Image, srgb: bool, mipmaps: bool) -> Texture {
+        let texture = Texture::new(
+            context,
+            TextureKind::Texture2D,
+            if srgb { GL_SRGB8_ALPHA8 } else { GL_RGBA8 },
+            image.width as GLsizei,
+            image.height as GLsizei,
+            1,
+            if mipmaps {
+                image.mip_level_count() as GLsizei
+            } else {
+                1
+            },
+        );
+        texture.upload(
+            0,
+            0,
+            image.width as GLsizei,
+            image.height as GLsizei,
+            &image.data,
+        );
+        if mipmaps {
+            texture.generate_mipmaps();
+        }
+        texture
+    }
+
+    pub fn from_ktx2(context: &Context, bytes: &[u8]) -> Result<Texture, String> {
+        let ktx2 = Ktx2::parse(bytes)?;
+        let (kind, layers) = match (ktx2.face_count, ktx2.layer_count) {
+            (6, _) => (TextureKind::Cube, 6),
+            (_, 0) => (TextureKind::Texture2D, 1),
+            (_, layer_count) => (TextureKind::Texture2DArray, layer_count),
+        };
+        let levels = if ktx2.generate_mipmaps {
+            crate::image::mip_level_count(ktx2.width, ktx2.height)
+        } else {
+            ktx2.levels.len()
+        };
+        let texture = Texture::new(
+            context,
+            kind,
+            get_internal_format(ktx2.format),
+            ktx2.width as GLsizei,
+            ktx2.height as GLsizei,
+            layers as GLsizei,
+            levels as GLsizei,
+        );
+        for level in 0..ktx2.levels.len() {
+            let (width, height) = ktx2.level_size(level);
+            texture.upload_level(
+                level as GLsizei,
+                width as GLsizei,
+                height as GLsizei,
+                ktx2.format.is_compressed(),
+                ktx2.level_data(bytes, level),
+            );
+        }
+        if ktx2.generate_mipmaps {
+            texture.generate_mipmaps();
+        }
+        Ok(texture)
+    }
+
+    pub fn decode(
+        context: &Context,
+        bytes: &[u8],
+        srgb: bool,
+        mipmaps: bool,
+    ) -> Result<Texture, String> {
+        if ktx2::is_ktx2(bytes) {
+            Texture::from_ktx2(context, bytes)
+        } else {
+            Ok(Texture::from_image(
+                context,
+                &Image::decode(bytes)?,
+                srgb,
+                mipmaps,
+            ))
+        }
+    }
+
+    pub fn upload(
+        &self,
+        level: GLsizei,
+        layer: GLsizei,
+        width: GLsizei,
+        height: GLsizei,
+        data: &[u8],
+    ) {
+        if self.is_compressed() {
+            panic!("can't upload RGBA8 data to a compressed texture");
+        }
+        if data.len() != width as usize * height as usize * 4 {
+            panic!("RGBA8 data has the wrong size for {}x{}", width, height);
+        }
+        unsafe {
+            self.bind(0);
+            match self.kind {
+                TextureKind::Texture2D => gl_check!(glTexSubImage2D(
+                    GL_TEXTURE_2D,
+                    level,
+                    0,
+                    0,
+                    width,
+                    height,
+                    GL_RGBA,
+                    GL_UNSIGNED_BYTE,
+                    data.as_ptr() as *const _,
+                )),
+                TextureKind::Texture2DArray => gl_check!(glTexSubImage3D(
+                    GL_TEXTURE_2D_ARRAY,
+                    level,
+                    0,
+                    0,
+                    layer,
+                    width,
+                    height,
+                    1,
+                    GL_RGBA,
+                    GL_UNSIGNED_BYTE,
+                    data.as_ptr() as *const _,
+                )),
+                TextureKind::Cube => gl_check!(glTexSubImage2D(
+                    GL_TEXTURE_CUBE_MAP_POSITIVE_X + layer as GLenum,
+                    level,
+                    0,
+                    0,
+                    width,
+                    height,
+                    GL_RGBA,
+                    GL_UNSIGNED_BYTE,
+                    data.as_ptr() as *const _,
+                )),
+            }
+        }
+    }
+
+    pub fn generate_mipmaps(&self) {
+        if self.is_compressed() {
+            panic!("can't generate mipmaps for a compressed texture");
+        }
+        unsafe {
+            self.bind(0);
+            gl_check!(glGenerateMipmap(self.kind.target()));
+        }
+    }
+
+    pub fn bind(&self, unit: GLuint) {
+        self.texture
+            .context()
+            .state()
+            .bind_texture(unit, self.kind.target(), self.texture.name());
+    }
+
+    pub fn texture(&self) -> GLuint {
+        self.texture.name()
+    }
+
+    pub fn kind(&self) -> TextureKind {
+        self.kind
+    }
+
+    pub fn width(&self) -> GLsizei {
+        self.width
+    }
+
+    pub fn height(&self) -> GLsizei {
+        self.height
+    }
+
+    pub fn layers(&self) -> GLsizei {
+        self.layers
+    }
+
+    pub fn levels(&self) -> GLsizei {
+        self.levels
+    }
+
+    fn is_compressed(&self) -> bool {
+        self.internal_format != GL_RGBA8 && self.internal_format != GL_SRGB8_ALPHA8
+    }
+
+    fn upload_level(
+        &self,
+        level: GLsizei,
+        width: GLsizei,
+        height: GLsizei,
+        compressed: bool,
+        data: &[u8],
+    ) {
+        let image_size = data.len() / self.layers as usize;
+        unsafe {
+            self.bind(0);
+            match self.kind {
+                TextureKind::Texture2DArray if compressed => {
+                    gl_check!(glCompressedTexSubImage3D(
+                        GL_TEXTURE_2D_ARRAY,
+                        level,
+                        0,
+                        0,
+                        0,
+                        width,
+                        height,
+                        self.layers,
+                        self.internal_format,
+                        data.len() as GLsizei,
+                        data.as_ptr() as *const _,
+                    ))
+                }
+                TextureKind::Texture2DArray => gl_check!(glTexSubImage3D(
+                    GL_TEXTURE_2D_ARRAY,
+                    level,
+                    0,
+                    0,
+                    0,
+                    width,
+                    height,
+                    self.layers,
+                    GL_RGBA,
+                    GL_UNSIGNED_BYTE,
+                    data.as_ptr() as *const _,
+                )),
+                TextureKind::Texture2D | TextureKind::Cube => {
+                    for (layer, image) in data.chunks_exact(image_size).enumerate() {
+                        let target = match self.kind {
+                            TextureKind::Cube => GL_TEXTURE_CUBE_MAP_POSITIVE_X + layer as GLenum,
+                            _ => GL_TEXTURE_2D,
+                        };
+                        if compressed {
+                            gl_check!(glCompressedTexSubImage2D(
+                                target,
+                                level,
+                                0,
+                                0,
+                                width,
+                                height,
+                                self.internal_format,
+                                image.len() as GLsizei,
+                                image.as_ptr() as *const _,
+                            ));
+                        } else {
+                            gl_check!(glTexSubImage2D(
+                                target,
+                                level,
+                                0,
+                                0,
+                                width,
+                                height,
+                                GL_RGBA,
+                                GL_UNSIGNED_BYTE,
+                                image.as_ptr() as *const _,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn get_internal_format(format: Format) -> GLenum {
+    match format {
+        Format::Rgba8 { srgb: false } => GL_RGBA8,
+        Format::Rgba8 { srgb: true } => GL_SRGB8_ALPHA8,
+        Format::Etc2Rgb8 { srgb: false } => GL_COMPRESSED_RGB8_ETC2,
+        Format::Etc2Rgb8 { srgb: true } => GL_COMPRESSED_SRGB8_ETC2,
+        Format::Etc2Rgb8A1 { srgb: false } => GL_COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+        Format::Etc2Rgb8A1 { srgb: true } => GL_COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+        Format::Etc2Rgba8 { srgb: false } => GL_COMPRESSED_RGBA8_ETC2_EAC,
+        Format::Etc2Rgba8 { srgb: true } => GL_COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+        Format::EacR11 => GL_COMPRESSED_R11_EAC,
+        Format::EacRg11 => GL_COMPRESSED_RG11_EAC,
+        Format::Astc {
+            block_width,
+            block_height,
+            srgb,
+        } => match (block_width, block_height, srgb) {
+            (4, 4, false) => GL_COMPRESSED_RGBA_ASTC_4x4_KHR,
+            (5, 4, false) => GL_COMPRESSED_RGBA_ASTC_5x4_KHR,
+            (5, 5, false) => GL_COMPRESSED_RGBA_ASTC_5x5_KHR,
+            (6, 5, false) => GL_COMPRESSED_RGBA_ASTC_6x5_KHR,
+            (6, 6, false) => GL_COMPRESSED_RGBA_ASTC_6x6_KHR,
+            (8, 5, false) => GL_COMPRESSED_RGBA_ASTC_8x5_KHR,
+            (8, 6, false) => GL_COMPRESSED_RGBA_ASTC_8x6_KHR,
+            (8, 8, false) => GL_COMPRESSED_RGBA_ASTC_8x8_KHR,
+            (10, 5, false) => GL_COMPRESSED_RGBA_ASTC_10x5_KHR,
+            (10, 6, false) => GL_COMPRESSED_RGBA_ASTC_10x6_KHR,
+            (10, 8, false) => GL_COMPRESSED_RGBA_ASTC_10x8_KHR,
+            (10, 10, false) => GL_COMPRESSED_RGBA_ASTC_10x10_KHR,
+            (12, 10, false) => GL_COMPRESSED_RGBA_ASTC_12x10_KHR,
+            (12, 12, false) => GL_COMPRESSED_RGBA_ASTC_12x12_KHR,
+            (4, 4, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_4x4_KHR,
+            (5, 4, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_5x4_KHR,
+            (5, 5, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_5x5_KHR,
+            (6, 5, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_6x5_KHR,
+            (6, 6, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_6x6_KHR,
+            (8, 5, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_8x5_KHR,
+            (8, 6, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_8x6_KHR,
+            (8, 8, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_8x8_KHR,
+            (10, 5, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_10x5_KHR,
+            (10, 6, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_10x6_KHR,
+            (10, 8, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_10x8_KHR,
+            (10, 10, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_10x10_KHR,
+            (12, 10, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_12x10_KHR,
+            (12, 12, true) => GL_COMPRESSED_SRGB8_ALPHA8_ASTC_12x12_KHR,
+            _ => unreachable!(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ktx2::tests::{ktx2, ETC2_RGB8_SRGB};
+    use crate::mock_gl::{MockGl, Object, Storage};
+    use libGLESv3_sys::GL_TEXTURE_CUBE_MAP_NEGATIVE_Z;
+    use std::rc::Rc;
+
+    fn storage(gl: &MockGl, texture: &Texture) -> Option<Storage> {
+        match gl.state().objects[&texture.texture()] {
+            Object::Texture(storage) => storage,
+            ref object => panic!("{:?} is not a texture", object),
+        }
+    }
+
+    fn uploads(gl: &MockGl) -> Vec<(&'static str, i64, i64)> {
+        gl.calls()
+            .iter()
+            .filter(|call| call.function.contains("TexSubImage"))
+            .map(|call| {
+                (
+                    call.function,
+                    call.argument(0).int(),
+                    call.argument(1).int(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn uploads_images_and_generates_mipmaps() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let image = Image {
+            width: 4,
+            height: 2,
+            data: vec![255; 32],
+        };
+        let texture = Texture::from_image(&context, &image, true, true);
+        assert_eq!(texture.kind(), TextureKind::Texture2D);
+        assert_eq!(
+            (
+                texture.width(),
+                texture.height(),
+                texture.layers(),
+                texture.levels()
+            ),
+            (4, 2, 1, 3)
+        );
+        assert_eq!(
+            storage(&gl, &texture),
+            Some(Storage {
+                internal_format: GL_SRGB8_ALPHA8,
+                samples: 1,
+            })
+        );
+        assert_eq!(uploads(&gl), [("glTexSubImage2D", GL_TEXTURE_2D as i64, 0)]);
+        assert_eq!(gl.functions().last(), Some(&"glGenerateMipmap"));
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn uploads_compressed_ktx2_levels() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let texture =
+            Texture::decode(&context, &ktx2(ETC2_RGB8_SRGB, 8, 2, 0), false, true).unwrap();
+        assert_eq!(
+            (
+                texture.width(),
+                texture.height(),
+                texture.layers(),
+                texture.levels()
+            ),
+            (8, 8, 1, 2)
+        );
+        assert_eq!(
+            storage(&gl, &texture).map(|storage| storage.internal_format),
+            Some(GL_COMPRESSED_SRGB8_ETC2)
+        );
+        assert_eq!(
+            uploads(&gl),
+            [
+                ("glCompressedTexSubImage2D", GL_TEXTURE_2D as i64, 0),
+                ("glCompressedTexSubImage2D", GL_TEXTURE_2D as i64, 1),
+            ]
+        );
+        assert!(!gl.functions().contains(&"glGenerateMipmap"));
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn uploads_cube_faces_and_binds_to_units() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let texture = Texture::new(&context, TextureKind::Cube, GL_RGBA8, 2, 2, 6, 1);
+        for face in 0..6 {
+            texture.upload(0, face, 2, 2, &[0; 16]);
+        }
+        let targets: Vec<i64> = uploads(&gl).iter().map(|(_, target, _)| *target).collect();
+        assert_eq!(
+            targets,
+            (GL_TEXTURE_CUBE_MAP_POSITIVE_X..=GL_TEXTURE_CUBE_MAP_NEGATIVE_Z)
+                .map(|target| target as i64)
+                .collect::<Vec<_>>()
+        );
+
+        texture.bind(5);
+        assert_eq!(
+            gl.state().textures[&(5, GL_TEXTURE_CUBE_MAP)],
+            texture.texture()
+        );
+        assert!(gl.errors().is_empty());
+    }
+}
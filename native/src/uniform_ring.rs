@@ -0,0 +1,260 @@
+use crate::gl;
+use crate::gl::Context;
+use crate::std140;
+use crate::std140::{Std140Block, Std140Writer};
+use libGLESv3_sys::{
+    GLint, GLintptr, GLsizeiptr, GLsync, GLuint, GL_ALREADY_SIGNALED, GL_CONDITION_SATISFIED,
+    GL_DYNAMIC_DRAW, GL_SYNC_FLUSH_COMMANDS_BIT, GL_SYNC_GPU_COMMANDS_COMPLETE, GL_UNIFORM_BUFFER,
+    GL_UNIFORM_BUFFER_OFFSET_ALIGNMENT, GL_WAIT_FAILED,
+};
+use std::ptr;
+
+const FENCE_TIMEOUT: u64 = 10_000_000;
+const MAX_FENCE_WAITS: u32 = 100;
+
+#[derive(Clone, Copy, Debug)]
+pub struct UniformRange {
+    pub offset: GLintptr,
+    pub size: GLsizeiptr,
+}
+
+pub struct UniformRing {
+    buffer: gl::Buffer,
+    frame_size: usize,
+    alignment: usize,
+    staging: Vec<u8>,
+    fences: Vec<GLsync>,
+    frame: usize,
+    offset: usize,
+    flushed: usize,
+}
+
+impl UniformRing {
+    pub fn new(context: &Context, frame_size: usize, frame_count: usize) -> UniformRing {
+        unsafe {
+            let mut alignment: GLint = 0;
+            gl_check!(glGetIntegerv(
+                GL_UNIFORM_BUFFER_OFFSET_ALIGNMENT,
+                &mut alignment
+            ));
+
+            let buffer = gl::Buffer::new(context);
+            gl_check!(glBindBuffer(GL_UNIFORM_BUFFER, buffer.name()));
+            gl_check!(glBufferData(
+                GL_UNIFORM_BUFFER,
+                (frame_size * frame_count) as GLsizeiptr,
+                ptr::null(),
+                GL_DYNAMIC_DRAW,
+            ));
+            gl_check!(glBindBuffer(GL_UNIFORM_BUFFER, 0));
+
+            UniformRing {
+                buffer,
+                frame_size,
+                alignment: alignment.max(1) as usize,
+                staging: vec![0; frame_size],
+                fences: vec![ptr::null_mut(); frame_count],
+                frame: 0,
+                offset: 0,
+                flushed: 0,
+            }
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame = (self.frame + 1) % self.fences.len();
+        let fence = self.fences[self.frame];
+        if !fence.is_null() {
+            unsafe {
+                wait_for_fence(fence);
+                gl_check!(glDeleteSync(fence));
+            }
+            self.fences[self.frame] = ptr::null_mut();
+        }
+        self.offset = 0;
+        self.flushed = 0;
+    }
+
+    pub fn allocate(&mut self, size: usize) -> (UniformRange, &mut [u8]) {
+        let offset = std140::align(self.offset, self.alignment);
+        if offset + size > self.frame_size {
+            panic!("can't allocate {} bytes from uniform ring", size);
+        }
+        self.offset = offset + size;
+        (
+            UniformRange {
+                offset: (self.frame * self.frame_size + offset) as GLintptr,
+                size: size as GLsizeiptr,
+            },
+            &mut self.staging[offset..offset + size],
+        )
+    }
+
+    pub fn push<T: Std140Block>(&mut self, value: &T) -> UniformRange {
+        let (range, bytes) = self.allocate(T::size());
+        value.write(&mut Std140Writer::new(bytes));
+        range
+    }
+
+    pub fn flush(&mut self) {
+        if self.flushed == self.offset {
+            return;
+        }
+        unsafe {
+            gl_check!(glBindBuffer(GL_UNIFORM_BUFFER, self.buffer.name()));
+            gl_check!(glBufferSubData(
+                GL_UNIFORM_BUFFER,
+                (self.frame * self.frame_size + self.flushed) as GLintptr,
+                (self.offset - self.flushed) as GLsizeiptr,
+                self.staging[self.flushed..].as_ptr() as *const _,
+            ));
+            gl_check!(glBindBuffer(GL_UNIFORM_BUFFER, 0));
+        }
+        self.flushed = self.offset;
+    }
+
+    pub fn bind(&self, binding: GLuint, range: UniformRange) {
+        unsafe {
+            gl_check!(glBindBufferRange(
+                GL_UNIFORM_BUFFER,
+                binding,
+                self.buffer.name(),
+                range.offset,
+                range.size,
+            ));
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        self.flush();
+        unsafe {
+            self.fences[self.frame] = gl_check!(glFenceSync(GL_SYNC_GPU_COMMANDS_COMPLETE, 0));
+        }
+    }
+}
+
+unsafe fn wait_for_fence(fence: GLsync) {
+    for wait in 0..MAX_FENCE_WAITS {
+        match gl_check!(glClientWaitSync(
+            fence,
+            if wait == 0 {
+                GL_SYNC_FLUSH_COMMANDS_BIT
+            } else {
+                0
+            },
+            FENCE_TIMEOUT,
+        )) {
+            GL_ALREADY_SIGNALED | GL_CONDITION_SATISFIED => {
+                if wait > 0 {
+                    logw!(
+                        "uniform ring stalled for {} ms waiting for fence",
+                        wait as u64 * FENCE_TIMEOUT / 1_000_000
+                    );
+                }
+                return;
+            }
+            GL_WAIT_FAILED => panic!("can't wait for uniform ring fence"),
+            _ => {}
+        }
+    }
+    loge!(
+        "uniform ring fence not signaled after {} ms, overwriting frame",
+        MAX_FENCE_WAITS as u64 * FENCE_TIMEOUT / 1_000_000
+    );
+}
+
+impl Drop for UniformRing {
+    fn drop(&mut self) {
+        unsafe {
+            logi!("delete uniform ring fences");
+            for fence in self.fences.iter().cloned() {
+                if !fence.is_null() {
+                    gl_check!(glDeleteSync(fence));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_gl::MockGl;
+    use std::ffi::c_void;
+    use std::rc::Rc;
+
+    #[test]
+    fn aligns_allocations_to_uniform_buffer_offset_alignment() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let mut alignment: GLint = 0;
+        unsafe {
+            gl_check!(glGetIntegerv(
+                GL_UNIFORM_BUFFER_OFFSET_ALIGNMENT,
+                &mut alignment
+            ));
+        }
+        let alignment = alignment as usize;
+        let mut ring = UniformRing::new(&context, 4 * alignment, 2);
+
+        ring.begin_frame();
+        let offsets: Vec<GLintptr> = (0..3).map(|_| ring.allocate(20).0.offset).collect();
+        let frame = 4 * alignment as GLintptr;
+        let alignment = alignment as GLintptr;
+        assert_eq!(offsets, [frame, frame + alignment, frame + 2 * alignment]);
+        ring.end_frame();
+
+        ring.begin_frame();
+        assert_eq!(ring.allocate(4).0.offset, 0);
+        assert_eq!(ring.allocate(4).0.offset, alignment);
+        ring.end_frame();
+
+        gl.clear_calls();
+        ring.begin_frame();
+        let functions: Vec<&str> = gl.calls().iter().map(|call| call.function).collect();
+        assert_eq!(functions, ["glClientWaitSync", "glDeleteSync"]);
+        assert!(gl.errors().is_empty());
+    }
+
+    #[test]
+    fn waits_for_and_deletes_fences_of_reused_frames() {
+        let gl = Rc::new(MockGl::new());
+        let context = Context::with_backend(gl.clone());
+        let frame_size = 1024;
+        let mut ring = UniformRing::new(&context, frame_size, 3);
+        let fences_in = |function: &str| -> Vec<usize> {
+            gl.calls()
+                .iter()
+                .filter(|call| call.function == function)
+                .map(|call| call.argument(0).pointer::<c_void>() as usize)
+                .collect()
+        };
+
+        let mut fences = Vec::new();
+        let mut frames = Vec::new();
+        for frame in 0..7usize {
+            gl.clear_calls();
+            ring.begin_frame();
+            let reused: Vec<usize> = frame
+                .checked_sub(3)
+                .map(|frame| fences[frame])
+                .into_iter()
+                .collect();
+            assert_eq!(fences_in("glClientWaitSync"), reused);
+            assert_eq!(fences_in("glDeleteSync"), reused);
+            frames.push(ring.allocate(16).0.offset as usize / frame_size);
+            ring.end_frame();
+            assert_eq!(gl.functions().last(), Some(&"glFenceSync"));
+            fences.push(ring.fences[ring.frame] as usize);
+        }
+        assert_eq!(frames, [1, 2, 0, 1, 2, 0, 1]);
+
+        gl.clear_calls();
+        drop(ring);
+        let mut deleted = fences_in("glDeleteSync");
+        deleted.sort();
+        assert_eq!(deleted, fences[4..]);
+        assert_eq!(gl.object_count(), 0);
+        assert!(gl.errors().is_empty());
+    }
+}
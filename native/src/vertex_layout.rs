@@ -0,0 +1,473 @@
+macro_rules! vertex_struct {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident: $type:ty => $semantic:ident,)*
+        }
+    ) => {
+        $(#[$attr])*
+        #[repr(C)]
+        $vis struct $name {
+            $($field_vis $field: $type,)*
+        }
+
+        impl $crate::vertex_layout::Vertex for $name {
+            fn layout() -> $crate::vertex_layout::VertexLayout {
+                let vertex = ::std::mem::MaybeUninit::<$name>::uninit();
+                let base = vertex.as_ptr() as usize;
+                let attributes = vec![
+                    $($crate::vertex_layout::Attribute {
+                        semantic: $crate::vertex_layout::Semantic::$semantic,
+                        format: <$type as $crate::vertex_layout::VertexAttribute>::FORMAT,
+                        normalized: <$type as $crate::vertex_layout::VertexAttribute>::NORMALIZED,
+                        offset: unsafe {
+                            ::std::ptr::addr_of!((*vertex.as_ptr()).$field) as usize - base
+                        },
+                    },)*
+                ];
+                $crate::vertex_layout::VertexLayout::new(
+                    ::std::mem::size_of::<$name>(),
+                    attributes,
+                )
+                .unwrap_or_else(|error| {
+                    panic!("invalid vertex layout for {}: {}", stringify!($name), error)
+                })
+            }
+        }
+    };
+}
+
+use crate::math::{Aabb, Vec3};
+
+pub const INSTANCE_MODEL_MATRIX_LOCATION: u32 = 8;
+pub const INSTANCE_COLOR_LOCATION: u32 = 12;
+
+pub const INSTANCE_ATTRIBUTES: &[(&str, u32)] = &[
+    ("aInstanceModelMatrix", INSTANCE_MODEL_MATRIX_LOCATION),
+    ("aInstanceColor", INSTANCE_COLOR_LOCATION),
+];
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Semantic {
+    Position,
+    Normal,
+    Tangent,
+    TexCoord0,
+    TexCoord1,
+    Color,
+    Joints,
+    Weights,
+}
+
+impl Semantic {
+    pub const ALL: [Semantic; 8] = [
+        Semantic::Position,
+        Semantic::Normal,
+        Semantic::Tangent,
+        Semantic::TexCoord0,
+        Semantic::TexCoord1,
+        Semantic::Color,
+        Semantic::Joints,
+        Semantic::Weights,
+    ];
+
+    pub fn from_attrib_name(name: &str) -> Option<Semantic> {
+        Semantic::ALL
+            .iter()
+            .cloned()
+            .find(|semantic| semantic.attrib_name() == name)
+    }
+
+    pub fn location(self) -> u32 {
+        Semantic::ALL
+            .iter()
+            .position(|semantic| *semantic == self)
+            .unwrap() as u32
+    }
+
+    pub fn attrib_name(self) -> &'static str {
+        match self {
+            Semantic::Position => "aPosition",
+            Semantic::Normal => "aNormal",
+            Semantic::Tangent => "aTangent",
+            Semantic::TexCoord0 => "aTexCoord0",
+            Semantic::TexCoord1 => "aTexCoord1",
+            Semantic::Color => "aColor",
+            Semantic::Joints => "aJoints",
+            Semantic::Weights => "aWeights",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ComponentType {
+    Byte,
+    UnsignedByte,
+    Short,
+    UnsignedShort,
+    Int,
+    UnsignedInt,
+    HalfFloat,
+    Float,
+}
+
+impl ComponentType {
+    pub fn size(self) -> usize {
+        match self {
+            ComponentType::Byte | ComponentType::UnsignedByte => 1,
+            ComponentType::Short | ComponentType::UnsignedShort | ComponentType::HalfFloat => 2,
+            ComponentType::Int | ComponentType::UnsignedInt | ComponentType::Float => 4,
+        }
+    }
+
+    pub fn is_integer(self) -> bool {
+        !matches!(self, ComponentType::HalfFloat | ComponentType::Float)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Format {
+    pub component_type: ComponentType,
+    pub component_count: usize,
+}
+
+impl Format {
+    pub const FLOAT2: Format = Format::new(ComponentType::Float, 2);
+    pub const FLOAT3: Format = Format::new(ComponentType::Float, 3);
+    pub const FLOAT4: Format = Format::new(ComponentType::Float, 4);
+
+    pub const fn new(component_type: ComponentType, component_count: usize) -> Format {
+        Format {
+            component_type,
+            component_count,
+        }
+    }
+
+    pub fn size(self) -> usize {
+        self.component_type.size() * self.component_count
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Attribute {
+    pub semantic: Semantic,
+    pub format: Format,
+    pub normalized: bool,
+    pub offset: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VertexLayout {
+    stride: usize,
+    attributes: Vec<Attribute>,
+}
+
+impl VertexLayout {
+    pub fn new(stride: usize, attributes: Vec<Attribute>) -> Result<VertexLayout, String> {
+        for (index, attribute) in attributes.iter().enumerate() {
+            if attributes[..index]
+                .iter()
+                .any(|other| other.semantic == attribute.semantic)
+            {
+                return Err(format!("duplicate attribute {:?}", attribute.semantic));
+            }
+            if attribute.format.component_count < 1 || attribute.format.component_count > 4 {
+                return Err(format!(
+                    "attribute {:?} has {} components",
+                    attribute.semantic, attribute.format.component_count
+                ));
+            }
+            if attribute.normalized && !attribute.format.component_type.is_integer() {
+                return Err(format!(
+                    "attribute {:?} is normalized but not an integer format",
+                    attribute.semantic
+                ));
+            }
+            if attribute.offset % attribute.format.component_type.size() != 0 {
+                return Err(format!(
+                    "attribute {:?} at offset {} is misaligned",
+                    attribute.semantic, attribute.offset
+                ));
+            }
+            if attribute.offset + attribute.format.size() > stride {
+                return Err(format!(
+                    "attribute {:?} at offset {} overflows stride {}",
+                    attribute.semantic, attribute.offset, stride
+                ));
+            }
+        }
+        Ok(VertexLayout { stride, attributes })
+    }
+
+    pub fn packed(attributes: &[(Semantic, Format, bool)]) -> VertexLayout {
+        let mut offset = 0;
+        let attributes: Vec<Attribute> = attributes
+            .iter()
+            .cloned()
+            .map(|(semantic, format, normalized)| {
+                let attribute = Attribute {
+                    semantic,
+                    format,
+                    normalized,
+                    offset,
+                };
+                offset = align(offset + format.size(), 4);
+                attribute
+            })
+            .collect();
+        VertexLayout::new(offset, attributes).unwrap()
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
+    pub fn attribute(&self, semantic: Semantic) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.semantic == semantic)
+    }
+
+    pub fn bounds(&self, vertex_bytes: &[u8]) -> Option<Aabb> {
+        let attribute = self.attribute(Semantic::Position)?;
+        if attribute.format.component_type != ComponentType::Float
+            || attribute.format.component_count < 3
+        {
+            return None;
+        }
+        let read = |offset: usize| {
+            f32::from_ne_bytes([
+                vertex_bytes[offset],
+                vertex_bytes[offset + 1],
+                vertex_bytes[offset + 2],
+                vertex_bytes[offset + 3],
+            ])
+        };
+        let count = vertex_bytes.len() / self.stride;
+        Aabb::from_points((0..count).map(|index| {
+            let offset = index * self.stride + attribute.offset;
+            Vec3::new(read(offset), read(offset + 4), read(offset + 8))
+        }))
+    }
+
+    pub fn validate(&self, attrib_names: &[String]) -> Result<(), String> {
+        for name in attrib_names.iter() {
+            if INSTANCE_ATTRIBUTES
+                .iter()
+                .any(|(instance_name, _)| instance_name == name)
+            {
+                continue;
+            }
+            match Semantic::from_attrib_name(name) {
+                Some(semantic) => {
+                    if self.attribute(semantic).is_none() {
+                        return Err(format!("vertex layout has no {:?} attribute", semantic));
+                    }
+                }
+                None => return Err(format!("attribute {} has no known semantic", name)),
+            }
+        }
+        Ok(())
+    }
+}
+
+pub trait Vertex {
+    fn layout() -> VertexLayout;
+}
+
+pub trait VertexAttribute {
+    const FORMAT: Format;
+    const NORMALIZED: bool;
+}
+
+impl VertexAttribute for [f32; 2] {
+    const FORMAT: Format = Format::FLOAT2;
+    const NORMALIZED: bool = false;
+}
+
+impl VertexAttribute for [f32; 3] {
+    const FORMAT: Format = Format::FLOAT3;
+    const NORMALIZED: bool = false;
+}
+
+impl VertexAttribute for [f32; 4] {
+    const FORMAT: Format = Format::FLOAT4;
+    const NORMALIZED: bool = false;
+}
+
+impl VertexAttribute for [u8; 4] {
+    const FORMAT: Format = Format::new(ComponentType::UnsignedByte, 4);
+    const NORMALIZED: bool = true;
+}
+
+impl VertexAttribute for [i8; 4] {
+    const FORMAT: Format = Format::new(ComponentType::Byte, 4);
+    const NORMALIZED: bool = true;
+}
+
+impl VertexAttribute for [i16; 4] {
+    const FORMAT: Format = Format::new(ComponentType::Short, 4);
+    const NORMALIZED: bool = true;
+}
+
+impl VertexAttribute for [u16; 4] {
+    const FORMAT: Format = Format::new(ComponentType::UnsignedShort, 4);
+    const NORMALIZED: bool = false;
+}
+
+impl VertexAttribute for [i32; 4] {
+    const FORMAT: Format = Format::new(ComponentType::Int, 4);
+    const NORMALIZED: bool = false;
+}
+
+impl VertexAttribute for [u32; 4] {
+    const FORMAT: Format = Format::new(ComponentType::UnsignedInt, 4);
+    const NORMALIZED: bool = false;
+}
+
+fn align(offset: usize, alignment: usize) -> usize {
+    offset.div_ceil(alignment) * alignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    vertex_struct! {
+        struct SkinnedVertex {
+            position: [f32; 3] => Position,
+            color: [u8; 4] => Color,
+            tex_coord: [f32; 2] => TexCoord0,
+            joints: [u16; 4] => Joints,
+            weights: [f32; 4] => Weights,
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| String::from(*name)).collect()
+    }
+
+    #[test]
+    fn lays_out_vertex_structs() {
+        let layout = SkinnedVertex::layout();
+        assert_eq!(layout.stride(), 48);
+        let attributes: Vec<(Semantic, usize, bool)> = layout
+            .attributes()
+            .iter()
+            .map(|attribute| (attribute.semantic, attribute.offset, attribute.normalized))
+            .collect();
+        assert_eq!(
+            attributes,
+            [
+                (Semantic::Position, 0, false),
+                (Semantic::Color, 12, true),
+                (Semantic::TexCoord0, 16, false),
+                (Semantic::Joints, 24, false),
+                (Semantic::Weights, 32, false),
+            ]
+        );
+        assert_eq!(
+            layout.attribute(Semantic::Joints).unwrap().format,
+            Format::new(ComponentType::UnsignedShort, 4)
+        );
+        assert_eq!(layout.attribute(Semantic::Normal), None);
+    }
+
+    #[test]
+    fn packs_attributes_to_four_bytes() {
+        let layout = VertexLayout::packed(&[
+            (Semantic::Position, Format::FLOAT3, false),
+            (
+                Semantic::Color,
+                Format::new(ComponentType::UnsignedByte, 3),
+                true,
+            ),
+            (Semantic::TexCoord0, Format::FLOAT2, false),
+        ]);
+        let offsets: Vec<usize> = layout
+            .attributes()
+            .iter()
+            .map(|attribute| attribute.offset)
+            .collect();
+        assert_eq!(offsets, [0, 12, 16]);
+        assert_eq!(layout.stride(), 24);
+    }
+
+    #[test]
+    fn rejects_invalid_attributes() {
+        let attribute = |semantic, format, normalized, offset| Attribute {
+            semantic,
+            format,
+            normalized,
+            offset,
+        };
+        let position = attribute(Semantic::Position, Format::FLOAT3, false, 0);
+        assert!(VertexLayout::new(12, vec![position, position]).is_err());
+        assert!(VertexLayout::new(8, vec![position]).is_err());
+        assert!(VertexLayout::new(
+            16,
+            vec![attribute(Semantic::Normal, Format::FLOAT3, false, 2)]
+        )
+        .is_err());
+        assert!(VertexLayout::new(
+            16,
+            vec![attribute(Semantic::Normal, Format::FLOAT3, true, 0)]
+        )
+        .is_err());
+        assert!(VertexLayout::new(
+            32,
+            vec![attribute(
+                Semantic::Weights,
+                Format::new(ComponentType::Float, 5),
+                false,
+                0
+            )]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn validates_program_attributes() {
+        let layout = SkinnedVertex::layout();
+        assert_eq!(
+            layout.validate(&names(&[
+                "aPosition",
+                "aJoints",
+                "aInstanceModelMatrix",
+                "aInstanceColor"
+            ])),
+            Ok(())
+        );
+        assert_eq!(
+            layout.validate(&names(&["aPosition", "aNormal"])),
+            Err(String::from("vertex layout has no Normal attribute"))
+        );
+        assert_eq!(
+            layout.validate(&names(&["aPosition", "aBitangent"])),
+            Err(String::from("attribute aBitangent has no known semantic"))
+        );
+    }
+
+    #[test]
+    fn computes_bounds_from_positions() {
+        let layout = VertexLayout::packed(&[(Semantic::Position, Format::FLOAT3, false)]);
+        let bytes: Vec<u8> = [[1.0f32, -2.0, 3.0], [-1.0, 4.0, 0.0]]
+            .iter()
+            .flat_map(|position| position.iter().flat_map(|value| value.to_ne_bytes()))
+            .collect();
+        let bounds = layout.bounds(&bytes).unwrap();
+        assert_eq!(
+            bounds,
+            Aabb::from_points(
+                [Vec3::new(-1.0, -2.0, 0.0), Vec3::new(1.0, 4.0, 3.0),]
+                    .iter()
+                    .cloned()
+            )
+            .unwrap()
+        );
+    }
+}
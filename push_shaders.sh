@@ -0,0 +1,3 @@
+#!/bin/bash
+adb shell mkdir -p /sdcard/rustquest/shaders
+adb push native/shaders/. /sdcard/rustquest/shaders